@@ -0,0 +1,110 @@
+use bitcoin::{AddressType, Network};
+
+use crate::{Error, Result};
+
+/// The standard output types walletb knows how to track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressKind {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+}
+
+impl AddressKind {
+    fn from_address_type(ty: AddressType) -> Option<Self> {
+        match ty {
+            AddressType::P2pkh => Some(AddressKind::P2pkh),
+            AddressType::P2sh => Some(AddressKind::P2sh),
+            AddressType::P2wpkh => Some(AddressKind::P2wpkh),
+            AddressType::P2wsh => Some(AddressKind::P2wsh),
+            AddressType::P2tr => Some(AddressKind::P2tr),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `s` as an address for `network`.
+///
+/// Base58 and bech32/bech32m checksums are verified by the parser. The
+/// address must also belong to `network`; because testnet, signet and
+/// regtest share base58 prefixes, a legacy testnet address is accepted on
+/// regtest and vice versa, while bech32 addresses are distinguished by HRP.
+pub fn parse_address(s: &str, network: Network) -> Result<(bitcoin::Address, AddressKind)> {
+    let unchecked = s
+        .parse::<bitcoin::Address<_>>()
+        .map_err(|source| Error::InvalidAddress {
+            address: s.to_owned(),
+            source,
+        })?;
+    let address = unchecked
+        .require_network(network)
+        .map_err(|_| Error::WrongNetwork {
+            address: s.to_owned(),
+            expected: network,
+        })?;
+    let kind = address
+        .address_type()
+        .and_then(AddressKind::from_address_type)
+        .ok_or_else(|| Error::UnsupportedAddress(s.to_owned()))?;
+    Ok((address, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_supported_mainnet_type() {
+        let cases = [
+            ("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", AddressKind::P2pkh),
+            ("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", AddressKind::P2sh),
+            (
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                AddressKind::P2wpkh,
+            ),
+            (
+                "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+                AddressKind::P2wsh,
+            ),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                AddressKind::P2tr,
+            ),
+        ];
+        for (s, kind) in cases {
+            let (_, parsed) = parse_address(s, Network::Bitcoin).unwrap();
+            assert_eq!(parsed, kind, "{s}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let err = parse_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb", Network::Bitcoin);
+        assert!(matches!(err, Err(Error::InvalidAddress { .. })));
+        let err = parse_address(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+            Network::Bitcoin,
+        );
+        assert!(matches!(err, Err(Error::InvalidAddress { .. })));
+    }
+
+    #[test]
+    fn enforces_network() {
+        let testnet = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+        assert!(parse_address(testnet, Network::Testnet).is_ok());
+        assert!(matches!(
+            parse_address(testnet, Network::Bitcoin),
+            Err(Error::WrongNetwork { .. })
+        ));
+        assert!(matches!(
+            parse_address(testnet, Network::Regtest),
+            Err(Error::WrongNetwork { .. })
+        ));
+        assert!(matches!(
+            parse_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", Network::Regtest),
+            Err(Error::WrongNetwork { .. })
+        ));
+    }
+}
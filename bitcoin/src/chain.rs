@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use bitcoin::block::{Header, Version};
+use bitcoin::hashes::Hash;
+use bitcoin::{Block, BlockHash, CompactTarget, Transaction, TxMerkleNode};
+
+use crate::{Error, Result};
+
+/// Header time of the first block of a [`MemoryChain`]; later blocks follow
+/// every ten minutes.
+const MEMORY_CHAIN_START: u32 = 1_231_006_505;
+
+/// A block's place in a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockId {
+    pub height: u32,
+    pub hash: BlockHash,
+}
+
+/// A backend that serves the best chain block by block, which is what
+/// incremental sync follows.
+///
+/// Heights refer to the backend's current best chain, which can change
+/// between calls when the chain reorganizes; callers compare hashes to
+/// notice.
+pub trait BlockSource {
+    fn tip(&self) -> Result<BlockId>;
+
+    /// The hash of the best-chain block at `height`, or `None` above the
+    /// tip.
+    fn block_hash(&self, height: u32) -> Result<Option<BlockHash>>;
+
+    /// Any block the backend knows, including ones no longer in the best
+    /// chain.
+    fn block(&self, hash: &BlockHash) -> Result<Block>;
+}
+
+impl<B: BlockSource + ?Sized> BlockSource for &B {
+    fn tip(&self) -> Result<BlockId> {
+        (**self).tip()
+    }
+
+    fn block_hash(&self, height: u32) -> Result<Option<BlockHash>> {
+        (**self).block_hash(height)
+    }
+
+    fn block(&self, hash: &BlockHash) -> Result<Block> {
+        (**self).block(hash)
+    }
+}
+
+impl<B: BlockSource + ?Sized> BlockSource for Box<B> {
+    fn tip(&self) -> Result<BlockId> {
+        (**self).tip()
+    }
+
+    fn block_hash(&self, height: u32) -> Result<Option<BlockHash>> {
+        (**self).block_hash(height)
+    }
+
+    fn block(&self, hash: &BlockHash) -> Result<Block> {
+        (**self).block(hash)
+    }
+}
+
+/// A chain of blocks held in memory, for tests. It starts with an empty
+/// genesis block; [`push`](MemoryChain::push) mines on top of the tip and
+/// [`reorg`](MemoryChain::reorg) abandons blocks so that a competing branch
+/// can be pushed in their place.
+///
+/// Abandoned blocks stay retrievable by hash, as they would from a node.
+#[derive(Debug, Clone)]
+pub struct MemoryChain {
+    best: Vec<BlockHash>,
+    blocks: HashMap<BlockHash, Block>,
+    mined: u32,
+}
+
+impl MemoryChain {
+    pub fn new() -> Self {
+        let mut chain = MemoryChain {
+            best: Vec::new(),
+            blocks: HashMap::new(),
+            mined: 0,
+        };
+        chain.push(Vec::new());
+        chain
+    }
+
+    /// Mines a block holding `txdata` on top of the tip and returns its
+    /// hash. Every block gets a distinct nonce, so a block replacing an
+    /// abandoned one at the same height has a different hash even when it
+    /// holds the same transactions.
+    pub fn push(&mut self, txdata: Vec<Transaction>) -> BlockHash {
+        let height = self.best.len() as u32;
+        let mut block = Block {
+            header: Header {
+                version: Version::TWO,
+                prev_blockhash: self
+                    .best
+                    .last()
+                    .copied()
+                    .unwrap_or_else(BlockHash::all_zeros),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: MEMORY_CHAIN_START + height * 600,
+                bits: CompactTarget::from_consensus(0x207f_ffff),
+                nonce: self.mined,
+            },
+            txdata,
+        };
+        if let Some(root) = block.compute_merkle_root() {
+            block.header.merkle_root = root;
+        }
+        self.mined += 1;
+        let hash = block.block_hash();
+        self.best.push(hash);
+        self.blocks.insert(hash, block);
+        hash
+    }
+
+    /// Abandons every block from `height` up, making the block below it the
+    /// tip. The genesis block cannot be abandoned.
+    pub fn reorg(&mut self, height: u32) {
+        self.best.truncate(height.max(1) as usize);
+    }
+
+    pub fn height(&self) -> u32 {
+        self.best.len() as u32 - 1
+    }
+}
+
+impl Default for MemoryChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockSource for MemoryChain {
+    fn tip(&self) -> Result<BlockId> {
+        Ok(BlockId {
+            height: self.height(),
+            hash: self.best[self.best.len() - 1],
+        })
+    }
+
+    fn block_hash(&self, height: u32) -> Result<Option<BlockHash>> {
+        Ok(self.best.get(height as usize).copied())
+    }
+
+    fn block(&self, hash: &BlockHash) -> Result<Block> {
+        self.blocks
+            .get(hash)
+            .cloned()
+            .ok_or(Error::UnknownBlock(*hash))
+    }
+}
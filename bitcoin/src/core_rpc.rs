@@ -0,0 +1,414 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use bitcoin::base64::engine::general_purpose::STANDARD as BASE64;
+use bitcoin::base64::Engine;
+use bitcoin::consensus::encode;
+use bitcoin::hex::DisplayHex;
+use bitcoin::{Amount, Block, BlockHash, OutPoint, ScriptBuf, Txid};
+use miniscript::descriptor::checksum::desc_checksum;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use walletb_core::{JsonRpcClient, RpcError};
+
+use crate::{
+    BlockId, BlockSource, Error, Keychain, Keychains, Result, Utxo, UtxoBackend, WalletDescriptor,
+};
+
+/// How long `scantxoutset` and rescanning `importdescriptors` calls may
+/// take; both walk the whole chain state before answering.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// `RPC_INVALID_ADDRESS_OR_KEY`, returned by `getblock` for an unknown
+/// hash.
+const INVALID_ADDRESS_OR_KEY: i64 = -5;
+/// `RPC_INVALID_PARAMETER`, returned by `getblockhash` above the tip.
+const INVALID_PARAMETER: i64 = -8;
+/// `RPC_WALLET_NOT_FOUND`, returned by `loadwallet` for a missing wallet.
+const WALLET_NOT_FOUND: i64 = -18;
+/// `RPC_WALLET_ALREADY_LOADED`.
+const WALLET_ALREADY_LOADED: i64 = -35;
+
+/// How to authenticate to Bitcoin Core's RPC server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreAuth {
+    /// The `.cookie` file bitcoind writes to its data directory. It changes
+    /// every time the node restarts and is read on connect.
+    Cookie(PathBuf),
+    /// `rpcuser`/`rpcpassword` or an `rpcauth` entry.
+    UserPass { user: String, password: String },
+}
+
+impl CoreAuth {
+    /// The `Authorization` header value.
+    fn header(&self) -> Result<String> {
+        let credentials = match self {
+            CoreAuth::Cookie(path) => fs::read_to_string(path)
+                .map_err(|source| Error::Cookie {
+                    path: path.clone(),
+                    source,
+                })?
+                .trim()
+                .to_owned(),
+            CoreAuth::UserPass { user, password } => format!("{user}:{password}"),
+        };
+        Ok(format!("Basic {}", BASE64.encode(credentials)))
+    }
+}
+
+/// A client for Bitcoin Core's JSON-RPC interface.
+///
+/// As a [`UtxoBackend`] it answers with `scantxoutset`, which needs no
+/// wallet and no setup but walks the whole UTXO set on every call and only
+/// sees confirmed outputs: good for one-off balance checks. For repeated
+/// reads, import the wallet's descriptors into a watch-only
+/// [`CoreWallet`] once and read that instead.
+///
+/// It is also a [`BlockSource`], for incremental sync from a node.
+#[derive(Debug)]
+pub struct BitcoinCore {
+    url: String,
+    auth: String,
+    rpc: JsonRpcClient,
+    slow: JsonRpcClient,
+}
+
+impl BitcoinCore {
+    /// Connects to the node at `url`, e.g. `http://127.0.0.1:8332`. Nothing
+    /// is sent until the first call.
+    pub fn new(url: impl Into<String>, auth: &CoreAuth) -> Result<Self> {
+        let url = url.into().trim_end_matches('/').to_owned();
+        let auth = auth.header()?;
+        Ok(BitcoinCore {
+            rpc: client(&url, &auth),
+            slow: client(&url, &auth).with_timeout(SCAN_TIMEOUT),
+            url,
+            auth,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Opens the descriptor wallet `name`, loading it if the node has not
+    /// and creating it, blank and without private keys, if it does not
+    /// exist.
+    pub fn wallet(&self, name: &str) -> Result<CoreWallet> {
+        match call::<Value>(&self.rpc, "loadwallet", json!([name])) {
+            Ok(_) => {}
+            Err(Error::Rpc(RpcError::Server { code, .. })) if code == WALLET_ALREADY_LOADED => {}
+            Err(Error::Rpc(RpcError::Server { code, .. })) if code == WALLET_NOT_FOUND => {
+                call::<Value>(
+                    &self.rpc,
+                    "createwallet",
+                    json!({
+                        "wallet_name": name,
+                        "disable_private_keys": true,
+                        "blank": true,
+                        "descriptors": true,
+                        "load_on_startup": true,
+                    }),
+                )?;
+            }
+            Err(e) => return Err(e),
+        }
+        let url = format!("{}/wallet/{name}", self.url);
+        Ok(CoreWallet {
+            name: name.to_owned(),
+            rpc: client(&url, &self.auth),
+            slow: client(&url, &self.auth).with_timeout(SCAN_TIMEOUT),
+        })
+    }
+
+    /// Scans the UTXO set for everything `wallet` derives up to index
+    /// `range` on each of its keychains, without a gap limit.
+    pub fn scan_wallet(&self, wallet: &WalletDescriptor, range: u32) -> Result<Vec<Utxo>> {
+        let objects = wallet
+            .keychains()
+            .into_iter()
+            .map(|keychain| {
+                let descriptor = wallet.descriptor(keychain);
+                if descriptor.has_wildcard() {
+                    json!({ "desc": descriptor.to_string(), "range": range })
+                } else {
+                    json!(descriptor.to_string())
+                }
+            })
+            .collect();
+        self.scan(objects)
+    }
+
+    fn scan(&self, objects: Vec<Value>) -> Result<Vec<Utxo>> {
+        if objects.is_empty() {
+            return Ok(Vec::new());
+        }
+        let result: ScanResult = call(&self.slow, "scantxoutset", json!(["start", objects]))?;
+        if !result.success {
+            return Err(RpcError::InvalidResponse("scantxoutset was aborted".into()).into());
+        }
+        Ok(result
+            .unspents
+            .into_iter()
+            .map(|u| Utxo {
+                outpoint: OutPoint::new(u.txid, u.vout),
+                script_pubkey: u.script_pubkey,
+                value: u.amount,
+                height: Some(u.height),
+            })
+            .collect())
+    }
+}
+
+impl UtxoBackend for BitcoinCore {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        self.scan(scripts.iter().map(|s| json!(raw(s))).collect())
+    }
+
+    fn tip_height(&self) -> Result<Option<u32>> {
+        Ok(Some(self.tip()?.height))
+    }
+}
+
+impl BlockSource for BitcoinCore {
+    fn tip(&self) -> Result<BlockId> {
+        let info: ChainInfo = call(&self.rpc, "getblockchaininfo", json!([]))?;
+        Ok(BlockId {
+            height: info.blocks,
+            hash: info.best_block_hash,
+        })
+    }
+
+    fn block_hash(&self, height: u32) -> Result<Option<BlockHash>> {
+        match call(&self.rpc, "getblockhash", json!([height])) {
+            Ok(hash) => Ok(Some(hash)),
+            Err(Error::Rpc(RpcError::Server { code, .. })) if code == INVALID_PARAMETER => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn block(&self, hash: &BlockHash) -> Result<Block> {
+        match call::<String>(&self.rpc, "getblock", json!([hash, 0])) {
+            Ok(hex) => encode::deserialize_hex(&hex)
+                .map_err(|e| RpcError::InvalidResponse(e.to_string()).into()),
+            Err(Error::Rpc(RpcError::Server { code, .. })) if code == INVALID_ADDRESS_OR_KEY => {
+                Err(Error::UnknownBlock(*hash))
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// A watch-only descriptor wallet on a Bitcoin Core node, opened with
+/// [`BitcoinCore::wallet`].
+///
+/// The node indexes the wallet's scripts as blocks and mempool
+/// transactions arrive, so reads are cheap once the descriptors are
+/// imported. Only imported scripts are seen: import a ranged descriptor
+/// at least a gap limit past its last used index, and the node keeps the
+/// range topped up from then on.
+#[derive(Debug)]
+pub struct CoreWallet {
+    name: String,
+    rpc: JsonRpcClient,
+    slow: JsonRpcClient,
+}
+
+impl CoreWallet {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The descriptors the wallet holds, with checksums.
+    pub fn descriptors(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Listed {
+            descriptors: Vec<Entry>,
+        }
+        #[derive(Deserialize)]
+        struct Entry {
+            desc: String,
+        }
+        let listed: Listed = call(&self.rpc, "listdescriptors", json!([]))?;
+        Ok(listed.descriptors.into_iter().map(|d| d.desc).collect())
+    }
+
+    /// Imports `wallet`'s keychains, deriving up to index `range`, and
+    /// rescans from `since` (a Unix time) or not at all for `None`.
+    ///
+    /// Descriptors the wallet already holds are skipped, so importing the
+    /// same wallet again is cheap. A rescan blocks until it is done, which
+    /// takes a long while from early timestamps.
+    ///
+    /// Returns how many descriptors were imported.
+    pub fn import(
+        &self,
+        wallet: &WalletDescriptor,
+        range: u32,
+        since: Option<u64>,
+    ) -> Result<usize> {
+        let requests = wallet
+            .keychains()
+            .into_iter()
+            .map(|keychain| {
+                let descriptor = wallet.descriptor(keychain).to_string();
+                let mut request = json!({
+                    "desc": descriptor,
+                    "timestamp": timestamp(since),
+                    "internal": keychain == Keychain::Internal,
+                });
+                if wallet.is_ranged() {
+                    request["range"] = json!([0, range]);
+                    request["active"] = json!(true);
+                }
+                request
+            })
+            .collect();
+        self.import_requests(requests)
+    }
+
+    /// Imports single output scripts, e.g. for a list of addresses, as
+    /// `raw()` descriptors.
+    pub fn import_scripts(&self, scripts: &[ScriptBuf], since: Option<u64>) -> Result<usize> {
+        let requests = scripts
+            .iter()
+            .map(|script| json!({ "desc": raw(script), "timestamp": timestamp(since) }))
+            .collect();
+        self.import_requests(requests)
+    }
+
+    fn import_requests(&self, requests: Vec<Value>) -> Result<usize> {
+        let held: HashSet<String> = self.descriptors()?.into_iter().collect();
+        let requests: Vec<Value> = requests
+            .into_iter()
+            .filter(|r| !held.contains(r["desc"].as_str().unwrap_or_default()))
+            .collect();
+        if requests.is_empty() {
+            return Ok(0);
+        }
+        #[derive(Deserialize)]
+        struct Imported {
+            success: bool,
+            #[serde(default)]
+            error: Option<ImportError>,
+        }
+        #[derive(Deserialize)]
+        struct ImportError {
+            message: String,
+        }
+        let results: Vec<Imported> = call(&self.slow, "importdescriptors", json!([requests]))?;
+        for (request, result) in requests.iter().zip(&results) {
+            if !result.success {
+                return Err(Error::DescriptorImport {
+                    descriptor: request["desc"].as_str().unwrap_or_default().to_owned(),
+                    reason: result
+                        .error
+                        .as_ref()
+                        .map_or_else(|| "unknown error".to_owned(), |e| e.message.clone()),
+                });
+            }
+        }
+        Ok(requests.len())
+    }
+}
+
+impl UtxoBackend for CoreWallet {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        #[derive(Deserialize)]
+        struct Unspent {
+            txid: Txid,
+            vout: u32,
+            #[serde(rename = "scriptPubKey")]
+            script_pubkey: ScriptBuf,
+            #[serde(with = "bitcoin::amount::serde::as_btc")]
+            amount: Amount,
+            confirmations: u32,
+        }
+        let wanted: HashSet<&ScriptBuf> = scripts.iter().collect();
+        let tip: u32 = call(&self.rpc, "getblockcount", json!([]))?;
+        // Unsafe outputs are unconfirmed ones from others; they still count
+        // towards the unconfirmed balance.
+        let unspent: Vec<Unspent> =
+            call(&self.rpc, "listunspent", json!([0, 9_999_999, [], true]))?;
+        Ok(unspent
+            .into_iter()
+            .filter(|u| wanted.contains(&u.script_pubkey))
+            .map(|u| Utxo {
+                outpoint: OutPoint::new(u.txid, u.vout),
+                script_pubkey: u.script_pubkey,
+                value: u.amount,
+                height: (u.confirmations > 0).then(|| tip + 1 - u.confirmations),
+            })
+            .collect())
+    }
+
+    fn used(&self, scripts: &[ScriptBuf]) -> Result<Vec<bool>> {
+        #[derive(Deserialize)]
+        struct Received {
+            address: String,
+        }
+        let received: Vec<Received> =
+            call(&self.rpc, "listreceivedbyaddress", json!([0, false, true]))?;
+        let mut used = HashSet::new();
+        for r in received {
+            let address = bitcoin::Address::from_str(&r.address)
+                .map_err(|e| RpcError::InvalidResponse(format!("address `{}`: {e}", r.address)))?;
+            used.insert(address.assume_checked().script_pubkey());
+        }
+        Ok(scripts.iter().map(|s| used.contains(s)).collect())
+    }
+
+    fn tip_height(&self) -> Result<Option<u32>> {
+        Ok(Some(call(&self.rpc, "getblockcount", json!([]))?))
+    }
+}
+
+#[derive(Deserialize)]
+struct ChainInfo {
+    blocks: u32,
+    #[serde(rename = "bestblockhash")]
+    best_block_hash: BlockHash,
+}
+
+#[derive(Deserialize)]
+struct ScanResult {
+    success: bool,
+    unspents: Vec<ScanUnspent>,
+}
+
+#[derive(Deserialize)]
+struct ScanUnspent {
+    txid: Txid,
+    vout: u32,
+    #[serde(rename = "scriptPubKey")]
+    script_pubkey: ScriptBuf,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    amount: Amount,
+    height: u32,
+}
+
+fn client(url: &str, auth: &str) -> JsonRpcClient {
+    JsonRpcClient::new(url).with_header("Authorization", auth)
+}
+
+fn call<T: DeserializeOwned>(rpc: &JsonRpcClient, method: &str, params: Value) -> Result<T> {
+    Ok(rpc.call(method, params)?)
+}
+
+/// A `raw()` descriptor for `script`, with its checksum.
+fn raw(script: &ScriptBuf) -> String {
+    let descriptor = format!("raw({})", script.as_bytes().to_lower_hex_string());
+    let checksum = desc_checksum(&descriptor).expect("hex is valid descriptor text");
+    format!("{descriptor}#{checksum}")
+}
+
+fn timestamp(since: Option<u64>) -> Value {
+    match since {
+        Some(time) => json!(time),
+        None => json!("now"),
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::{Network, NetworkKind};
+use miniscript::descriptor::{DefiniteDescriptorKey, Descriptor, DescriptorPublicKey};
+use miniscript::ForEachKey;
+
+use crate::discovery::{Keychain, Keychains};
+use crate::{Error, Result};
+
+/// A watch-only wallet described by BIP380 output script descriptors.
+///
+/// Accepts anything miniscript can parse that has an address form, e.g.
+/// `wpkh(...)`, `tr(...)` or `sh(wsh(multi(...)))`. A trailing `#checksum`
+/// is verified when present. Ranged descriptors (`/*`) are expanded per
+/// index; a BIP389 multipath key (`/<0;1>/*`) supplies both the receive and
+/// the change descriptor at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletDescriptor {
+    external: Descriptor<DescriptorPublicKey>,
+    internal: Option<Descriptor<DescriptorPublicKey>>,
+    network: Network,
+}
+
+impl WalletDescriptor {
+    pub fn parse(s: &str, network: Network) -> Result<Self> {
+        let mut descriptors = parse_single_or_multipath(s, network)?.into_iter();
+        let external = descriptors.next().expect("at least one descriptor");
+        let internal = descriptors.next();
+        if descriptors.next().is_some() {
+            return Err(invalid(s, "multipath keys may have at most two paths"));
+        }
+        Ok(WalletDescriptor {
+            external,
+            internal,
+            network,
+        })
+    }
+
+    /// Adds a separate change descriptor, as exported by wallets that keep
+    /// `.../0/*` and `.../1/*` in two strings.
+    pub fn with_change(mut self, s: &str) -> Result<Self> {
+        if self.internal.is_some() {
+            return Err(invalid(s, "wallet already has a change descriptor"));
+        }
+        let mut descriptors = parse_single_or_multipath(s, self.network)?;
+        if descriptors.len() != 1 {
+            return Err(invalid(s, "change descriptor must not be multipath"));
+        }
+        self.internal = descriptors.pop();
+        Ok(self)
+    }
+
+    pub fn external(&self) -> &Descriptor<DescriptorPublicKey> {
+        &self.external
+    }
+
+    pub fn internal(&self) -> Option<&Descriptor<DescriptorPublicKey>> {
+        self.internal.as_ref()
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// The descriptor for `keychain`. Wallets without a change descriptor
+    /// use the receive descriptor for both.
+    pub fn descriptor(&self, keychain: Keychain) -> &Descriptor<DescriptorPublicKey> {
+        match keychain {
+            Keychain::External => &self.external,
+            Keychain::Internal => self.internal.as_ref().unwrap_or(&self.external),
+        }
+    }
+
+    /// The descriptor for one derived address, with every key fully
+    /// resolved, as needed to fill in PSBT inputs and outputs.
+    pub fn at_index(
+        &self,
+        keychain: Keychain,
+        index: u32,
+    ) -> Result<Descriptor<DefiniteDescriptorKey>> {
+        self.descriptor(keychain)
+            .at_derivation_index(index)
+            .map_err(|e| Error::InvalidDescriptor(e.to_string()))
+    }
+}
+
+impl Keychains for WalletDescriptor {
+    fn keychains(&self) -> Vec<Keychain> {
+        match self.internal {
+            Some(_) => vec![Keychain::External, Keychain::Internal],
+            None => vec![Keychain::External],
+        }
+    }
+
+    fn is_ranged(&self) -> bool {
+        self.external.has_wildcard()
+    }
+
+    fn derive(&self, keychain: Keychain, index: u32) -> Result<bitcoin::Address> {
+        let descriptor = self.at_index(keychain, index)?;
+        descriptor
+            .address(self.network)
+            .map_err(|e| Error::InvalidDescriptor(format!("{descriptor}: {e}")))
+    }
+}
+
+/// Displays the receive descriptor, followed by the change descriptor on a
+/// second line if there is one, each with its checksum.
+impl fmt::Display for WalletDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.external)?;
+        if let Some(internal) = &self.internal {
+            write!(f, "\n{internal}")?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid(descriptor: &str, reason: impl fmt::Display) -> Error {
+    Error::InvalidDescriptor(format!("`{descriptor}`: {reason}"))
+}
+
+fn parse_single_or_multipath(
+    s: &str,
+    network: Network,
+) -> Result<Vec<Descriptor<DescriptorPublicKey>>> {
+    let descriptor =
+        Descriptor::<DescriptorPublicKey>::from_str(s.trim()).map_err(|e| invalid(s, e))?;
+    descriptor.sanity_check().map_err(|e| invalid(s, e))?;
+    let expected = NetworkKind::from(network);
+    let on_network = descriptor.for_each_key(|key| match key {
+        DescriptorPublicKey::Single(_) => true,
+        DescriptorPublicKey::XPub(x) => x.xkey.network == expected,
+        DescriptorPublicKey::MultiXPub(x) => x.xkey.network == expected,
+    });
+    if !on_network {
+        return Err(invalid(s, format!("extended keys are not for {network}")));
+    }
+    if descriptor.is_multipath() {
+        descriptor
+            .into_single_descriptors()
+            .map_err(|e| invalid(s, e))
+    } else {
+        Ok(vec![descriptor])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{Xpriv, Xpub};
+    use bitcoin::secp256k1::Secp256k1;
+
+    use super::*;
+
+    fn tpub() -> String {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(NetworkKind::Test, &[7u8; 32]).unwrap();
+        Xpub::from_priv(&secp, &master).to_string()
+    }
+
+    #[test]
+    fn verifies_checksum_when_present() {
+        let plain = format!("wpkh({}/0/*)", tpub());
+        let parsed = WalletDescriptor::parse(&plain, Network::Testnet).unwrap();
+        let with_checksum = parsed.external().to_string();
+        assert!(with_checksum.contains('#'));
+        assert!(WalletDescriptor::parse(&with_checksum, Network::Testnet).is_ok());
+
+        let mut corrupted = with_checksum.clone();
+        let last = corrupted.pop().unwrap();
+        corrupted.push(if last == 'q' { 'p' } else { 'q' });
+        assert!(WalletDescriptor::parse(&corrupted, Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn enforces_key_network() {
+        let descriptor = format!("wpkh({}/0/*)", tpub());
+        assert!(WalletDescriptor::parse(&descriptor, Network::Bitcoin).is_err());
+        assert!(WalletDescriptor::parse(&descriptor, Network::Regtest).is_ok());
+    }
+
+    #[test]
+    fn splits_multipath_into_keychains() {
+        let descriptor = format!("tr({}/<0;1>/*)", tpub());
+        let wallet = WalletDescriptor::parse(&descriptor, Network::Testnet).unwrap();
+        assert_eq!(
+            wallet.keychains(),
+            vec![Keychain::External, Keychain::Internal]
+        );
+        assert_ne!(
+            wallet.derive(Keychain::External, 0).unwrap(),
+            wallet.derive(Keychain::Internal, 0).unwrap()
+        );
+        assert!(wallet.with_change(&format!("tr({}/2/*)", tpub())).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use bitcoin::ScriptBuf;
+
+use crate::{Result, UtxoBackend};
+
+/// BIP44's recommended gap limit.
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// The two address chains of an HD account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Keychain {
+    /// Receive addresses, `.../0/*`.
+    External,
+    /// Change addresses, `.../1/*`.
+    Internal,
+}
+
+impl Keychain {
+    /// The child number of this chain below the account key.
+    pub fn index(&self) -> u32 {
+        match self {
+            Keychain::External => 0,
+            Keychain::Internal => 1,
+        }
+    }
+}
+
+/// Something that derives an address for each `(keychain, index)` pair,
+/// such as an account xpub or a ranged descriptor.
+pub trait Keychains {
+    /// The keychains this wallet has. Most have both.
+    fn keychains(&self) -> Vec<Keychain> {
+        vec![Keychain::External, Keychain::Internal]
+    }
+
+    /// Whether addresses vary with the index. A non-ranged wallet has a
+    /// single address per keychain, derived at index 0.
+    fn is_ranged(&self) -> bool {
+        true
+    }
+
+    fn derive(&self, keychain: Keychain, index: u32) -> Result<bitcoin::Address>;
+}
+
+/// One address visited during [`discover`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedAddress {
+    pub keychain: Keychain,
+    pub index: u32,
+    pub address: bitcoin::Address,
+    pub used: bool,
+}
+
+/// The result of scanning a wallet's keychains.
+#[derive(Debug, Clone, Default)]
+pub struct Discovery {
+    /// Every address up to and including the last used one on each keychain.
+    pub addresses: Vec<DerivedAddress>,
+    /// The unused addresses checked after the last used one on each
+    /// keychain, up to the gap limit. Empty for a discovery rebuilt with
+    /// [`from_addresses`](Self::from_addresses).
+    pub gap: Vec<DerivedAddress>,
+    next_external: u32,
+    next_internal: u32,
+}
+
+impl Discovery {
+    /// Rebuilds a discovery from previously scanned addresses, e.g. ones
+    /// kept in a cache, so that [`discover_from`] can resume after them.
+    pub fn from_addresses(addresses: Vec<DerivedAddress>) -> Self {
+        let next = |keychain| {
+            addresses
+                .iter()
+                .filter(|a| a.keychain == keychain && a.used)
+                .map(|a| a.index + 1)
+                .max()
+                .unwrap_or(0)
+        };
+        Discovery {
+            next_external: next(Keychain::External),
+            next_internal: next(Keychain::Internal),
+            addresses,
+            gap: Vec::new(),
+        }
+    }
+
+    /// The first index after the last used address on `keychain`.
+    pub fn next_index(&self, keychain: Keychain) -> u32 {
+        match keychain {
+            Keychain::External => self.next_external,
+            Keychain::Internal => self.next_internal,
+        }
+    }
+
+    pub fn used(&self) -> impl Iterator<Item = &DerivedAddress> {
+        self.addresses.iter().filter(|a| a.used)
+    }
+
+    /// The used addresses, ready to hand to a
+    /// [`BalanceSource`](walletb_core::BalanceSource).
+    pub fn used_addresses(&self) -> Vec<walletb_core::Address> {
+        self.used()
+            .map(|a| {
+                walletb_core::Address::new(walletb_core::Chain::Bitcoin, a.address.to_string())
+            })
+            .collect()
+    }
+
+    /// The scripts of every address visited, the gap included: the ones
+    /// to watch for new payments.
+    pub fn scripts(&self) -> impl Iterator<Item = ScriptBuf> + '_ {
+        self.addresses
+            .iter()
+            .chain(&self.gap)
+            .map(|a| a.address.script_pubkey())
+    }
+}
+
+/// Walks each keychain of `wallet`, asking `backend` which addresses have
+/// been used, until `gap_limit` consecutive unused addresses follow the
+/// last used one.
+pub fn discover<K, B>(wallet: &K, backend: &B, gap_limit: u32) -> Result<Discovery>
+where
+    K: Keychains + ?Sized,
+    B: UtxoBackend + ?Sized,
+{
+    discover_from(wallet, backend, gap_limit, &Discovery::default())
+}
+
+/// Like [`discover`], but trusts `known` for every address up to its last
+/// used one and only scans beyond it. An address never becomes unused
+/// again, so only the gap after the last known use needs checking.
+pub fn discover_from<K, B>(
+    wallet: &K,
+    backend: &B,
+    gap_limit: u32,
+    known: &Discovery,
+) -> Result<Discovery>
+where
+    K: Keychains + ?Sized,
+    B: UtxoBackend + ?Sized,
+{
+    let gap_limit = gap_limit.max(1);
+    let end = if wallet.is_ranged() { u32::MAX } else { 1 };
+    let mut discovery = Discovery::default();
+    for keychain in wallet.keychains() {
+        let mut next_unused = known.next_index(keychain).min(end);
+        let mut scanned: Vec<DerivedAddress> = known
+            .addresses
+            .iter()
+            .filter(|a| a.keychain == keychain && a.index < next_unused)
+            .cloned()
+            .collect();
+        let mut start = next_unused;
+        while start < end && start - next_unused < gap_limit {
+            let batch = (start..start.saturating_add(gap_limit).min(end))
+                .map(|index| wallet.derive(keychain, index).map(|a| (index, a)))
+                .collect::<Result<Vec<_>>>()?;
+            let scripts: Vec<ScriptBuf> = batch.iter().map(|(_, a)| a.script_pubkey()).collect();
+            let used = backend.used(&scripts)?;
+            for ((index, address), used) in batch.into_iter().zip(used) {
+                // Anything past a full gap is out of reach, even if this
+                // batch happened to fetch it.
+                if index - next_unused >= gap_limit {
+                    break;
+                }
+                if used {
+                    next_unused = index + 1;
+                }
+                scanned.push(DerivedAddress {
+                    keychain,
+                    index,
+                    address,
+                    used,
+                });
+            }
+            start = start.saturating_add(gap_limit);
+        }
+        let (used, gap): (Vec<_>, Vec<_>) =
+            scanned.into_iter().partition(|a| a.index < next_unused);
+        discovery.addresses.extend(used);
+        discovery.gap.extend(gap);
+        match keychain {
+            Keychain::External => discovery.next_external = next_unused,
+            Keychain::Internal => discovery.next_internal = next_unused,
+        }
+    }
+    Ok(discovery)
+}
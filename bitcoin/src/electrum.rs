@@ -0,0 +1,581 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bitcoin::block::Header;
+use bitcoin::consensus::encode;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::hex::DisplayHex;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Transaction, Txid};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use walletb_core::{Balance, RpcError};
+
+use crate::{btc, HistoryBackend, HistoryTx, Result, Utxo, UtxoBackend};
+
+/// Protocol version requested in the `server.version` handshake.
+pub const ELECTRUM_PROTOCOL_VERSION: &str = "1.4";
+
+/// How long a call waits for its response before failing.
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The key Electrum servers index scripts by: the SHA-256 of the script,
+/// byte-reversed, in hex.
+pub fn script_hash(script: &ScriptBuf) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
+    hash.reverse();
+    hash.to_lower_hex_string()
+}
+
+/// The balance of one script as an Electrum server reports it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct ScriptBalance {
+    /// Satoshis in confirmed outputs, spent or not by the mempool.
+    pub confirmed: u64,
+    /// Net effect of mempool transactions, negative when they spend more
+    /// confirmed value than they add.
+    pub unconfirmed: i64,
+}
+
+/// One transaction in a script's history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct HistoryItem {
+    #[serde(rename = "tx_hash")]
+    pub txid: Txid,
+    /// Confirmation height; 0 or -1 while in the mempool.
+    pub height: i64,
+}
+
+impl HistoryItem {
+    pub fn is_confirmed(&self) -> bool {
+        self.height > 0
+    }
+}
+
+/// Something an Electrum server pushed after a subscription.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// The history of a subscribed script changed. `status` is the new
+    /// status hash, `None` once the history is empty.
+    Script {
+        script: ScriptBuf,
+        status: Option<String>,
+    },
+    /// A new best block.
+    Header { height: u32, header: Header },
+}
+
+/// A client for the Electrum server protocol spoken by electrs, Fulcrum
+/// and ElectrumX, over plain TCP.
+///
+/// Queries go by script hash, so a balance or history lookup costs the
+/// server an index read instead of a UTXO set scan. Calls for several
+/// scripts are pipelined on the one connection.
+///
+/// The client is also a [`UtxoBackend`] and a [`HistoryBackend`], so it can
+/// back a [`BitcoinSource`](crate::BitcoinSource) directly. After
+/// [`subscribe`](Self::subscribe), the server pushes a [`Notification`]
+/// whenever a script's history changes; read them with
+/// [`next_notification`](Self::next_notification).
+///
+/// TLS is not supported; connect to the server's TCP port, locally or
+/// through a tunnel.
+#[derive(Debug)]
+pub struct ElectrumClient {
+    /// Where the server was found, to reconnect to after a timeout.
+    addrs: Vec<SocketAddr>,
+    conn: Mutex<Connection>,
+    next_id: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// A partly received line, kept across read timeouts.
+    line: String,
+    notifications: VecDeque<Notification>,
+    subscribed: HashMap<String, ScriptBuf>,
+}
+
+impl ElectrumClient {
+    /// Connects and negotiates the protocol version.
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs().map_err(transport)?.collect();
+        let client = ElectrumClient {
+            conn: Mutex::new(Connection::open(&addrs)?),
+            addrs,
+            next_id: AtomicU64::new(1),
+        };
+        client.handshake(&mut client.conn.lock().unwrap())?;
+        Ok(client)
+    }
+
+    fn handshake(&self, conn: &mut Connection) -> Result<()> {
+        let version = json!([
+            concat!("walletb ", env!("CARGO_PKG_VERSION")),
+            ELECTRUM_PROTOCOL_VERSION
+        ]);
+        self.exchange(conn, &[("server.version", version)])?;
+        Ok(())
+    }
+
+    /// Keeps the connection alive; servers drop idle clients.
+    pub fn ping(&self) -> Result<()> {
+        self.call::<Value>("server.ping", json!([]))?;
+        Ok(())
+    }
+
+    /// `blockchain.scripthash.get_balance` for each script.
+    pub fn script_balances(&self, scripts: &[ScriptBuf]) -> Result<Vec<ScriptBalance>> {
+        self.per_script("blockchain.scripthash.get_balance", scripts)
+    }
+
+    /// `blockchain.scripthash.get_history` for each script: confirmed
+    /// transactions by height, then the mempool.
+    pub fn histories(&self, scripts: &[ScriptBuf]) -> Result<Vec<Vec<HistoryItem>>> {
+        self.per_script("blockchain.scripthash.get_history", scripts)
+    }
+
+    /// `blockchain.scripthash.listunspent` for each script.
+    pub fn list_unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Vec<Utxo>>> {
+        #[derive(Deserialize)]
+        struct Unspent {
+            tx_hash: Txid,
+            tx_pos: u32,
+            height: i64,
+            value: u64,
+        }
+
+        let lists: Vec<Vec<Unspent>> =
+            self.per_script("blockchain.scripthash.listunspent", scripts)?;
+        Ok(lists
+            .into_iter()
+            .zip(scripts)
+            .map(|(list, script)| {
+                list.into_iter()
+                    .map(|u| Utxo {
+                        outpoint: OutPoint::new(u.tx_hash, u.tx_pos),
+                        script_pubkey: script.clone(),
+                        value: Amount::from_sat(u.value),
+                        height: u32::try_from(u.height).ok().filter(|&h| h > 0),
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Subscribes to changes of each script and returns their current
+    /// status hashes (`None` for scripts without history).
+    pub fn subscribe(&self, scripts: &[ScriptBuf]) -> Result<Vec<Option<String>>> {
+        // Registered first, so that notifications arriving while the calls
+        // are answered are recognized.
+        {
+            let mut conn = self.conn.lock().unwrap();
+            for script in scripts {
+                conn.subscribed.insert(script_hash(script), script.clone());
+            }
+        }
+        self.per_script("blockchain.scripthash.subscribe", scripts)
+    }
+
+    /// Subscribes to new best blocks and returns the current tip.
+    pub fn subscribe_headers(&self) -> Result<(u32, Header)> {
+        let tip: RawHeader = self.call("blockchain.headers.subscribe", json!([]))?;
+        tip.parse()
+    }
+
+    /// Returns the next notification, waiting up to `timeout` for one to
+    /// arrive. `None` means nothing arrived in time.
+    pub fn next_notification(&self, timeout: Duration) -> Result<Option<Notification>> {
+        let mut conn = self.conn.lock().unwrap();
+        if let Some(notification) = conn.notifications.pop_front() {
+            return Ok(Some(notification));
+        }
+        // A zero timeout would mean "block forever" to the socket.
+        let timeout = timeout.max(Duration::from_millis(1));
+        conn.reader
+            .get_ref()
+            .set_read_timeout(Some(timeout))
+            .map_err(transport)?;
+        let received = conn.read_message();
+        conn.reader
+            .get_ref()
+            .set_read_timeout(Some(CALL_TIMEOUT))
+            .map_err(transport)?;
+        match received {
+            Ok(Some(message)) => {
+                if message.get("id").is_some_and(|id| !id.is_null()) {
+                    return Err(invalid("unexpected response").into());
+                }
+                conn.queue_notification(&message)?;
+                Ok(conn.notifications.pop_front())
+            }
+            Ok(None) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Fetches raw transactions by id.
+    pub fn transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>> {
+        let calls = txids
+            .iter()
+            .map(|txid| ("blockchain.transaction.get", json!([txid.to_string()])))
+            .collect::<Vec<_>>();
+        self.pipeline::<String>(&calls)?
+            .iter()
+            .map(|hex| encode::deserialize_hex(hex).map_err(|e| invalid(e.to_string()).into()))
+            .collect()
+    }
+
+    /// Fetches the headers of best-chain blocks by height.
+    pub fn block_headers(&self, heights: &[u32]) -> Result<Vec<Header>> {
+        let calls = heights
+            .iter()
+            .map(|height| ("blockchain.block.header", json!([height])))
+            .collect::<Vec<_>>();
+        self.pipeline::<String>(&calls)?
+            .iter()
+            .map(|hex| encode::deserialize_hex(hex).map_err(|e| invalid(e.to_string()).into()))
+            .collect()
+    }
+
+    fn per_script<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        scripts: &[ScriptBuf],
+    ) -> Result<Vec<T>> {
+        let calls = scripts
+            .iter()
+            .map(|script| (method, json!([script_hash(script)])))
+            .collect::<Vec<_>>();
+        self.pipeline(&calls)
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        Ok(self.pipeline(&[(method, params)])?.remove(0))
+    }
+
+    /// Sends every call before reading any response, then matches the
+    /// responses up by id. Notifications arriving in between are queued.
+    ///
+    /// Every response is read before a call's error is returned, so that
+    /// none is left on the connection. A server that stops answering, or
+    /// answers out of turn, is dropped and connected to afresh,
+    /// subscriptions and all, for the next call.
+    fn pipeline<T: DeserializeOwned>(&self, calls: &[(&str, Value)]) -> Result<Vec<T>> {
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let results = match self.exchange(&mut conn, calls) {
+            Ok(results) => results,
+            Err(e) => {
+                // Whatever the server still sends would be read as the
+                // answer to the next call.
+                if let Ok(fresh) = self.reopen(&mut conn) {
+                    *conn = fresh;
+                }
+                return Err(e);
+            }
+        };
+        results
+            .into_iter()
+            .map(|result| {
+                serde_json::from_value(result?).map_err(|e| invalid(e.to_string()).into())
+            })
+            .collect()
+    }
+
+    /// Writes `calls` and reads a response to each, in the order of the
+    /// calls.
+    fn exchange(
+        &self,
+        conn: &mut Connection,
+        calls: &[(&str, Value)],
+    ) -> Result<Vec<Result<Value>>> {
+        let first = self
+            .next_id
+            .fetch_add(calls.len() as u64, Ordering::Relaxed);
+        let mut request = String::new();
+        for (id, (method, params)) in (first..).zip(calls) {
+            let call = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+            request.push_str(&call.to_string());
+            request.push('\n');
+        }
+        conn.writer
+            .write_all(request.as_bytes())
+            .map_err(transport)?;
+
+        let mut results = BTreeMap::new();
+        while results.len() < calls.len() {
+            let Some(message) = conn.read_message()? else {
+                return Err(RpcError::Transport("timed out waiting for the server".into()).into());
+            };
+            match message.get("id").and_then(Value::as_u64) {
+                Some(id) if (first..first + calls.len() as u64).contains(&id) => {
+                    results.insert(id, into_result(message));
+                }
+                Some(id) => return Err(invalid(format!("response to unknown id {id}")).into()),
+                None => conn.queue_notification(&message)?,
+            }
+        }
+        Ok(results.into_values().collect())
+    }
+
+    /// A new connection to the server, with the handshake done and the
+    /// scripts of `old` subscribed again.
+    fn reopen(&self, old: &mut Connection) -> Result<Connection> {
+        let mut conn = Connection::open(&self.addrs)?;
+        conn.subscribed = old.subscribed.clone();
+        conn.notifications = std::mem::take(&mut old.notifications);
+        self.handshake(&mut conn)?;
+        let hashes: Vec<(&str, Value)> = conn
+            .subscribed
+            .keys()
+            .map(|hash| ("blockchain.scripthash.subscribe", json!([hash])))
+            .collect();
+        if !hashes.is_empty() {
+            self.exchange(&mut conn, &hashes)?;
+        }
+        Ok(conn)
+    }
+}
+
+impl Connection {
+    fn open(addrs: &[SocketAddr]) -> Result<Self> {
+        let stream = TcpStream::connect(addrs).map_err(transport)?;
+        stream
+            .set_read_timeout(Some(CALL_TIMEOUT))
+            .map_err(transport)?;
+        let writer = stream.try_clone().map_err(transport)?;
+        Ok(Connection {
+            reader: BufReader::new(stream),
+            writer,
+            line: String::new(),
+            notifications: VecDeque::new(),
+            subscribed: HashMap::new(),
+        })
+    }
+
+    /// Reads one message, or `None` if the read timed out.
+    fn read_message(&mut self) -> Result<Option<Value>> {
+        match self.reader.read_line(&mut self.line) {
+            Ok(0) => Err(RpcError::Transport("server closed the connection".into()).into()),
+            Ok(_) => {
+                let line = std::mem::take(&mut self.line);
+                let message = serde_json::from_str(&line).map_err(|e| invalid(e.to_string()))?;
+                Ok(Some(message))
+            }
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Ok(None)
+            }
+            Err(err) => Err(transport(err)),
+        }
+    }
+
+    fn queue_notification(&mut self, message: &Value) -> Result<()> {
+        let params = &message["params"];
+        let notification = match message["method"].as_str() {
+            Some("blockchain.scripthash.subscribe") => {
+                let Some(script) = params[0]
+                    .as_str()
+                    .and_then(|hash| self.subscribed.get(hash))
+                else {
+                    return Ok(());
+                };
+                Notification::Script {
+                    script: script.clone(),
+                    status: params[1].as_str().map(str::to_owned),
+                }
+            }
+            Some("blockchain.headers.subscribe") => {
+                let raw: RawHeader = serde_json::from_value(params[0].clone())
+                    .map_err(|e| invalid(e.to_string()))?;
+                let (height, header) = raw.parse()?;
+                Notification::Header { height, header }
+            }
+            _ => return Ok(()),
+        };
+        self.notifications.push_back(notification);
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawHeader {
+    height: u32,
+    hex: String,
+}
+
+impl RawHeader {
+    fn parse(self) -> Result<(u32, Header)> {
+        let header = encode::deserialize_hex(&self.hex).map_err(|e| invalid(e.to_string()))?;
+        Ok((self.height, header))
+    }
+}
+
+impl UtxoBackend for ElectrumClient {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        Ok(self.list_unspent(scripts)?.into_iter().flatten().collect())
+    }
+
+    fn used(&self, scripts: &[ScriptBuf]) -> Result<Vec<bool>> {
+        Ok(self
+            .histories(scripts)?
+            .iter()
+            .map(|history| !history.is_empty())
+            .collect())
+    }
+
+    /// Totals the server's per-script balances. Mempool spends of
+    /// confirmed outputs are taken off the confirmed value, so the
+    /// unconfirmed part is never negative.
+    fn balance(&self, scripts: &[ScriptBuf]) -> Result<Balance> {
+        let (confirmed, unconfirmed) = self
+            .script_balances(scripts)?
+            .iter()
+            .fold((0u64, 0i64), |(c, u), b| {
+                (c + b.confirmed, u + b.unconfirmed)
+            });
+        let confirmed = confirmed.saturating_sub(unconfirmed.min(0).unsigned_abs());
+        let unconfirmed = unconfirmed.max(0) as u64;
+        Ok(
+            Balance::new(btc(), walletb_core::Amount::from_u64(confirmed))
+                .with_unconfirmed(walletb_core::Amount::from_u64(unconfirmed)),
+        )
+    }
+
+    fn tip_height(&self) -> Result<Option<u32>> {
+        Ok(Some(self.subscribe_headers()?.0))
+    }
+
+    fn txids(&self, scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        let histories = self.histories(scripts)?;
+        Ok(Some(
+            histories.iter().flatten().map(|item| item.txid).collect(),
+        ))
+    }
+
+    /// Only the transactions of the scripts' history can have paid them,
+    /// so only those are fetched to check what an input spends.
+    fn spends_from(&self, txids: &[Txid], scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        let wanted: HashSet<&ScriptBuf> = scripts.iter().collect();
+        let history = self.txids(scripts)?.unwrap_or_default();
+        let txs = self.transactions(txids)?;
+        let funding: Vec<Txid> = txs
+            .iter()
+            .flat_map(|tx| &tx.input)
+            .map(|i| i.previous_output.txid)
+            .filter(|txid| history.contains(txid))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let funding: HashMap<Txid, Transaction> = funding
+            .iter()
+            .copied()
+            .zip(self.transactions(&funding)?)
+            .collect();
+        let spends = |tx: &Transaction| {
+            tx.input.iter().any(|i| {
+                funding
+                    .get(&i.previous_output.txid)
+                    .and_then(|prev| prev.output.get(i.previous_output.vout as usize))
+                    .is_some_and(|o| wanted.contains(&o.script_pubkey))
+            })
+        };
+        Ok(Some(
+            txids
+                .iter()
+                .zip(&txs)
+                .filter(|(_, tx)| spends(tx))
+                .map(|(txid, _)| *txid)
+                .collect(),
+        ))
+    }
+}
+
+impl HistoryBackend for ElectrumClient {
+    fn history(&self, scripts: &[ScriptBuf]) -> Result<Vec<HistoryTx>> {
+        let confirmed: BTreeMap<Txid, u32> = self
+            .histories(scripts)?
+            .into_iter()
+            .flatten()
+            .filter_map(|item| Some((item.txid, u32::try_from(item.height).ok()?)))
+            .filter(|&(_, height)| height > 0)
+            .collect();
+        let txids: Vec<Txid> = confirmed.keys().copied().collect();
+        let heights: Vec<u32> = confirmed
+            .values()
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let times: HashMap<u32, u64> = heights
+            .iter()
+            .copied()
+            .zip(self.block_headers(&heights)?)
+            .map(|(height, header)| (height, u64::from(header.time)))
+            .collect();
+        Ok(self
+            .transactions(&txids)?
+            .into_iter()
+            .zip(&txids)
+            .map(|(tx, txid)| {
+                let height = confirmed[txid];
+                HistoryTx::new(tx, height, times[&height])
+            })
+            .collect())
+    }
+}
+
+fn into_result(mut message: Value) -> Result<Value> {
+    match message.get_mut("error").map(Value::take) {
+        Some(Value::Null) | None => {}
+        Some(error) => {
+            return Err(RpcError::Server {
+                code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                message: error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+            }
+            .into())
+        }
+    }
+    message
+        .get_mut("result")
+        .map(Value::take)
+        .ok_or_else(|| invalid("missing `result`").into())
+}
+
+fn transport(err: std::io::Error) -> crate::Error {
+    RpcError::Transport(err.to_string()).into()
+}
+
+fn invalid(reason: impl Into<String>) -> RpcError {
+    RpcError::InvalidResponse(reason.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_address;
+    use bitcoin::Network;
+
+    #[test]
+    fn script_hash_matches_the_protocol_example() {
+        // The example from the Electrum protocol documentation.
+        let (address, _) =
+            parse_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", Network::Bitcoin).unwrap();
+        assert_eq!(
+            script_hash(&address.script_pubkey()),
+            "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161"
+        );
+    }
+}
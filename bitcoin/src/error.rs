@@ -0,0 +1,67 @@
+use bitcoin::Network;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid address `{address}`: {source}")]
+    InvalidAddress {
+        address: String,
+        #[source]
+        source: bitcoin::address::ParseError,
+    },
+
+    #[error("address `{address}` is not valid on {expected}")]
+    WrongNetwork { address: String, expected: Network },
+
+    #[error("address `{0}` has an unsupported script type")]
+    UnsupportedAddress(String),
+
+    #[error("invalid extended key `{key}`: {reason}")]
+    InvalidExtendedKey { key: String, reason: String },
+
+    #[error("invalid descriptor {0}")]
+    InvalidDescriptor(String),
+
+    #[error(transparent)]
+    Bip32(#[from] bitcoin::bip32::Error),
+
+    #[error(transparent)]
+    Rpc(#[from] walletb_core::RpcError),
+
+    #[error("cannot read RPC cookie {}: {source}", path.display())]
+    Cookie {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("node refused to import `{descriptor}`: {reason}")]
+    DescriptorImport { descriptor: String, reason: String },
+
+    #[error("unknown block {0}")]
+    UnknownBlock(bitcoin::BlockHash),
+
+    #[error("invalid fixture: {0}")]
+    Fixture(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<Error> for walletb_core::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::InvalidAddress { ref address, .. }
+            | Error::WrongNetwork { ref address, .. }
+            | Error::UnsupportedAddress(ref address) => walletb_core::Error::InvalidAddress {
+                address: address.clone(),
+                reason: err.to_string(),
+            },
+            other => walletb_core::Error::source(other),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bitcoin::consensus::encode;
+use bitcoin::{OutPoint, ScriptBuf, Transaction, Txid};
+use serde::Deserialize;
+use walletb_core::PointInTime;
+
+use crate::{Error, Result, Utxo, UtxoBackend};
+
+/// A confirmed transaction and the block it was mined in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryTx {
+    pub tx: Transaction,
+    pub height: u32,
+    /// Block header timestamp, in seconds since the Unix epoch.
+    pub time: u64,
+}
+
+impl HistoryTx {
+    pub fn new(tx: Transaction, height: u32, time: u64) -> Self {
+        HistoryTx { tx, height, time }
+    }
+
+    pub fn txid(&self) -> Txid {
+        self.tx.compute_txid()
+    }
+
+    pub fn is_included(&self, at: PointInTime) -> bool {
+        at.includes(u64::from(self.height), self.time)
+    }
+}
+
+/// A backend that knows the confirmed transaction history of scripts,
+/// which is what point-in-time balances are replayed from.
+pub trait HistoryBackend {
+    /// Returns every confirmed transaction that pays to one of `scripts` or
+    /// spends an output that did, in any order.
+    fn history(&self, scripts: &[ScriptBuf]) -> Result<Vec<HistoryTx>>;
+}
+
+impl<B: HistoryBackend + ?Sized> HistoryBackend for &B {
+    fn history(&self, scripts: &[ScriptBuf]) -> Result<Vec<HistoryTx>> {
+        (**self).history(scripts)
+    }
+}
+
+impl<B: HistoryBackend + ?Sized> HistoryBackend for Box<B> {
+    fn history(&self, scripts: &[ScriptBuf]) -> Result<Vec<HistoryTx>> {
+        (**self).history(scripts)
+    }
+}
+
+/// Replays `history` up to `at` and returns the outputs locked to
+/// `scripts` that were still unspent at that point.
+///
+/// Only membership matters, not order: an output counts if the
+/// transaction creating it is included and no included transaction
+/// spends it. That also makes receive-and-spend within one block come out
+/// right.
+pub fn unspent_at(history: &[HistoryTx], scripts: &[ScriptBuf], at: PointInTime) -> Vec<Utxo> {
+    let scripts: HashSet<&ScriptBuf> = scripts.iter().collect();
+    let included: Vec<&HistoryTx> = history.iter().filter(|t| t.is_included(at)).collect();
+    let spent: HashSet<OutPoint> = included
+        .iter()
+        .flat_map(|t| &t.tx.input)
+        .map(|input| input.previous_output)
+        .collect();
+    let mut unspent = Vec::new();
+    for entry in included {
+        let txid = entry.txid();
+        for (vout, output) in entry.tx.output.iter().enumerate() {
+            let outpoint = OutPoint::new(txid, vout as u32);
+            if scripts.contains(&output.script_pubkey) && !spent.contains(&outpoint) {
+                unspent.push(Utxo {
+                    outpoint,
+                    script_pubkey: output.script_pubkey.clone(),
+                    value: output.value,
+                    height: Some(entry.height),
+                });
+            }
+        }
+    }
+    unspent
+}
+
+/// A transaction history held in memory.
+///
+/// It also serves as a [`UtxoBackend`] for the current state, by replaying
+/// the whole history.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryHistory {
+    txs: Vec<HistoryTx>,
+}
+
+impl MemoryHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a confirmed transaction, replacing an earlier entry with the
+    /// same txid (e.g. after a reorg moved it to another block).
+    pub fn insert(&mut self, entry: HistoryTx) {
+        let txid = entry.txid();
+        self.txs.retain(|t| t.txid() != txid);
+        self.txs.push(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    /// Every output ever paid to `scripts`, keyed by outpoint.
+    fn funding(&self, scripts: &HashSet<&ScriptBuf>) -> HashMap<OutPoint, &ScriptBuf> {
+        let mut funding = HashMap::new();
+        for entry in &self.txs {
+            let txid = entry.txid();
+            for (vout, output) in entry.tx.output.iter().enumerate() {
+                if scripts.contains(&output.script_pubkey) {
+                    funding.insert(OutPoint::new(txid, vout as u32), &output.script_pubkey);
+                }
+            }
+        }
+        funding
+    }
+}
+
+impl FromIterator<HistoryTx> for MemoryHistory {
+    fn from_iter<I: IntoIterator<Item = HistoryTx>>(iter: I) -> Self {
+        let mut history = MemoryHistory::new();
+        for entry in iter {
+            history.insert(entry);
+        }
+        history
+    }
+}
+
+impl HistoryBackend for MemoryHistory {
+    fn history(&self, scripts: &[ScriptBuf]) -> Result<Vec<HistoryTx>> {
+        let wanted: HashSet<&ScriptBuf> = scripts.iter().collect();
+        let funding = self.funding(&wanted);
+        Ok(self
+            .txs
+            .iter()
+            .filter(|entry| {
+                entry
+                    .tx
+                    .output
+                    .iter()
+                    .any(|o| wanted.contains(&o.script_pubkey))
+                    || entry
+                        .tx
+                        .input
+                        .iter()
+                        .any(|i| funding.contains_key(&i.previous_output))
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+impl UtxoBackend for MemoryHistory {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        let tip = PointInTime::Height(u64::MAX);
+        Ok(unspent_at(&self.history(scripts)?, scripts, tip))
+    }
+
+    fn used(&self, scripts: &[ScriptBuf]) -> Result<Vec<bool>> {
+        let wanted: HashSet<&ScriptBuf> = scripts.iter().collect();
+        let funded: HashSet<&ScriptBuf> = self.funding(&wanted).into_values().collect();
+        Ok(scripts.iter().map(|s| funded.contains(s)).collect())
+    }
+
+    /// The highest block of the history, as it holds nothing newer.
+    fn tip_height(&self) -> Result<Option<u32>> {
+        Ok(self.txs.iter().map(|entry| entry.height).max())
+    }
+
+    fn txids(&self, scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        Ok(Some(
+            self.history(scripts)?.iter().map(HistoryTx::txid).collect(),
+        ))
+    }
+
+    fn spends_from(&self, txids: &[Txid], scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        let wanted: HashSet<&ScriptBuf> = scripts.iter().collect();
+        let funding = self.funding(&wanted);
+        let txids: HashSet<&Txid> = txids.iter().collect();
+        Ok(Some(
+            self.txs
+                .iter()
+                .filter(|entry| txids.contains(&entry.txid()))
+                .filter(|entry| {
+                    entry
+                        .tx
+                        .input
+                        .iter()
+                        .any(|i| funding.contains_key(&i.previous_output))
+                })
+                .map(HistoryTx::txid)
+                .collect(),
+        ))
+    }
+}
+
+/// A transaction history loaded from a JSON file of raw transactions:
+///
+/// ```json
+/// [
+///   { "tx": "02000000000101…", "height": 800000, "time": 1690168629 }
+/// ]
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileHistory {
+    path: PathBuf,
+    history: MemoryHistory,
+}
+
+#[derive(Deserialize)]
+struct HistoryEntry {
+    tx: String,
+    height: u32,
+    time: u64,
+}
+
+impl FileHistory {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let history = Self::load(&path)?;
+        Ok(FileHistory { path, history })
+    }
+
+    /// Re-reads the history from disk.
+    pub fn reload(&mut self) -> Result<()> {
+        self.history = Self::load(&self.path)?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(path: &Path) -> Result<MemoryHistory> {
+        let entries: Vec<HistoryEntry> = serde_json::from_slice(&fs::read(path)?)?;
+        entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, e)| {
+                let tx = encode::deserialize_hex(&e.tx)
+                    .map_err(|err| Error::Fixture(format!("transaction #{i}: {err}")))?;
+                Ok(HistoryTx::new(tx, e.height, e.time))
+            })
+            .collect()
+    }
+}
+
+impl HistoryBackend for FileHistory {
+    fn history(&self, scripts: &[ScriptBuf]) -> Result<Vec<HistoryTx>> {
+        self.history.history(scripts)
+    }
+}
+
+impl UtxoBackend for FileHistory {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        self.history.unspent(scripts)
+    }
+
+    fn used(&self, scripts: &[ScriptBuf]) -> Result<Vec<bool>> {
+        self.history.used(scripts)
+    }
+
+    fn tip_height(&self) -> Result<Option<u32>> {
+        self.history.tip_height()
+    }
+
+    fn txids(&self, scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        self.history.txids(scripts)
+    }
+
+    fn spends_from(&self, txids: &[Txid], scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        self.history.spends_from(txids, scripts)
+    }
+}
@@ -0,0 +1,65 @@
+//! Bitcoin support for walletb.
+//!
+//! [`BitcoinSource`] implements [`walletb_core::BalanceSource`] on top of a
+//! pluggable [`UtxoBackend`]. The backends in this crate read a UTXO set
+//! snapshot from memory or from a JSON file, which is what the tests use in
+//! place of a live node.
+//!
+//! HD wallets are handled by [`discover`], which walks the receive and
+//! change chains of anything implementing [`Keychains`] (an [`AccountXpub`]
+//! or a [`WalletDescriptor`]) up to a gap limit and yields the used
+//! addresses.
+//!
+//! Balances at a past block or date come from replaying confirmed
+//! transactions supplied by a [`HistoryBackend`] such as [`MemoryHistory`]
+//! or [`FileHistory`].
+//!
+//! [`ElectrumClient`] is a [`UtxoBackend`] and [`HistoryBackend`] that
+//! queries an Electrum server (electrs, Fulcrum) by script hash and can
+//! subscribe to changes.
+//!
+//! [`BitcoinCore`] talks to a Bitcoin Core node over JSON-RPC: it scans the
+//! UTXO set with `scantxoutset` for one-off checks, or reads a watch-only
+//! [`CoreWallet`] that the wallet's descriptors were imported into.
+//!
+//! A [`BlockSource`] serves the best chain block by block for incremental
+//! sync; [`MemoryChain`] is an in-memory chain that tests can reorganize.
+
+mod address;
+mod chain;
+mod core_rpc;
+mod descriptor;
+mod discovery;
+mod electrum;
+mod error;
+mod history;
+mod source;
+mod utxo;
+mod xpub;
+
+pub use bitcoin::Network;
+pub use miniscript;
+
+pub use address::{parse_address, AddressKind};
+pub use chain::{BlockId, BlockSource, MemoryChain};
+pub use core_rpc::{BitcoinCore, CoreAuth, CoreWallet};
+pub use descriptor::WalletDescriptor;
+pub use discovery::{
+    discover, discover_from, DerivedAddress, Discovery, Keychain, Keychains, DEFAULT_GAP_LIMIT,
+};
+pub use electrum::{
+    script_hash, ElectrumClient, HistoryItem, Notification, ScriptBalance,
+    ELECTRUM_PROTOCOL_VERSION,
+};
+pub use error::{Error, Result};
+pub use history::{unspent_at, FileHistory, HistoryBackend, HistoryTx, MemoryHistory};
+pub use source::BitcoinSource;
+pub use utxo::{FileUtxoSet, MemoryUtxoSet, Utxo, UtxoBackend};
+pub use xpub::{AccountXpub, ScriptType};
+
+use walletb_core::{Asset, Chain};
+
+/// The native bitcoin asset, denominated in satoshis.
+pub fn btc() -> Asset {
+    Asset::native(Chain::Bitcoin, "BTC", 8)
+}
@@ -0,0 +1,211 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use bitcoin::{Network, OutPoint, ScriptBuf};
+use walletb_core::{
+    Address, Amount, Balance, BalanceSource, Chain, HistoricalSource, LedgerEntry, Period,
+    PointInTime, TransactionSource,
+};
+
+use crate::{
+    btc, discover, discover_from, parse_address, unspent_at, Discovery, HistoryBackend, Keychains,
+    Result, UtxoBackend,
+};
+
+/// A [`BalanceSource`] that totals what a [`UtxoBackend`] reports for a set
+/// of addresses.
+#[derive(Debug, Clone)]
+pub struct BitcoinSource<B> {
+    network: Network,
+    backend: B,
+}
+
+impl<B: UtxoBackend> BitcoinSource<B> {
+    pub fn new(network: Network, backend: B) -> Self {
+        BitcoinSource { network, backend }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Parses and validates `addresses`, returning their distinct output
+    /// scripts.
+    pub fn scripts_for(&self, addresses: &[Address]) -> walletb_core::Result<Vec<ScriptBuf>> {
+        let mut scripts = BTreeSet::new();
+        for address in addresses {
+            address.expect_chain(Chain::Bitcoin)?;
+            let (parsed, _) = parse_address(address.as_str(), self.network)?;
+            scripts.insert(parsed.script_pubkey());
+        }
+        Ok(scripts.into_iter().collect())
+    }
+
+    /// Sums the confirmed and unconfirmed value locked to `scripts`.
+    pub fn script_balance(&self, scripts: &[ScriptBuf]) -> Result<Balance> {
+        self.backend.balance(scripts)
+    }
+
+    /// Scans `wallet`'s keychains against this source's backend.
+    pub fn discover<K: Keychains + ?Sized>(&self, wallet: &K, gap_limit: u32) -> Result<Discovery> {
+        discover(wallet, &self.backend, gap_limit)
+    }
+
+    /// Resumes a scan of `wallet` after the addresses `known` to be used.
+    pub fn discover_from<K: Keychains + ?Sized>(
+        &self,
+        wallet: &K,
+        gap_limit: u32,
+        known: &Discovery,
+    ) -> Result<Discovery> {
+        discover_from(wallet, &self.backend, gap_limit, known)
+    }
+
+    /// Discovers `wallet`'s used addresses and returns their combined
+    /// balance through [`BalanceSource::balances`].
+    pub fn wallet_balances<K: Keychains + ?Sized>(
+        &self,
+        wallet: &K,
+        gap_limit: u32,
+    ) -> walletb_core::Result<Vec<Balance>> {
+        let discovery = self.discover(wallet, gap_limit)?;
+        self.balances(&discovery.used_addresses())
+    }
+}
+
+impl<B: UtxoBackend + HistoryBackend> BitcoinSource<B> {
+    /// Replays the history of `scripts` to find what they held at `at`.
+    pub fn script_balance_at(&self, scripts: &[ScriptBuf], at: PointInTime) -> Result<Balance> {
+        let history = self.backend.history(scripts)?;
+        let value: bitcoin::Amount = unspent_at(&history, scripts, at)
+            .iter()
+            .map(|u| u.value)
+            .sum();
+        Ok(Balance::new(btc(), Amount::from_u64(value.to_sat())))
+    }
+
+    /// The ledger of `scripts` over `period`, one entry per transaction.
+    ///
+    /// Only the funding of `scripts` is known, so a transaction's fee is
+    /// counted when every input spent one of them; a transaction others
+    /// paid into too, such as a coinjoin, is reported by its net effect.
+    /// Payees are named as counterparties; payers are not, as an input
+    /// does not say whose output it spends.
+    pub fn script_transactions(
+        &self,
+        scripts: &[ScriptBuf],
+        period: Period,
+    ) -> Result<Vec<LedgerEntry>> {
+        let mut history = self.backend.history(scripts)?;
+        history.sort_by_key(|entry| (entry.height, entry.txid()));
+        let ours: HashSet<&ScriptBuf> = scripts.iter().collect();
+        let mut funding: HashMap<OutPoint, u64> = HashMap::new();
+        for entry in &history {
+            let txid = entry.txid();
+            for (vout, output) in entry.tx.output.iter().enumerate() {
+                if ours.contains(&output.script_pubkey) {
+                    funding.insert(OutPoint::new(txid, vout as u32), output.value.to_sat());
+                }
+            }
+        }
+        let mut entries = Vec::new();
+        for entry in &history {
+            if !period.contains(u64::from(entry.height), entry.time) {
+                continue;
+            }
+            let tx = &entry.tx;
+            let received: u64 = tx
+                .output
+                .iter()
+                .filter(|o| ours.contains(&o.script_pubkey))
+                .map(|o| o.value.to_sat())
+                .sum();
+            let spent: Vec<Option<u64>> = tx
+                .input
+                .iter()
+                .map(|i| funding.get(&i.previous_output).copied())
+                .collect();
+            let spent_total: u64 = spent.iter().flatten().sum();
+            if received == 0 && spent_total == 0 {
+                continue;
+            }
+            let fee = if spent.iter().all(Option::is_some) {
+                let paid_out: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
+                spent_total.saturating_sub(paid_out)
+            } else {
+                0
+            };
+            let (received, sent) = if spent_total >= received + fee {
+                (0, spent_total - received - fee)
+            } else {
+                (received - spent_total, 0)
+            };
+            let counterparty = tx
+                .output
+                .iter()
+                .find(|o| sent > 0 && !ours.contains(&o.script_pubkey))
+                .and_then(|o| bitcoin::Address::from_script(&o.script_pubkey, self.network).ok())
+                .map(|address| address.to_string());
+            entries.push(LedgerEntry {
+                txid: entry.txid().to_string(),
+                height: u64::from(entry.height),
+                time: entry.time,
+                asset: btc(),
+                received: Amount::from_u64(received),
+                sent: Amount::from_u64(sent),
+                fee: Amount::from_u64(fee),
+                counterparty,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Discovers `wallet`'s used addresses and returns their combined
+    /// balance at `at`. Discovery looks at the whole history, so addresses
+    /// first used after `at` are scanned too and simply contribute zero.
+    pub fn wallet_balances_at<K: Keychains + ?Sized>(
+        &self,
+        wallet: &K,
+        gap_limit: u32,
+        at: PointInTime,
+    ) -> walletb_core::Result<Vec<Balance>> {
+        let discovery = self.discover(wallet, gap_limit)?;
+        self.balances_at(&discovery.used_addresses(), at)
+    }
+}
+
+impl<B: UtxoBackend> BalanceSource for BitcoinSource<B> {
+    fn chain(&self) -> Chain {
+        Chain::Bitcoin
+    }
+
+    fn balances(&self, addresses: &[Address]) -> walletb_core::Result<Vec<Balance>> {
+        let scripts = self.scripts_for(addresses)?;
+        Ok(vec![self.script_balance(&scripts)?])
+    }
+}
+
+impl<B: UtxoBackend + HistoryBackend> HistoricalSource for BitcoinSource<B> {
+    fn balances_at(
+        &self,
+        addresses: &[Address],
+        at: PointInTime,
+    ) -> walletb_core::Result<Vec<Balance>> {
+        let scripts = self.scripts_for(addresses)?;
+        Ok(vec![self.script_balance_at(&scripts, at)?])
+    }
+}
+
+impl<B: UtxoBackend + HistoryBackend> TransactionSource for BitcoinSource<B> {
+    fn transactions(
+        &self,
+        addresses: &[Address],
+        period: Period,
+    ) -> walletb_core::Result<Vec<LedgerEntry>> {
+        let scripts = self.scripts_for(addresses)?;
+        Ok(self.script_transactions(&scripts, period)?)
+    }
+}
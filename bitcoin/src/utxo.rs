@@ -0,0 +1,333 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bitcoin::{Amount, OutPoint, ScriptBuf, Txid};
+use serde::Deserialize;
+use walletb_core::Balance;
+
+use crate::{btc, Error, Result};
+
+/// An unspent transaction output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub script_pubkey: ScriptBuf,
+    pub value: Amount,
+    /// Height of the block that confirmed the output, or `None` while it is
+    /// still in the mempool.
+    pub height: Option<u32>,
+}
+
+impl Utxo {
+    pub fn is_confirmed(&self) -> bool {
+        self.height.is_some()
+    }
+}
+
+/// Where a [`BitcoinSource`](crate::BitcoinSource) gets its UTXOs from.
+///
+/// A backend only has to answer "what is unspent for these scripts"; address
+/// parsing and summing happen in the source.
+pub trait UtxoBackend {
+    /// Returns every unspent output locked to one of `scripts`.
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>>;
+
+    /// Reports, for each script, whether it has ever received funds. Used
+    /// for gap-limit address discovery.
+    ///
+    /// The default only sees unspent outputs, so an address that was funded
+    /// and then emptied looks unused. Backends with transaction history
+    /// should override it.
+    fn used(&self, scripts: &[ScriptBuf]) -> Result<Vec<bool>> {
+        let funded: HashSet<ScriptBuf> = self
+            .unspent(scripts)?
+            .into_iter()
+            .map(|u| u.script_pubkey)
+            .collect();
+        Ok(scripts.iter().map(|s| funded.contains(s)).collect())
+    }
+
+    /// Returns the confirmed and unconfirmed value locked to `scripts`.
+    ///
+    /// The default sums [`unspent`](Self::unspent). Backends that can total
+    /// balances server-side should override it.
+    fn balance(&self, scripts: &[ScriptBuf]) -> Result<Balance> {
+        let mut confirmed = walletb_core::Amount::ZERO;
+        let mut unconfirmed = walletb_core::Amount::ZERO;
+        for utxo in self.unspent(scripts)? {
+            let value = walletb_core::Amount::from_u64(utxo.value.to_sat());
+            if utxo.is_confirmed() {
+                confirmed = confirmed + value;
+            } else {
+                unconfirmed = unconfirmed + value;
+            }
+        }
+        Ok(Balance::new(btc(), confirmed).with_unconfirmed(unconfirmed))
+    }
+
+    /// Height of the best block, if the backend follows the chain; needed
+    /// to count an output's confirmations.
+    fn tip_height(&self) -> Result<Option<u32>> {
+        Ok(None)
+    }
+
+    /// Every transaction paying to or spending from `scripts` that the
+    /// backend still knows of, confirmed or in the mempool, or `None` if it
+    /// cannot tell. This is what tells an output that left the UTXO set
+    /// because it was spent from one whose transaction was reorganized out.
+    fn txids(&self, scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        let _ = scripts;
+        Ok(None)
+    }
+
+    /// Which of `txids` spend an output locked to one of `scripts`, so that
+    /// what they pay back to `scripts` is change; `None` if the backend
+    /// cannot tell.
+    fn spends_from(&self, txids: &[Txid], scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        let _ = (txids, scripts);
+        Ok(None)
+    }
+}
+
+impl<B: UtxoBackend + ?Sized> UtxoBackend for &B {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        (**self).unspent(scripts)
+    }
+
+    fn used(&self, scripts: &[ScriptBuf]) -> Result<Vec<bool>> {
+        (**self).used(scripts)
+    }
+
+    fn balance(&self, scripts: &[ScriptBuf]) -> Result<Balance> {
+        (**self).balance(scripts)
+    }
+
+    fn tip_height(&self) -> Result<Option<u32>> {
+        (**self).tip_height()
+    }
+
+    fn txids(&self, scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        (**self).txids(scripts)
+    }
+
+    fn spends_from(&self, txids: &[Txid], scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        (**self).spends_from(txids, scripts)
+    }
+}
+
+impl<B: UtxoBackend + ?Sized> UtxoBackend for Box<B> {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        (**self).unspent(scripts)
+    }
+
+    fn used(&self, scripts: &[ScriptBuf]) -> Result<Vec<bool>> {
+        (**self).used(scripts)
+    }
+
+    fn balance(&self, scripts: &[ScriptBuf]) -> Result<Balance> {
+        (**self).balance(scripts)
+    }
+
+    fn tip_height(&self) -> Result<Option<u32>> {
+        (**self).tip_height()
+    }
+
+    fn txids(&self, scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        (**self).txids(scripts)
+    }
+
+    fn spends_from(&self, txids: &[Txid], scripts: &[ScriptBuf]) -> Result<Option<HashSet<Txid>>> {
+        (**self).spends_from(txids, scripts)
+    }
+}
+
+/// A UTXO set held in memory, indexed by script.
+///
+/// It also remembers every script that was ever inserted, so spending an
+/// output does not make its address look unused.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryUtxoSet {
+    by_script: HashMap<ScriptBuf, Vec<Utxo>>,
+}
+
+impl MemoryUtxoSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `utxo`, replacing any existing entry for the same outpoint.
+    pub fn insert(&mut self, utxo: Utxo) {
+        let entries = self
+            .by_script
+            .entry(utxo.script_pubkey.clone())
+            .or_default();
+        entries.retain(|u| u.outpoint != utxo.outpoint);
+        entries.push(utxo);
+    }
+
+    /// Removes the output at `outpoint`, returning it if it was present.
+    /// Its script stays known as used.
+    pub fn spend(&mut self, outpoint: &OutPoint) -> Option<Utxo> {
+        for entries in self.by_script.values_mut() {
+            if let Some(pos) = entries.iter().position(|u| &u.outpoint == outpoint) {
+                return Some(entries.swap_remove(pos));
+            }
+        }
+        None
+    }
+
+    /// Number of unspent outputs.
+    pub fn len(&self) -> usize {
+        self.by_script.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl FromIterator<Utxo> for MemoryUtxoSet {
+    fn from_iter<I: IntoIterator<Item = Utxo>>(iter: I) -> Self {
+        let mut set = MemoryUtxoSet::new();
+        for utxo in iter {
+            set.insert(utxo);
+        }
+        set
+    }
+}
+
+impl UtxoBackend for MemoryUtxoSet {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        Ok(scripts
+            .iter()
+            .filter_map(|s| self.by_script.get(s))
+            .flatten()
+            .cloned()
+            .collect())
+    }
+
+    fn used(&self, scripts: &[ScriptBuf]) -> Result<Vec<bool>> {
+        // Spent outputs leave an empty entry behind for their script.
+        Ok(scripts
+            .iter()
+            .map(|s| self.by_script.contains_key(s))
+            .collect())
+    }
+}
+
+/// A UTXO set snapshot loaded from a JSON file.
+///
+/// The file holds an array of outputs. Each output names its script either
+/// directly as hex or through an address:
+///
+/// ```json
+/// [
+///   { "txid": "…", "vout": 0, "address": "bc1q…", "value": 150000, "height": 800000 },
+///   { "txid": "…", "vout": 1, "script_pubkey": "0014…", "value": 2500, "height": null }
+/// ]
+/// ```
+///
+/// A missing or `null` height marks a mempool output.
+#[derive(Debug, Clone)]
+pub struct FileUtxoSet {
+    path: PathBuf,
+    set: MemoryUtxoSet,
+}
+
+#[derive(Deserialize)]
+struct FixtureEntry {
+    txid: Txid,
+    vout: u32,
+    #[serde(default)]
+    address: Option<bitcoin::Address<bitcoin::address::NetworkUnchecked>>,
+    #[serde(default)]
+    script_pubkey: Option<ScriptBuf>,
+    value: u64,
+    #[serde(default)]
+    height: Option<u32>,
+}
+
+impl FileUtxoSet {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let set = Self::load(&path)?;
+        Ok(FileUtxoSet { path, set })
+    }
+
+    /// Re-reads the snapshot from disk.
+    pub fn reload(&mut self) -> Result<()> {
+        self.set = Self::load(&self.path)?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(path: &Path) -> Result<MemoryUtxoSet> {
+        let entries: Vec<FixtureEntry> = serde_json::from_slice(&fs::read(path)?)?;
+        entries
+            .into_iter()
+            .map(|e| {
+                let script_pubkey = match (e.script_pubkey, e.address) {
+                    (Some(script), _) => script,
+                    // The snapshot is trusted; network checks apply to the
+                    // addresses being queried, not to the fixture.
+                    (None, Some(address)) => address.assume_checked().script_pubkey(),
+                    (None, None) => {
+                        return Err(Error::Fixture(format!(
+                            "{}:{} has neither `address` nor `script_pubkey`",
+                            e.txid, e.vout
+                        )))
+                    }
+                };
+                Ok(Utxo {
+                    outpoint: OutPoint::new(e.txid, e.vout),
+                    script_pubkey,
+                    value: Amount::from_sat(e.value),
+                    height: e.height,
+                })
+            })
+            .collect()
+    }
+}
+
+impl UtxoBackend for FileUtxoSet {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> Result<Vec<Utxo>> {
+        self.set.unspent(scripts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn utxo(n: u8, script: &ScriptBuf, sats: u64) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(Txid::from_byte_array([n; 32]), 0),
+            script_pubkey: script.clone(),
+            value: Amount::from_sat(sats),
+            height: Some(1),
+        }
+    }
+
+    #[test]
+    fn insert_replaces_same_outpoint_and_spend_removes() {
+        let script = ScriptBuf::from_bytes(vec![0x51]);
+        let mut set = MemoryUtxoSet::new();
+        set.insert(utxo(1, &script, 10));
+        set.insert(utxo(1, &script, 20));
+        set.insert(utxo(2, &script, 5));
+        assert_eq!(set.len(), 2);
+
+        let spent = set.spend(&OutPoint::new(Txid::from_byte_array([1; 32]), 0));
+        assert_eq!(spent.unwrap().value, Amount::from_sat(20));
+        assert_eq!(set.unspent(std::slice::from_ref(&script)).unwrap().len(), 1);
+
+        set.spend(&OutPoint::new(Txid::from_byte_array([2; 32]), 0));
+        assert!(set.is_empty());
+        assert_eq!(set.used(&[script]).unwrap(), vec![true]);
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::base58;
+use bitcoin::bip32::{ChildNumber, Xpub};
+use bitcoin::secp256k1::{Secp256k1, VerifyOnly};
+use bitcoin::{Network, NetworkKind};
+
+use crate::discovery::{Keychain, Keychains};
+use crate::{Error, Result, WalletDescriptor};
+
+/// The output script an HD account pays to, named after the BIP that
+/// defines its derivation path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptType {
+    /// BIP44 legacy pay-to-pubkey-hash.
+    P2pkh,
+    /// BIP49 P2WPKH nested in P2SH.
+    P2shP2wpkh,
+    /// BIP84 native segwit v0.
+    P2wpkh,
+    /// BIP86 single-key taproot.
+    P2tr,
+}
+
+impl ScriptType {
+    /// The BIP43 purpose field of the account's derivation path.
+    pub fn purpose(&self) -> u32 {
+        match self {
+            ScriptType::P2pkh => 44,
+            ScriptType::P2shP2wpkh => 49,
+            ScriptType::P2wpkh => 84,
+            ScriptType::P2tr => 86,
+        }
+    }
+}
+
+impl fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ScriptType::P2pkh => "p2pkh",
+            ScriptType::P2shP2wpkh => "p2sh-p2wpkh",
+            ScriptType::P2wpkh => "p2wpkh",
+            ScriptType::P2tr => "p2tr",
+        })
+    }
+}
+
+/// Accepts the script names used by [`Display`](fmt::Display) as well as
+/// the BIP numbers (`bip44`, `bip49`, `bip84`, `bip86`).
+impl FromStr for ScriptType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "p2pkh" | "bip44" | "bip32" => Ok(ScriptType::P2pkh),
+            "p2sh-p2wpkh" | "bip49" => Ok(ScriptType::P2shP2wpkh),
+            "p2wpkh" | "bip84" => Ok(ScriptType::P2wpkh),
+            "p2tr" | "bip86" => Ok(ScriptType::P2tr),
+            _ => Err(Error::InvalidExtendedKey {
+                key: s.to_owned(),
+                reason: "unknown script type".into(),
+            }),
+        }
+    }
+}
+
+const XPUB: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+/// SLIP-132 version bytes and the script type they imply.
+#[rustfmt::skip]
+const VERSIONS: [([u8; 4], NetworkKind, ScriptType); 6] = [
+    (XPUB,                     NetworkKind::Main, ScriptType::P2pkh),
+    (TPUB,                     NetworkKind::Test, ScriptType::P2pkh),
+    ([0x04, 0x9d, 0x7c, 0xb2], NetworkKind::Main, ScriptType::P2shP2wpkh), // ypub
+    ([0x04, 0x4a, 0x52, 0x62], NetworkKind::Test, ScriptType::P2shP2wpkh), // upub
+    ([0x04, 0xb2, 0x47, 0x46], NetworkKind::Main, ScriptType::P2wpkh),     // zpub
+    ([0x04, 0x5f, 0x1c, 0xf6], NetworkKind::Test, ScriptType::P2wpkh),     // vpub
+];
+
+/// An account-level extended public key (`m/purpose'/coin'/account'`) from
+/// which receive and change addresses are derived as `<keychain>/<index>`.
+#[derive(Debug, Clone)]
+pub struct AccountXpub {
+    xpub: Xpub,
+    script_type: ScriptType,
+    network: Network,
+    secp: Secp256k1<VerifyOnly>,
+}
+
+impl AccountXpub {
+    pub fn new(xpub: Xpub, script_type: ScriptType, network: Network) -> Self {
+        AccountXpub {
+            xpub,
+            script_type,
+            network,
+            secp: Secp256k1::verification_only(),
+        }
+    }
+
+    /// Parses an `xpub`/`ypub`/`zpub` (or testnet `tpub`/`upub`/`vpub`)
+    /// string. The prefix selects the script type; plain `xpub`/`tpub` keys
+    /// default to P2PKH and can be switched with
+    /// [`with_script_type`](Self::with_script_type), e.g. for BIP86.
+    pub fn parse(s: &str, network: Network) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidExtendedKey {
+            key: s.to_owned(),
+            reason: reason.to_owned(),
+        };
+        let mut data = base58::decode_check(s).map_err(|_| invalid("bad base58 checksum"))?;
+        if data.len() != 78 {
+            return Err(invalid("wrong length"));
+        }
+        let (kind, script_type) = VERSIONS
+            .iter()
+            .find(|(version, ..)| data[..4] == version[..])
+            .map(|(_, kind, script_type)| (*kind, *script_type))
+            .ok_or_else(|| invalid("unknown version bytes"))?;
+        if kind != NetworkKind::from(network) {
+            return Err(invalid(&format!("not a key for {network}")));
+        }
+        data[..4].copy_from_slice(match kind {
+            NetworkKind::Main => &XPUB,
+            NetworkKind::Test => &TPUB,
+        });
+        let xpub = Xpub::decode(&data).map_err(|e| invalid(&e.to_string()))?;
+        Ok(AccountXpub::new(xpub, script_type, network))
+    }
+
+    pub fn with_script_type(mut self, script_type: ScriptType) -> Self {
+        self.script_type = script_type;
+        self
+    }
+
+    pub fn xpub(&self) -> &Xpub {
+        &self.xpub
+    }
+
+    pub fn script_type(&self) -> ScriptType {
+        self.script_type
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// The same account as a descriptor wallet, e.g.
+    /// `wpkh(xpub…/<0;1>/*)`, for tools that only take descriptors. The key
+    /// carries no origin, since an account xpub does not record its path.
+    pub fn to_descriptor(&self) -> WalletDescriptor {
+        let key = format!("{}/<0;1>/*", self.xpub);
+        let descriptor = match self.script_type {
+            ScriptType::P2pkh => format!("pkh({key})"),
+            ScriptType::P2shP2wpkh => format!("sh(wpkh({key}))"),
+            ScriptType::P2wpkh => format!("wpkh({key})"),
+            ScriptType::P2tr => format!("tr({key})"),
+        };
+        WalletDescriptor::parse(&descriptor, self.network)
+            .expect("descriptor built from a valid xpub")
+    }
+}
+
+impl Keychains for AccountXpub {
+    fn derive(&self, keychain: Keychain, index: u32) -> Result<bitcoin::Address> {
+        let path = [
+            ChildNumber::from_normal_idx(keychain.index())?,
+            ChildNumber::from_normal_idx(index)?,
+        ];
+        let child = self.xpub.derive_pub(&self.secp, &path)?;
+        let network = self.network;
+        Ok(match self.script_type {
+            ScriptType::P2pkh => bitcoin::Address::p2pkh(child.to_pub(), network),
+            ScriptType::P2shP2wpkh => bitcoin::Address::p2shwpkh(&child.to_pub(), network),
+            ScriptType::P2wpkh => bitcoin::Address::p2wpkh(&child.to_pub(), network),
+            ScriptType::P2tr => {
+                bitcoin::Address::p2tr(&self.secp, child.to_x_only_pub(), None, network)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Account keys for the "abandon ... about" mnemonic, from the BIP49,
+    // BIP84 and BIP86 test vectors.
+    const BIP44: &str = "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj";
+    const BIP49: &str = "ypub6Ww3ibxVfGzLrAH1PNcjyAWenMTbbAosGNB6VvmSEgytSER9azLDWCxoJwW7Ke7icmizBMXrzBx9979FfaHxHcrArf3zbeJJJUZPf663zsP";
+    const BIP84: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+    const BIP86: &str = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+
+    fn derive(account: &AccountXpub, keychain: Keychain, index: u32) -> String {
+        account.derive(keychain, index).unwrap().to_string()
+    }
+
+    #[test]
+    fn infers_script_type_from_prefix() {
+        let cases = [
+            (
+                BIP44,
+                ScriptType::P2pkh,
+                "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA",
+            ),
+            (
+                BIP49,
+                ScriptType::P2shP2wpkh,
+                "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf",
+            ),
+            (
+                BIP84,
+                ScriptType::P2wpkh,
+                "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu",
+            ),
+        ];
+        for (key, script_type, first) in cases {
+            let account = AccountXpub::parse(key, Network::Bitcoin).unwrap();
+            assert_eq!(account.script_type(), script_type);
+            assert_eq!(derive(&account, Keychain::External, 0), first);
+        }
+    }
+
+    #[test]
+    fn derives_receive_and_change_chains() {
+        let account = AccountXpub::parse(BIP84, Network::Bitcoin).unwrap();
+        assert_eq!(
+            derive(&account, Keychain::External, 1),
+            "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g"
+        );
+        assert_eq!(
+            derive(&account, Keychain::Internal, 0),
+            "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+        );
+    }
+
+    #[test]
+    fn derives_bip86_taproot_from_xpub() {
+        let account = AccountXpub::parse(BIP86, Network::Bitcoin)
+            .unwrap()
+            .with_script_type("bip86".parse().unwrap());
+        assert_eq!(
+            derive(&account, Keychain::External, 0),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+    }
+
+    #[test]
+    fn converts_to_an_equivalent_descriptor() {
+        for key in [BIP44, BIP49, BIP84, BIP86] {
+            let account = AccountXpub::parse(key, Network::Bitcoin).unwrap();
+            let descriptor = account.to_descriptor();
+            for keychain in [Keychain::External, Keychain::Internal] {
+                assert_eq!(
+                    descriptor.derive(keychain, 3).unwrap(),
+                    account.derive(keychain, 3).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_network_and_corrupt_keys() {
+        assert!(AccountXpub::parse(BIP84, Network::Testnet).is_err());
+        let mut corrupt = BIP84.to_owned();
+        corrupt.replace_range(10..11, "X");
+        assert!(AccountXpub::parse(&corrupt, Network::Bitcoin).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use bitcoin::base64::engine::general_purpose::STANDARD as BASE64;
+use bitcoin::base64::Engine;
+use bitcoin::hashes::Hash;
+use bitcoin::{BlockHash, ScriptBuf};
+use serde_json::{json, Value};
+use walletb_bitcoin::{
+    AccountXpub, BitcoinCore, BitcoinSource, BlockSource, CoreAuth, Error, Keychain, Keychains,
+    Network, UtxoBackend,
+};
+use walletb_core::testing::{MockServer, RpcFailure};
+
+const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+const PAYEE: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+fn account() -> AccountXpub {
+    AccountXpub::parse(ZPUB, Network::Bitcoin).unwrap()
+}
+
+fn script(keychain: Keychain, index: u32) -> ScriptBuf {
+    account().derive(keychain, index).unwrap().script_pubkey()
+}
+
+/// A node that replays the responses recorded in
+/// `tests/fixtures/core_rpc.json`. A recording without `params` answers any
+/// call of its method that no other recording matches exactly.
+fn node() -> MockServer {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/core_rpc.json");
+    let recorded: Vec<Value> =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    MockServer::json_rpc(move |method, params| {
+        let of_method = || recorded.iter().filter(|r| r["method"] == method);
+        let Some(recording) = of_method()
+            .find(|r| r.get("params") == Some(params))
+            .or_else(|| of_method().find(|r| r.get("params").is_none()))
+        else {
+            return Err(RpcFailure::method_not_found(method));
+        };
+        match recording.get("error") {
+            Some(error) => Err(RpcFailure::new(
+                error["code"].as_i64().unwrap(),
+                error["message"].as_str().unwrap(),
+            )),
+            None => Ok(recording["result"].clone()),
+        }
+    })
+}
+
+fn user_pass() -> CoreAuth {
+    CoreAuth::UserPass {
+        user: "walletb".into(),
+        password: "hunter2".into(),
+    }
+}
+
+#[test]
+fn authenticates_with_a_cookie_or_a_password() {
+    let node = node();
+    let cookie = std::env::temp_dir().join(format!("walletb-core-{}.cookie", std::process::id()));
+    std::fs::write(&cookie, "__cookie__:5f1e0c7a9b\n").unwrap();
+
+    BitcoinCore::new(node.url(), &CoreAuth::Cookie(cookie.clone()))
+        .unwrap()
+        .tip()
+        .unwrap();
+    BitcoinCore::new(node.url(), &user_pass())
+        .unwrap()
+        .tip()
+        .unwrap();
+    std::fs::remove_file(&cookie).unwrap();
+
+    let headers: Vec<String> = node
+        .requests()
+        .iter()
+        .map(|r| r.header("Authorization").unwrap().to_owned())
+        .collect();
+    assert_eq!(
+        headers,
+        [
+            format!("Basic {}", BASE64.encode("__cookie__:5f1e0c7a9b")),
+            format!("Basic {}", BASE64.encode("walletb:hunter2")),
+        ]
+    );
+
+    assert!(matches!(
+        BitcoinCore::new(node.url(), &CoreAuth::Cookie(cookie)),
+        Err(Error::Cookie { .. })
+    ));
+}
+
+#[test]
+fn scans_the_utxo_set_for_one_off_checks() {
+    let node = node();
+    let source = BitcoinSource::new(
+        Network::Bitcoin,
+        BitcoinCore::new(node.url(), &user_pass()).unwrap(),
+    );
+
+    let payee = walletb_core::Address::new(walletb_core::Chain::Bitcoin, PAYEE);
+    let balance = walletb_core::BalanceSource::balances(&source, &[payee]).unwrap();
+    assert_eq!(balance[0].confirmed.to_u64(), Some(600_000));
+    assert_eq!(
+        node.requests()[0].json()["params"],
+        json!([
+            "start",
+            ["raw(0014e8df018c7e326cc253faac7e46cdc51e68542c42)#rzauefm4"]
+        ])
+    );
+
+    // A ranged scan finds every derived address without gap-limit
+    // discovery.
+    let unspent = source
+        .backend()
+        .scan_wallet(&account().to_descriptor(), 20)
+        .unwrap();
+    let mut found: Vec<(ScriptBuf, u64, Option<u32>)> = unspent
+        .into_iter()
+        .map(|u| (u.script_pubkey, u.value.to_sat(), u.height))
+        .collect();
+    found.sort_by_key(|(_, sats, _)| *sats);
+    assert_eq!(
+        found,
+        [
+            (script(Keychain::External, 1), 200_000, Some(300)),
+            (script(Keychain::Internal, 0), 390_000, Some(200)),
+        ]
+    );
+}
+
+#[test]
+fn imports_descriptors_into_a_watch_only_wallet() {
+    let node = node();
+    let core = BitcoinCore::new(format!("{}/", node.url()), &user_pass()).unwrap();
+    let wallet = core.wallet("walletb").unwrap();
+    let calls: Vec<Value> = node.requests().iter().map(|r| r.json()).collect();
+    assert_eq!(calls[0]["method"], "loadwallet");
+    assert_eq!(calls[1]["method"], "createwallet");
+    assert_eq!(calls[1]["params"]["disable_private_keys"], true);
+    assert_eq!(calls[1]["params"]["descriptors"], true);
+
+    // The receive descriptor is already in the wallet; only change is
+    // imported.
+    let descriptor = account().to_descriptor();
+    assert_eq!(
+        wallet
+            .import(&descriptor, 100, Some(1_704_067_200))
+            .unwrap(),
+        1
+    );
+    let import = node.requests().last().unwrap().clone();
+    assert_eq!(import.path(), "/wallet/walletb");
+    assert_eq!(
+        import.json()["params"],
+        json!([[{
+            "desc": descriptor.internal().unwrap().to_string(),
+            "timestamp": 1_704_067_200,
+            "internal": true,
+            "range": [0, 100],
+            "active": true,
+        }]])
+    );
+
+    let source = BitcoinSource::new(Network::Bitcoin, wallet);
+    let balance = &source.wallet_balances(&account(), 20).unwrap()[0];
+    assert_eq!(balance.confirmed.to_u64(), Some(590_000));
+    assert_eq!(balance.unconfirmed.to_u64(), Some(50_000));
+
+    let unspent = source
+        .backend()
+        .unspent(&[script(Keychain::Internal, 0)])
+        .unwrap();
+    assert_eq!(unspent.len(), 1);
+    assert_eq!(unspent[0].height, Some(200));
+}
+
+#[test]
+fn reports_refused_imports() {
+    let node = node();
+    let core = BitcoinCore::new(node.url(), &user_pass()).unwrap();
+    let wallet = core.wallet("walletb").unwrap();
+    let payee = walletb_bitcoin::parse_address(PAYEE, Network::Bitcoin)
+        .unwrap()
+        .0
+        .script_pubkey();
+    assert!(matches!(
+        wallet.import_scripts(&[payee], None),
+        Err(Error::DescriptorImport { reason, .. }) if reason.contains("private keys enabled")
+    ));
+}
+
+#[test]
+fn serves_blocks_for_sync() {
+    let node = node();
+    let core = BitcoinCore::new(node.url(), &user_pass()).unwrap();
+    assert_eq!(core.tip().unwrap().height, 300);
+    assert_eq!(core.block_hash(301).unwrap(), None);
+
+    let genesis = core.block_hash(0).unwrap().unwrap();
+    let block = core.block(&genesis).unwrap();
+    assert_eq!(block.block_hash(), genesis);
+    assert_eq!(block.txdata.len(), 1);
+
+    let unknown = BlockHash::from_byte_array([7; 32]);
+    assert!(matches!(
+        core.block(&unknown),
+        Err(Error::UnknownBlock(hash)) if hash == unknown
+    ));
+}
@@ -0,0 +1,114 @@
+use bitcoin::hashes::Hash;
+use bitcoin::{Amount, OutPoint, Txid};
+use walletb_bitcoin::{
+    AccountXpub, BitcoinSource, Keychain, Keychains, MemoryUtxoSet, Network, Utxo, WalletDescriptor,
+};
+
+// Account keys for the "abandon ... about" mnemonic (BIP84 and BIP86 test
+// vectors), converted to plain xpub form for use in descriptors.
+const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+const BIP86_XPUB: &str = "xpub6BgBgsespWvERF3LHQu6CnqdvfEvtMcQjYrcRzx53QJjSxarj2afYWcLteoGVky7D3UKDP9QyrLprQ3VCECoY49yfdDEHGCtMMj92pReUsQ";
+
+fn bip84_xpub() -> String {
+    AccountXpub::parse(ZPUB, Network::Bitcoin)
+        .unwrap()
+        .xpub()
+        .to_string()
+}
+
+fn fund(set: &mut MemoryUtxoSet, address: &bitcoin::Address, n: u8, sats: u64) {
+    set.insert(Utxo {
+        outpoint: OutPoint::new(Txid::from_byte_array([n; 32]), 0),
+        script_pubkey: address.script_pubkey(),
+        value: Amount::from_sat(sats),
+        height: Some(1),
+    });
+}
+
+#[test]
+fn wpkh_multipath_matches_bip84_vectors() {
+    let descriptor = format!("wpkh([73c5da0a/84'/0'/0']{}/<0;1>/*)", bip84_xpub());
+    let wallet = WalletDescriptor::parse(&descriptor, Network::Bitcoin).unwrap();
+    assert_eq!(
+        wallet.derive(Keychain::External, 0).unwrap().to_string(),
+        "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+    );
+    assert_eq!(
+        wallet.derive(Keychain::Internal, 0).unwrap().to_string(),
+        "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+    );
+}
+
+#[test]
+fn separate_change_descriptor_with_checksums() {
+    let receive = WalletDescriptor::parse(&format!("wpkh({}/0/*)", bip84_xpub()), Network::Bitcoin)
+        .unwrap()
+        .external()
+        .to_string();
+    let change = receive.replace("/0/*", "/1/*");
+    // Changing the path invalidates the copied checksum.
+    assert!(WalletDescriptor::parse(&change, Network::Bitcoin).is_err());
+
+    let change = change.split('#').next().unwrap();
+    let wallet = WalletDescriptor::parse(&receive, Network::Bitcoin)
+        .unwrap()
+        .with_change(change)
+        .unwrap();
+    assert_eq!(
+        wallet.derive(Keychain::Internal, 0).unwrap().to_string(),
+        "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+    );
+}
+
+#[test]
+fn tr_key_path_matches_bip86() {
+    let wallet =
+        WalletDescriptor::parse(&format!("tr({BIP86_XPUB}/0/*)"), Network::Bitcoin).unwrap();
+    assert_eq!(
+        wallet.derive(Keychain::External, 0).unwrap().to_string(),
+        "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+    );
+}
+
+#[test]
+fn multisig_vault_balance_via_discovery() {
+    let descriptor = format!(
+        "sh(wsh(multi(2,{a}/<0;1>/*,{b}/<0;1>/*)))",
+        a = bip84_xpub(),
+        b = BIP86_XPUB
+    );
+    let vault = WalletDescriptor::parse(&descriptor, Network::Bitcoin).unwrap();
+    let receive = vault.derive(Keychain::External, 3).unwrap();
+    let change = vault.derive(Keychain::Internal, 0).unwrap();
+    assert!(receive.to_string().starts_with('3'));
+
+    let mut set = MemoryUtxoSet::new();
+    fund(&mut set, &receive, 1, 1_000_000);
+    fund(&mut set, &change, 2, 250_000);
+    let source = BitcoinSource::new(Network::Bitcoin, set);
+
+    let discovery = source.discover(&vault, 20).unwrap();
+    assert_eq!(discovery.next_index(Keychain::External), 4);
+    assert_eq!(discovery.next_index(Keychain::Internal), 1);
+    let balances = source.wallet_balances(&vault, 20).unwrap();
+    assert_eq!(balances[0].confirmed.to_u64(), Some(1_250_000));
+}
+
+#[test]
+fn non_ranged_descriptor_has_one_address() {
+    let wallet =
+        WalletDescriptor::parse(&format!("wpkh({}/0/0)", bip84_xpub()), Network::Bitcoin).unwrap();
+    let address = wallet.derive(Keychain::External, 0).unwrap();
+    let mut set = MemoryUtxoSet::new();
+    fund(&mut set, &address, 1, 42);
+    let source = BitcoinSource::new(Network::Bitcoin, set);
+
+    let discovery = source.discover(&wallet, 20).unwrap();
+    assert_eq!(discovery.addresses.len(), 1);
+    assert_eq!(
+        source.wallet_balances(&wallet, 20).unwrap()[0]
+            .confirmed
+            .to_u64(),
+        Some(42)
+    );
+}
@@ -0,0 +1,346 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::block::{Header, Version};
+use bitcoin::consensus::encode;
+use bitcoin::hashes::Hash;
+use bitcoin::{
+    Amount, BlockHash, CompactTarget, OutPoint, ScriptBuf, Sequence, Transaction, TxIn,
+    TxMerkleNode, TxOut, Txid, Witness,
+};
+use serde_json::{json, Value};
+use walletb_bitcoin::{
+    script_hash, AccountXpub, BitcoinSource, ElectrumClient, Error, Keychain, Keychains, Network,
+    Notification, UtxoBackend,
+};
+use walletb_core::testing::{MockLineServer, RpcFailure};
+use walletb_core::{HistoricalSource, PointInTime, RpcError};
+
+const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+const PAYEE: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+fn account() -> AccountXpub {
+    AccountXpub::parse(ZPUB, Network::Bitcoin).unwrap()
+}
+
+fn script(keychain: Keychain, index: u32) -> ScriptBuf {
+    account().derive(keychain, index).unwrap().script_pubkey()
+}
+
+/// A transaction and its confirmation height, 0 for the mempool.
+#[derive(Clone)]
+struct Entry {
+    tx: Transaction,
+    height: i64,
+    time: u32,
+}
+
+/// The history in `tests/fixtures/history.json`: 0.01 BTC received at
+/// block 100, spent at block 200 with 0.0039 change, 0.002 received at
+/// block 300.
+fn fixture() -> Vec<Entry> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/history.json");
+    let entries: Vec<Value> =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    entries
+        .iter()
+        .map(|e| Entry {
+            tx: encode::deserialize_hex(e["tx"].as_str().unwrap()).unwrap(),
+            height: e["height"].as_i64().unwrap(),
+            time: e["time"].as_u64().unwrap() as u32,
+        })
+        .collect()
+}
+
+/// A mempool transaction spending the 0.0039 change: 0.001 out, 0.00289
+/// back to the second change address.
+fn mempool_spend(history: &[Entry]) -> Entry {
+    let payee = walletb_bitcoin::parse_address(PAYEE, Network::Bitcoin)
+        .unwrap()
+        .0
+        .script_pubkey();
+    let change = OutPoint::new(history[1].tx.compute_txid(), 1);
+    Entry {
+        tx: Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: change,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(100_000),
+                    script_pubkey: payee,
+                },
+                TxOut {
+                    value: Amount::from_sat(289_000),
+                    script_pubkey: script(Keychain::Internal, 1),
+                },
+            ],
+        },
+        height: 0,
+        time: 0,
+    }
+}
+
+fn header(height: u64, time: u32) -> Header {
+    Header {
+        version: Version::TWO,
+        prev_blockhash: BlockHash::all_zeros(),
+        merkle_root: TxMerkleNode::all_zeros(),
+        time,
+        bits: CompactTarget::from_consensus(0x1d00_ffff),
+        nonce: height as u32,
+    }
+}
+
+/// An Electrum server answering from `entries`, indexed the way electrs
+/// indexes: by script hash over funding and spending transactions.
+fn server(entries: Vec<Entry>) -> MockLineServer {
+    let mut scripts: HashMap<String, ScriptBuf> = HashMap::new();
+    let mut funding: HashMap<OutPoint, (ScriptBuf, u64, i64)> = HashMap::new();
+    for e in &entries {
+        for (vout, out) in e.tx.output.iter().enumerate() {
+            scripts.insert(script_hash(&out.script_pubkey), out.script_pubkey.clone());
+            funding.insert(
+                OutPoint::new(e.tx.compute_txid(), vout as u32),
+                (out.script_pubkey.clone(), out.value.to_sat(), e.height),
+            );
+        }
+    }
+    let spent = |with_mempool: bool| -> HashMap<OutPoint, Txid> {
+        entries
+            .iter()
+            .filter(|e| with_mempool || e.height > 0)
+            .flat_map(|e| {
+                e.tx.input
+                    .iter()
+                    .map(|i| (i.previous_output, e.tx.compute_txid()))
+            })
+            .collect()
+    };
+    let spent = [spent(false), spent(true)];
+    // Unspent outputs locked to `script`, counting the mempool or not.
+    let unspent = move |script: &ScriptBuf, with_mempool: bool| -> Vec<(OutPoint, u64, i64)> {
+        funding
+            .iter()
+            .filter(|(op, (s, _, height))| {
+                s == script
+                    && (with_mempool || *height > 0)
+                    && !spent[with_mempool as usize].contains_key(op)
+            })
+            .map(|(op, (_, value, height))| (*op, *value, *height))
+            .collect()
+    };
+    let tip = entries.iter().map(|e| e.height).max().unwrap_or(0) as u64;
+
+    MockLineServer::new(move |method, params| {
+        let by_hash = || {
+            let hash = params[0].as_str().unwrap_or_default();
+            scripts.get(hash).cloned()
+        };
+        match method {
+            "server.version" => Ok(json!(["mock electrs", "1.4"])),
+            "server.ping" => Ok(Value::Null),
+            "blockchain.headers.subscribe" => {
+                Ok(json!({ "height": tip, "hex": encode::serialize_hex(&header(tip, 0)) }))
+            }
+            "blockchain.block.header" => {
+                let height = params[0].as_u64().unwrap();
+                let time = entries
+                    .iter()
+                    .find(|e| e.height as u64 == height)
+                    .map_or(0, |e| e.time);
+                Ok(json!(encode::serialize_hex(&header(height, time))))
+            }
+            "blockchain.transaction.get" => entries
+                .iter()
+                .find(|e| e.tx.compute_txid().to_string() == params[0].as_str().unwrap())
+                .map(|e| json!(encode::serialize_hex(&e.tx)))
+                .ok_or_else(|| RpcFailure::new(2, "no such mempool or blockchain transaction")),
+            "blockchain.scripthash.get_history" | "blockchain.scripthash.subscribe" => {
+                let Some(script) = by_hash() else {
+                    return Ok(if method.ends_with("subscribe") {
+                        Value::Null
+                    } else {
+                        json!([])
+                    });
+                };
+                let touching: Vec<Value> = entries
+                    .iter()
+                    .filter(|e| {
+                        e.tx.output.iter().any(|o| o.script_pubkey == script)
+                            || e.tx.input.iter().any(|i| {
+                                entries.iter().any(|f| {
+                                    f.tx.compute_txid() == i.previous_output.txid
+                                        && f.tx.output[i.previous_output.vout as usize]
+                                            .script_pubkey
+                                            == script
+                                })
+                            })
+                    })
+                    .map(|e| json!({ "tx_hash": e.tx.compute_txid(), "height": e.height }))
+                    .collect();
+                Ok(if method.ends_with("subscribe") {
+                    json!(format!("status-{}", touching.len()))
+                } else {
+                    Value::Array(touching)
+                })
+            }
+            "blockchain.scripthash.listunspent" => {
+                let list: Vec<Value> = by_hash()
+                    .map(|s| unspent(&s, true))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(op, value, height)| {
+                        json!({ "tx_hash": op.txid, "tx_pos": op.vout, "height": height, "value": value })
+                    })
+                    .collect();
+                Ok(Value::Array(list))
+            }
+            "blockchain.scripthash.get_balance" => {
+                let sum = |with_mempool| -> i64 {
+                    by_hash()
+                        .map(|s| unspent(&s, with_mempool))
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|(_, value, _)| *value as i64)
+                        .sum()
+                };
+                let confirmed = sum(false);
+                Ok(json!({ "confirmed": confirmed, "unconfirmed": sum(true) - confirmed }))
+            }
+            _ => Err(RpcFailure::method_not_found(method)),
+        }
+    })
+}
+
+#[test]
+fn reads_balances_and_history_by_script_hash() {
+    let server = server(fixture());
+    let client = ElectrumClient::connect(server.addr()).unwrap();
+    let source = BitcoinSource::new(Network::Bitcoin, client);
+
+    let now = source.wallet_balances(&account(), 20).unwrap();
+    assert_eq!(now[0].confirmed.to_u64(), Some(590_000));
+    assert!(now[0].unconfirmed.is_zero());
+
+    let jan_1 = PointInTime::Time(1_704_067_200);
+    let discovery = source.discover(&account(), 20).unwrap();
+    let then = source
+        .balances_at(&discovery.used_addresses(), jan_1)
+        .unwrap();
+    assert_eq!(then[0].confirmed.to_u64(), Some(1_000_000));
+
+    let unspent = source
+        .backend()
+        .unspent(&[script(Keychain::Internal, 0), script(Keychain::External, 1)])
+        .unwrap();
+    assert_eq!(unspent.len(), 2);
+    assert!(unspent.iter().all(|u| u.is_confirmed()));
+
+    let calls = server.calls();
+    assert_eq!(calls[0].method, "server.version");
+    assert!(calls
+        .iter()
+        .any(|c| c.method == "blockchain.scripthash.get_balance"
+            && c.params[0] == script_hash(&script(Keychain::External, 1))));
+}
+
+#[test]
+fn takes_mempool_spends_off_the_confirmed_balance() {
+    let mut entries = fixture();
+    entries.push(mempool_spend(&entries));
+    let server = server(entries.clone());
+    let source = BitcoinSource::new(
+        Network::Bitcoin,
+        ElectrumClient::connect(server.addr()).unwrap(),
+    );
+
+    let balance = &source.wallet_balances(&account(), 20).unwrap()[0];
+    assert_eq!(balance.confirmed.to_u64(), Some(489_000));
+    assert!(balance.unconfirmed.is_zero());
+
+    let pending = source
+        .backend()
+        .unspent(&[script(Keychain::Internal, 1)])
+        .unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].height, None);
+
+    // Paying the wallet change, unlike the payment received at block 300.
+    let scripts: Vec<ScriptBuf> = source.discover(&account(), 20).unwrap().scripts().collect();
+    let txids: Vec<Txid> = entries.iter().map(|e| e.tx.compute_txid()).collect();
+    let change = source.backend().spends_from(&txids, &scripts).unwrap();
+    assert_eq!(change, Some(HashSet::from([txids[1], txids[3]])));
+}
+
+#[test]
+fn pushes_notifications_for_subscriptions() {
+    let server = server(fixture());
+    let client = ElectrumClient::connect(server.addr()).unwrap();
+    let watched = script(Keychain::External, 0);
+    let statuses = client
+        .subscribe(&[watched.clone(), script(Keychain::External, 5)])
+        .unwrap();
+    assert_eq!(statuses, [Some("status-2".to_owned()), None]);
+    let (tip, _) = client.subscribe_headers().unwrap();
+    assert_eq!(tip, 300);
+
+    assert_eq!(
+        client.next_notification(Duration::from_millis(50)).unwrap(),
+        None
+    );
+
+    server.notify(
+        "blockchain.scripthash.subscribe",
+        json!([script_hash(&watched), "status-3"]),
+    );
+    server.notify(
+        "blockchain.headers.subscribe",
+        json!([{ "height": 301, "hex": encode::serialize_hex(&header(301, 7)) }]),
+    );
+    // Notifications arriving between calls are kept for later.
+    client.ping().unwrap();
+    assert_eq!(
+        client.next_notification(Duration::from_secs(5)).unwrap(),
+        Some(Notification::Script {
+            script: watched,
+            status: Some("status-3".into()),
+        })
+    );
+    assert_eq!(
+        client.next_notification(Duration::from_secs(5)).unwrap(),
+        Some(Notification::Header {
+            height: 301,
+            header: header(301, 7),
+        })
+    );
+}
+
+#[test]
+fn reports_server_and_connection_errors() {
+    let entries = fixture();
+    let known = entries[0].tx.compute_txid();
+    let server = server(entries);
+    let client = ElectrumClient::connect(server.addr()).unwrap();
+    let missing = Txid::from_byte_array([1; 32]);
+    assert!(matches!(
+        client.transactions(&[missing, known]),
+        Err(Error::Rpc(RpcError::Server { code: 2, .. }))
+    ));
+    // The rest of the failed batch was read off the connection, not taken
+    // for the answer to the next call.
+    let txs = client.transactions(&[known]).unwrap();
+    assert_eq!(txs[0].compute_txid(), known);
+
+    drop(server);
+    assert!(matches!(
+        client.ping(),
+        Err(Error::Rpc(RpcError::Transport(_)))
+    ));
+}
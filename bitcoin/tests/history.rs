@@ -0,0 +1,216 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::hashes::Hash;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use walletb_bitcoin::{
+    AccountXpub, BitcoinSource, FileHistory, HistoryBackend, HistoryTx, Keychain, Keychains,
+    MemoryHistory, Network, UtxoBackend,
+};
+use walletb_core::{Address, Chain, HistoricalSource, Period, PointInTime, TransactionSource};
+
+const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+const PAYEE: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+// 2024-01-01T00:00:00Z and 2024-02-01T00:00:00Z.
+const JAN_1: u64 = 1_704_067_200;
+const FEB_1: u64 = 1_706_745_600;
+
+fn account() -> AccountXpub {
+    AccountXpub::parse(ZPUB, Network::Bitcoin).unwrap()
+}
+
+fn script(keychain: Keychain, index: u32) -> ScriptBuf {
+    account().derive(keychain, index).unwrap().script_pubkey()
+}
+
+fn payee() -> ScriptBuf {
+    walletb_bitcoin::parse_address(PAYEE, Network::Bitcoin)
+        .unwrap()
+        .0
+        .script_pubkey()
+}
+
+fn tx(inputs: &[OutPoint], outputs: &[(ScriptBuf, u64)]) -> Transaction {
+    Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|&previous_output| TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: outputs
+            .iter()
+            .map(|(script_pubkey, sats)| TxOut {
+                value: Amount::from_sat(*sats),
+                script_pubkey: script_pubkey.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// Receives 0.01 BTC at block 100 (New Year's Eve), spends it at block 200
+/// (mid-January) paying 0.006 out with 0.0039 change, then receives 0.002
+/// more at block 300 (February).
+fn history() -> MemoryHistory {
+    let external = OutPoint::new(Txid::from_byte_array([7; 32]), 0);
+    let receive = tx(&[external], &[(script(Keychain::External, 0), 1_000_000)]);
+    let spend = tx(
+        &[OutPoint::new(receive.compute_txid(), 0)],
+        &[(payee(), 600_000), (script(Keychain::Internal, 0), 390_000)],
+    );
+    let later = tx(
+        &[OutPoint::new(Txid::from_byte_array([8; 32]), 1)],
+        &[(script(Keychain::External, 1), 200_000)],
+    );
+    [
+        HistoryTx::new(receive, 100, JAN_1 - 3_600),
+        HistoryTx::new(spend, 200, JAN_1 + 14 * 86_400),
+        HistoryTx::new(later, 300, FEB_1 + 3_600),
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn sats(balances: &[walletb_core::Balance]) -> u64 {
+    assert_eq!(balances.len(), 1);
+    assert!(balances[0].unconfirmed.is_zero());
+    balances[0].confirmed.to_u64().unwrap()
+}
+
+#[test]
+fn replays_history_to_a_block_height() {
+    let source = BitcoinSource::new(Network::Bitcoin, history());
+    let addresses: Vec<Address> = [(Keychain::External, 0), (Keychain::Internal, 0)]
+        .into_iter()
+        .map(|(keychain, index)| {
+            let address = account().derive(keychain, index).unwrap();
+            Address::new(Chain::Bitcoin, address.to_string())
+        })
+        .collect();
+
+    let at = |height| {
+        sats(
+            &source
+                .balances_at(&addresses, PointInTime::Height(height))
+                .unwrap(),
+        )
+    };
+    assert_eq!(at(99), 0);
+    assert_eq!(at(100), 1_000_000);
+    assert_eq!(at(199), 1_000_000);
+    assert_eq!(at(200), 390_000);
+}
+
+#[test]
+fn reports_a_discovered_wallet_at_month_end() {
+    let source = BitcoinSource::new(Network::Bitcoin, history());
+    let at = |when: &str| {
+        sats(
+            &source
+                .wallet_balances_at(&account(), 20, when.parse().unwrap())
+                .unwrap(),
+        )
+    };
+    assert_eq!(at("2023-12-30"), 0);
+    assert_eq!(at("2023-12-31"), 1_000_000);
+    assert_eq!(at("2024-01-31"), 390_000);
+    assert_eq!(at("2024-02-01T00:59:59Z"), 390_000);
+    assert_eq!(at("2024-02-01"), 590_000);
+
+    // The current view replays the whole history.
+    let now = source.wallet_balances(&account(), 20).unwrap();
+    assert_eq!(sats(&now), 590_000);
+    assert_eq!(
+        source
+            .discover(&account(), 20)
+            .unwrap()
+            .next_index(Keychain::External),
+        2
+    );
+}
+
+#[test]
+fn lists_the_transactions_behind_the_balance() {
+    let source = BitcoinSource::new(Network::Bitcoin, history());
+    let addresses: Vec<Address> = [
+        (Keychain::External, 0),
+        (Keychain::Internal, 0),
+        (Keychain::External, 1),
+    ]
+    .into_iter()
+    .map(|(keychain, index)| {
+        let address = account().derive(keychain, index).unwrap();
+        Address::new(Chain::Bitcoin, address.to_string())
+    })
+    .collect();
+
+    let entries = source.transactions(&addresses, Period::default()).unwrap();
+    let flows: Vec<(u64, u64, u64, u64)> = entries
+        .iter()
+        .map(|e| {
+            (
+                e.height,
+                e.received.to_u64().unwrap(),
+                e.sent.to_u64().unwrap(),
+                e.fee.to_u64().unwrap(),
+            )
+        })
+        .collect();
+    // The change stays in the wallet, so the spend sent only the payment,
+    // plus its fee.
+    assert_eq!(
+        flows,
+        [
+            (100, 1_000_000, 0, 0),
+            (200, 0, 600_000, 10_000),
+            (300, 200_000, 0, 0)
+        ]
+    );
+    assert_eq!(entries[0].counterparty, None);
+    assert_eq!(entries[1].counterparty.as_deref(), Some(PAYEE));
+    assert_eq!(entries[1].time, JAN_1 + 14 * 86_400);
+
+    let january = Period {
+        after: Some("2023-12-31".parse().unwrap()),
+        until: Some("2024-01-31".parse().unwrap()),
+    };
+    let entries = source.transactions(&addresses, january).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].height, 200);
+
+    // Seen from the change address alone, the spend only paid it.
+    let change = &addresses[1..2];
+    let entries = source.transactions(change, Period::default()).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].received.to_u64(), Some(390_000));
+    assert!(entries[0].fee.is_zero());
+}
+
+#[test]
+fn knows_its_tip_and_the_transactions_of_scripts() {
+    let history = history();
+    assert_eq!(history.tip_height().unwrap(), Some(300));
+    let change = script(Keychain::Internal, 0);
+    let txids = history.txids(&[change]).unwrap().unwrap();
+    // The spend paying the change, and not the receive before it.
+    assert_eq!(txids.len(), 1);
+    let receive = script(Keychain::External, 0);
+    assert_eq!(history.txids(&[receive]).unwrap().unwrap().len(), 2);
+}
+
+#[test]
+fn loads_the_same_history_from_a_file() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/history.json");
+    let file = FileHistory::open(&path).unwrap();
+    let scripts = [script(Keychain::External, 0), script(Keychain::Internal, 0)];
+    let mut from_file = file.history(&scripts).unwrap();
+    let mut built = history().history(&scripts).unwrap();
+    from_file.sort_by_key(|t| t.height);
+    built.sort_by_key(|t| t.height);
+    assert_eq!(from_file, built);
+}
@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use walletb_bitcoin::{btc, BitcoinSource, FileUtxoSet, Network};
+use walletb_core::{Address, Amount, BalanceSource, Chain, Error};
+
+fn fixture() -> FileUtxoSet {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/utxos.json");
+    FileUtxoSet::open(path).unwrap()
+}
+
+fn addr(s: &str) -> Address {
+    Address::new(Chain::Bitcoin, s)
+}
+
+#[test]
+fn sums_confirmed_and_unconfirmed_from_file_snapshot() {
+    let source = BitcoinSource::new(Network::Bitcoin, fixture());
+    let balances = source
+        .balances(&[
+            addr("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+            addr("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"),
+            // Listed twice: must not be double counted.
+            addr("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+        ])
+        .unwrap();
+
+    assert_eq!(balances.len(), 1);
+    let balance = &balances[0];
+    assert_eq!(balance.asset, btc());
+    assert_eq!(balance.confirmed, Amount::from_u64(170_000));
+    assert_eq!(balance.unconfirmed, Amount::from_u64(2_500));
+}
+
+#[test]
+fn unknown_address_has_zero_balance() {
+    let source = BitcoinSource::new(Network::Bitcoin, fixture());
+    let balances = source
+        .balances(&[addr(
+            "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+        )])
+        .unwrap();
+    assert!(balances[0].total().is_zero());
+}
+
+#[test]
+fn rejects_addresses_for_other_networks_and_chains() {
+    let source = BitcoinSource::new(Network::Regtest, fixture());
+    let err = source
+        .balances(&[addr("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")])
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidAddress { .. }));
+
+    let err = source
+        .balances(&[Address::new(Chain::ETHEREUM, "0x00")])
+        .unwrap_err();
+    assert!(matches!(err, Error::ChainMismatch { .. }));
+}
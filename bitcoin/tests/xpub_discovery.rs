@@ -0,0 +1,141 @@
+use std::cell::Cell;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Txid};
+use walletb_bitcoin::{
+    discover, discover_from, AccountXpub, BitcoinSource, DerivedAddress, Discovery, Keychain,
+    Keychains, MemoryUtxoSet, Network, Utxo, UtxoBackend,
+};
+
+const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+
+fn account() -> AccountXpub {
+    AccountXpub::parse(ZPUB, Network::Bitcoin).unwrap()
+}
+
+fn fund(set: &mut MemoryUtxoSet, keychain: Keychain, index: u32, sats: u64) {
+    let address = account().derive(keychain, index).unwrap();
+    let mut txid = [0u8; 32];
+    txid[..4].copy_from_slice(&index.to_le_bytes());
+    txid[4] = keychain.index() as u8;
+    set.insert(Utxo {
+        outpoint: OutPoint::new(Txid::from_byte_array(txid), 0),
+        script_pubkey: address.script_pubkey(),
+        value: Amount::from_sat(sats),
+        height: Some(100),
+    });
+}
+
+fn wallet_set() -> MemoryUtxoSet {
+    let mut set = MemoryUtxoSet::new();
+    fund(&mut set, Keychain::External, 0, 10_000);
+    fund(&mut set, Keychain::External, 5, 20_000);
+    // 25 unused addresses after index 5: only found with a gap limit > 24.
+    fund(&mut set, Keychain::External, 31, 40_000);
+    fund(&mut set, Keychain::Internal, 1, 3_000);
+    set
+}
+
+#[test]
+fn stops_at_gap_limit() {
+    let source = BitcoinSource::new(Network::Bitcoin, wallet_set());
+    let discovery = source.discover(&account(), 20).unwrap();
+
+    assert_eq!(discovery.next_index(Keychain::External), 6);
+    assert_eq!(discovery.next_index(Keychain::Internal), 2);
+    assert_eq!(discovery.used().count(), 3);
+    // The unused addresses checked after the last used ones are kept, for
+    // watching.
+    let gap = |keychain| -> Vec<u32> {
+        discovery
+            .gap
+            .iter()
+            .filter(|a| a.keychain == keychain)
+            .map(|a| a.index)
+            .collect()
+    };
+    assert_eq!(gap(Keychain::External), (6..26).collect::<Vec<_>>());
+    assert_eq!(gap(Keychain::Internal), (2..22).collect::<Vec<_>>());
+    assert!(discovery.gap.iter().all(|a| !a.used));
+    assert_eq!(discovery.scripts().count(), 6 + 2 + 40);
+
+    let balances = source.wallet_balances(&account(), 20).unwrap();
+    assert_eq!(balances[0].confirmed.to_u64(), Some(33_000));
+}
+
+#[test]
+fn larger_gap_limit_finds_distant_addresses() {
+    let source = BitcoinSource::new(Network::Bitcoin, wallet_set());
+    let discovery = source.discover(&account(), 30).unwrap();
+    assert_eq!(discovery.next_index(Keychain::External), 32);
+
+    let balances = source.wallet_balances(&account(), 30).unwrap();
+    assert_eq!(balances[0].confirmed.to_u64(), Some(73_000));
+}
+
+#[test]
+fn spent_addresses_still_extend_the_scan() {
+    let mut set = MemoryUtxoSet::new();
+    fund(&mut set, Keychain::External, 10, 1);
+    fund(&mut set, Keychain::External, 25, 5_000);
+    let mut spent = [0u8; 32];
+    spent[..4].copy_from_slice(&10u32.to_le_bytes());
+    set.spend(&OutPoint::new(Txid::from_byte_array(spent), 0));
+
+    let source = BitcoinSource::new(Network::Bitcoin, set);
+    let balances = source.wallet_balances(&account(), 20).unwrap();
+    assert_eq!(balances[0].confirmed.to_u64(), Some(5_000));
+}
+
+#[test]
+fn empty_wallet_has_no_used_addresses() {
+    let source = BitcoinSource::new(Network::Bitcoin, MemoryUtxoSet::new());
+    let discovery = source.discover(&account(), 20).unwrap();
+    assert!(discovery.addresses.is_empty());
+    assert_eq!(discovery.next_index(Keychain::External), 0);
+}
+
+/// Counts how many scripts discovery asks about.
+struct Counting<'a> {
+    set: &'a MemoryUtxoSet,
+    checked: Cell<usize>,
+}
+
+impl UtxoBackend for Counting<'_> {
+    fn unspent(&self, scripts: &[ScriptBuf]) -> walletb_bitcoin::Result<Vec<Utxo>> {
+        self.set.unspent(scripts)
+    }
+
+    fn used(&self, scripts: &[ScriptBuf]) -> walletb_bitcoin::Result<Vec<bool>> {
+        self.checked.set(self.checked.get() + scripts.len());
+        self.set.used(scripts)
+    }
+}
+
+#[test]
+fn resumes_after_known_addresses() {
+    let set = wallet_set();
+    let full = discover(&account(), &set, 30).unwrap();
+
+    // A cache that knows the first two used receive addresses.
+    let cached: Vec<DerivedAddress> = full
+        .addresses
+        .iter()
+        .filter(|a| a.keychain == Keychain::External && a.index <= 5)
+        .cloned()
+        .collect();
+    let known = Discovery::from_addresses(cached);
+    assert_eq!(known.next_index(Keychain::External), 6);
+    assert_eq!(known.next_index(Keychain::Internal), 0);
+
+    let backend = Counting {
+        set: &set,
+        checked: Cell::new(0),
+    };
+    let resumed = discover_from(&account(), &backend, 30, &known).unwrap();
+    assert_eq!(resumed.addresses, full.addresses);
+    assert_eq!(resumed.next_index(Keychain::External), 32);
+    // Two batches of 30 per chain, the receive chain starting at index 6
+    // instead of 0.
+    assert_eq!(backend.checked.get(), 60 + 60);
+}
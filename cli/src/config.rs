@@ -0,0 +1,1207 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table};
+use walletb_bitcoin::{Network, DEFAULT_GAP_LIMIT};
+use walletb_core::Category;
+use walletb_cosmos::CosmosChain;
+use walletb_ethereum::{ChainRegistry, EthAddress, EvmChain};
+use walletb_notify::{parse_duration, Condition, EventKind, Rule};
+use walletb_portfolio::Decimal;
+
+use crate::{Error, Result};
+
+/// The file `walletb` reads its wallets and sources from.
+///
+/// ```toml
+/// currency = "USD"
+/// prices = "prices.csv"
+/// database = "walletb.db"
+///
+/// [sources.btc]
+/// kind = "bitcoin"
+/// utxos = "utxos.json"
+///
+/// [sources.electrs]
+/// kind = "bitcoin"
+/// electrum = "127.0.0.1:50001"
+///
+/// [sources.node]
+/// kind = "bitcoin-core"
+/// url = "http://127.0.0.1:8332"
+/// cookie = "/var/lib/bitcoind/.cookie"
+/// wallet = "walletb"
+///
+/// [sources.eth]
+/// kind = "ethereum"
+/// url = "http://localhost:8545"
+/// tokens = [{ contract = "0xA0b8…eB48", symbol = "USDC", decimals = 6 }]
+/// discover_tokens_from = 17000000
+///
+/// [sources.l2s]
+/// kind = "evm"
+/// chains = ["ethereum", "arbitrum", "optimism", "base"]
+/// urls = { ethereum = "http://localhost:8545" }
+///
+/// [sources.validators]
+/// kind = "beacon"
+/// url = "http://localhost:5052"
+///
+/// [sources.sol]
+/// kind = "solana"
+/// url = "https://api.mainnet-beta.solana.com"
+///
+/// [sources.atom]
+/// kind = "cosmos"
+/// url = "https://cosmos-rest.publicnode.com"
+/// chain_id = "cosmoshub-4"
+///
+/// [chains.zksync]
+/// id = 324
+/// symbol = "ETH"
+/// urls = ["https://mainnet.era.zksync.io"]
+///
+/// [[wallets]]
+/// id = "cold"
+/// name = "Cold storage"
+/// source = "btc"
+/// xpub = "zpub6r…"
+///
+/// [server]
+/// listen = "127.0.0.1:8080"
+///
+/// [[server.keys]]
+/// name = "billing"
+/// key = "…"
+/// requests_per_minute = 120
+///
+/// [events]
+/// confirmations = 6
+/// listen = "127.0.0.1:8081"
+///
+/// [[events.webhooks]]
+/// url = "https://ops.example.com/walletb"
+/// secret = "…"
+/// events = ["confirmed", "confirmations_reached", "reorged"]
+///
+/// [[alerts.rules]]
+/// name = "hot-btc-low"
+/// wallet = "hot"
+/// asset = "BTC"
+/// below = "0.5"
+/// notify = ["ops"]
+///
+/// [alerts.notifiers.ops]
+/// kind = "email"
+/// smtp = "127.0.0.1:25"
+/// from = "walletb@example.com"
+/// to = ["ops@example.com"]
+/// ```
+///
+/// Relative paths are resolved against the directory holding the file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Fiat currency for valuations.
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Price file for valuations, CSV or JSON.
+    #[serde(default)]
+    pub prices: Option<PathBuf>,
+    #[serde(default = "default_gap_limit")]
+    pub gap_limit: u32,
+    /// SQLite database caching scanned addresses and balance snapshots.
+    #[serde(default)]
+    pub database: Option<PathBuf>,
+    /// EVM chains to add to, or change in, the built-in registry.
+    #[serde(default)]
+    pub chains: BTreeMap<String, ChainConfig>,
+    #[serde(default)]
+    pub sources: BTreeMap<String, SourceConfig>,
+    #[serde(default)]
+    pub wallets: Vec<WalletConfig>,
+    /// Settings for `walletb-server`; the command-line tool ignores them.
+    #[serde(default)]
+    pub server: Option<ServerConfig>,
+    /// Where `walletb watch` sends events about the wallets it polls.
+    #[serde(default)]
+    pub events: Option<EventsConfig>,
+    /// Rules `walletb watch` checks on every poll, and who to tell.
+    #[serde(default)]
+    pub alerts: Option<AlertsConfig>,
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    registry: ChainRegistry,
+}
+
+fn default_currency() -> String {
+    "USD".to_owned()
+}
+
+fn default_gap_limit() -> u32 {
+    DEFAULT_GAP_LIMIT
+}
+
+fn default_network() -> Network {
+    Network::Bitcoin
+}
+
+fn default_listen() -> String {
+    "127.0.0.1:8080".to_owned()
+}
+
+fn default_confirmations() -> u32 {
+    6
+}
+
+fn default_requests_per_minute() -> u32 {
+    60
+}
+
+/// Where balances for one chain come from, selected by `kind`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum SourceConfig {
+    /// A UTXO snapshot (`utxos`) or transaction history (`history`) file,
+    /// or the `host:port` of an Electrum server (`electrum`). A UTXO
+    /// snapshot cannot answer for past blocks.
+    Bitcoin {
+        #[serde(default = "default_network")]
+        network: Network,
+        #[serde(default)]
+        utxos: Option<PathBuf>,
+        #[serde(default)]
+        history: Option<PathBuf>,
+        #[serde(default)]
+        electrum: Option<String>,
+    },
+    /// A Bitcoin Core node, authenticated by its `cookie` file or by `user`
+    /// and `password`. With `wallet`, the wallets are imported into that
+    /// watch-only wallet on the node, rescanning the chain on first import;
+    /// without it, every read scans the UTXO set, which suits occasional
+    /// checks of a few addresses. With `sync_from` instead, the wallets are
+    /// followed block by block from that height into the `database`, and
+    /// each read only fetches the blocks mined since the last.
+    BitcoinCore {
+        #[serde(default = "default_network")]
+        network: Network,
+        url: String,
+        #[serde(default)]
+        cookie: Option<PathBuf>,
+        #[serde(default)]
+        user: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        wallet: Option<String>,
+        #[serde(default)]
+        sync_from: Option<u32>,
+    },
+    /// A JSON-RPC node for one EVM `chain`, Ethereum unless named; an
+    /// archive node for past blocks. Without a `url`, the chain's
+    /// registered endpoint is used. Besides the listed `tokens`, every
+    /// token found in transfer logs from block `discover_tokens_from` on is
+    /// read, and with `liquid_staking` the chain's liquid-staking tokens,
+    /// valued in its native asset.
+    Ethereum {
+        #[serde(default)]
+        chain: Option<String>,
+        #[serde(default)]
+        url: Option<String>,
+        #[serde(default)]
+        tokens: Vec<TokenConfig>,
+        #[serde(default)]
+        discover_tokens_from: Option<u64>,
+        #[serde(default)]
+        liquid_staking: bool,
+    },
+    /// A consensus node's Beacon API, for validator balances. Wallets list
+    /// validator indices, public keys or withdrawal addresses. Reads are
+    /// at `state`, `head` unless set.
+    Beacon {
+        url: String,
+        #[serde(default)]
+        state: Option<String>,
+    },
+    /// The same addresses on several EVM `chains`, each read with its
+    /// native asset and registered tokens through the endpoint in `urls`
+    /// or, failing that, its registered one.
+    Evm {
+        chains: Vec<String>,
+        #[serde(default)]
+        urls: BTreeMap<String, String>,
+    },
+    /// A Solana JSON-RPC node. Every SPL token an address holds is read;
+    /// `tokens` only names mints, by their address in `contract`.
+    Solana {
+        url: String,
+        #[serde(default)]
+        tokens: Vec<TokenConfig>,
+    },
+    /// A Cosmos SDK node's LCD. A chain walletb knows by `chain_id` needs
+    /// nothing else; any other needs its address `prefix`, staking `denom`,
+    /// `symbol` and `decimals`, which also override a known chain's.
+    /// `tokens` names other denominations, by their denom in `contract`.
+    Cosmos {
+        url: String,
+        chain_id: String,
+        #[serde(default)]
+        prefix: Option<String>,
+        #[serde(default)]
+        denom: Option<String>,
+        #[serde(default)]
+        symbol: Option<String>,
+        #[serde(default)]
+        decimals: Option<u8>,
+        #[serde(default)]
+        tokens: Vec<TokenConfig>,
+    },
+}
+
+impl SourceConfig {
+    /// The chain a `cosmos` source reads, with its configured overrides
+    /// and tokens; `None` for other kinds.
+    pub fn cosmos_chain(&self) -> Option<Result<CosmosChain, String>> {
+        let SourceConfig::Cosmos {
+            chain_id,
+            prefix,
+            denom,
+            symbol,
+            decimals,
+            tokens,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let chain = match CosmosChain::known(chain_id) {
+            Some(known) => {
+                let native = &known.native;
+                let mut chain = CosmosChain::new(
+                    known.id,
+                    prefix.as_deref().unwrap_or(&known.prefix),
+                    denom.as_deref().unwrap_or(&known.denom),
+                    symbol.as_deref().unwrap_or(&native.symbol),
+                    decimals.unwrap_or(native.decimals),
+                );
+                chain.tokens = known.tokens;
+                chain
+            }
+            None => {
+                let Ok(id) = chain_id.parse() else {
+                    return Some(Err(format!("has an invalid `chain_id` `{chain_id}`")));
+                };
+                let (Some(prefix), Some(denom), Some(symbol), Some(decimals)) =
+                    (prefix, denom, symbol, decimals)
+                else {
+                    return Some(Err(format!(
+                        "needs `prefix`, `denom`, `symbol` and `decimals` for unknown chain `{chain_id}`"
+                    )));
+                };
+                CosmosChain::new(id, prefix, denom, symbol, *decimals)
+            }
+        };
+        Some(Ok(tokens.iter().fold(chain, |chain, token| {
+            chain.with_token(&token.contract, &token.symbol, token.decimals)
+        })))
+    }
+}
+
+/// An EVM chain of the registry. A built-in chain needs only the fields
+/// to change: `symbol` and `decimals` replace its native asset's, `urls`
+/// are tried before its own and `tokens` are read besides its own. A new
+/// chain needs an `id` and a `symbol`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    #[serde(default)]
+    pub id: Option<u64>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub decimals: Option<u8>,
+    #[serde(default)]
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub tokens: Vec<TokenConfig>,
+}
+
+/// An ERC-20 token to read for every address of an EVM source, an SPL
+/// mint to name on a Solana source or a denomination to name on a Cosmos
+/// one.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenConfig {
+    pub contract: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// One wallet: a fixed address list, an account xpub or an output
+/// descriptor, read through the named source.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WalletConfig {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub source: String,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub xpub: Option<String>,
+    #[serde(default)]
+    pub descriptor: Option<String>,
+    #[serde(default)]
+    pub change_descriptor: Option<String>,
+}
+
+/// Where `walletb-server` listens and who may call it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `host:port` to listen on.
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+/// An API key and the service holding it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Who holds the key, as logged with each request.
+    pub name: String,
+    /// The key itself, at least 16 characters.
+    pub key: String,
+    /// Requests allowed per minute, in bursts of up to as many.
+    #[serde(default = "default_requests_per_minute")]
+    pub requests_per_minute: u32,
+}
+
+/// Events about watched wallets and where they go.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventsConfig {
+    /// Blocks after which a payment is announced as final.
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u32,
+    /// `host:port` to serve the WebSocket event stream on. Clients need
+    /// one of the `[[server.keys]]`, if any are configured.
+    #[serde(default)]
+    pub listen: Option<String>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+/// An endpoint events are POSTed to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key of the HMAC signing each request, at least 16 characters.
+    pub secret: String,
+    /// Event types to send; every type if empty.
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+}
+
+/// Alert rules and the notifiers they raise alerts through.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    #[serde(default)]
+    pub notifiers: BTreeMap<String, NotifierConfig>,
+}
+
+/// One alert rule: which wallets and assets it watches, and exactly one
+/// of `below`, `drop_percent` or `outgoing`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    pub name: String,
+    /// Wallet id; every wallet if unset.
+    #[serde(default)]
+    pub wallet: Option<String>,
+    /// Asset symbol; every asset if unset.
+    #[serde(default)]
+    pub asset: Option<String>,
+    /// Category to measure; all categories summed if unset.
+    #[serde(default)]
+    pub category: Option<Category>,
+    /// Fires when the amount held falls under this many whole units.
+    #[serde(default)]
+    pub below: Option<Decimal>,
+    /// Fires when the amount falls by more than this percentage of its
+    /// high within `window`.
+    #[serde(default)]
+    pub drop_percent: Option<Decimal>,
+    /// `90s`, `30m`, `1h` (the default) or `7d`.
+    #[serde(default)]
+    pub window: Option<String>,
+    /// Fires whenever the wallet spends outputs, or, from sources that
+    /// report balances only, whenever the amount falls.
+    #[serde(default)]
+    pub outgoing: bool,
+    /// Names of the `[alerts.notifiers]` to tell.
+    #[serde(default)]
+    pub notify: Vec<String>,
+}
+
+impl RuleConfig {
+    /// The rule to evaluate, or why the config does not describe one.
+    pub fn rule(&self) -> std::result::Result<Rule, String> {
+        let condition = match (self.below, self.drop_percent, self.outgoing) {
+            (Some(below), None, false) => {
+                if self.asset.is_none() {
+                    return Err("needs an `asset` for `below`".to_owned());
+                }
+                Condition::Below(below)
+            }
+            (None, Some(percent), false) => {
+                if percent <= Decimal::ZERO || percent > Decimal::ONE_HUNDRED {
+                    return Err("needs a `drop_percent` above 0 and at most 100".to_owned());
+                }
+                let window = parse_duration(self.window.as_deref().unwrap_or("1h"))
+                    .map_err(|e| e.to_string())?;
+                Condition::Drop { percent, window }
+            }
+            (None, None, true) => Condition::Outgoing,
+            _ => {
+                return Err(
+                    "needs exactly one of `below`, `drop_percent` or `outgoing = true`".to_owned(),
+                )
+            }
+        };
+        if self.window.is_some() && self.drop_percent.is_none() {
+            return Err("has a `window` but no `drop_percent`".to_owned());
+        }
+        let mut rule = Rule::new(&self.name, condition);
+        if let Some(wallet) = &self.wallet {
+            rule = rule.with_wallet(wallet);
+        }
+        if let Some(asset) = &self.asset {
+            rule = rule.with_asset(asset);
+        }
+        if let Some(category) = self.category {
+            rule = rule.with_category(category);
+        }
+        Ok(rule)
+    }
+}
+
+/// Where alerts go, selected by `kind`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum NotifierConfig {
+    /// Mail through a plain SMTP relay at `smtp` (`host:port`), such as a
+    /// local MTA.
+    Email {
+        smtp: String,
+        from: String,
+        to: Vec<String>,
+    },
+    /// POST each alert as signed JSON, like event webhooks.
+    Webhook { url: String, secret: String },
+    /// Print each alert as a line on standard output.
+    Stdout,
+}
+
+impl WalletConfig {
+    /// The configured name, or the id if there is none.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+
+    /// The wallet's keys as one string, to tell whether a cached wallet
+    /// still describes the same funds.
+    pub fn spec(&self) -> String {
+        match (&self.xpub, &self.descriptor) {
+            (Some(xpub), _) => format!("xpub:{xpub}"),
+            (None, Some(descriptor)) => match &self.change_descriptor {
+                Some(change) => format!("descriptor:{descriptor};{change}"),
+                None => format!("descriptor:{descriptor}"),
+            },
+            (None, None) => format!("addresses:{}", self.addresses.join(",")),
+        }
+    }
+
+    /// Adds this wallet as a new `[[wallets]]` entry of a TOML document,
+    /// leaving the rest of it, comments included, untouched.
+    fn append_to(&self, doc: &mut DocumentMut) -> Option<()> {
+        let mut table = Table::new();
+        table["id"] = value(&self.id);
+        if let Some(name) = &self.name {
+            table["name"] = value(name);
+        }
+        table["source"] = value(&self.source);
+        if !self.addresses.is_empty() {
+            table["addresses"] = value(self.addresses.iter().collect::<Array>());
+        }
+        for (key, field) in [
+            ("xpub", &self.xpub),
+            ("descriptor", &self.descriptor),
+            ("change_descriptor", &self.change_descriptor),
+        ] {
+            if let Some(v) = field {
+                table[key] = value(v);
+            }
+        }
+        doc.entry("wallets")
+            .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()?
+            .push(table);
+        Some(())
+    }
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| Error::ReadConfig {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&text, path)
+    }
+
+    /// Parses and validates `text`, read from `path`.
+    pub fn parse(text: &str, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut config: Config = toml::from_str(text).map_err(|e| Error::Config {
+            path: path.to_path_buf(),
+            reason: e.message().to_owned(),
+        })?;
+        config.path = path.to_path_buf();
+        config.registry = config.build_registry()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The built-in EVM chains with the configured `chains` applied.
+    pub fn registry(&self) -> &ChainRegistry {
+        &self.registry
+    }
+
+    /// The registered chain an EVM source names.
+    pub fn chain(&self, name: &str) -> Result<&EvmChain> {
+        self.registry.find(name).ok_or_else(|| Error::Config {
+            path: self.path.clone(),
+            reason: format!("unknown chain `{name}`; add it under `[chains.{name}]`"),
+        })
+    }
+
+    /// `path` relative to the directory of the config file.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        match self.path.parent() {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    pub fn wallet(&self, id: &str) -> Result<&WalletConfig> {
+        self.wallets
+            .iter()
+            .find(|w| w.id == id)
+            .ok_or_else(|| Error::UnknownWallet(id.to_owned()))
+    }
+
+    /// Appends `wallet` to the config file on disk and to this config.
+    ///
+    /// The file is edited in place rather than re-serialized, so comments
+    /// and formatting survive.
+    pub fn add_wallet(&mut self, wallet: WalletConfig) -> Result<()> {
+        let mut updated = self.clone();
+        updated.wallets.push(wallet.clone());
+        updated.validate()?;
+
+        let text = fs::read_to_string(&self.path)?;
+        let invalid = |reason: &str| Error::Config {
+            path: self.path.clone(),
+            reason: reason.to_owned(),
+        };
+        let mut doc: DocumentMut = text.parse().map_err(|_| invalid("not valid TOML"))?;
+        wallet
+            .append_to(&mut doc)
+            .ok_or_else(|| invalid("`wallets` must be an array of tables"))?;
+        fs::write(&self.path, doc.to_string())?;
+        *self = updated;
+        Ok(())
+    }
+
+    fn build_registry(&self) -> Result<ChainRegistry> {
+        let mut registry = ChainRegistry::builtin();
+        for (name, config) in &self.chains {
+            let invalid = |reason: &str| Error::Config {
+                path: self.path.clone(),
+                reason: format!("chain `{name}` {reason}"),
+            };
+            let builtin = match config.id {
+                Some(id) => registry.get(id),
+                None => registry.find(name),
+            };
+            let mut chain = match (builtin, config.id, &config.symbol) {
+                (Some(chain), ..) if chain.name != *name => {
+                    return Err(invalid(&format!("is built in as `{}`", chain.name)));
+                }
+                (Some(chain), ..) => chain.clone(),
+                (None, Some(id), Some(symbol)) => EvmChain::new(id, name, symbol, 18),
+                (None, ..) => {
+                    return Err(invalid("is not built in, so needs an `id` and a `symbol`"))
+                }
+            };
+            if let Some(symbol) = &config.symbol {
+                chain.native.symbol = symbol.clone();
+            }
+            if let Some(decimals) = config.decimals {
+                chain.native.decimals = decimals;
+            }
+            chain.rpc_urls.splice(0..0, config.urls.iter().cloned());
+            for token in &config.tokens {
+                let contract: EthAddress = token
+                    .contract
+                    .parse()
+                    .map_err(|e: walletb_ethereum::Error| invalid(&e.to_string()))?;
+                chain = chain.with_token(&contract.to_checksum(), &token.symbol, token.decimals);
+            }
+            registry.insert(chain);
+        }
+        Ok(registry)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for wallet in &self.wallets {
+            let invalid = |reason: &str| Error::InvalidWallet {
+                wallet: wallet.id.clone(),
+                reason: reason.to_owned(),
+            };
+            if !ids.insert(wallet.id.as_str()) {
+                return Err(invalid("defined more than once"));
+            }
+            if !self.sources.contains_key(&wallet.source) {
+                return Err(Error::UnknownSource {
+                    wallet: wallet.id.clone(),
+                    source_id: wallet.source.clone(),
+                });
+            }
+            let kinds = usize::from(!wallet.addresses.is_empty())
+                + usize::from(wallet.xpub.is_some())
+                + usize::from(wallet.descriptor.is_some());
+            if kinds != 1 {
+                return Err(invalid(
+                    "needs exactly one of `addresses`, `xpub` or `descriptor`",
+                ));
+            }
+            if wallet.change_descriptor.is_some() && wallet.descriptor.is_none() {
+                return Err(invalid("`change_descriptor` needs a `descriptor`"));
+            }
+        }
+        for (id, source) in &self.sources {
+            let invalid = |reason: &str| Error::Config {
+                path: self.path.clone(),
+                reason: format!("source `{id}` {reason}"),
+            };
+            match source {
+                SourceConfig::Bitcoin {
+                    utxos,
+                    history,
+                    electrum,
+                    ..
+                } => {
+                    let backends = [utxos.is_some(), history.is_some(), electrum.is_some()];
+                    if backends.iter().filter(|&&set| set).count() != 1 {
+                        return Err(invalid(
+                            "needs exactly one of `utxos`, `history` or `electrum`",
+                        ));
+                    }
+                }
+                SourceConfig::BitcoinCore {
+                    cookie,
+                    user,
+                    password,
+                    wallet,
+                    sync_from,
+                    ..
+                } => {
+                    if cookie.is_some() == (user.is_some() || password.is_some())
+                        || user.is_some() != password.is_some()
+                    {
+                        return Err(invalid(
+                            "needs either a `cookie` or both `user` and `password`",
+                        ));
+                    }
+                    if sync_from.is_some() && wallet.is_some() {
+                        return Err(invalid("cannot have both `wallet` and `sync_from`"));
+                    }
+                    if sync_from.is_some() && self.database.is_none() {
+                        return Err(invalid("needs a `database` to sync into"));
+                    }
+                }
+                SourceConfig::Ethereum { chain, url, .. } => {
+                    let chain = self.chain(chain.as_deref().unwrap_or("ethereum"))?;
+                    if url.is_none() && chain.rpc_urls.is_empty() {
+                        return Err(invalid(&format!("needs a `url` for {}", chain.name)));
+                    }
+                }
+                SourceConfig::Evm { chains, urls } => {
+                    if chains.is_empty() {
+                        return Err(invalid("needs at least one of `chains`"));
+                    }
+                    let mut seen = HashSet::new();
+                    for name in chains {
+                        let chain = self.chain(name)?;
+                        if !seen.insert(chain.id) {
+                            return Err(invalid(&format!("lists {} more than once", chain.name)));
+                        }
+                        if !urls.contains_key(name) && chain.rpc_urls.is_empty() {
+                            return Err(invalid(&format!("needs a `urls` entry for {name}")));
+                        }
+                    }
+                    if let Some(name) = urls.keys().find(|name| !chains.contains(name)) {
+                        return Err(invalid(&format!("has a url for unlisted chain `{name}`")));
+                    }
+                }
+                SourceConfig::Solana { .. } | SourceConfig::Beacon { .. } => {}
+                SourceConfig::Cosmos { .. } => {
+                    if let Some(Err(reason)) = source.cosmos_chain() {
+                        return Err(invalid(&reason));
+                    }
+                }
+            }
+        }
+        let keys = self.server.iter().flat_map(|server| &server.keys);
+        let (mut names, mut secrets) = (HashSet::new(), HashSet::new());
+        for key in keys {
+            let invalid = |reason: &str| Error::Config {
+                path: self.path.clone(),
+                reason: format!("server key `{}` {reason}", key.name),
+            };
+            if !names.insert(key.name.as_str()) {
+                return Err(invalid("is defined more than once"));
+            }
+            if key.key.chars().count() < 16 {
+                return Err(invalid("is shorter than 16 characters"));
+            }
+            if !secrets.insert(key.key.as_str()) {
+                return Err(invalid("reuses another key's secret"));
+            }
+            if key.requests_per_minute == 0 {
+                return Err(invalid("allows no requests per minute"));
+            }
+        }
+        if let Some(events) = &self.events {
+            let invalid = |reason: String| Error::Config {
+                path: self.path.clone(),
+                reason,
+            };
+            if events.confirmations == 0 {
+                return Err(invalid(
+                    "`events.confirmations` must be at least 1".to_owned(),
+                ));
+            }
+            for webhook in &events.webhooks {
+                check_webhook(&webhook.url, &webhook.secret).map_err(invalid)?;
+            }
+        }
+        if let Some(alerts) = &self.alerts {
+            self.validate_alerts(alerts)?;
+        }
+        Ok(())
+    }
+
+    fn validate_alerts(&self, alerts: &AlertsConfig) -> Result<()> {
+        let invalid = |reason: String| Error::Config {
+            path: self.path.clone(),
+            reason,
+        };
+        for (name, notifier) in &alerts.notifiers {
+            match notifier {
+                NotifierConfig::Email { to, .. } if to.is_empty() => {
+                    return Err(invalid(format!("notifier `{name}` has no `to` addresses")));
+                }
+                NotifierConfig::Webhook { url, secret } => {
+                    check_webhook(url, secret)
+                        .map_err(|reason| invalid(format!("notifier `{name}`: {reason}")))?;
+                }
+                NotifierConfig::Email { .. } | NotifierConfig::Stdout => {}
+            }
+        }
+        let mut names = HashSet::new();
+        for rule in &alerts.rules {
+            let invalid = |reason: &str| invalid(format!("alert rule `{}` {reason}", rule.name));
+            if !names.insert(rule.name.as_str()) {
+                return Err(invalid("is defined more than once"));
+            }
+            if let Some(wallet) = &rule.wallet {
+                if !self.wallets.iter().any(|w| &w.id == wallet) {
+                    return Err(invalid(&format!("watches unknown wallet `{wallet}`")));
+                }
+            }
+            rule.rule().map_err(|reason| invalid(&reason))?;
+            if rule.notify.is_empty() {
+                return Err(invalid("notifies no one; list notifiers in `notify`"));
+            }
+            if let Some(name) = rule
+                .notify
+                .iter()
+                .find(|n| !alerts.notifiers.contains_key(*n))
+            {
+                return Err(invalid(&format!("uses undefined notifier `{name}`")));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why `url` and `secret` cannot make a webhook, if they cannot.
+fn check_webhook(url: &str, secret: &str) -> std::result::Result<(), String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("webhook {url} is not an http(s) url"));
+    }
+    if secret.chars().count() < 16 {
+        return Err(format!(
+            "webhook {url} has a secret shorter than 16 characters"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const CONFIG: &str = r#"
+# Treasury wallets.
+currency = "EUR"
+
+[sources.btc]
+kind = "bitcoin"
+utxos = "utxos.json"
+
+[sources.eth]
+kind = "ethereum"
+url = "http://localhost:8545"
+tokens = [{ contract = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", symbol = "USDC", decimals = 6 }]
+
+[[wallets]]
+id = "hot"
+source = "eth"
+addresses = ["0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"]
+"#;
+
+    #[test]
+    fn parses_sources_and_wallets() {
+        let config = Config::parse(CONFIG, "/etc/walletb/walletb.toml").unwrap();
+        assert_eq!(config.currency, "EUR");
+        assert_eq!(config.gap_limit, DEFAULT_GAP_LIMIT);
+        assert_eq!(
+            config.sources["btc"],
+            SourceConfig::Bitcoin {
+                network: Network::Bitcoin,
+                utxos: Some("utxos.json".into()),
+                history: None,
+                electrum: None,
+            }
+        );
+        assert_eq!(config.wallet("hot").unwrap().display_name(), "hot");
+        assert_eq!(
+            config.resolve(Path::new("utxos.json")),
+            Path::new("/etc/walletb/utxos.json")
+        );
+    }
+
+    #[test]
+    fn rejects_inconsistent_wallets() {
+        for (wallet, expected) in [
+            (
+                "id = \"x\"\nsource = \"sol\"\naddresses = [\"a\"]",
+                "undefined source",
+            ),
+            ("id = \"x\"\nsource = \"btc\"", "exactly one"),
+            (
+                "id = \"hot\"\nsource = \"eth\"\naddresses = [\"a\"]",
+                "more than once",
+            ),
+            (
+                "id = \"x\"\nsource = \"btc\"\ncolor = \"red\"",
+                "unknown field",
+            ),
+        ] {
+            let text = format!("{CONFIG}\n[[wallets]]\n{wallet}\n");
+            let err = Config::parse(&text, "walletb.toml").unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn checks_server_keys() {
+        let server = "[server]\n[[server.keys]]\nname = \"billing\"\nkey = \"0123456789abcdef\"";
+        let config = Config::parse(&format!("{CONFIG}\n{server}\n"), "walletb.toml").unwrap();
+        let server = config.server.unwrap();
+        assert_eq!(server.listen, "127.0.0.1:8080");
+        assert_eq!(server.keys[0].requests_per_minute, 60);
+
+        for (key, expected) in [
+            ("name = \"a\"\nkey = \"short\"", "shorter than 16"),
+            (
+                "name = \"a\"\nkey = \"fedcba9876543210\"\nrequests_per_minute = 0",
+                "no requests",
+            ),
+            (
+                "name = \"billing\"\nkey = \"fedcba9876543210\"",
+                "more than once",
+            ),
+            ("name = \"a\"\nkey = \"0123456789abcdef\"", "reuses"),
+        ] {
+            let text = format!(
+                "{CONFIG}\n[[server.keys]]\nname = \"billing\"\nkey = \"0123456789abcdef\"\n\n[[server.keys]]\n{key}\n"
+            );
+            let err = Config::parse(&text, "walletb.toml").unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn checks_event_webhooks() {
+        let events = "[events]\n[[events.webhooks]]\nurl = \"https://ops.example.com/hook\"\nsecret = \"0123456789abcdef\"\nevents = [\"confirmed\", \"reorged\"]";
+        let config = Config::parse(&format!("{CONFIG}\n{events}\n"), "walletb.toml").unwrap();
+        let events = config.events.unwrap();
+        assert_eq!(events.confirmations, 6);
+        assert_eq!(events.listen, None);
+        assert_eq!(
+            events.webhooks[0].events,
+            [EventKind::Confirmed, EventKind::Reorged]
+        );
+
+        for (webhook, expected) in [
+            ("url = \"ops.example.com\"\nsecret = \"0123456789abcdef\"", "not an http(s) url"),
+            ("url = \"https://ops.example.com\"\nsecret = \"short\"", "shorter than 16"),
+            ("url = \"https://ops.example.com\"\nsecret = \"0123456789abcdef\"\nevents = [\"deposit\"]", "unknown variant"),
+        ] {
+            let text = format!("{CONFIG}\n[[events.webhooks]]\n{webhook}\n");
+            let err = Config::parse(&text, "walletb.toml").unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+        let text = format!("{CONFIG}\n[events]\nconfirmations = 0\n");
+        let err = Config::parse(&text, "walletb.toml").unwrap_err();
+        assert!(err.to_string().contains("at least 1"), "{err}");
+    }
+
+    #[test]
+    fn checks_alert_rules() {
+        let notifiers = "[alerts.notifiers.ops]\nkind = \"email\"\nsmtp = \"127.0.0.1:25\"\nfrom = \"walletb@example.com\"\nto = [\"ops@example.com\"]\n[alerts.notifiers.log]\nkind = \"stdout\"";
+        let rules = "[[alerts.rules]]\nname = \"hot-low\"\nwallet = \"hot\"\nasset = \"ETH\"\nbelow = \"0.5\"\nnotify = [\"ops\", \"log\"]\n[[alerts.rules]]\nname = \"hot-drop\"\ndrop_percent = \"5\"\nnotify = [\"log\"]";
+        let config =
+            Config::parse(&format!("{CONFIG}\n{rules}\n{notifiers}\n"), "walletb.toml").unwrap();
+        let alerts = config.alerts.unwrap();
+        assert_eq!(alerts.notifiers["log"], NotifierConfig::Stdout);
+        let low = alerts.rules[0].rule().unwrap();
+        assert_eq!(low.wallet.as_deref(), Some("hot"));
+        assert_eq!(low.condition, Condition::Below(Decimal::new(5, 1)));
+        assert_eq!(
+            alerts.rules[1].rule().unwrap().condition,
+            Condition::Drop {
+                percent: Decimal::from(5),
+                window: Duration::from_secs(3600),
+            }
+        );
+
+        for (rule, expected) in [
+            (
+                "asset = \"ETH\"\nbelow = \"1\"\noutgoing = true\nnotify = [\"log\"]",
+                "exactly one",
+            ),
+            ("below = \"1\"\nnotify = [\"log\"]", "needs an `asset`"),
+            ("drop_percent = \"150\"\nnotify = [\"log\"]", "at most 100"),
+            (
+                "drop_percent = \"5\"\nwindow = \"an hour\"\nnotify = [\"log\"]",
+                "invalid duration",
+            ),
+            (
+                "outgoing = true\nwindow = \"1h\"\nnotify = [\"log\"]",
+                "no `drop_percent`",
+            ),
+            (
+                "wallet = \"cold\"\noutgoing = true\nnotify = [\"log\"]",
+                "unknown wallet `cold`",
+            ),
+            ("outgoing = true", "notifies no one"),
+            (
+                "outgoing = true\nnotify = [\"pager\"]",
+                "undefined notifier `pager`",
+            ),
+        ] {
+            let text = format!("{CONFIG}\n[[alerts.rules]]\nname = \"r\"\n{rule}\n{notifiers}\n");
+            let err = Config::parse(&text, "walletb.toml").unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+        let twice = format!("{CONFIG}\n{rules}\n{rules}\n{notifiers}\n");
+        let err = Config::parse(&twice, "walletb.toml").unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{err}");
+        let hook = "[alerts.notifiers.hook]\nkind = \"webhook\"\nurl = \"https://ops.example.com\"\nsecret = \"short\"";
+        let err = Config::parse(&format!("{CONFIG}\n{hook}\n"), "walletb.toml").unwrap_err();
+        assert!(err.to_string().contains("shorter than 16"), "{err}");
+    }
+
+    #[test]
+    fn requires_one_way_to_authenticate_to_bitcoin_core() {
+        let node = "[sources.node]\nkind = \"bitcoin-core\"\nurl = \"http://127.0.0.1:8332\"";
+        for (auth, valid) in [
+            ("cookie = \".cookie\"", true),
+            ("user = \"u\"\npassword = \"p\"", true),
+            ("", false),
+            ("user = \"u\"", false),
+            (
+                "cookie = \".cookie\"\nuser = \"u\"\npassword = \"p\"",
+                false,
+            ),
+        ] {
+            let text = format!("{CONFIG}\n{node}\n{auth}\n");
+            match Config::parse(&text, "walletb.toml") {
+                Ok(_) => assert!(valid, "{auth}"),
+                Err(err) => {
+                    assert!(!valid, "{auth}: {err}");
+                    assert!(err.to_string().contains("`cookie`"), "{err}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn syncs_bitcoin_core_only_into_a_database() {
+        let node = "[sources.node]\nkind = \"bitcoin-core\"\nurl = \"http://127.0.0.1:8332\"\ncookie = \".cookie\"\nsync_from = 800000";
+        let parse = |before: &str, after: &str| {
+            Config::parse(
+                &format!("{before}{CONFIG}\n{node}\n{after}\n"),
+                "walletb.toml",
+            )
+        };
+        parse("database = \"walletb.db\"\n", "").unwrap();
+        let err = parse("", "").unwrap_err();
+        assert!(err.to_string().contains("needs a `database`"), "{err}");
+        let err = parse("database = \"walletb.db\"\n", "wallet = \"walletb\"").unwrap_err();
+        assert!(err.to_string().contains("`sync_from`"), "{err}");
+    }
+
+    #[test]
+    fn extends_the_chain_registry() {
+        let chains = r#"
+[chains.polygon]
+urls = ["http://polygon.internal:8545"]
+tokens = [{ contract = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619", symbol = "WETH", decimals = 18 }]
+
+[chains.zksync]
+id = 324
+symbol = "ETH"
+"#;
+        let config = Config::parse(&format!("{CONFIG}{chains}"), "walletb.toml").unwrap();
+        let polygon = config.chain("polygon").unwrap();
+        assert_eq!(polygon.rpc_urls[0], "http://polygon.internal:8545");
+        assert_eq!(polygon.rpc_urls.len(), 2);
+        assert_eq!(polygon.tokens.last().unwrap().symbol, "WETH");
+        assert_eq!(config.chain("zksync").unwrap().native.decimals, 18);
+
+        for (text, expected) in [
+            ("[chains.zksync]\nsymbol = \"ETH\"", "needs an `id`"),
+            ("[chains.matic]\nid = 137", "built in as `polygon`"),
+            (
+                "[sources.l2]\nkind = \"evm\"\nchains = [\"base\", \"evm:8453\"]",
+                "more than once",
+            ),
+            (
+                "[chains.zksync]\nid = 324\nsymbol = \"ETH\"\n\
+                 [sources.l2]\nkind = \"evm\"\nchains = [\"zksync\"]",
+                "needs a `urls` entry for zksync",
+            ),
+            (
+                "[sources.l2]\nkind = \"evm\"\nchains = [\"base\"]\nurls = { bsc = \"x\" }",
+                "unlisted chain `bsc`",
+            ),
+        ] {
+            let err = Config::parse(&format!("{CONFIG}\n{text}\n"), "walletb.toml").unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn fills_in_known_cosmos_chains() {
+        let sources = r#"
+[sources.osmo]
+kind = "cosmos"
+url = "http://localhost:1317"
+chain_id = "osmosis-1"
+
+[sources.dydx]
+kind = "cosmos"
+url = "http://localhost:1318"
+chain_id = "dydx-mainnet-1"
+prefix = "dydx"
+denom = "adydx"
+symbol = "DYDX"
+decimals = 18
+tokens = [{ contract = "ibc/8E27BA2D5493AF5636760E354E46004562C46AB7EC0CC4C1CA14E9E20E2545B5", symbol = "USDC", decimals = 6 }]
+"#;
+        let config = Config::parse(&format!("{CONFIG}{sources}"), "walletb.toml").unwrap();
+        let osmosis = config.sources["osmo"].cosmos_chain().unwrap().unwrap();
+        assert_eq!(
+            (osmosis.prefix.as_str(), osmosis.denom.as_str()),
+            ("osmo", "uosmo")
+        );
+        let dydx = config.sources["dydx"].cosmos_chain().unwrap().unwrap();
+        assert_eq!(dydx.chain().to_string(), "cosmos:dydx-mainnet-1");
+        assert_eq!(dydx.native.decimals, 18);
+        assert_eq!(dydx.tokens[0].symbol, "USDC");
+        assert!(config.sources["btc"].cosmos_chain().is_none());
+
+        for (text, expected) in [
+            ("chain_id = \"dydx-mainnet-1\"", "needs `prefix`, `denom`"),
+            ("chain_id = \"cosmos hub\"", "invalid `chain_id`"),
+        ] {
+            let text = format!("[sources.c]\nkind = \"cosmos\"\nurl = \"x\"\n{text}");
+            let err = Config::parse(&format!("{CONFIG}\n{text}\n"), "walletb.toml").unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn appends_wallets_without_losing_comments() {
+        let mut config = Config::parse(CONFIG, "walletb.toml").unwrap();
+        let wallet = WalletConfig {
+            id: "cold".into(),
+            name: Some("Cold storage".into()),
+            source: "btc".into(),
+            xpub: Some("zpub".into()),
+            ..WalletConfig::default()
+        };
+        let mut doc: DocumentMut = CONFIG.parse().unwrap();
+        wallet.append_to(&mut doc).unwrap();
+        let text = doc.to_string();
+        assert!(text.starts_with("\n# Treasury wallets.\n"));
+        let reparsed = Config::parse(&text, "walletb.toml").unwrap();
+        assert_eq!(reparsed.wallets[1], wallet);
+
+        // Validation runs before the file is touched.
+        config.path = "/nonexistent/walletb.toml".into();
+        let duplicate = WalletConfig {
+            id: "hot".into(),
+            ..wallet
+        };
+        assert!(matches!(
+            config.add_wallet(duplicate),
+            Err(Error::InvalidWallet { .. })
+        ));
+        assert_eq!(config.wallets.len(), 1);
+    }
+}
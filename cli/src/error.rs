@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("cannot read config {path}: {source}")]
+    ReadConfig {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("invalid config {path}: {reason}")]
+    Config { path: PathBuf, reason: String },
+
+    #[error("no wallet `{0}` in the config")]
+    UnknownWallet(String),
+
+    #[error("wallet `{wallet}` uses undefined source `{source_id}`")]
+    UnknownSource { wallet: String, source_id: String },
+
+    #[error("wallet `{wallet}`: {reason}")]
+    InvalidWallet { wallet: String, reason: String },
+
+    #[error("source `{0}` cannot report past balances; a Bitcoin source needs a `history` file or an `electrum` server")]
+    NoHistory(String),
+
+    #[error("source `{0}` cannot list transactions; a Bitcoin source needs a `history` file or an `electrum` server")]
+    NoTransactions(String),
+
+    #[error("source `{0}` is not synced block by block; a Bitcoin Core source needs `sync_from`")]
+    NotSynced(String),
+
+    #[error("{0} takes a date, not a block height")]
+    NeedsDate(&'static str),
+
+    #[error("no price file configured; set `prices` in the config or pass --prices")]
+    NoPrices,
+
+    #[error("no database configured; set `database` in the config")]
+    NoDatabase,
+
+    #[error(transparent)]
+    Store(#[from] walletb_store::Error),
+
+    #[error(transparent)]
+    Core(#[from] walletb_core::Error),
+
+    #[error(transparent)]
+    Bitcoin(#[from] walletb_bitcoin::Error),
+
+    #[error(transparent)]
+    Ethereum(#[from] walletb_ethereum::Error),
+
+    #[error(transparent)]
+    Solana(#[from] walletb_solana::Error),
+
+    #[error(transparent)]
+    Cosmos(#[from] walletb_cosmos::Error),
+
+    #[error(transparent)]
+    Notify(#[from] walletb_notify::Error),
+
+    #[error(transparent)]
+    Portfolio(#[from] walletb_portfolio::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,20 @@
+//! Library side of the `walletb` command-line tool.
+//!
+//! A [`Config`] is a TOML file naming balance sources (a Bitcoin UTXO or
+//! history file, a Bitcoin, EVM, Solana or Cosmos node) and the wallets
+//! read through them.
+//! [`Wallets::open`] turns it into something that can be read, and
+//! [`Table`] renders the results as aligned text, JSON or CSV.
+
+mod config;
+mod error;
+mod output;
+mod wallets;
+
+pub use config::{
+    AlertsConfig, ApiKeyConfig, ChainConfig, Config, EventsConfig, NotifierConfig, RuleConfig,
+    ServerConfig, SourceConfig, TokenConfig, WalletConfig, WebhookConfig,
+};
+pub use error::{Error, Result};
+pub use output::{Format, Table};
+pub use wallets::{Source, Wallet, Wallets};
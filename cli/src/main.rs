@@ -0,0 +1,789 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, Subcommand};
+use walletb_cli::{Config, Error, Format, NotifierConfig, Result, Table, WalletConfig, Wallets};
+use walletb_core::{Balance, Period, PointInTime};
+use walletb_notify::{
+    Email, EventStream, Notifier, Observation, Rules, Sink, Stdout, Tracker, Webhook,
+};
+use walletb_portfolio::{
+    gains, movements, value_balances, CostMethod, GainsReport, PortfolioReport, PriceFile,
+    WalletBalances,
+};
+
+/// Wallet balances across chains.
+#[derive(Debug, Parser)]
+#[command(name = "walletb", version)]
+struct Cli {
+    /// Config file naming the sources and wallets.
+    #[arg(
+        long,
+        short,
+        global = true,
+        env = "WALLETB_CONFIG",
+        default_value = "walletb.toml"
+    )]
+    config: PathBuf,
+
+    #[arg(long, short, global = true, value_enum, default_value_t)]
+    format: Format,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the current balances of some or all wallets.
+    Balance {
+        /// Wallet ids; all wallets if none are given.
+        wallets: Vec<String>,
+        /// Print the balances last recorded in the database instead of
+        /// reading the sources.
+        #[arg(long)]
+        cached: bool,
+    },
+
+    /// Add a wallet to the config file.
+    AddWallet {
+        id: String,
+        /// The source to read the wallet through.
+        #[arg(long)]
+        source: String,
+        #[arg(long)]
+        name: Option<String>,
+        /// A fixed address; repeat for several.
+        #[arg(long = "address", conflicts_with_all = ["xpub", "descriptor"])]
+        addresses: Vec<String>,
+        /// An account xpub, ypub or zpub, scanned up to the gap limit.
+        #[arg(long, conflicts_with = "descriptor")]
+        xpub: Option<String>,
+        /// An output descriptor, scanned up to the gap limit.
+        #[arg(long)]
+        descriptor: Option<String>,
+        #[arg(long, requires = "descriptor")]
+        change_descriptor: Option<String>,
+    },
+
+    /// Print balances at past block heights or dates.
+    History {
+        /// Wallet ids; all wallets if none are given.
+        wallets: Vec<String>,
+        /// `height:N`, a date `YYYY-MM-DD` (end of that UTC day) or a UTC
+        /// timestamp; repeat for several.
+        #[arg(long = "at", required = true)]
+        at: Vec<PointInTime>,
+    },
+
+    /// List the transactions of some or all wallets, telling income and
+    /// expense from moves between the wallets listed.
+    Transactions {
+        /// Wallet ids; all wallets if none are given.
+        wallets: Vec<String>,
+        /// List what came after this height, date or timestamp.
+        #[arg(long)]
+        after: Option<PointInTime>,
+        /// List up to and including this height, date or timestamp.
+        #[arg(long)]
+        until: Option<PointInTime>,
+    },
+
+    /// Work out the cost basis of the wallets' transactions, at the dated
+    /// prices of the price file, and the realized and unrealized gains.
+    Gains {
+        /// Wallet ids; all wallets if none are given.
+        wallets: Vec<String>,
+        /// `fifo`, `lifo`, `hifo` or `average`.
+        #[arg(long, default_value_t)]
+        method: CostMethod,
+        /// Read the history from after this height, date or timestamp.
+        #[arg(long)]
+        after: Option<PointInTime>,
+        /// Read the history up to this date or timestamp, and value what is
+        /// left at the prices of that date rather than the current ones.
+        #[arg(long)]
+        until: Option<PointInTime>,
+        /// List only the disposals after this date, such as the end of the
+        /// last tax year; the lots they draw on still come from the whole
+        /// history.
+        #[arg(long)]
+        disposed_after: Option<PointInTime>,
+        /// Price file, overriding the config.
+        #[arg(long)]
+        prices: Option<PathBuf>,
+        /// Currency, overriding the config.
+        #[arg(long)]
+        currency: Option<String>,
+    },
+
+    /// Value every wallet in fiat and write the report.
+    Export {
+        /// Value the balances held at this point instead of now, at the
+        /// prices in the price file.
+        #[arg(long)]
+        at: Option<PointInTime>,
+        /// Write to a file instead of standard output.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Price file, overriding the config.
+        #[arg(long)]
+        prices: Option<PathBuf>,
+        /// Currency, overriding the config.
+        #[arg(long)]
+        currency: Option<String>,
+    },
+
+    /// Bring the database up to the chain tip for the wallets of Bitcoin
+    /// Core sources with `sync_from`, undoing blocks the node has since
+    /// reorganized away.
+    Sync {
+        /// Wallet ids; all synced wallets if none are given.
+        wallets: Vec<String>,
+    },
+
+    /// Poll balances and print every change, sending events to the
+    /// webhooks and stream of the config's `[events]` and raising the
+    /// alerts of its `[alerts]` rules.
+    Watch {
+        /// Wallet ids; all wallets if none are given.
+        wallets: Vec<String>,
+        /// Seconds between polls.
+        #[arg(long, default_value_t = 60)]
+        interval: u64,
+        /// Stop after this many polls.
+        #[arg(long)]
+        count: Option<u64>,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli, &mut io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("walletb: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli, out: &mut dyn Write) -> Result<()> {
+    let mut config = Config::load(&cli.config)?;
+    match cli.command {
+        Command::Balance { wallets, cached } => {
+            if cached {
+                cached_balance(&config, &wallets, cli.format, out)
+            } else {
+                balance(&config, &wallets, cli.format, out)
+            }
+        }
+        Command::AddWallet {
+            id,
+            source,
+            name,
+            addresses,
+            xpub,
+            descriptor,
+            change_descriptor,
+        } => {
+            let wallet = WalletConfig {
+                id,
+                name,
+                source,
+                addresses,
+                xpub,
+                descriptor,
+                change_descriptor,
+            };
+            // Parse the keys against the source before touching the file.
+            let mut candidate = config.clone();
+            candidate.wallets.push(wallet.clone());
+            Wallets::open(&candidate)?;
+            let id = wallet.id.clone();
+            config.add_wallet(wallet)?;
+            writeln!(out, "added wallet `{id}` to {}", config.path().display())?;
+            Ok(())
+        }
+        Command::History { wallets, at } => history(&config, &wallets, &at, cli.format, out),
+        Command::Transactions {
+            wallets,
+            after,
+            until,
+        } => transactions(&config, &wallets, Period { after, until }, cli.format, out),
+        Command::Gains {
+            wallets: ids,
+            method,
+            after,
+            until,
+            disposed_after,
+            prices,
+            currency,
+        } => {
+            let prices = price_file(&config, prices)?;
+            let currency = currency.unwrap_or_else(|| config.currency.clone());
+            let disposed_after = match disposed_after {
+                Some(PointInTime::Time(time)) => Some(time),
+                Some(PointInTime::Height(_)) => return Err(Error::NeedsDate("--disposed-after")),
+                None => None,
+            };
+            // Heights differ from chain to chain, so what is left can only
+            // be valued at a date.
+            let valued_at = match until {
+                Some(PointInTime::Time(time)) => Some(time),
+                Some(PointInTime::Height(_)) => return Err(Error::NeedsDate("--until")),
+                None => None,
+            };
+            let wallets = Wallets::open(&config)?;
+            let ledgers = wallets
+                .select(&ids)?
+                .into_iter()
+                .map(|w| wallets.ledger(w, Period { after, until }))
+                .collect::<Result<Vec<_>>>()?;
+            let mut report = gains(&movements(&ledgers), method, &prices, &currency, valued_at)?;
+            if let Some(time) = disposed_after {
+                report = report.disposed_after(time)?;
+            }
+            gains_report(&report, cli.format, out)
+        }
+        Command::Export {
+            at,
+            output,
+            prices,
+            currency,
+        } => {
+            let prices = price_file(&config, prices)?;
+            let currency = currency.unwrap_or_else(|| config.currency.clone());
+            let wallets = Wallets::open(&config)?;
+            let balances = wallets
+                .iter()
+                .map(|w| match at {
+                    Some(at) => wallets.balances_at(w, at),
+                    None => wallets.balances(w),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let report = value_balances(&balances, &prices, &currency)?;
+            match output {
+                Some(path) => export(&report, cli.format, &mut File::create(path)?),
+                None => export(&report, cli.format, out),
+            }
+        }
+        Command::Sync { wallets } => sync(&config, &wallets, cli.format, out),
+        Command::Watch {
+            wallets,
+            interval,
+            count,
+        } => watch(
+            &config,
+            &wallets,
+            Duration::from_secs(interval),
+            count,
+            cli.format,
+            out,
+        ),
+    }
+}
+
+fn balance(config: &Config, ids: &[String], format: Format, out: &mut dyn Write) -> Result<()> {
+    let wallets = Wallets::open(config)?;
+    let mut table = balance_table(Table::new());
+    for wallet in wallets.select(ids)? {
+        let read = wallets.balances(wallet)?;
+        for balance in &read.balances {
+            table.push(balance_row(&read, balance, Vec::new()));
+        }
+    }
+    table.write(format, out)?;
+    Ok(())
+}
+
+/// Prints the latest snapshot of each wallet, with when it was taken.
+/// Wallets never read are left out.
+fn cached_balance(
+    config: &Config,
+    ids: &[String],
+    format: Format,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let wallets = Wallets::open(config)?;
+    let mut table = balance_table(Table::new().text("taken at"));
+    for wallet in wallets.select(ids)? {
+        let Some(snapshot) = wallets.cached(wallet)? else {
+            continue;
+        };
+        let taken_at = PointInTime::Time(snapshot.taken_at).to_string();
+        let read = WalletBalances {
+            id: wallet.id.clone(),
+            name: wallet.name.clone(),
+            balances: snapshot.balances,
+        };
+        for balance in &read.balances {
+            table.push(balance_row(&read, balance, vec![taken_at.clone()]));
+        }
+    }
+    table.write(format, out)?;
+    Ok(())
+}
+
+fn history(
+    config: &Config,
+    ids: &[String],
+    points: &[PointInTime],
+    format: Format,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let wallets = Wallets::open(config)?;
+    let mut table = Table::new()
+        .text("at")
+        .text("wallet")
+        .text("chain")
+        .text("asset")
+        .text("category")
+        .number("balance");
+    for wallet in wallets.select(ids)? {
+        for &at in points {
+            let read = wallets.balances_at(wallet, at)?;
+            for balance in &read.balances {
+                table.push(vec![
+                    at.to_string(),
+                    read.id.clone(),
+                    balance.asset.chain.to_string(),
+                    balance.asset.label(),
+                    balance.category.to_string(),
+                    balance.confirmed.to_decimal_string(balance.asset.decimals),
+                ]);
+            }
+        }
+    }
+    table.write(format, out)?;
+    Ok(())
+}
+
+fn transactions(
+    config: &Config,
+    ids: &[String],
+    period: Period,
+    format: Format,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let wallets = Wallets::open(config)?;
+    let ledgers = wallets
+        .select(ids)?
+        .into_iter()
+        .map(|w| wallets.ledger(w, period))
+        .collect::<Result<Vec<_>>>()?;
+    let mut table = Table::new()
+        .text("time")
+        .text("wallet")
+        .text("chain")
+        .text("txid")
+        .text("asset")
+        .text("flow")
+        .number("received")
+        .number("sent")
+        .number("fee")
+        .text("counterparty");
+    for movement in movements(&ledgers) {
+        let entry = &movement.entry;
+        let decimals = entry.asset.decimals;
+        table.push(vec![
+            PointInTime::Time(entry.time).to_string(),
+            movement.wallet.clone(),
+            entry.asset.chain.to_string(),
+            entry.txid.clone(),
+            entry.asset.label(),
+            movement.flow.to_string(),
+            entry.received.to_decimal_string(decimals),
+            entry.sent.to_decimal_string(decimals),
+            entry.fee.to_decimal_string(decimals),
+            entry.counterparty.clone().unwrap_or_default(),
+        ]);
+    }
+    table.write(format, out)?;
+    Ok(())
+}
+
+/// The price file given on the command line or, failing that, in the
+/// config.
+fn price_file(config: &Config, path: Option<PathBuf>) -> Result<PriceFile> {
+    match path.or_else(|| config.prices.as_ref().map(|p| config.resolve(p))) {
+        Some(path) => Ok(PriceFile::open(path)?),
+        None => Err(Error::NoPrices),
+    }
+}
+
+fn gains_report(report: &GainsReport, format: Format, out: &mut dyn Write) -> Result<()> {
+    if format == Format::Json {
+        serde_json::to_writer_pretty(&mut *out, report)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+    let mut table = Table::new()
+        .text("time")
+        .text("wallet")
+        .text("txid")
+        .text("asset")
+        .number("amount")
+        .text("acquired")
+        .number("proceeds")
+        .number("cost")
+        .number("gain");
+    let time = |time: u64| PointInTime::Time(time).to_string();
+    for disposal in &report.disposals {
+        table.push(vec![
+            time(disposal.time),
+            disposal.wallet.clone(),
+            disposal.txid.clone(),
+            disposal.asset.label(),
+            disposal.amount.to_decimal_string(disposal.asset.decimals),
+            disposal.acquired.map(time).unwrap_or_default(),
+            disposal.proceeds.normalize().to_string(),
+            disposal.cost.normalize().to_string(),
+            disposal.gain.normalize().to_string(),
+        ]);
+    }
+    table.write(format, out)?;
+    if format == Format::Table {
+        let currency = &report.currency;
+        writeln!(
+            out,
+            "\nRealized gain ({}): {} {currency}",
+            report.method,
+            report.realized.normalize()
+        )?;
+        writeln!(
+            out,
+            "Unrealized gain: {} {currency}",
+            report.unrealized.normalize()
+        )?;
+        for held in &report.holdings {
+            let value = held.value.map(|v| v.normalize().to_string());
+            writeln!(
+                out,
+                "  {} {}: cost {}, value {} {currency}",
+                held.amount.to_decimal_string(held.asset.decimals),
+                held.asset.label(),
+                held.cost.normalize(),
+                value.as_deref().unwrap_or("unknown"),
+            )?;
+        }
+        for disposal in report.disposals.iter().filter(|d| !d.unmatched.is_zero()) {
+            writeln!(
+                out,
+                "No lot for {} {}, counted at no cost: {}",
+                disposal
+                    .unmatched
+                    .to_decimal_string(disposal.asset.decimals),
+                disposal.asset.label(),
+                disposal.txid
+            )?;
+        }
+        for missing in &report.missing {
+            writeln!(
+                out,
+                "No price for {} on {}, counted at nothing: {}",
+                missing.asset.label(),
+                time(missing.time),
+                missing.txid
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn export(report: &PortfolioReport, format: Format, out: &mut dyn Write) -> Result<()> {
+    if format == Format::Json {
+        serde_json::to_writer_pretty(&mut *out, report)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+    let mut table = Table::new()
+        .text("wallet")
+        .text("chain")
+        .text("asset")
+        .text("category")
+        .number("amount")
+        .text("currency")
+        .number("price")
+        .number("value");
+    let text = |d: Option<walletb_portfolio::Decimal>| d.map(|d| d.to_string()).unwrap_or_default();
+    for wallet in &report.wallets {
+        for held in &wallet.assets {
+            table.push(vec![
+                wallet.id.clone(),
+                held.asset.chain.to_string(),
+                held.asset.label(),
+                held.category.to_string(),
+                held.amount.to_decimal_string(held.asset.decimals),
+                report.currency.clone(),
+                text(held.price),
+                text(held.value),
+            ]);
+        }
+    }
+    table.write(format, out)?;
+    if format == Format::Table {
+        writeln!(out, "\nTotal: {} {}", report.total, report.currency)?;
+        if report.categories.len() > 1 {
+            for (category, total) in &report.categories {
+                writeln!(out, "  {category}: {total} {}", report.currency)?;
+            }
+        }
+        let unpriced: Vec<&str> = report.unpriced().map(|a| a.symbol.as_str()).collect();
+        if !unpriced.is_empty() {
+            writeln!(out, "Not priced: {}", unpriced.join(", "))?;
+        }
+    }
+    Ok(())
+}
+
+/// Where `watch` sends the events it finds, when the config has an
+/// `[events]` table.
+struct Events {
+    tracker: Tracker,
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Events {
+    fn open(config: &Config) -> Result<Option<Self>> {
+        let Some(events) = &config.events else {
+            return Ok(None);
+        };
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+        for webhook in &events.webhooks {
+            let webhook = Webhook::new(&webhook.url, &webhook.secret)
+                .with_events(webhook.events.iter().copied());
+            sinks.push(Box::new(webhook.spawn(|id, err| {
+                eprintln!("walletb: event {id}: {err}");
+            })));
+        }
+        if let Some(listen) = &events.listen {
+            let keys = config
+                .server
+                .iter()
+                .flat_map(|server| &server.keys)
+                .map(|key| key.key.clone())
+                .collect();
+            let stream = EventStream::bind(listen, keys)?;
+            eprintln!("walletb: streaming events on ws://{}", stream.local_addr());
+            sinks.push(Box::new(stream));
+        }
+        Ok(Some(Events {
+            tracker: Tracker::new(events.confirmations),
+            sinks,
+        }))
+    }
+
+    fn publish(&mut self, wallet: &str, observation: &Observation, time: u64) {
+        for event in self.tracker.observe(wallet, observation, time) {
+            for sink in &self.sinks {
+                if let Err(err) = sink.send(&event) {
+                    eprintln!("walletb: event {}: {err}", event.id);
+                }
+            }
+        }
+    }
+}
+
+/// The rules `watch` checks on every poll and the notifiers each one
+/// raises its alerts through, when the config has an `[alerts]` table.
+struct Alerts {
+    rules: Rules,
+    notifiers: HashMap<String, Box<dyn Notifier>>,
+    routes: HashMap<String, Vec<String>>,
+}
+
+impl Alerts {
+    fn open(config: &Config) -> Result<Option<Self>> {
+        let Some(alerts) = &config.alerts else {
+            return Ok(None);
+        };
+        let mut notifiers: HashMap<String, Box<dyn Notifier>> = HashMap::new();
+        for (name, notifier) in &alerts.notifiers {
+            let notifier: Box<dyn Notifier> = match notifier {
+                NotifierConfig::Email { smtp, from, to } => {
+                    Box::new(Email::new(smtp, from, to.clone()))
+                }
+                NotifierConfig::Webhook { url, secret } => {
+                    Box::new(Webhook::new(url, secret).spawn(|id, err| {
+                        eprintln!("walletb: alert {id}: {err}");
+                    }))
+                }
+                NotifierConfig::Stdout => Box::new(Stdout),
+            };
+            notifiers.insert(name.clone(), notifier);
+        }
+        let mut rules = Vec::new();
+        let mut routes = HashMap::new();
+        for rule in &alerts.rules {
+            // The config checked every rule when it was read.
+            rules.push(rule.rule().map_err(|reason| Error::Config {
+                path: config.path().to_owned(),
+                reason,
+            })?);
+            routes.insert(rule.name.clone(), rule.notify.clone());
+        }
+        Ok(Some(Alerts {
+            rules: Rules::new(rules),
+            notifiers,
+            routes,
+        }))
+    }
+
+    fn raise(&mut self, wallet: &str, observation: &Observation, time: u64) {
+        for alert in self.rules.observe(wallet, observation, time) {
+            let names = self.routes.get(&alert.rule).into_iter().flatten();
+            for notifier in names.filter_map(|name| self.notifiers.get(name)) {
+                if let Err(err) = notifier.notify(&alert) {
+                    eprintln!("walletb: alert {}: {err}", alert.id);
+                }
+            }
+        }
+    }
+}
+
+fn sync(config: &Config, ids: &[String], format: Format, out: &mut dyn Write) -> Result<()> {
+    let wallets = Wallets::open(config)?;
+    let mut table = Table::new()
+        .text("wallet")
+        .number("height")
+        .text("block")
+        .number("applied")
+        .number("rolled back");
+    for wallet in wallets.select(ids)? {
+        let report = match wallets.sync(wallet)? {
+            Some(report) => report,
+            None if ids.is_empty() => continue,
+            None => return Err(Error::NotSynced(wallet.source().to_owned())),
+        };
+        let (height, block) = match report.tip {
+            Some(tip) => (tip.height.to_string(), tip.block_hash),
+            None => (String::new(), String::new()),
+        };
+        table.push(vec![
+            wallet.id.clone(),
+            height,
+            block,
+            report.applied.to_string(),
+            report.rolled_back.to_string(),
+        ]);
+    }
+    table.write(format, out)?;
+    Ok(())
+}
+
+/// Polls `ids` every `interval` and prints the balances of each wallet
+/// whose holdings changed since the previous poll, starting with all of
+/// them. A failed read is reported and retried on the next poll.
+///
+/// Wallets of sources with `sync_from` are synced to the chain tip on each
+/// poll.
+///
+/// With `[events]` configured, what changed is also sent as events; the
+/// first poll of each wallet only sets the baseline. With `[alerts]`
+/// configured, every successful read is checked against its rules.
+fn watch(
+    config: &Config,
+    ids: &[String],
+    interval: Duration,
+    count: Option<u64>,
+    format: Format,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let wallets = Wallets::open(config)?;
+    let selected = wallets.select(ids)?;
+    let mut events = Events::open(config)?;
+    let mut alerts = Alerts::open(config)?;
+    let mut last: HashMap<&str, Vec<Balance>> = HashMap::new();
+    let mut header = true;
+    let mut poll = 0;
+    loop {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut table = balance_table(Table::new().text("time"));
+        for wallet in &selected {
+            let read = if events.is_some() || alerts.is_some() {
+                wallets.observe(wallet).map(|(read, observation)| {
+                    if let Some(events) = &mut events {
+                        events.publish(&wallet.id, &observation, now);
+                    }
+                    if let Some(alerts) = &mut alerts {
+                        alerts.raise(&wallet.id, &observation, now);
+                    }
+                    read
+                })
+            } else {
+                wallets.balances(wallet)
+            };
+            let read = match read {
+                Ok(read) => read,
+                Err(err) => {
+                    eprintln!("walletb: {err}");
+                    continue;
+                }
+            };
+            if last.get(wallet.id.as_str()) == Some(&read.balances) {
+                continue;
+            }
+            for balance in &read.balances {
+                let time = PointInTime::Time(now).to_string();
+                table.push(balance_row(&read, balance, vec![time]));
+            }
+            last.insert(&wallet.id, read.balances);
+        }
+        if !table.is_empty() {
+            match format {
+                Format::Table => table.write(format, out)?,
+                Format::Csv => {
+                    table.write_csv(out, header)?;
+                    header = false;
+                }
+                // One object per line, so the stream can be consumed as it
+                // arrives.
+                Format::Json => {
+                    for row in table.to_json().as_array().into_iter().flatten() {
+                        writeln!(out, "{row}")?;
+                    }
+                }
+            }
+            out.flush()?;
+        }
+        poll += 1;
+        if count.is_some_and(|count| poll >= count) {
+            return Ok(());
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn balance_table(table: Table) -> Table {
+    table
+        .text("wallet")
+        .text("chain")
+        .text("asset")
+        .text("category")
+        .number("confirmed")
+        .number("unconfirmed")
+        .number("total")
+}
+
+fn balance_row(wallet: &WalletBalances, balance: &Balance, mut prefix: Vec<String>) -> Vec<String> {
+    let decimals = balance.asset.decimals;
+    prefix.extend([
+        wallet.id.clone(),
+        balance.asset.chain.to_string(),
+        balance.asset.label(),
+        balance.category.to_string(),
+        balance.confirmed.to_decimal_string(decimals),
+        balance.unconfirmed.to_decimal_string(decimals),
+        balance.total().to_decimal_string(decimals),
+    ]);
+    prefix
+}
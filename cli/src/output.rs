@@ -0,0 +1,207 @@
+use std::io::{self, Write};
+
+use serde_json::{Map, Value};
+
+/// How command output is printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Aligned columns for people.
+    #[default]
+    Table,
+    /// An array of objects keyed by column name.
+    Json,
+    /// RFC 4180 with a header row.
+    Csv,
+}
+
+/// Rows of text cells under named columns, rendered in any [`Format`].
+///
+/// Amounts are kept as decimal strings in every format so that no
+/// precision is lost to a JSON consumer's floating point.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Column {
+    name: &'static str,
+    numeric: bool,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(mut self, name: &'static str) -> Self {
+        self.columns.push(Column {
+            name,
+            numeric: false,
+        });
+        self
+    }
+
+    /// A right-aligned column.
+    pub fn number(mut self, name: &'static str) -> Self {
+        self.columns.push(Column {
+            name,
+            numeric: true,
+        });
+        self
+    }
+
+    /// Appends a row; empty cells are rendered as blanks, or `null` in
+    /// JSON.
+    pub fn push(&mut self, row: Vec<String>) {
+        assert_eq!(row.len(), self.columns.len(), "row width");
+        self.rows.push(row);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn write(&self, format: Format, out: &mut dyn Write) -> io::Result<()> {
+        match format {
+            Format::Table => self.write_table(out),
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut *out, &self.to_json())?;
+                writeln!(out)
+            }
+            Format::Csv => self.write_csv(out, true),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        self.rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, cell)| {
+                        let value = match cell.as_str() {
+                            "" => Value::Null,
+                            cell => Value::String(cell.to_owned()),
+                        };
+                        (column.name.to_owned(), value)
+                    })
+                    .collect();
+                Value::Object(object)
+            })
+            .collect()
+    }
+
+    pub fn write_csv(&self, out: &mut dyn Write, header: bool) -> io::Result<()> {
+        if header {
+            let names: Vec<&str> = self.columns.iter().map(|c| c.name).collect();
+            write_csv_record(out, &names)?;
+        }
+        for row in &self.rows {
+            let cells: Vec<&str> = row.iter().map(String::as_str).collect();
+            write_csv_record(out, &cells)?;
+        }
+        Ok(())
+    }
+
+    fn write_table(&self, out: &mut dyn Write) -> io::Result<()> {
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                self.rows
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([column.name.len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let line = |out: &mut dyn Write, cells: &[&str]| {
+            let padded: Vec<String> = self
+                .columns
+                .iter()
+                .zip(cells)
+                .zip(&widths)
+                .map(|((column, cell), &width)| match column.numeric {
+                    true => format!("{cell:>width$}"),
+                    false => format!("{cell:<width$}"),
+                })
+                .collect();
+            writeln!(out, "{}", padded.join("  ").trim_end())
+        };
+        let names: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| c.name.to_ascii_uppercase())
+            .collect();
+        line(out, &names.iter().map(String::as_str).collect::<Vec<_>>())?;
+        for row in &self.rows {
+            line(out, &row.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        Ok(())
+    }
+}
+
+fn write_csv_record(out: &mut dyn Write, cells: &[&str]) -> io::Result<()> {
+    let fields: Vec<String> = cells
+        .iter()
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                (*cell).to_owned()
+            }
+        })
+        .collect();
+    write!(out, "{}\r\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut table = Table::new().text("wallet").number("total");
+        table.push(vec!["Cold, offline".into(), "1.5".into()]);
+        table.push(vec!["hot".into(), String::new()]);
+        table
+    }
+
+    fn render(format: Format) -> String {
+        let mut out = Vec::new();
+        table().write(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn aligns_columns_for_people() {
+        assert_eq!(
+            render(Format::Table),
+            "WALLET         TOTAL\nCold, offline    1.5\nhot\n"
+        );
+    }
+
+    #[test]
+    fn quotes_csv_fields_and_nulls_empty_json_cells() {
+        assert_eq!(
+            render(Format::Csv),
+            "wallet,total\r\n\"Cold, offline\",1.5\r\nhot,\r\n"
+        );
+        assert_eq!(
+            table().to_json(),
+            serde_json::json!([
+                { "wallet": "Cold, offline", "total": "1.5" },
+                { "wallet": "hot", "total": null },
+            ])
+        );
+    }
+}
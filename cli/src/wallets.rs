@@ -0,0 +1,677 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use walletb_bitcoin::{
+    AccountXpub, BitcoinCore, BitcoinSource, CoreAuth, CoreWallet, Discovery, ElectrumClient,
+    FileHistory, FileUtxoSet, Keychains, UtxoBackend, WalletDescriptor,
+};
+use walletb_core::{
+    Address, Amount, Asset, Balance, BalanceSource, Chain, HistoricalSource, Period, PointInTime,
+    TransactionSource,
+};
+use walletb_cosmos::{CosmosAddress, CosmosSource, LcdClient};
+use walletb_ethereum::{
+    BeaconClient, BeaconSource, EthAddress, EthClient, EthereumSource, MultiChainSource,
+    ValidatorKey,
+};
+use walletb_notify::{Observation, Receipt};
+use walletb_portfolio::{WalletBalances, WalletLedger};
+use walletb_solana::{Pubkey, SolanaClient, SolanaSource};
+use walletb_store::{BalanceSnapshot, Store, SyncReport, Syncer, WalletRecord};
+
+use crate::{Config, Error, Result, SourceConfig, WalletConfig};
+
+/// An opened [`SourceConfig`].
+pub enum Source {
+    BitcoinUtxos(BitcoinSource<FileUtxoSet>),
+    BitcoinHistory(BitcoinSource<FileHistory>),
+    BitcoinElectrum(BitcoinSource<ElectrumClient>),
+    /// A Bitcoin Core node read by scanning its UTXO set.
+    BitcoinCoreScan(BitcoinSource<BitcoinCore>),
+    /// A watch-only wallet on a Bitcoin Core node.
+    BitcoinCoreWallet(BitcoinSource<CoreWallet>),
+    Ethereum(EthereumSource),
+    /// The same addresses on several EVM chains.
+    Evm(MultiChainSource),
+    /// Validators on the beacon chain.
+    Beacon(BeaconSource),
+    Solana(SolanaSource),
+    Cosmos(CosmosSource),
+}
+
+impl Source {
+    pub fn open(config: &Config, source: &SourceConfig) -> Result<Self> {
+        Ok(match source {
+            SourceConfig::Bitcoin {
+                network,
+                utxos: Some(path),
+                ..
+            } => Source::BitcoinUtxos(BitcoinSource::new(
+                *network,
+                FileUtxoSet::open(config.resolve(path))?,
+            )),
+            SourceConfig::Bitcoin {
+                network,
+                history: Some(path),
+                ..
+            } => Source::BitcoinHistory(BitcoinSource::new(
+                *network,
+                FileHistory::open(config.resolve(path))?,
+            )),
+            SourceConfig::Bitcoin {
+                network,
+                electrum: Some(addr),
+                ..
+            } => Source::BitcoinElectrum(BitcoinSource::new(
+                *network,
+                ElectrumClient::connect(addr.as_str())?,
+            )),
+            SourceConfig::Bitcoin { .. } => unreachable!("rejected by Config::validate"),
+            SourceConfig::BitcoinCore {
+                network,
+                url,
+                cookie,
+                user,
+                password,
+                wallet,
+                ..
+            } => {
+                let auth = match (cookie, user, password) {
+                    (Some(cookie), ..) => CoreAuth::Cookie(config.resolve(cookie)),
+                    (None, Some(user), Some(password)) => CoreAuth::UserPass {
+                        user: user.clone(),
+                        password: password.clone(),
+                    },
+                    _ => unreachable!("rejected by Config::validate"),
+                };
+                let node = BitcoinCore::new(url.clone(), &auth)?;
+                match wallet {
+                    Some(name) => {
+                        Source::BitcoinCoreWallet(BitcoinSource::new(*network, node.wallet(name)?))
+                    }
+                    None => Source::BitcoinCoreScan(BitcoinSource::new(*network, node)),
+                }
+            }
+            SourceConfig::Ethereum {
+                chain,
+                url,
+                tokens,
+                discover_tokens_from,
+                liquid_staking,
+            } => {
+                let chain = config.chain(chain.as_deref().unwrap_or("ethereum"))?;
+                let client = match url {
+                    Some(url) => EthClient::new(url.clone()),
+                    None => chain.client().expect("rejected by Config::validate"),
+                };
+                let mut source =
+                    EthereumSource::new(client).with_native_asset(chain.native.clone());
+                if let Some(block) = discover_tokens_from {
+                    source = source.with_token_discovery(*block);
+                }
+                for token in tokens {
+                    let contract: EthAddress = token.contract.parse()?;
+                    source = source.with_token(Asset::token(
+                        chain.chain(),
+                        contract.to_checksum(),
+                        &token.symbol,
+                        token.decimals,
+                    ));
+                }
+                if *liquid_staking {
+                    for lst in &chain.liquid_staking {
+                        source = source.with_liquid_staking(lst.clone());
+                    }
+                }
+                Source::Ethereum(source)
+            }
+            SourceConfig::Evm { chains, urls } => {
+                let mut multi = MultiChainSource::new();
+                for name in chains {
+                    let chain = config.chain(name)?;
+                    let client = match urls.get(name) {
+                        Some(url) => EthClient::new(url.clone()),
+                        None => chain.client().expect("rejected by Config::validate"),
+                    };
+                    multi = multi.with_source(chain.source(client));
+                }
+                Source::Evm(multi)
+            }
+            SourceConfig::Solana { url, tokens } => {
+                let mut source = SolanaSource::new(SolanaClient::new(url.clone()));
+                for token in tokens {
+                    let mint: Pubkey = token.contract.parse()?;
+                    source = source.with_token(Asset::token(
+                        Chain::Solana,
+                        mint.to_string(),
+                        &token.symbol,
+                        token.decimals,
+                    ));
+                }
+                Source::Solana(source)
+            }
+            SourceConfig::Beacon { url, state } => {
+                let mut source = BeaconSource::new(BeaconClient::new(url.clone()));
+                if let Some(state) = state {
+                    source = source.at_state(state.clone());
+                }
+                Source::Beacon(source)
+            }
+            SourceConfig::Cosmos { url, .. } => {
+                let chain = source
+                    .cosmos_chain()
+                    .and_then(|chain| chain.ok())
+                    .expect("rejected by Config::validate");
+                Source::Cosmos(CosmosSource::new(LcdClient::new(url.clone()), chain))
+            }
+        })
+    }
+
+    pub fn chain(&self) -> Chain {
+        match self {
+            Source::BitcoinUtxos(_)
+            | Source::BitcoinHistory(_)
+            | Source::BitcoinElectrum(_)
+            | Source::BitcoinCoreScan(_)
+            | Source::BitcoinCoreWallet(_) => Chain::Bitcoin,
+            Source::Ethereum(source) => source.chain(),
+            Source::Evm(source) => source.chain(),
+            Source::Beacon(source) => source.chain(),
+            Source::Solana(source) => source.chain(),
+            Source::Cosmos(source) => source.chain(),
+        }
+    }
+
+    /// Parses a wallet's keys for this source's chain and network. A
+    /// Bitcoin Core wallet source imports them, deriving `gap_limit`
+    /// addresses ahead, unless the node already has them.
+    fn keys(&self, wallet: &WalletConfig, gap_limit: u32) -> Result<WalletKeys> {
+        let network = match self {
+            Source::BitcoinUtxos(source) => Some(source.network()),
+            Source::BitcoinHistory(source) => Some(source.network()),
+            Source::BitcoinElectrum(source) => Some(source.network()),
+            Source::BitcoinCoreScan(source) => Some(source.network()),
+            Source::BitcoinCoreWallet(source) => Some(source.network()),
+            Source::Ethereum(_)
+            | Source::Evm(_)
+            | Source::Beacon(_)
+            | Source::Solana(_)
+            | Source::Cosmos(_) => None,
+        };
+        let node = match self {
+            Source::BitcoinCoreWallet(source) => Some(source.backend()),
+            _ => None,
+        };
+        let invalid = |reason: String| Error::InvalidWallet {
+            wallet: wallet.id.clone(),
+            reason,
+        };
+        if !wallet.addresses.is_empty() {
+            let addresses = wallet
+                .addresses
+                .iter()
+                .map(|a| Address::new(self.chain(), a.as_str()))
+                .collect::<Vec<_>>();
+            for address in &addresses {
+                let valid = match (network, self) {
+                    (Some(network), _) => walletb_bitcoin::parse_address(address.as_str(), network)
+                        .map(drop)
+                        .map_err(|e| e.to_string()),
+                    (None, Source::Beacon(_)) => address
+                        .as_str()
+                        .parse::<ValidatorKey>()
+                        .map(drop)
+                        .map_err(|e| e.to_string()),
+                    (None, Source::Solana(_)) => address
+                        .as_str()
+                        .parse::<Pubkey>()
+                        .map(drop)
+                        .map_err(|e| e.to_string()),
+                    (None, Source::Cosmos(source)) => CosmosAddress::parse_with_prefix(
+                        address.as_str(),
+                        &source.cosmos_chain().prefix,
+                    )
+                    .map(drop)
+                    .map_err(|e| e.to_string()),
+                    (None, _) => address
+                        .as_str()
+                        .parse::<EthAddress>()
+                        .map(drop)
+                        .map_err(|e| e.to_string()),
+                };
+                valid.map_err(invalid)?;
+            }
+            if let (Some(node), Some(network)) = (node, network) {
+                let scripts = BitcoinSource::new(network, node).scripts_for(&addresses)?;
+                node.import_scripts(&scripts, Some(0))?;
+            }
+            return Ok(WalletKeys::Addresses(addresses));
+        }
+        let Some(network) = network else {
+            return Err(invalid(format!(
+                "{} wallets are configured by `addresses` only",
+                self.chain()
+            )));
+        };
+        let (keychains, descriptor): (Box<dyn Keychains>, WalletDescriptor) =
+            match (&wallet.xpub, &wallet.descriptor) {
+                (Some(xpub), _) => {
+                    let account = AccountXpub::parse(xpub, network)?;
+                    let descriptor = account.to_descriptor();
+                    (Box::new(account), descriptor)
+                }
+                (None, Some(descriptor)) => {
+                    let mut parsed = WalletDescriptor::parse(descriptor, network)?;
+                    if let Some(change) = &wallet.change_descriptor {
+                        parsed = parsed.with_change(change)?;
+                    }
+                    (Box::new(parsed.clone()), parsed)
+                }
+                (None, None) => unreachable!("rejected by Config::validate"),
+            };
+        if let Some(node) = node {
+            node.import(&descriptor, gap_limit, Some(0))?;
+        }
+        Ok(WalletKeys::Keychains(keychains))
+    }
+
+    /// Scans an HD wallet, resuming after the addresses `known` to be used.
+    fn discover(
+        &self,
+        keychains: &dyn Keychains,
+        gap_limit: u32,
+        known: &Discovery,
+    ) -> walletb_core::Result<Discovery> {
+        Ok(match self {
+            Source::BitcoinUtxos(source) => source.discover_from(keychains, gap_limit, known)?,
+            Source::BitcoinHistory(source) => source.discover_from(keychains, gap_limit, known)?,
+            Source::BitcoinElectrum(source) => source.discover_from(keychains, gap_limit, known)?,
+            Source::BitcoinCoreScan(source) => source.discover_from(keychains, gap_limit, known)?,
+            Source::BitcoinCoreWallet(source) => {
+                source.discover_from(keychains, gap_limit, known)?
+            }
+            Source::Ethereum(_)
+            | Source::Evm(_)
+            | Source::Beacon(_)
+            | Source::Solana(_)
+            | Source::Cosmos(_) => unreachable!("rejected by keys"),
+        })
+    }
+
+    /// Reads the balances of `addresses` and, from Bitcoin sources, the
+    /// outputs making them up.
+    fn observe(&self, addresses: &[Address]) -> walletb_core::Result<Observation> {
+        let mut observation = Observation::new(self.as_balance_source().balances(addresses)?);
+        match self {
+            Source::BitcoinUtxos(source) => receipts(source, addresses, &mut observation)?,
+            Source::BitcoinHistory(source) => receipts(source, addresses, &mut observation)?,
+            Source::BitcoinElectrum(source) => receipts(source, addresses, &mut observation)?,
+            Source::BitcoinCoreScan(source) => receipts(source, addresses, &mut observation)?,
+            Source::BitcoinCoreWallet(source) => receipts(source, addresses, &mut observation)?,
+            Source::Ethereum(_)
+            | Source::Evm(_)
+            | Source::Beacon(_)
+            | Source::Solana(_)
+            | Source::Cosmos(_) => {}
+        }
+        Ok(observation)
+    }
+
+    fn as_balance_source(&self) -> &dyn BalanceSource {
+        match self {
+            Source::BitcoinUtxos(source) => source,
+            Source::BitcoinHistory(source) => source,
+            Source::BitcoinElectrum(source) => source,
+            Source::BitcoinCoreScan(source) => source,
+            Source::BitcoinCoreWallet(source) => source,
+            Source::Ethereum(source) => source,
+            Source::Evm(source) => source,
+            Source::Beacon(source) => source,
+            Source::Solana(source) => source,
+            Source::Cosmos(source) => source,
+        }
+    }
+
+    fn as_historical_source(&self) -> Option<&dyn HistoricalSource> {
+        match self {
+            Source::BitcoinUtxos(_)
+            | Source::BitcoinCoreScan(_)
+            | Source::BitcoinCoreWallet(_)
+            | Source::Beacon(_)
+            | Source::Solana(_)
+            | Source::Cosmos(_) => None,
+            Source::BitcoinHistory(source) => Some(source),
+            Source::BitcoinElectrum(source) => Some(source),
+            Source::Ethereum(source) => Some(source),
+            Source::Evm(source) => Some(source),
+        }
+    }
+
+    fn as_transaction_source(&self) -> Option<&dyn TransactionSource> {
+        match self {
+            Source::BitcoinUtxos(_)
+            | Source::BitcoinCoreScan(_)
+            | Source::BitcoinCoreWallet(_)
+            | Source::Beacon(_)
+            | Source::Cosmos(_) => None,
+            Source::BitcoinHistory(source) => Some(source),
+            Source::BitcoinElectrum(source) => Some(source),
+            Source::Ethereum(source) => Some(source),
+            Source::Evm(source) => Some(source),
+            Source::Solana(source) => Some(source),
+        }
+    }
+}
+
+enum WalletKeys {
+    Addresses(Vec<Address>),
+    /// An xpub or descriptor, scanned up to the gap limit.
+    Keychains(Box<dyn Keychains>),
+}
+
+/// A configured wallet, ready to be read.
+pub struct Wallet {
+    pub id: String,
+    pub name: String,
+    source: String,
+    keys: WalletKeys,
+}
+
+impl Wallet {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+/// Every source and wallet of a [`Config`], opened and parsed.
+pub struct Wallets {
+    sources: BTreeMap<String, Source>,
+    wallets: Vec<Wallet>,
+    gap_limit: u32,
+    store: Option<Store>,
+    /// The sources whose wallets are synced block by block, with the
+    /// height to start from.
+    sync_from: BTreeMap<String, u32>,
+}
+
+impl Wallets {
+    /// Opens every source and the database, if configured, and parses
+    /// every wallet's keys, so that configuration mistakes surface before
+    /// anything is read.
+    pub fn open(config: &Config) -> Result<Self> {
+        let sources = config
+            .sources
+            .iter()
+            .map(|(id, source)| Ok((id.clone(), Source::open(config, source)?)))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+        let wallets = config
+            .wallets
+            .iter()
+            .map(|wallet| {
+                Ok(Wallet {
+                    id: wallet.id.clone(),
+                    name: wallet.display_name().to_owned(),
+                    source: wallet.source.clone(),
+                    keys: sources[&wallet.source].keys(wallet, config.gap_limit)?,
+                })
+            })
+            .collect::<Result<Vec<Wallet>>>()?;
+        let store = match &config.database {
+            Some(path) => {
+                let store = Store::open(config.resolve(path))?;
+                for wallet in &config.wallets {
+                    store.save_wallet(&WalletRecord {
+                        id: wallet.id.clone(),
+                        name: wallet.display_name().to_owned(),
+                        source: wallet.source.clone(),
+                        spec: wallet.spec(),
+                    })?;
+                }
+                Some(store)
+            }
+            None => None,
+        };
+        let sync_from = config
+            .sources
+            .iter()
+            .filter_map(|(id, source)| match source {
+                SourceConfig::BitcoinCore {
+                    sync_from: Some(height),
+                    ..
+                } => Some((id.clone(), *height)),
+                _ => None,
+            })
+            .collect();
+        Ok(Wallets {
+            sources,
+            wallets,
+            gap_limit: config.gap_limit,
+            store,
+            sync_from,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Wallet> {
+        self.wallets.iter()
+    }
+
+    /// The wallets named by `ids`, in that order, or every wallet if `ids`
+    /// is empty.
+    pub fn select(&self, ids: &[String]) -> Result<Vec<&Wallet>> {
+        if ids.is_empty() {
+            return Ok(self.wallets.iter().collect());
+        }
+        ids.iter()
+            .map(|id| {
+                self.wallets
+                    .iter()
+                    .find(|w| &w.id == id)
+                    .ok_or_else(|| Error::UnknownWallet(id.clone()))
+            })
+            .collect()
+    }
+
+    /// Reads a wallet's current balances, recording them in the store if
+    /// there is one. A synced wallet is synced first and read from the
+    /// store.
+    pub fn balances(&self, wallet: &Wallet) -> Result<WalletBalances> {
+        if self.sync(wallet)?.is_some() {
+            return self.synced_balances(wallet);
+        }
+        let source = &self.sources[&wallet.source];
+        let balances = self
+            .addresses(wallet, source)
+            .and_then(|addresses| source.as_balance_source().balances(&addresses));
+        let read = named(wallet, balances)?;
+        self.record(wallet, &read)?;
+        Ok(read)
+    }
+
+    /// Like [`balances`](Self::balances), also returning what the source
+    /// reports beyond them, for events. A synced wallet reports only its
+    /// balances and the block it is synced to.
+    pub fn observe(&self, wallet: &Wallet) -> Result<(WalletBalances, Observation)> {
+        if let Some(report) = self.sync(wallet)? {
+            let read = self.synced_balances(wallet)?;
+            let mut observation = Observation::new(read.balances.clone());
+            observation.tip = report.tip.and_then(|tip| u32::try_from(tip.height).ok());
+            return Ok((read, observation));
+        }
+        let source = &self.sources[&wallet.source];
+        let observation = self
+            .addresses(wallet, source)
+            .and_then(|addresses| source.observe(&addresses))
+            .map_err(|source| failed(wallet, source))?;
+        let read = named(wallet, Ok(observation.balances.clone()))?;
+        self.record(wallet, &read)?;
+        Ok((read, observation))
+    }
+
+    /// Brings a wallet of a source with `sync_from` up to the node's tip in
+    /// the store, resuming from the block it was last synced to and undoing
+    /// blocks the node has since reorganized away. Returns `None` for the
+    /// wallets of other sources.
+    pub fn sync(&self, wallet: &Wallet) -> Result<Option<SyncReport>> {
+        let (Some(store), Some(&from)) = (&self.store, self.sync_from.get(&wallet.source)) else {
+            return Ok(None);
+        };
+        let Source::BitcoinCoreScan(source) = &self.sources[&wallet.source] else {
+            unreachable!("rejected by Config::validate");
+        };
+        let scripts = match &wallet.keys {
+            WalletKeys::Addresses(addresses) => source.scripts_for(addresses),
+            WalletKeys::Keychains(keychains) => self
+                .discovery(wallet, keychains.as_ref(), &self.sources[&wallet.source])
+                .map(|discovery| discovery.scripts().collect()),
+        }
+        .map_err(|source| failed(wallet, source))?;
+        let report = Syncer::new(store, source.backend(), &wallet.id, scripts)
+            .with_start_height(from)
+            .sync()?;
+        Ok(Some(report))
+    }
+
+    /// The balances of a synced wallet, as kept in the store.
+    fn synced_balances(&self, wallet: &Wallet) -> Result<WalletBalances> {
+        let store = self.store.as_ref().ok_or(Error::NoDatabase)?;
+        named(wallet, Ok(vec![store.synced_balance(&wallet.id)?]))
+    }
+
+    fn record(&self, wallet: &Wallet, read: &WalletBalances) -> Result<()> {
+        if let Some(store) = &self.store {
+            let snapshot = BalanceSnapshot {
+                height: None,
+                taken_at: unix_now(),
+                balances: read.balances.clone(),
+            };
+            store.save_snapshot(&wallet.id, &snapshot)?;
+        }
+        Ok(())
+    }
+
+    pub fn balances_at(&self, wallet: &Wallet, at: PointInTime) -> Result<WalletBalances> {
+        let source = &self.sources[&wallet.source];
+        let historical = source
+            .as_historical_source()
+            .ok_or_else(|| Error::NoHistory(wallet.source.clone()))?;
+        let balances = self
+            .addresses(wallet, source)
+            .and_then(|addresses| historical.balances_at(&addresses, at));
+        named(wallet, balances)
+    }
+
+    /// Lists the transactions of a wallet over `period`.
+    pub fn ledger(&self, wallet: &Wallet, period: Period) -> Result<WalletLedger> {
+        let source = &self.sources[&wallet.source];
+        let transactions = source
+            .as_transaction_source()
+            .ok_or_else(|| Error::NoTransactions(wallet.source.clone()))?;
+        let addresses = self
+            .addresses(wallet, source)
+            .map_err(|source| failed(wallet, source))?;
+        let entries = transactions
+            .transactions(&addresses, period)
+            .map_err(|source| failed(wallet, source))?;
+        Ok(WalletLedger {
+            id: wallet.id.clone(),
+            addresses,
+            entries,
+        })
+    }
+
+    /// The most recent balances recorded for a wallet, without reading its
+    /// source.
+    pub fn cached(&self, wallet: &Wallet) -> Result<Option<BalanceSnapshot>> {
+        let store = self.store.as_ref().ok_or(Error::NoDatabase)?;
+        Ok(store.latest_snapshot(&wallet.id)?)
+    }
+
+    /// The addresses to read: the configured ones, or the used addresses
+    /// found by scanning an HD wallet. Scans resume from, and update, the
+    /// addresses kept in the store.
+    fn addresses(&self, wallet: &Wallet, source: &Source) -> walletb_core::Result<Vec<Address>> {
+        Ok(match &wallet.keys {
+            WalletKeys::Addresses(addresses) => addresses.clone(),
+            WalletKeys::Keychains(keychains) => self
+                .discovery(wallet, keychains.as_ref(), source)?
+                .used_addresses(),
+        })
+    }
+
+    fn discovery(
+        &self,
+        wallet: &Wallet,
+        keychains: &dyn Keychains,
+        source: &Source,
+    ) -> walletb_core::Result<Discovery> {
+        let known = match &self.store {
+            Some(store) => store.discovery(&wallet.id)?,
+            None => Discovery::default(),
+        };
+        let discovery = source.discover(keychains, self.gap_limit, &known)?;
+        if let Some(store) = &self.store {
+            store.save_addresses(&wallet.id, &discovery.addresses)?;
+        }
+        Ok(discovery)
+    }
+}
+
+fn named(wallet: &Wallet, balances: walletb_core::Result<Vec<Balance>>) -> Result<WalletBalances> {
+    let balances = balances.map_err(|source| failed(wallet, source))?;
+    Ok(WalletBalances {
+        id: wallet.id.clone(),
+        name: wallet.name.clone(),
+        balances,
+    })
+}
+
+fn failed(wallet: &Wallet, source: walletb_core::Error) -> Error {
+    walletb_portfolio::Error::Wallet {
+        wallet: wallet.id.clone(),
+        source,
+    }
+    .into()
+}
+
+/// Fills in the unspent outputs behind a Bitcoin wallet's balance, with
+/// what the backend knows of the chain tip and the wallet's transactions.
+fn receipts<B: UtxoBackend>(
+    source: &BitcoinSource<B>,
+    addresses: &[Address],
+    observation: &mut Observation,
+) -> walletb_core::Result<()> {
+    let scripts = source.scripts_for(addresses)?;
+    let backend = source.backend();
+    let unspent = backend.unspent(&scripts)?;
+    let txids: Vec<_> = unspent
+        .iter()
+        .map(|utxo| utxo.outpoint.txid)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let change = backend.spends_from(&txids, &scripts)?.unwrap_or_default();
+    let receipts = unspent
+        .into_iter()
+        .map(|utxo| Receipt {
+            txid: utxo.outpoint.txid.to_string(),
+            vout: utxo.outpoint.vout,
+            asset: walletb_bitcoin::btc(),
+            amount: Amount::from_u64(utxo.value.to_sat()),
+            height: utxo.height,
+            change: change.contains(&utxo.outpoint.txid),
+        })
+        .collect();
+    observation.receipts = Some(receipts);
+    observation.tip = backend.tip_height()?;
+    observation.known_txids = backend
+        .txids(&scripts)?
+        .map(|txids| txids.iter().map(ToString::to_string).collect());
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,985 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::Hash;
+use bitcoin::{OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid};
+use serde_json::{json, Value};
+use walletb_bitcoin::{BlockSource, Keychain, MemoryChain};
+use walletb_core::testing::{MockLineServer, MockResponse, MockServer, RpcFailure};
+use walletb_store::Store;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// A config directory holding copies of the fixtures, with the Ethereum
+/// source pointed at a mock node. The node has a block a day from
+/// 2023-12-31T00:00:00Z up to block 16; the hot wallet holds 1.5 ETH and
+/// 2500.5 USDC from block 10 on, and nothing before.
+struct Setup {
+    dir: PathBuf,
+    _node: MockServer,
+}
+
+impl Setup {
+    fn new(name: &str) -> Self {
+        let node = MockServer::json_rpc(|method, params| {
+            let block = |i: usize| {
+                let tag = params[i].as_str().unwrap_or_default();
+                u64::from_str_radix(tag.trim_start_matches("0x"), 16).unwrap()
+            };
+            match method {
+                "eth_blockNumber" => Ok(json!("0x10")),
+                "eth_getBlockByNumber" => Ok(json!({
+                    "timestamp": format!("0x{:x}", 1_703_980_800 + block(0) * 86_400),
+                })),
+                "eth_getBalance" if block(1) < 10 => Ok(json!("0x0")),
+                "eth_getBalance" => Ok(json!("0x14d1120d7b160000")),
+                "eth_call" if block(1) < 10 => Ok(json!(format!("0x{:064x}", 0))),
+                "eth_call" => Ok(json!(format!("0x{:064x}", 2_500_500_000u64))),
+                _ => Err(RpcFailure::method_not_found(method)),
+            }
+        });
+        let dir = std::env::temp_dir().join(format!("walletb-cli-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in ["history.json", "prices.csv"] {
+            fs::copy(Path::new(FIXTURES).join(file), dir.join(file)).unwrap();
+        }
+        let config = fs::read_to_string(Path::new(FIXTURES).join("walletb.toml")).unwrap();
+        fs::write(
+            dir.join("walletb.toml"),
+            config.replace("{ETH_URL}", &node.url()),
+        )
+        .unwrap();
+        Setup { dir, _node: node }
+    }
+
+    fn config(&self) -> PathBuf {
+        self.dir.join("walletb.toml")
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_walletb"))
+            .arg("--config")
+            .arg(self.config())
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    /// Runs `args`, expecting success, and returns standard output.
+    fn stdout(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "{args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn prints_balances_as_a_table_json_or_csv() {
+    let setup = Setup::new("balance");
+    assert_eq!(
+        setup.stdout(&["balance"]),
+        "\
+WALLET  CHAIN     ASSET  CATEGORY   CONFIRMED  UNCONFIRMED   TOTAL
+cold    bitcoin   BTC    available     0.0059            0  0.0059
+hot     ethereum  ETH    available        1.5            0     1.5
+hot     ethereum  USDC   available     2500.5            0  2500.5
+"
+    );
+
+    let json: Value =
+        serde_json::from_str(&setup.stdout(&["balance", "hot", "-f", "json"])).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 2);
+    assert_eq!(json[1]["asset"], "USDC");
+    assert_eq!(json[1]["total"], "2500.5");
+
+    assert_eq!(
+        setup.stdout(&["balance", "cold", "--format", "csv"]),
+        "wallet,chain,asset,category,confirmed,unconfirmed,total\r\ncold,bitcoin,BTC,available,0.0059,0,0.0059\r\n"
+    );
+}
+
+#[test]
+fn reports_balances_at_month_ends() {
+    let setup = Setup::new("history");
+    assert_eq!(
+        setup.stdout(&[
+            "history",
+            "--at",
+            "2023-12-31",
+            "--at",
+            "2024-01-31",
+            "--at",
+            "height:300",
+            "-f",
+            "csv",
+        ]),
+        "\
+at,wallet,chain,asset,category,balance\r
+2023-12-31T23:59:59Z,cold,bitcoin,BTC,available,0.01\r
+2024-01-31T23:59:59Z,cold,bitcoin,BTC,available,0.0039\r
+height:300,cold,bitcoin,BTC,available,0.0059\r
+2023-12-31T23:59:59Z,hot,ethereum,ETH,available,0\r
+2024-01-31T23:59:59Z,hot,ethereum,ETH,available,1.5\r
+2024-01-31T23:59:59Z,hot,ethereum,USDC,available,2500.5\r
+height:300,hot,ethereum,ETH,available,1.5\r
+height:300,hot,ethereum,USDC,available,2500.5\r
+"
+    );
+
+    let before = setup.stdout(&["history", "hot", "--at", "height:9", "-f", "csv"]);
+    assert_eq!(
+        before,
+        "at,wallet,chain,asset,category,balance\r\nheight:9,hot,ethereum,ETH,available,0\r\n"
+    );
+}
+
+#[test]
+fn lists_transactions_with_their_flow() {
+    let setup = Setup::new("transactions");
+    assert_eq!(
+        setup.stdout(&["transactions", "cold", "--after", "2024-01-01", "-f", "csv"]),
+        "\
+time,wallet,chain,txid,asset,flow,received,sent,fee,counterparty\r
+2024-01-15T00:00:00Z,cold,bitcoin,7c7775d31260a299bcf744a92e4552124244a780cf0d194410c63bc4ea5b983f,BTC,expense,0,0.006,0.0001,bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq\r
+2024-02-01T01:00:00Z,cold,bitcoin,ff9da3c2d1ce353ed1ce674a9e55d78583af9aea8f143731f03240cd6fa7769f,BTC,income,0.002,0,0,\r
+"
+    );
+
+    let output = setup.run(&["transactions", "hot"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs a start"));
+}
+
+#[test]
+fn works_out_gains_from_dated_prices() {
+    let setup = Setup::new("gains");
+    fs::write(
+        setup.dir.join("dated.csv"),
+        "symbol,currency,price,date\n\
+         BTC,USD,42000,2023-12-31\n\
+         BTC,USD,43000,2024-01-15\n\
+         BTC,USD,60000,\n",
+    )
+    .unwrap();
+    // 0.01 BTC bought at 42,000, 0.006 sent for a 0.0001 fee at 43,000,
+    // and 0.002 more at 43,000 too, the last price before February.
+    let prices = setup.dir.join("dated.csv");
+    let prices = prices.to_str().unwrap();
+    let output = setup.stdout(&["gains", "cold", "--prices", prices]);
+    assert_eq!(
+        output,
+        "\
+TIME                  WALLET  TXID                                                              ASSET  AMOUNT  ACQUIRED              PROCEEDS   COST  GAIN
+2024-01-15T00:00:00Z  cold    7c7775d31260a299bcf744a92e4552124244a780cf0d194410c63bc4ea5b983f  BTC    0.0061  2023-12-31T23:00:00Z       258  256.2   1.8
+
+Realized gain (fifo): 1.8 USD
+Unrealized gain: 104.2 USD
+  0.0059 BTC: cost 249.8, value 354 USD
+"
+    );
+
+    let json: Value = serde_json::from_str(&setup.stdout(&[
+        "gains",
+        "cold",
+        "--prices",
+        prices,
+        "--method",
+        "hifo",
+        "--disposed-after",
+        "2024-01-31",
+        "-f",
+        "json",
+    ]))
+    .unwrap();
+    assert_eq!(json["method"], "hifo");
+    assert_eq!(json["realized"], "0");
+    assert_eq!(json["disposals"].as_array().unwrap().len(), 0);
+    assert_eq!(json["holdings"][0]["lots"].as_array().unwrap().len(), 2);
+
+    for flag in ["--disposed-after", "--until"] {
+        let output = setup.run(&["gains", "cold", flag, "height:5"]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&format!("{flag} takes a date")), "{stderr}");
+    }
+}
+
+#[test]
+fn exports_a_valued_report() {
+    let setup = Setup::new("export");
+    let path = setup.dir.join("report.json");
+    let output = setup.stdout(&["export", "-f", "json", "-o", path.to_str().unwrap()]);
+    assert!(output.is_empty());
+
+    let report: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(report["currency"], "USD");
+    // 0.0059 BTC, 1.5 ETH and 2500.5 USDC.
+    assert_eq!(report["total"], "8020.451861");
+    assert_eq!(report["wallets"][0]["total"], "369.801911");
+
+    // The hot wallet was only funded in January.
+    assert_eq!(
+        setup.stdout(&["export", "--at", "2023-12-31", "--currency", "usd"]),
+        "\
+WALLET  CHAIN     ASSET  CATEGORY   AMOUNT  CURRENCY     PRICE     VALUE
+cold    bitcoin   BTC    available    0.01  USD       62678.29  626.7829
+hot     ethereum  ETH    available       0  USD        3433.60         0
+
+Total: 626.7829 USD
+"
+    );
+}
+
+#[test]
+fn adds_wallets_to_the_config() {
+    let setup = Setup::new("add-wallet");
+    let original = fs::read_to_string(setup.config()).unwrap();
+
+    let output = setup.run(&[
+        "add-wallet",
+        "ops",
+        "--source",
+        "btc",
+        "--address",
+        "bc1qnotanaddress",
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("wallet `ops`"));
+    assert_eq!(fs::read_to_string(setup.config()).unwrap(), original);
+
+    setup.stdout(&[
+        "add-wallet",
+        "ops",
+        "--name",
+        "Operations",
+        "--source",
+        "btc",
+        "--address",
+        "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq",
+    ]);
+    let updated = fs::read_to_string(setup.config()).unwrap();
+    assert!(updated.starts_with(&original), "existing content is kept");
+    assert_eq!(
+        setup
+            .stdout(&["balance", "ops", "-f", "csv"])
+            .lines()
+            .nth(1),
+        Some("ops,bitcoin,BTC,available,0.006,0,0.006")
+    );
+
+    let duplicate = setup.run(&[
+        "add-wallet",
+        "ops",
+        "--source",
+        "eth",
+        "--address",
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+    ]);
+    assert!(String::from_utf8_lossy(&duplicate.stderr).contains("more than once"));
+}
+
+#[test]
+fn caches_balances_in_the_database() {
+    let setup = Setup::new("database");
+    let output = setup.run(&["balance", "--cached"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no database configured"));
+
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        config.replace(
+            "prices = \"prices.csv\"",
+            "prices = \"prices.csv\"\ndatabase = \"walletb.db\"",
+        ),
+    )
+    .unwrap();
+    // Nothing has been read yet.
+    assert_eq!(
+        setup.stdout(&["balance", "--cached", "-f", "csv"]),
+        "taken at,wallet,chain,asset,category,confirmed,unconfirmed,total\r\n"
+    );
+
+    setup.stdout(&["balance"]);
+    let cached: Value =
+        serde_json::from_str(&setup.stdout(&["balance", "--cached", "-f", "json"])).unwrap();
+    let rows = cached.as_array().unwrap();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0]["wallet"], "cold");
+    assert_eq!(rows[0]["total"], "0.0059");
+    assert!(rows[0]["taken at"].as_str().unwrap().ends_with('Z'));
+
+    // The scanned addresses were kept for the next run to resume from.
+    let store = Store::open(setup.dir.join("walletb.db")).unwrap();
+    let known = store.discovery("cold").unwrap();
+    assert_eq!(known.next_index(Keychain::External), 2);
+    assert_eq!(known.next_index(Keychain::Internal), 1);
+    assert_eq!(store.snapshots("hot").unwrap().len(), 1);
+}
+
+#[test]
+fn reads_bitcoin_from_an_electrum_server() {
+    let setup = Setup::new("electrum");
+    let electrum = MockLineServer::new(|method, params| match method {
+        "server.version" => Ok(json!(["mock", "1.4"])),
+        "blockchain.scripthash.get_balance" => {
+            assert_eq!(params[0].as_str().unwrap().len(), 64);
+            Ok(json!({ "confirmed": 600_000, "unconfirmed": 25_000 }))
+        }
+        _ => Err(RpcFailure::method_not_found(method)),
+    });
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[sources.electrs]
+kind = \"bitcoin\"
+electrum = \"{}\"
+
+[[wallets]]
+id = \"ops\"
+source = \"electrs\"
+addresses = [\"bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq\"]
+",
+            electrum.addr()
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        setup
+            .stdout(&["balance", "ops", "-f", "csv"])
+            .lines()
+            .nth(1),
+        Some("ops,bitcoin,BTC,available,0.006,0.00025,0.00625")
+    );
+}
+
+#[test]
+fn imports_wallets_into_a_bitcoin_core_node() {
+    let setup = Setup::new("core");
+    let node = MockServer::json_rpc(|method, _| match method {
+        "loadwallet" => Ok(json!({ "name": "walletb" })),
+        "listdescriptors" => Ok(json!({ "wallet_name": "walletb", "descriptors": [] })),
+        "importdescriptors" => Ok(json!([{ "success": true }])),
+        "getblockcount" => Ok(json!(300)),
+        "listunspent" => Ok(json!([{
+            "txid": "7c7775d31260a299bcf744a92e4552124244a780cf0d194410c63bc4ea5b983f",
+            "vout": 0,
+            "scriptPubKey": "0014e8df018c7e326cc253faac7e46cdc51e68542c42",
+            "amount": 0.006,
+            "confirmations": 101,
+        }])),
+        _ => Err(RpcFailure::method_not_found(method)),
+    });
+    fs::write(setup.dir.join(".cookie"), "__cookie__:abc").unwrap();
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[sources.node]
+kind = \"bitcoin-core\"
+url = \"{}\"
+cookie = \".cookie\"
+wallet = \"walletb\"
+
+[[wallets]]
+id = \"ops\"
+source = \"node\"
+addresses = [\"bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq\"]
+",
+            node.url()
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        setup
+            .stdout(&["balance", "ops", "-f", "csv"])
+            .lines()
+            .nth(1),
+        Some("ops,bitcoin,BTC,available,0.006,0,0.006")
+    );
+
+    let requests = node.requests();
+    let import = requests
+        .iter()
+        .find(|r| r.json()["method"] == "importdescriptors")
+        .unwrap();
+    assert_eq!(import.path(), "/wallet/walletb");
+    assert_eq!(
+        import.json()["params"][0][0]["desc"],
+        "raw(0014e8df018c7e326cc253faac7e46cdc51e68542c42)#rzauefm4"
+    );
+}
+
+#[test]
+fn syncs_bitcoin_core_wallets_block_by_block() {
+    let setup = Setup::new("sync");
+    let chain = Arc::new(Mutex::new(MemoryChain::new()));
+    chain.lock().unwrap().push(vec![Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::from_byte_array([7; 32]), 0),
+            ..TxIn::default()
+        }],
+        output: vec![TxOut {
+            value: bitcoin::Amount::from_sat(600_000),
+            script_pubkey: ScriptBuf::from_hex("0014e8df018c7e326cc253faac7e46cdc51e68542c42")
+                .unwrap(),
+        }],
+    }]);
+    let node = MockServer::json_rpc({
+        let chain = Arc::clone(&chain);
+        move |method, params| {
+            let chain = chain.lock().unwrap();
+            match method {
+                "getblockhash" => match chain.block_hash(params[0].as_u64().unwrap() as u32) {
+                    Ok(Some(hash)) => Ok(json!(hash)),
+                    _ => Err(RpcFailure::new(-8, "Block height out of range")),
+                },
+                "getblock" => {
+                    let hash = params[0].as_str().unwrap().parse().unwrap();
+                    Ok(json!(serialize_hex(&chain.block(&hash).unwrap())))
+                }
+                _ => Err(RpcFailure::method_not_found(method)),
+            }
+        }
+    });
+    fs::write(setup.dir.join(".cookie"), "__cookie__:abc").unwrap();
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "database = \"walletb.db\"
+{config}
+[sources.node]
+kind = \"bitcoin-core\"
+url = \"{}\"
+cookie = \".cookie\"
+sync_from = 0
+
+[[wallets]]
+id = \"ops\"
+source = \"node\"
+addresses = [\"bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq\"]
+",
+            node.url()
+        ),
+    )
+    .unwrap();
+    let tip = || chain.lock().unwrap().tip().unwrap().hash;
+    let balance = || {
+        setup
+            .stdout(&["balance", "ops", "-f", "csv"])
+            .lines()
+            .nth(1)
+            .map(str::to_owned)
+    };
+
+    // From the genesis block up; wallets of other sources are left alone.
+    assert_eq!(
+        setup.stdout(&["sync", "-f", "csv"]),
+        format!(
+            "wallet,height,block,applied,rolled back\r\nops,1,{},2,0\r\n",
+            tip()
+        )
+    );
+    assert_eq!(
+        balance().as_deref(),
+        Some("ops,bitcoin,BTC,available,0.006,0,0.006")
+    );
+
+    // The block paying the wallet is reorganized away.
+    {
+        let mut chain = chain.lock().unwrap();
+        chain.reorg(1);
+        chain.push(Vec::new());
+        chain.push(Vec::new());
+    }
+    assert_eq!(
+        setup.stdout(&["sync", "ops", "-f", "csv"]).lines().nth(1),
+        Some(format!("ops,2,{},2,1", tip()).as_str())
+    );
+    // Its transaction is pending again until a block confirms it anew.
+    assert_eq!(
+        balance().as_deref(),
+        Some("ops,bitcoin,BTC,available,0,0.006,0.006")
+    );
+
+    let output = setup.run(&["sync", "cold"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not synced block by block"));
+}
+
+#[test]
+fn breaks_evm_balances_down_by_chain() {
+    let setup = Setup::new("evm");
+    // Ether on Base, and 3 of every token on a zkSync node.
+    let node = |wei: u64, tokens: u64| {
+        MockServer::json_rpc(move |method, _| match method {
+            "eth_blockNumber" => Ok(json!("0x10")),
+            "eth_getBalance" => Ok(json!(format!("0x{wei:x}"))),
+            "eth_call" => Ok(json!(format!("0x{tokens:064x}"))),
+            _ => Err(RpcFailure::method_not_found(method)),
+        })
+    };
+    let (base, zksync) = (node(250_000_000_000_000_000, 0), node(0, 3_000_000));
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[chains.zksync]
+id = 324
+symbol = \"ETH\"
+tokens = [{{ contract = \"0x1d17CBcF0D6D143135aE902365D2E5e2A16538D4\", symbol = \"USDC\", decimals = 6 }}]
+
+[sources.l2s]
+kind = \"evm\"
+chains = [\"base\", \"zksync\"]
+urls = {{ base = \"{}\", zksync = \"{}\" }}
+
+[[wallets]]
+id = \"team\"
+source = \"l2s\"
+addresses = [\"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\"]
+",
+            base.url(),
+            zksync.url()
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        setup.stdout(&["balance", "team", "-f", "csv"]),
+        "\
+wallet,chain,asset,category,confirmed,unconfirmed,total\r
+team,base,ETH,available,0.25,0,0.25\r
+team,evm:324,ETH,available,0,0,0\r
+team,evm:324,USDC,available,3,0,3\r
+"
+    );
+
+    // Only base has known tokens; zkSync has only the configured one.
+    let calls = |server: &MockServer| {
+        server
+            .requests()
+            .iter()
+            .flat_map(|r| match r.json() {
+                Value::Array(batch) => batch,
+                call => vec![call],
+            })
+            .filter(|call| call["method"] == "eth_call")
+            .count()
+    };
+    assert_eq!(calls(&base), 1);
+    assert_eq!(calls(&zksync), 1);
+
+    let missing_url = config.replace(
+        "kind = \"ethereum\"",
+        "kind = \"ethereum\"\nchain = \"fantom\"",
+    );
+    fs::write(setup.config(), missing_url).unwrap();
+    let output = setup.run(&["balance"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown chain `fantom`"));
+}
+
+#[test]
+fn reads_solana_with_its_tokens() {
+    let setup = Setup::new("solana");
+    let alice = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    let node = MockServer::json_rpc(|method, params| {
+        let context = |value: Value| json!({ "context": { "slot": 300 }, "value": value });
+        match method {
+            "getBalance" => Ok(context(json!(2_000_000_000u64))),
+            "getTokenAccountsByOwner"
+                if params[1]["programId"] == "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA" =>
+            {
+                Ok(context(json!([{
+                    "pubkey": "11111111111111111111111111111112",
+                    "account": {
+                        "lamports": 2_039_280,
+                        "data": { "parsed": { "info": {
+                            "mint": "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263",
+                            "owner": params[0],
+                            "tokenAmount": { "amount": "125000", "decimals": 5 },
+                        } } },
+                    },
+                }])))
+            }
+            "getTokenAccountsByOwner" => Ok(context(json!([]))),
+            "getProgramAccounts" => Ok(json!([])),
+            _ => Err(RpcFailure::method_not_found(method)),
+        }
+    });
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[sources.sol]
+kind = \"solana\"
+url = \"{}\"
+tokens = [{{ contract = \"DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263\", symbol = \"BONK\", decimals = 5 }}]
+
+[[wallets]]
+id = \"sol\"
+source = \"sol\"
+addresses = [\"{alice}\"]
+",
+            node.url()
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        setup.stdout(&["balance", "sol", "-f", "csv"]),
+        "\
+wallet,chain,asset,category,confirmed,unconfirmed,total\r
+sol,solana,SOL,available,2,0,2\r
+sol,solana,BONK,available,1.25,0,1.25\r
+"
+    );
+    let output = setup.run(&["history", "sol", "--at", "height:10"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot report past balances"));
+}
+
+#[test]
+fn counts_staked_atom_in_cosmos_balances() {
+    let setup = Setup::new("cosmos");
+    let alice = "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd02";
+    let lcd = MockServer::http(|request| {
+        let coin = |amount: &str| json!({ "denom": "uatom", "amount": amount });
+        let body = match request.path() {
+            p if p.starts_with("/cosmos/bank/") => {
+                json!({ "balances": [coin("2000000")], "pagination": { "next_key": null } })
+            }
+            p if p.starts_with("/cosmos/staking/v1beta1/delegations/") => json!({
+                "delegation_responses": [{
+                    "delegation": { "validator_address": "cosmosvaloper1x" },
+                    "balance": coin("5000000"),
+                }],
+                "pagination": { "next_key": null },
+            }),
+            p if p.ends_with("/unbonding_delegations") => {
+                json!({ "unbonding_responses": [], "pagination": { "next_key": null } })
+            }
+            p if p.ends_with("/rewards") => json!({
+                "rewards": [{ "validator_address": "cosmosvaloper1x", "reward": [coin("250000.5")] }],
+            }),
+            _ => return MockResponse::status(501, "{}"),
+        };
+        MockResponse::json(&body)
+    });
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[sources.atom]
+kind = \"cosmos\"
+url = \"{}\"
+chain_id = \"cosmoshub-4\"
+
+[[wallets]]
+id = \"atom\"
+source = \"atom\"
+addresses = [\"{alice}\"]
+",
+            lcd.url()
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        setup.stdout(&["balance", "atom", "-f", "csv"]),
+        "\
+wallet,chain,asset,category,confirmed,unconfirmed,total\r
+atom,cosmos:cosmoshub-4,ATOM,available,2,0,2\r
+atom,cosmos:cosmoshub-4,ATOM,staked,5,0,5\r
+atom,cosmos:cosmoshub-4,ATOM,pending_withdrawal,0.25,0,0.25\r
+"
+    );
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[[wallets]]
+id = \"osmo\"
+source = \"atom\"
+addresses = [\"osmo1hsk6jryyqjfhp5dhc55tc9jtckygx0eplp7aec\"]
+"
+        ),
+    )
+    .unwrap();
+    let output = setup.run(&["balance", "osmo"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected a `cosmos` address"));
+}
+
+#[test]
+fn reports_validator_balances_as_staked() {
+    let setup = Setup::new("beacon");
+    let node = MockServer::http(|request| {
+        if !request.path().ends_with("/states/head/validators") {
+            return MockResponse::status(404, "{}");
+        }
+        let validator = |index: u64, status: &str, gwei: &str| {
+            json!({
+                "index": index.to_string(),
+                "balance": gwei,
+                "status": status,
+                "validator": {
+                    "pubkey": format!("0x{}", format!("{index:02x}").repeat(48)),
+                    "withdrawal_credentials": format!("0x00{}", "00".repeat(31)),
+                    "effective_balance": "32000000000",
+                    "slashed": false,
+                },
+            })
+        };
+        MockResponse::json(&json!({
+            "data": [
+                validator(7, "active_ongoing", "32012345678"),
+                validator(9, "withdrawal_possible", "31900000000"),
+            ],
+        }))
+    });
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[sources.validators]
+kind = \"beacon\"
+url = \"{}\"
+
+[[wallets]]
+id = \"stake\"
+source = \"validators\"
+addresses = [\"7\", \"9\"]
+",
+            node.url()
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        setup.stdout(&["balance", "stake", "-f", "csv"]),
+        "\
+wallet,chain,asset,category,confirmed,unconfirmed,total\r
+stake,ethereum,ETH,staked,32.012345678,0,32.012345678\r
+stake,ethereum,ETH,pending_withdrawal,31.9,0,31.9\r
+"
+    );
+    assert_eq!(node.requests()[0].query("id"), Some("7%2C9"));
+}
+
+#[test]
+fn watch_prints_only_changes() {
+    let setup = Setup::new("watch");
+    let output = setup.stdout(&["watch", "--interval", "0", "--count", "3", "-f", "json"]);
+    let rows: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    // Every balance once on the first poll; nothing changes afterwards.
+    assert_eq!(rows.len(), 3);
+    assert!(rows
+        .iter()
+        .all(|row| row["time"].as_str().unwrap().ends_with('Z')));
+}
+
+#[test]
+fn watch_sends_signed_events_to_webhooks() {
+    let setup = Setup::new("events");
+    // Each poll finds another ether deposited.
+    let polls = AtomicU64::new(0);
+    let node = MockServer::json_rpc(move |method, _| match method {
+        "eth_blockNumber" => Ok(json!("0x10")),
+        "eth_getBalance" => {
+            let ether = polls.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(json!(format!("0x{:x}", ether * 1_000_000_000_000_000_000)))
+        }
+        _ => Err(RpcFailure::method_not_found(method)),
+    });
+    let receiver = MockServer::http(|_| MockResponse::status(204, ""));
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[sources.vault]
+kind = \"ethereum\"
+url = \"{}\"
+
+[[wallets]]
+id = \"vault\"
+source = \"vault\"
+addresses = [\"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\"]
+
+[events]
+
+[[events.webhooks]]
+url = \"{}/hooks/walletb\"
+secret = \"whsec-0123456789abcdef\"
+
+[[events.webhooks]]
+url = \"{}/hooks/reorgs\"
+secret = \"whsec-fedcba9876543210\"
+events = [\"reorged\"]
+",
+            node.url(),
+            receiver.url(),
+            receiver.url()
+        ),
+    )
+    .unwrap();
+    let output = setup.stdout(&[
+        "watch",
+        "vault",
+        "--interval",
+        "0",
+        "--count",
+        "2",
+        "-f",
+        "csv",
+    ]);
+    // Both polls are printed, as the balance changed.
+    assert_eq!(output.lines().count(), 3);
+
+    // The first poll is the baseline; the second is one change, and only
+    // the webhook taking every event gets it.
+    let requests = receiver.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.path(), "/hooks/walletb");
+    assert_eq!(request.header("X-Walletb-Event"), Some("balance_changed"));
+    let timestamp = request.header("X-Walletb-Timestamp").unwrap();
+    assert!(walletb_notify::verify(
+        "whsec-0123456789abcdef",
+        timestamp.parse().unwrap(),
+        &request.body,
+        request.header("X-Walletb-Signature").unwrap(),
+    ));
+    let event = request.json();
+    assert_eq!(event["type"], "balance_changed");
+    assert_eq!(event["wallet"], "vault");
+    assert_eq!(event["asset"]["symbol"], "ETH");
+    assert_eq!(event["balance"]["category"], "available");
+    assert_eq!(
+        event["balance"]["previous_confirmed"],
+        "1000000000000000000"
+    );
+    assert_eq!(event["balance"]["confirmed"], "2000000000000000000");
+}
+
+#[test]
+fn watch_raises_alerts_through_notifiers() {
+    let setup = Setup::new("alerts");
+    // The vault pays out one ether between the polls.
+    let polls = AtomicU64::new(0);
+    let node = MockServer::json_rpc(move |method, _| match method {
+        "eth_blockNumber" => Ok(json!("0x10")),
+        "eth_getBalance" => {
+            let ether = 3 - polls.fetch_add(1, Ordering::SeqCst).min(1);
+            Ok(json!(format!("0x{:x}", ether * 1_000_000_000_000_000_000)))
+        }
+        _ => Err(RpcFailure::method_not_found(method)),
+    });
+    let receiver = MockServer::http(|_| MockResponse::status(204, ""));
+    let config = fs::read_to_string(setup.config()).unwrap();
+    fs::write(
+        setup.config(),
+        format!(
+            "{config}
+[sources.vault]
+kind = \"ethereum\"
+url = \"{}\"
+
+[[wallets]]
+id = \"vault\"
+source = \"vault\"
+addresses = [\"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\"]
+
+[[alerts.rules]]
+name = \"vault-out\"
+wallet = \"vault\"
+outgoing = true
+notify = [\"log\", \"ops\"]
+
+[[alerts.rules]]
+name = \"vault-low\"
+wallet = \"vault\"
+asset = \"ETH\"
+below = \"2.5\"
+notify = [\"log\"]
+
+[alerts.notifiers.log]
+kind = \"stdout\"
+
+[alerts.notifiers.ops]
+kind = \"webhook\"
+url = \"{}/hooks/alerts\"
+secret = \"whsec-0123456789abcdef\"
+",
+            node.url(),
+            receiver.url()
+        ),
+    )
+    .unwrap();
+    let output = setup.stdout(&[
+        "watch",
+        "vault",
+        "--interval",
+        "0",
+        "--count",
+        "2",
+        "-f",
+        "csv",
+    ]);
+    assert!(
+        output.contains(" vault-out vault: ETH fell from 3 to 2\n"),
+        "{output}"
+    );
+    assert!(
+        output.contains(" vault-low vault: ETH is 2, below 2.5\n"),
+        "{output}"
+    );
+
+    // Only the outgoing rule notifies the webhook, once.
+    let requests = receiver.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].header("X-Walletb-Event"), Some("alert"));
+    let alert = requests[0].json();
+    assert_eq!(alert["rule"], "vault-out");
+    assert_eq!(alert["amount"], "2");
+}
+
+#[test]
+fn fails_with_a_message() {
+    let setup = Setup::new("errors");
+    let output = setup.run(&["balance", "savings"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "walletb: no wallet `savings` in the config\n"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_walletb"))
+        .args(["--config", "/nonexistent/walletb.toml", "balance"])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("walletb: cannot read config"));
+}
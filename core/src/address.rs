@@ -0,0 +1,55 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Chain, Error, Result};
+
+/// An address on a particular chain, kept in its canonical string form.
+///
+/// No validation happens here; each chain's [`BalanceSource`] parses and
+/// checks the addresses it is handed.
+///
+/// [`BalanceSource`]: crate::BalanceSource
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Address {
+    pub chain: Chain,
+    value: String,
+}
+
+impl Address {
+    pub fn new(chain: Chain, value: impl Into<String>) -> Self {
+        Address {
+            chain,
+            value: value.into(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns an error unless the address belongs to `chain`.
+    pub fn expect_chain(&self, chain: Chain) -> Result<&Self> {
+        if self.chain == chain {
+            Ok(self)
+        } else {
+            Err(Error::ChainMismatch {
+                address: self.value.clone(),
+                expected: chain,
+                actual: self.chain,
+            })
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.value)
+    }
+}
+
+impl AsRef<str> for Address {
+    fn as_ref(&self) -> &str {
+        &self.value
+    }
+}
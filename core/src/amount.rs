@@ -0,0 +1,221 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::Add;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Error, Result};
+
+pub use ruint::aliases::U256;
+
+/// A non-negative quantity of an asset in its smallest unit (satoshi, wei,
+/// lamport, ...).
+///
+/// Amounts never carry a decimal point themselves; the number of decimals
+/// lives on the [`Asset`](crate::Asset). 256 bits is enough for any ERC-20
+/// supply, so the same type is used for every chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(U256);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(U256::ZERO);
+
+    pub const fn from_base_units(units: U256) -> Self {
+        Amount(units)
+    }
+
+    pub fn from_u64(units: u64) -> Self {
+        Amount(U256::from(units))
+    }
+
+    pub fn from_u128(units: u128) -> Self {
+        Amount(U256::from(units))
+    }
+
+    pub const fn base_units(&self) -> U256 {
+        self.0
+    }
+
+    /// Returns the amount as a `u64`, or `None` if it does not fit.
+    pub fn to_u64(&self) -> Option<u64> {
+        u64::try_from(self.0).ok()
+    }
+
+    /// Returns the amount as a `u128`, or `None` if it does not fit.
+    pub fn to_u128(&self) -> Option<u128> {
+        u128::try_from(self.0).ok()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_add(rhs.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).map(Amount)
+    }
+
+    pub fn saturating_sub(self, rhs: Amount) -> Amount {
+        Amount(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Parses a decimal string such as `"1.25"` into base units using
+    /// `decimals` fractional digits.
+    ///
+    /// More fractional digits than `decimals` is an error rather than a
+    /// silent truncation.
+    pub fn from_decimal_str(s: &str, decimals: u8) -> Result<Amount> {
+        let invalid = || Error::InvalidAmount(s.to_owned());
+        let (int, frac) = match s.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (s, ""),
+        };
+        if int.is_empty() && frac.is_empty() {
+            return Err(invalid());
+        }
+        if frac.len() > decimals as usize {
+            return Err(invalid());
+        }
+        let digits = int.bytes().chain(frac.bytes());
+        let padding = decimals as usize - frac.len();
+        let ten = U256::from(10u8);
+        let mut value = U256::ZERO;
+        for b in digits {
+            if !b.is_ascii_digit() {
+                return Err(invalid());
+            }
+            value = value
+                .checked_mul(ten)
+                .and_then(|v| v.checked_add(U256::from(b - b'0')))
+                .ok_or(Error::Overflow)?;
+        }
+        for _ in 0..padding {
+            value = value.checked_mul(ten).ok_or(Error::Overflow)?;
+        }
+        Ok(Amount(value))
+    }
+
+    /// Formats the amount with `decimals` fractional digits, dropping
+    /// trailing zeros (`150000000` with 8 decimals is `"1.5"`).
+    pub fn to_decimal_string(&self, decimals: u8) -> String {
+        let digits = self.0.to_string();
+        let decimals = decimals as usize;
+        if decimals == 0 {
+            return digits;
+        }
+        let padded = if digits.len() <= decimals {
+            format!("{}{}", "0".repeat(decimals + 1 - digits.len()), digits)
+        } else {
+            digits
+        };
+        let (int, frac) = padded.split_at(padded.len() - decimals);
+        let frac = frac.trim_end_matches('0');
+        if frac.is_empty() {
+            int.to_owned()
+        } else {
+            format!("{int}.{frac}")
+        }
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(units: u64) -> Self {
+        Amount::from_u64(units)
+    }
+}
+
+impl From<U256> for Amount {
+    fn from(units: U256) -> Self {
+        Amount(units)
+    }
+}
+
+impl Add for Amount {
+    type Output = Amount;
+
+    fn add(self, rhs: Amount) -> Amount {
+        self.checked_add(rhs).expect("amount overflow")
+    }
+}
+
+impl Sum for Amount {
+    fn sum<I: Iterator<Item = Amount>>(iter: I) -> Amount {
+        iter.fold(Amount::ZERO, Add::add)
+    }
+}
+
+/// Displays the raw base units; use [`Amount::to_decimal_string`] for a
+/// human readable value.
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for Amount {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Amount::from_decimal_str(s, 0)
+    }
+}
+
+// Serialized as a base-10 string of base units so values above 2^53 survive
+// JSON consumers that parse numbers as doubles.
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_decimals() {
+        let a = Amount::from_decimal_str("1.5", 8).unwrap();
+        assert_eq!(a, Amount::from_u64(150_000_000));
+        assert_eq!(a.to_decimal_string(8), "1.5");
+
+        let dust = Amount::from_u64(1);
+        assert_eq!(dust.to_decimal_string(8), "0.00000001");
+        assert_eq!(Amount::ZERO.to_decimal_string(18), "0");
+        assert_eq!(Amount::from_u64(42).to_decimal_string(0), "42");
+    }
+
+    #[test]
+    fn rejects_excess_precision_and_garbage() {
+        assert!(Amount::from_decimal_str("0.123", 2).is_err());
+        assert!(Amount::from_decimal_str("1,5", 8).is_err());
+        assert!(Amount::from_decimal_str("-1", 8).is_err());
+        assert!(Amount::from_decimal_str(".", 8).is_err());
+    }
+
+    #[test]
+    fn handles_values_beyond_u128() {
+        let max = Amount::from_base_units(U256::MAX);
+        let s = max.to_decimal_string(18);
+        assert_eq!(Amount::from_decimal_str(&s, 18).unwrap(), max);
+        assert!(max.checked_add(Amount::from_u64(1)).is_none());
+    }
+
+    #[test]
+    fn serializes_as_string() {
+        let a = Amount::from_u128(u128::MAX);
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, format!("\"{}\"", u128::MAX));
+        assert_eq!(serde_json::from_str::<Amount>(&json).unwrap(), a);
+    }
+}
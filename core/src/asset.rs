@@ -0,0 +1,297 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Error, Result};
+
+/// The ledger an [`Asset`] or [`Address`](crate::Address) lives on.
+///
+/// EVM networks are identified by their EIP-155 chain id so that the same
+/// code path serves Ethereum mainnet and every compatible chain; Cosmos SDK
+/// networks likewise by their chain id, such as `cosmoshub-4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Chain {
+    Bitcoin,
+    Evm(u64),
+    Solana,
+    Cosmos(ChainId),
+}
+
+/// A Cosmos SDK chain id, kept inline so that [`Chain`] stays `Copy`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChainId {
+    len: u8,
+    bytes: [u8; ChainId::MAX_LEN],
+}
+
+impl ChainId {
+    /// The longest chain id accepted; with the length byte and the enum
+    /// tag, [`Chain`] stays at 32 bytes.
+    pub const MAX_LEN: usize = 30;
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..usize::from(self.len)]).expect("checked on creation")
+    }
+}
+
+impl FromStr for ChainId {
+    type Err = Error;
+
+    /// Accepts ASCII letters, digits, `-`, `_` and `.`, up to
+    /// [`MAX_LEN`](Self::MAX_LEN) of them.
+    fn from_str(s: &str) -> Result<Self> {
+        let valid = s
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b));
+        if s.is_empty() || s.len() > Self::MAX_LEN || !valid {
+            return Err(Error::UnknownChain(s.to_owned()));
+        }
+        let mut bytes = [0; Self::MAX_LEN];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(ChainId {
+            len: s.len() as u8,
+            bytes,
+        })
+    }
+}
+
+impl fmt::Display for ChainId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for ChainId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Chain {
+    pub const ETHEREUM: Chain = Chain::Evm(1);
+    pub const OPTIMISM: Chain = Chain::Evm(10);
+    pub const BSC: Chain = Chain::Evm(56);
+    pub const POLYGON: Chain = Chain::Evm(137);
+    pub const BASE: Chain = Chain::Evm(8453);
+    pub const ARBITRUM: Chain = Chain::Evm(42161);
+    pub const AVALANCHE: Chain = Chain::Evm(43114);
+}
+
+/// Names that well-known EVM chains display and parse as, instead of
+/// `evm:<id>`.
+const EVM_NAMES: [(Chain, &str); 7] = [
+    (Chain::ETHEREUM, "ethereum"),
+    (Chain::OPTIMISM, "optimism"),
+    (Chain::BSC, "bsc"),
+    (Chain::POLYGON, "polygon"),
+    (Chain::BASE, "base"),
+    (Chain::ARBITRUM, "arbitrum"),
+    (Chain::AVALANCHE, "avalanche"),
+];
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chain::Bitcoin => f.write_str("bitcoin"),
+            Chain::Solana => f.write_str("solana"),
+            Chain::Cosmos(id) => write!(f, "cosmos:{id}"),
+            Chain::Evm(id) => match EVM_NAMES.iter().find(|(chain, _)| chain == self) {
+                Some((_, name)) => f.write_str(name),
+                None => write!(f, "evm:{id}"),
+            },
+        }
+    }
+}
+
+impl FromStr for Chain {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bitcoin" => Ok(Chain::Bitcoin),
+            "solana" => Ok(Chain::Solana),
+            _ if s.starts_with("cosmos:") => Ok(Chain::Cosmos(s["cosmos:".len()..].parse()?)),
+            _ => EVM_NAMES
+                .iter()
+                .find(|(_, name)| *name == s)
+                .map(|(chain, _)| *chain)
+                .or_else(|| {
+                    s.strip_prefix("evm:")
+                        .and_then(|id| id.parse().ok())
+                        .map(Chain::Evm)
+                })
+                .ok_or_else(|| Error::UnknownChain(s.to_owned())),
+        }
+    }
+}
+
+impl Serialize for Chain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Chain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Whether an asset is the chain's native coin, a contract-issued token or
+/// one non-fungible item of a contract.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssetKind {
+    Native,
+    Token {
+        contract: String,
+    },
+    /// An ERC-721 item, or an ERC-1155 id, which may have several copies.
+    Nft {
+        contract: String,
+        token_id: String,
+    },
+}
+
+/// Something that can be held in a wallet, together with the number of
+/// decimals used to render its [`Amount`](crate::Amount)s.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Asset {
+    pub chain: Chain,
+    pub kind: AssetKind,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl Asset {
+    pub fn native(chain: Chain, symbol: impl Into<String>, decimals: u8) -> Self {
+        Asset {
+            chain,
+            kind: AssetKind::Native,
+            symbol: symbol.into(),
+            decimals,
+        }
+    }
+
+    pub fn token(
+        chain: Chain,
+        contract: impl Into<String>,
+        symbol: impl Into<String>,
+        decimals: u8,
+    ) -> Self {
+        Asset {
+            chain,
+            kind: AssetKind::Token {
+                contract: contract.into(),
+            },
+            symbol: symbol.into(),
+            decimals,
+        }
+    }
+
+    /// Item `token_id` of the NFT contract `contract`, counted in whole
+    /// copies.
+    pub fn nft(
+        chain: Chain,
+        contract: impl Into<String>,
+        token_id: impl Into<String>,
+        symbol: impl Into<String>,
+    ) -> Self {
+        Asset {
+            chain,
+            kind: AssetKind::Nft {
+                contract: contract.into(),
+                token_id: token_id.into(),
+            },
+            symbol: symbol.into(),
+            decimals: 0,
+        }
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.kind == AssetKind::Native
+    }
+
+    /// The token contract address, if this is not the native asset.
+    pub fn contract(&self) -> Option<&str> {
+        match &self.kind {
+            AssetKind::Native => None,
+            AssetKind::Token { contract } | AssetKind::Nft { contract, .. } => Some(contract),
+        }
+    }
+
+    /// The symbol, followed by `#id` for NFTs, to tell items of one
+    /// collection apart in listings.
+    pub fn label(&self) -> String {
+        match self.token_id() {
+            Some(id) => format!("{} #{id}", self.symbol),
+            None => self.symbol.clone(),
+        }
+    }
+
+    /// The item id within its contract, for NFTs.
+    pub fn token_id(&self) -> Option<&str> {
+        match &self.kind {
+            AssetKind::Nft { token_id, .. } => Some(token_id),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.label(), self.chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chains_stay_small() {
+        // Errors carry chains; keep them from growing every `Result`.
+        assert_eq!(std::mem::size_of::<Chain>(), 32);
+    }
+
+    #[test]
+    fn chain_round_trips_through_strings() {
+        for chain in [
+            Chain::Bitcoin,
+            Chain::ETHEREUM,
+            Chain::Evm(137),
+            Chain::Evm(324),
+        ] {
+            assert_eq!(chain.to_string().parse::<Chain>().unwrap(), chain);
+        }
+        assert_eq!("evm:1".parse::<Chain>().unwrap(), Chain::ETHEREUM);
+        assert_eq!(Chain::Evm(42161).to_string(), "arbitrum");
+        assert_eq!(Chain::Evm(324).to_string(), "evm:324");
+        assert!("cosmos:".parse::<Chain>().is_err());
+        assert!("cosmos:osmosis/1".parse::<Chain>().is_err());
+        assert!(format!("cosmos:{}", "a".repeat(32))
+            .parse::<Chain>()
+            .is_err());
+        assert!("dogecoin".parse::<Chain>().is_err());
+    }
+
+    #[test]
+    fn token_exposes_contract() {
+        let usdc = Asset::token(Chain::ETHEREUM, "0xa0b8", "USDC", 6);
+        assert_eq!(usdc.contract(), Some("0xa0b8"));
+        assert!(!usdc.is_native());
+        assert!(Asset::native(Chain::Bitcoin, "BTC", 8).is_native());
+    }
+
+    #[test]
+    fn nft_names_its_item() {
+        let punk = Asset::nft(Chain::ETHEREUM, "0xb47e", "7804", "PUNK");
+        assert_eq!(punk.contract(), Some("0xb47e"));
+        assert_eq!(punk.token_id(), Some("7804"));
+        assert_eq!(punk.decimals, 0);
+        assert_eq!(punk.to_string(), "PUNK #7804 (ethereum)");
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Amount, Asset, Error, Result};
+
+/// How freely a balance can be moved.
+///
+/// Staking keeps funds owned but out of reach: bonded to a validator,
+/// locked until a date, or on their way back after an exit or unbonding.
+/// Reports keep these apart from what can be spent now.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Spendable now.
+    #[default]
+    Available,
+    /// Bonded to a validator, earning rewards and exposed to slashing.
+    Staked,
+    /// Held back until a date or condition, such as a lockup or a deposit
+    /// waiting to be activated.
+    Locked,
+    /// Leaving stake: unbonding, exiting or earned and not yet paid out,
+    /// becoming available without further action.
+    PendingWithdrawal,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Available,
+        Category::Staked,
+        Category::Locked,
+        Category::PendingWithdrawal,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Available => "available",
+            Category::Staked => "staked",
+            Category::Locked => "locked",
+            Category::PendingWithdrawal => "pending_withdrawal",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Category {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Category::ALL
+            .into_iter()
+            .find(|category| category.as_str() == s)
+            .ok_or_else(|| Error::source(format!("unknown balance category `{s}`")))
+    }
+}
+
+/// What a liquid-staking token is worth in the asset it stakes, such as
+/// the ether behind a balance of wstETH.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Underlying {
+    pub asset: Asset,
+    pub amount: Amount,
+}
+
+/// How much of one [`Asset`] a set of addresses holds in one [`Category`].
+///
+/// `unconfirmed` counts funds seen by the source but not yet final (mempool
+/// outputs on Bitcoin, for example). Sources without that notion leave it
+/// at zero.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
+    pub asset: Asset,
+    pub confirmed: Amount,
+    pub unconfirmed: Amount,
+    #[serde(default)]
+    pub category: Category,
+    /// The value of the confirmed amount in the asset it represents, for
+    /// liquid-staking tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underlying: Option<Underlying>,
+}
+
+impl Balance {
+    pub fn new(asset: Asset, confirmed: Amount) -> Self {
+        Balance {
+            asset,
+            confirmed,
+            unconfirmed: Amount::ZERO,
+            category: Category::Available,
+            underlying: None,
+        }
+    }
+
+    pub fn zero(asset: Asset) -> Self {
+        Balance::new(asset, Amount::ZERO)
+    }
+
+    pub fn with_unconfirmed(mut self, unconfirmed: Amount) -> Self {
+        self.unconfirmed = unconfirmed;
+        self
+    }
+
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn with_underlying(mut self, asset: Asset, amount: Amount) -> Self {
+        self.underlying = Some(Underlying { asset, amount });
+        self
+    }
+
+    /// Confirmed plus unconfirmed funds.
+    pub fn total(&self) -> Amount {
+        self.confirmed + self.unconfirmed
+    }
+
+    /// Adds `other` into `self`. Both balances must be of the same asset
+    /// and category, and either both or neither have an underlying value.
+    pub fn merge(&mut self, other: &Balance) -> Result<()> {
+        if self.asset != other.asset || self.category != other.category {
+            return Err(Error::source(format!(
+                "cannot merge {} {} balance into {} {}",
+                other.category, other.asset, self.category, self.asset
+            )));
+        }
+        let underlying = match (&self.underlying, &other.underlying) {
+            (Some(ours), Some(theirs)) if ours.asset == theirs.asset => Some(Underlying {
+                asset: ours.asset.clone(),
+                amount: ours
+                    .amount
+                    .checked_add(theirs.amount)
+                    .ok_or(Error::Overflow)?,
+            }),
+            (None, None) => None,
+            _ => {
+                return Err(Error::source(format!(
+                    "cannot merge {} balances valued in different assets",
+                    self.asset
+                )))
+            }
+        };
+        self.confirmed = self
+            .confirmed
+            .checked_add(other.confirmed)
+            .ok_or(Error::Overflow)?;
+        self.unconfirmed = self
+            .unconfirmed
+            .checked_add(other.unconfirmed)
+            .ok_or(Error::Overflow)?;
+        self.underlying = underlying;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.confirmed.to_decimal_string(self.asset.decimals),
+            self.asset.symbol
+        )?;
+        if self.category != Category::Available {
+            write!(f, " {}", self.category)?;
+        }
+        if let Some(underlying) = &self.underlying {
+            write!(
+                f,
+                " (= {} {})",
+                underlying
+                    .amount
+                    .to_decimal_string(underlying.asset.decimals),
+                underlying.asset.symbol
+            )?;
+        }
+        if !self.unconfirmed.is_zero() {
+            write!(
+                f,
+                " (+{} unconfirmed)",
+                self.unconfirmed.to_decimal_string(self.asset.decimals)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chain;
+
+    fn btc() -> Asset {
+        Asset::native(Chain::Bitcoin, "BTC", 8)
+    }
+
+    #[test]
+    fn merges_same_asset() {
+        let mut a = Balance::new(btc(), Amount::from_u64(100));
+        let b = Balance::new(btc(), Amount::from_u64(50)).with_unconfirmed(Amount::from_u64(7));
+        a.merge(&b).unwrap();
+        assert_eq!(a.confirmed, Amount::from_u64(150));
+        assert_eq!(a.total(), Amount::from_u64(157));
+    }
+
+    #[test]
+    fn refuses_to_merge_different_assets() {
+        let mut a = Balance::zero(btc());
+        let eth = Balance::zero(Asset::native(Chain::ETHEREUM, "ETH", 18));
+        assert!(a.merge(&eth).is_err());
+    }
+
+    #[test]
+    fn keeps_categories_apart() {
+        let mut available = Balance::new(btc(), Amount::from_u64(1));
+        let locked = Balance::new(btc(), Amount::from_u64(2)).with_category(Category::Locked);
+        assert!(available.merge(&locked).is_err());
+
+        let steth = Asset::token(Chain::ETHEREUM, "0xae7a…", "stETH", 18);
+        let eth = Asset::native(Chain::ETHEREUM, "ETH", 18);
+        let mut a = Balance::new(steth.clone(), Amount::from_u64(10))
+            .with_underlying(eth.clone(), Amount::from_u64(10));
+        let b = Balance::new(steth.clone(), Amount::from_u64(5))
+            .with_underlying(eth.clone(), Amount::from_u64(6));
+        a.merge(&b).unwrap();
+        assert_eq!(a.underlying.as_ref().unwrap().amount, Amount::from_u64(16));
+        assert!(a.merge(&Balance::zero(steth)).is_err());
+
+        assert_eq!(
+            "pending_withdrawal".parse::<Category>().unwrap(),
+            Category::PendingWithdrawal
+        );
+        assert!("spendable".parse::<Category>().is_err());
+    }
+
+    #[test]
+    fn reads_balances_stored_without_a_category() {
+        let stored = r#"{"asset":{"chain":"bitcoin","kind":{"type":"native"},"symbol":"BTC","decimals":8},"confirmed":"5","unconfirmed":"0"}"#;
+        let balance: Balance = serde_json::from_str(stored).unwrap();
+        assert_eq!(balance.category, Category::Available);
+        assert!(balance.underlying.is_none());
+    }
+
+    #[test]
+    fn displays_with_decimals() {
+        let b = Balance::new(btc(), Amount::from_u64(150_000_000))
+            .with_unconfirmed(Amount::from_u64(1_000));
+        assert_eq!(b.to_string(), "1.5 BTC (+0.00001 unconfirmed)");
+        let staked = Balance::new(btc(), Amount::from_u64(1)).with_category(Category::Staked);
+        assert_eq!(staked.to_string(), "0.00000001 BTC staked");
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{Chain, RpcError};
+
+/// Errors produced by the core types and surfaced by [`BalanceSource`]
+/// implementations.
+///
+/// [`BalanceSource`]: crate::BalanceSource
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid amount `{0}`")]
+    InvalidAmount(String),
+
+    #[error("amount overflow")]
+    Overflow,
+
+    #[error("invalid address `{address}`: {reason}")]
+    InvalidAddress { address: String, reason: String },
+
+    #[error("address `{address}` is on {actual}, expected {expected}")]
+    ChainMismatch {
+        address: String,
+        expected: Chain,
+        actual: Chain,
+    },
+
+    #[error("unknown chain `{0}`")]
+    UnknownChain(String),
+
+    #[error("`{0}` is not a block height or UTC date")]
+    InvalidPointInTime(String),
+
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error(transparent)]
+    Source(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    /// Wraps a backend specific error so it can cross the
+    /// [`BalanceSource`](crate::BalanceSource) boundary.
+    pub fn source<E>(err: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Error::Source(err.into())
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
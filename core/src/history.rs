@@ -0,0 +1,224 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Address, Balance, BalanceSource, Error, Result};
+
+/// A point in a chain's history: a block height, or a moment in time that
+/// resolves to the last block at or before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointInTime {
+    Height(u64),
+    /// Seconds since the Unix epoch, UTC.
+    Time(u64),
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+impl PointInTime {
+    /// The last second of a UTC calendar day, the usual cut-off for
+    /// month-end and year-end statements.
+    pub fn end_of_day(year: i32, month: u32, day: u32) -> Result<Self> {
+        let days = days_from_civil(year, month, day)
+            .ok_or_else(|| Error::InvalidPointInTime(format!("{year}-{month:02}-{day:02}")))?;
+        Ok(PointInTime::Time(days * DAY + DAY - 1))
+    }
+
+    /// Whether a block at `height` with timestamp `time` is included.
+    pub fn includes(&self, height: u64, time: u64) -> bool {
+        match *self {
+            PointInTime::Height(h) => height <= h,
+            PointInTime::Time(t) => time <= t,
+        }
+    }
+}
+
+/// Displays `height:N` or an RFC 3339 UTC timestamp, both of which parse
+/// back.
+impl fmt::Display for PointInTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PointInTime::Height(height) => write!(f, "height:{height}"),
+            PointInTime::Time(time) => {
+                let (year, month, day) = civil_from_days(time / DAY);
+                let secs = time % DAY;
+                write!(
+                    f,
+                    "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+                    secs / 3600,
+                    secs / 60 % 60,
+                    secs % 60
+                )
+            }
+        }
+    }
+}
+
+/// Accepts `height:N` (or `block:N`), `time:UNIX_SECONDS`, a date
+/// `YYYY-MM-DD` meaning the end of that UTC day, or a UTC timestamp
+/// `YYYY-MM-DDTHH:MM:SSZ`.
+impl FromStr for PointInTime {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidPointInTime(s.to_owned());
+        if let Some(n) = s
+            .strip_prefix("height:")
+            .or_else(|| s.strip_prefix("block:"))
+        {
+            return n.parse().map(PointInTime::Height).map_err(|_| invalid());
+        }
+        if let Some(n) = s.strip_prefix("time:") {
+            return n.parse().map(PointInTime::Time).map_err(|_| invalid());
+        }
+        let (date, time) = match s.split_once('T') {
+            Some((date, time)) => (date, Some(time)),
+            None => (s, None),
+        };
+        let mut parts = date.splitn(3, '-');
+        let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let (year, month, day) = match (year.parse(), month.parse(), day.parse()) {
+            (Ok(y), Ok(m), Ok(d)) if year.len() == 4 => (y, m, d),
+            _ => return Err(invalid()),
+        };
+        let Some(time) = time else {
+            return PointInTime::end_of_day(year, month, day).map_err(|_| invalid());
+        };
+        let days = days_from_civil(year, month, day).ok_or_else(invalid)?;
+        let clock: Vec<u64> = time
+            .strip_suffix('Z')
+            .ok_or_else(invalid)?
+            .split(':')
+            .map(|n| n.parse().map_err(|_| invalid()))
+            .collect::<Result<_>>()?;
+        match clock[..] {
+            [h, m, s] if h < 24 && m < 60 && s < 60 => {
+                Ok(PointInTime::Time(days * DAY + h * 3600 + m * 60 + s))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Serialize for PointInTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PointInTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A [`BalanceSource`] that can also answer for a past block or date.
+///
+/// Historical balances are final by definition, so every amount is
+/// reported as confirmed.
+pub trait HistoricalSource: BalanceSource {
+    fn balances_at(&self, addresses: &[Address], at: PointInTime) -> Result<Vec<Balance>>;
+}
+
+impl<S: HistoricalSource + ?Sized> HistoricalSource for &S {
+    fn balances_at(&self, addresses: &[Address], at: PointInTime) -> Result<Vec<Balance>> {
+        (**self).balances_at(addresses, at)
+    }
+}
+
+impl<S: HistoricalSource + ?Sized> HistoricalSource for Box<S> {
+    fn balances_at(&self, addresses: &[Address], at: PointInTime) -> Result<Vec<Balance>> {
+        (**self).balances_at(addresses, at)
+    }
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date, or `None` for an
+/// invalid date or one before the epoch.
+fn days_from_civil(year: i32, month: u32, day: u32) -> Option<u64> {
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if day == 0 || day > days_in_month {
+        return None;
+    }
+    // Howard Hinnant's algorithm, with years starting in March.
+    let y = i64::from(year) - i64::from(month <= 2);
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    u64::try_from(era * 146_097 + doe - 719_468).ok()
+}
+
+fn civil_from_days(days: u64) -> (i64, u32, u32) {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_heights_dates_and_timestamps() {
+        assert_eq!(
+            "height:840000".parse::<PointInTime>().unwrap(),
+            PointInTime::Height(840_000)
+        );
+        assert_eq!(
+            "block:1".parse::<PointInTime>().unwrap(),
+            PointInTime::Height(1)
+        );
+        // 2024-01-01T00:00:00Z is 1704067200.
+        assert_eq!(
+            "2023-12-31".parse::<PointInTime>().unwrap(),
+            PointInTime::Time(1_704_067_199)
+        );
+        assert_eq!(
+            "2024-02-29T12:30:00Z".parse::<PointInTime>().unwrap(),
+            PointInTime::Time(1_709_209_800)
+        );
+        for bad in [
+            "840000",
+            "2023-02-29",
+            "2024-13-01",
+            "2024-01-01T25:00:00Z",
+            "height:x",
+        ] {
+            assert!(bad.parse::<PointInTime>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn displays_in_a_form_that_parses_back() {
+        for at in [
+            PointInTime::Height(7),
+            PointInTime::Time(0),
+            PointInTime::Time(1_709_209_800),
+            PointInTime::Time(4_102_444_799),
+        ] {
+            assert_eq!(at.to_string().parse::<PointInTime>().unwrap(), at);
+        }
+        assert_eq!(
+            PointInTime::Time(1_704_067_199).to_string(),
+            "2023-12-31T23:59:59Z"
+        );
+    }
+}
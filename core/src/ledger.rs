@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Address, Amount, Asset, BalanceSource, PointInTime, Result};
+
+/// A stretch of a chain's history: everything after `after` up to and
+/// including `until`. Either end may be left open.
+///
+/// Dates mean the end of that UTC day at both ends, so a period after
+/// `2023-12-31` until `2024-12-31` is the year 2024.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Period {
+    pub after: Option<PointInTime>,
+    pub until: Option<PointInTime>,
+}
+
+impl Period {
+    /// Whether a block at `height` with timestamp `time` falls in the
+    /// period.
+    pub fn contains(&self, height: u64, time: u64) -> bool {
+        self.after
+            .map_or(true, |after| !after.includes(height, time))
+            && self
+                .until
+                .map_or(true, |until| until.includes(height, time))
+    }
+}
+
+/// How one transaction moved one asset in or out of a set of addresses.
+///
+/// `received - sent - fee` is what the transaction changed their balance
+/// of `asset` by. Value moving between the addresses themselves, such as
+/// Bitcoin change, nets out and is in neither.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub txid: String,
+    /// Height of the block that included the transaction, or its slot on
+    /// Solana.
+    pub height: u64,
+    /// Block timestamp, in seconds since the Unix epoch.
+    pub time: u64,
+    pub asset: Asset,
+    pub received: Amount,
+    pub sent: Amount,
+    /// What the addresses paid for the transaction, always in the chain's
+    /// native asset and so only on that asset's entry.
+    pub fee: Amount,
+    /// The address on the other side, where the chain tells: who was paid
+    /// or, where a single sender is known, who paid.
+    pub counterparty: Option<String>,
+}
+
+/// A [`BalanceSource`] that can also list the transactions behind its
+/// balances.
+pub trait TransactionSource: BalanceSource {
+    /// Every confirmed transaction in `period` that moved value in or out
+    /// of `addresses`, taken together, oldest first, as one entry per
+    /// asset it moved.
+    fn transactions(&self, addresses: &[Address], period: Period) -> Result<Vec<LedgerEntry>>;
+}
+
+impl<S: TransactionSource + ?Sized> TransactionSource for &S {
+    fn transactions(&self, addresses: &[Address], period: Period) -> Result<Vec<LedgerEntry>> {
+        (**self).transactions(addresses, period)
+    }
+}
+
+impl<S: TransactionSource + ?Sized> TransactionSource for Box<S> {
+    fn transactions(&self, addresses: &[Address], period: Period) -> Result<Vec<LedgerEntry>> {
+        (**self).transactions(addresses, period)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periods_exclude_their_start() {
+        let year = Period {
+            after: Some("2023-12-31".parse().unwrap()),
+            until: Some("2024-12-31".parse().unwrap()),
+        };
+        let new_year: PointInTime = "2024-01-01T00:00:00Z".parse().unwrap();
+        let PointInTime::Time(new_year) = new_year else {
+            unreachable!()
+        };
+        assert!(year.contains(0, new_year));
+        assert!(!year.contains(0, new_year - 1));
+        assert!(year.contains(0, new_year + 366 * 86_400 - 1));
+        assert!(!year.contains(0, new_year + 366 * 86_400));
+
+        let blocks = Period {
+            after: Some(PointInTime::Height(100)),
+            until: None,
+        };
+        assert!(!blocks.contains(100, 0));
+        assert!(blocks.contains(101, 0));
+        assert!(Period::default().contains(0, 0));
+    }
+}
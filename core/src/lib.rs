@@ -0,0 +1,32 @@
+//! Core types shared by every walletb crate.
+//!
+//! Balances are always carried as integer base units ([`Amount`]) together
+//! with the [`Asset`] that defines how many decimals those units have. Chain
+//! specific crates implement [`BalanceSource`] on top of these types, and
+//! [`HistoricalSource`] where they can also report balances at a past
+//! [`PointInTime`], and [`TransactionSource`] where they can list the
+//! transactions behind them as [`LedgerEntry`]s.
+
+mod address;
+mod amount;
+mod asset;
+mod balance;
+mod error;
+mod history;
+mod ledger;
+pub mod rest;
+pub mod rpc;
+mod source;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+
+pub use address::Address;
+pub use amount::{Amount, U256};
+pub use asset::{Asset, AssetKind, Chain, ChainId};
+pub use balance::{Balance, Category, Underlying};
+pub use error::{Error, Result};
+pub use history::{HistoricalSource, PointInTime};
+pub use ledger::{LedgerEntry, Period, TransactionSource};
+pub use rest::RestClient;
+pub use rpc::{JsonRpcClient, RpcError};
+pub use source::BalanceSource;
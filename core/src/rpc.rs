@@ -0,0 +1,243 @@
+//! A small blocking JSON-RPC 2.0 client over HTTP, shared by the chain
+//! crates that talk to nodes.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+/// Largest number of calls sent in one HTTP request unless overridden.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
+/// How long a request may take unless overridden.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum RpcError {
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    #[error("HTTP {status}: {body}")]
+    Http { status: u16, body: String },
+
+    #[error("RPC error {code}: {message}")]
+    Server { code: i64, message: String },
+
+    #[error("invalid RPC response: {0}")]
+    InvalidResponse(String),
+}
+
+/// One method invocation in a [`JsonRpcClient::batch`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcCall {
+    pub method: String,
+    pub params: Value,
+}
+
+impl RpcCall {
+    pub fn new(method: impl Into<String>, params: Value) -> Self {
+        RpcCall {
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JsonRpcClient {
+    url: String,
+    agent: ureq::Agent,
+    headers: Vec<(String, String)>,
+    max_batch_size: usize,
+    next_id: AtomicU64,
+}
+
+impl JsonRpcClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        JsonRpcClient {
+            url: url.into(),
+            agent: agent(DEFAULT_TIMEOUT),
+            headers: Vec::new(),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Caps how many calls [`batch`](Self::batch) packs into a single HTTP
+    /// request; larger batches are split.
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self
+    }
+
+    /// Sets how long a request may take, for methods that do a lot of work
+    /// before answering.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = agent(timeout);
+        self
+    }
+
+    /// Adds a header sent with every request.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Calls `method` and deserializes its result.
+    pub fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, RpcError> {
+        let id = self.next_id();
+        let response = self.post(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))?;
+        let result = into_result(response)?;
+        serde_json::from_value(result).map_err(|e| RpcError::InvalidResponse(e.to_string()))
+    }
+
+    /// Sends `calls` as JSON-RPC batches and returns one result per call,
+    /// in the same order.
+    ///
+    /// The outer error is for failures of a whole HTTP request; per-call
+    /// server errors are reported in the inner results.
+    pub fn batch(&self, calls: &[RpcCall]) -> Result<Vec<Result<Value, RpcError>>, RpcError> {
+        let mut results = Vec::with_capacity(calls.len());
+        for chunk in calls.chunks(self.max_batch_size) {
+            let ids: Vec<u64> = chunk.iter().map(|_| self.next_id()).collect();
+            let body: Vec<Value> = chunk
+                .iter()
+                .zip(&ids)
+                .map(|(call, id)| {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "method": call.method,
+                        "params": call.params,
+                    })
+                })
+                .collect();
+            let response = self.post(&Value::Array(body))?;
+            let Value::Array(responses) = response else {
+                // Some servers answer a batch with a single error object.
+                into_result(response)?;
+                return Err(RpcError::InvalidResponse(
+                    "expected a batch response".into(),
+                ));
+            };
+            // Batch responses may come back in any order.
+            let mut by_id: HashMap<u64, Value> = responses
+                .into_iter()
+                .filter_map(|r| Some((r.get("id")?.as_u64()?, r)))
+                .collect();
+            for id in ids {
+                results.push(match by_id.remove(&id) {
+                    Some(r) => into_result(r),
+                    None => Err(RpcError::InvalidResponse(format!(
+                        "missing response for id {id}"
+                    ))),
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn post(&self, body: &Value) -> Result<Value, RpcError> {
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/json");
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        match request.send_json(body) {
+            Ok(response) => response
+                .into_json()
+                .map_err(|e| RpcError::InvalidResponse(e.to_string())),
+            // Nodes such as bitcoind report RPC errors with a non-2xx status
+            // and a regular JSON-RPC body.
+            Err(ureq::Error::Status(status, response)) => {
+                let text = response.into_string().unwrap_or_default();
+                match serde_json::from_str::<Value>(&text) {
+                    Ok(value) if value.get("error").is_some() || value.is_array() => Ok(value),
+                    _ => Err(RpcError::Http { status, body: text }),
+                }
+            }
+            Err(err) => Err(RpcError::Transport(err.to_string())),
+        }
+    }
+}
+
+fn agent(timeout: Duration) -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(timeout).build()
+}
+
+fn into_result(mut response: Value) -> Result<Value, RpcError> {
+    match response.get_mut("error").map(Value::take) {
+        Some(Value::Null) | None => {}
+        Some(error) => {
+            return Err(RpcError::Server {
+                code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                message: error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+            })
+        }
+    }
+    response
+        .get_mut("result")
+        .map(Value::take)
+        .ok_or_else(|| RpcError::InvalidResponse("missing `result`".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockServer, RpcFailure};
+
+    fn echo_server() -> MockServer {
+        MockServer::json_rpc(|method, params| match method {
+            "echo" => Ok(params.clone()),
+            _ => Err(RpcFailure::method_not_found(method)),
+        })
+    }
+
+    #[test]
+    fn single_call_round_trips() {
+        let server = echo_server();
+        let client = JsonRpcClient::new(server.url());
+        let result: Vec<u32> = client.call("echo", json!([1, 2])).unwrap();
+        assert_eq!(result, vec![1, 2]);
+
+        let err = client.call::<Value>("nope", json!([])).unwrap_err();
+        assert!(matches!(err, RpcError::Server { code: -32601, .. }));
+    }
+
+    #[test]
+    fn batch_preserves_order_and_splits_by_size() {
+        let server = echo_server();
+        let client = JsonRpcClient::new(server.url()).with_max_batch_size(2);
+        let calls = vec![
+            RpcCall::new("echo", json!(["a"])),
+            RpcCall::new("nope", json!([])),
+            RpcCall::new("echo", json!(["c"])),
+        ];
+        let results = client.batch(&calls).unwrap();
+        assert_eq!(results[0], Ok(json!(["a"])));
+        assert!(results[1].is_err());
+        assert_eq!(results[2], Ok(json!(["c"])));
+        assert_eq!(server.requests().len(), 2);
+    }
+}
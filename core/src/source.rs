@@ -0,0 +1,35 @@
+use crate::{Address, Balance, Chain, Result};
+
+/// Something that can report balances for addresses on one chain.
+///
+/// Implementations return one [`Balance`] per asset and
+/// [`Category`](crate::Category), summed across all of the given
+/// addresses. Assets with a zero balance may be omitted, except for the
+/// chain's native asset which is always present as available, unless the
+/// source only ever sees staked funds.
+pub trait BalanceSource {
+    /// The chain whose addresses this source understands.
+    fn chain(&self) -> Chain;
+
+    fn balances(&self, addresses: &[Address]) -> Result<Vec<Balance>>;
+}
+
+impl<S: BalanceSource + ?Sized> BalanceSource for &S {
+    fn chain(&self) -> Chain {
+        (**self).chain()
+    }
+
+    fn balances(&self, addresses: &[Address]) -> Result<Vec<Balance>> {
+        (**self).balances(addresses)
+    }
+}
+
+impl<S: BalanceSource + ?Sized> BalanceSource for Box<S> {
+    fn chain(&self) -> Chain {
+        (**self).chain()
+    }
+
+    fn balances(&self, addresses: &[Address]) -> Result<Vec<Balance>> {
+        (**self).balances(addresses)
+    }
+}
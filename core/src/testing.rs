@@ -0,0 +1,320 @@
+//! In-process mock servers for tests.
+//!
+//! Enabled with the `test-util` feature. A server listens on an ephemeral
+//! localhost port, answers each request with a caller supplied handler, and
+//! records what it received so tests can assert on batching and parameters.
+//! [`MockServer`] speaks HTTP; [`MockLineServer`] speaks newline-delimited
+//! JSON-RPC over plain TCP, as Electrum servers do.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use serde_json::{json, Value};
+
+use crate::rpc::RpcCall;
+
+/// A request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    /// Path including any query string.
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn path(&self) -> &str {
+        self.url.split('?').next().unwrap_or_default()
+    }
+
+    /// Value of query parameter `name`, without percent-decoding.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        let (_, query) = self.url.split_once('?')?;
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v)
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn json(value: &Value) -> Self {
+        MockResponse {
+            status: 200,
+            body: value.to_string(),
+        }
+    }
+
+    pub fn status(status: u16, body: impl Into<String>) -> Self {
+        MockResponse {
+            status,
+            body: body.into(),
+        }
+    }
+}
+
+/// A JSON-RPC error returned by a [`MockServer::json_rpc`] handler.
+#[derive(Debug, Clone)]
+pub struct RpcFailure {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcFailure {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcFailure {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        RpcFailure::new(-32601, format!("method `{method}` not found"))
+    }
+}
+
+pub struct MockServer {
+    url: String,
+    server: Arc<tiny_http::Server>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Starts a server that answers every request with `handler`.
+    pub fn http<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + 'static,
+    {
+        let server = Arc::new(tiny_http::Server::http("127.0.0.1:0").expect("bind mock server"));
+        let url = format!(
+            "http://{}",
+            server
+                .server_addr()
+                .to_ip()
+                .expect("mock server ip address")
+        );
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let thread = {
+            let server = Arc::clone(&server);
+            let requests = Arc::clone(&requests);
+            thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let mut body = String::new();
+                    let _ = request.as_reader().read_to_string(&mut body);
+                    let received = MockRequest {
+                        method: request.method().to_string(),
+                        url: request.url().to_owned(),
+                        headers: request
+                            .headers()
+                            .iter()
+                            .map(|h| (h.field.to_string(), h.value.to_string()))
+                            .collect(),
+                        body,
+                    };
+                    let response = handler(&received);
+                    requests.lock().unwrap().push(received);
+                    let header =
+                        tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+                    let _ = request.respond(
+                        tiny_http::Response::from_string(response.body)
+                            .with_status_code(response.status)
+                            .with_header(header),
+                    );
+                }
+            })
+        };
+        MockServer {
+            url,
+            server,
+            requests,
+            thread: Some(thread),
+        }
+    }
+
+    /// Starts a JSON-RPC 2.0 server that dispatches each call, including
+    /// every call of a batch, to `handler(method, params)`.
+    ///
+    /// Batch responses are returned in reverse order so clients cannot rely
+    /// on the server preserving it.
+    pub fn json_rpc<F>(handler: F) -> Self
+    where
+        F: Fn(&str, &Value) -> Result<Value, RpcFailure> + Send + 'static,
+    {
+        MockServer::http(move |request| {
+            let answer = |call: &Value| {
+                let method = call["method"].as_str().unwrap_or_default();
+                match handler(method, &call["params"]) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": call["id"], "result": result }),
+                    Err(e) => json!({
+                        "jsonrpc": "2.0",
+                        "id": call["id"],
+                        "error": { "code": e.code, "message": e.message },
+                    }),
+                }
+            };
+            let response = match request.json() {
+                Value::Array(calls) => Value::Array(calls.iter().rev().map(answer).collect()),
+                call => answer(&call),
+            };
+            MockResponse::json(&response)
+        })
+    }
+
+    /// Base URL, e.g. `http://127.0.0.1:41234`.
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Every request received so far, oldest first.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+type LineHandler = dyn Fn(&str, &Value) -> Result<Value, RpcFailure> + Send + Sync;
+
+/// A JSON-RPC server over TCP with one message per line, able to push
+/// notifications to its clients.
+pub struct MockLineServer {
+    addr: SocketAddr,
+    calls: Arc<Mutex<Vec<RpcCall>>>,
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockLineServer {
+    /// Starts a server that answers every call with `handler(method,
+    /// params)`. Each connection is served on its own thread.
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(&str, &Value) -> Result<Value, RpcFailure> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let addr = listener.local_addr().expect("mock server address");
+        let handler: Arc<LineHandler> = Arc::new(handler);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let calls = Arc::clone(&calls);
+            let clients = Arc::clone(&clients);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    let Ok(writer) = stream.try_clone() else {
+                        continue;
+                    };
+                    clients.lock().unwrap().push(writer);
+                    let handler = Arc::clone(&handler);
+                    let calls = Arc::clone(&calls);
+                    thread::spawn(move || serve_lines(stream, &*handler, &calls));
+                }
+            })
+        };
+        MockLineServer {
+            addr,
+            calls,
+            clients,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Address to connect to, e.g. `127.0.0.1:41234`.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Every call received so far, oldest first.
+    pub fn calls(&self) -> Vec<RpcCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Sends a notification to every connected client.
+    pub fn notify(&self, method: &str, params: Value) {
+        let line = format!(
+            "{}\n",
+            json!({ "jsonrpc": "2.0", "method": method, "params": params })
+        );
+        for client in self.clients.lock().unwrap().iter_mut() {
+            let _ = client.write_all(line.as_bytes());
+        }
+    }
+}
+
+fn serve_lines(stream: TcpStream, handler: &LineHandler, calls: &Mutex<Vec<RpcCall>>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { return };
+        let call: Value = serde_json::from_str(&line).unwrap_or(Value::Null);
+        let method = call["method"].as_str().unwrap_or_default();
+        let response = match handler(method, &call["params"]) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": call["id"], "result": result }),
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": call["id"],
+                "error": { "code": e.code, "message": e.message },
+            }),
+        };
+        calls
+            .lock()
+            .unwrap()
+            .push(RpcCall::new(method, call["params"].clone()));
+        if writeln!(writer, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+impl Drop for MockLineServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        for client in self.clients.lock().unwrap().iter() {
+            let _ = client.shutdown(Shutdown::Both);
+        }
+        // Wake the accept loop so it sees the stop flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bech32::{Bech32, Hrp};
+
+use crate::{Error, Result};
+
+/// A bech32 account address such as `cosmos1…` or `osmo1…`.
+///
+/// The prefix only names the chain; the same key has an address on every
+/// chain, which [`with_prefix`](Self::with_prefix) converts to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CosmosAddress {
+    hrp: Hrp,
+    bytes: Vec<u8>,
+}
+
+impl CosmosAddress {
+    /// The address of the account `bytes`, usually the hash of its public
+    /// key, under `prefix`.
+    pub fn new(prefix: &str, bytes: &[u8]) -> Result<Self> {
+        let hrp = Hrp::parse(prefix).map_err(|e| Error::InvalidAddress {
+            address: prefix.to_owned(),
+            reason: e.to_string(),
+        })?;
+        Ok(CosmosAddress {
+            hrp,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub fn prefix(&self) -> &str {
+        self.hrp.as_str()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The address of the same account under `prefix`.
+    pub fn with_prefix(&self, prefix: &str) -> Result<Self> {
+        let hrp = Hrp::parse(prefix).map_err(|e| Error::InvalidAddress {
+            address: self.to_string(),
+            reason: format!("prefix `{prefix}`: {e}"),
+        })?;
+        Ok(CosmosAddress {
+            hrp,
+            bytes: self.bytes.clone(),
+        })
+    }
+
+    /// Parses `s`, which must use `prefix`.
+    pub fn parse_with_prefix(s: &str, prefix: &str) -> Result<Self> {
+        let address: CosmosAddress = s.parse()?;
+        if address.prefix() != prefix {
+            return Err(Error::InvalidAddress {
+                address: s.to_owned(),
+                reason: format!("expected a `{prefix}` address"),
+            });
+        }
+        Ok(address)
+    }
+}
+
+impl FromStr for CosmosAddress {
+    type Err = Error;
+
+    /// Parses a bech32 address of a 20-byte account or a 32-byte module or
+    /// contract account.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidAddress {
+            address: s.to_owned(),
+            reason,
+        };
+        let (hrp, bytes) = bech32::decode(s).map_err(|e| invalid(e.to_string()))?;
+        if ![20, 32].contains(&bytes.len()) {
+            return Err(invalid(format!("{} bytes, expected 20 or 32", bytes.len())));
+        }
+        Ok(CosmosAddress { hrp, bytes })
+    }
+}
+
+impl fmt::Display for CosmosAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = bech32::encode_lower::<Bech32>(self.hrp, &self.bytes).map_err(|_| fmt::Error)?;
+        f.write_str(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HUB: &str = "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd02";
+
+    #[test]
+    fn converts_between_prefixes() {
+        let address: CosmosAddress = HUB.parse().unwrap();
+        assert_eq!(address.prefix(), "cosmos");
+        assert_eq!(address.to_string(), HUB);
+        let osmo = address.with_prefix("osmo").unwrap();
+        assert!(osmo.to_string().starts_with("osmo1"));
+        assert_eq!(osmo.as_bytes(), address.as_bytes());
+        assert_eq!(osmo.to_string().parse::<CosmosAddress>().unwrap(), osmo);
+    }
+
+    #[test]
+    fn rejects_bad_addresses() {
+        assert!(CosmosAddress::parse_with_prefix(HUB, "osmo").is_err());
+        let mut typo = HUB.to_owned();
+        typo.replace_range(10..11, "q");
+        assert!(typo.parse::<CosmosAddress>().is_err());
+        assert!("cosmos1qqqqqqqqqqqqqqqqcv9yu6"
+            .parse::<CosmosAddress>()
+            .is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use walletb_core::{Asset, Chain, ChainId};
+
+/// What walletb needs to know about a Cosmos SDK chain: its address prefix,
+/// its staking denomination and the other denominations worth naming.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosmosChain {
+    pub id: ChainId,
+    /// The bech32 prefix of account addresses, such as `cosmos`.
+    pub prefix: String,
+    /// The base denomination of the native asset, such as `uatom`.
+    pub denom: String,
+    pub native: Asset,
+    /// Other denominations, each an asset whose contract is the denom
+    /// (`ibc/…`, `factory/…` and the like).
+    pub tokens: Vec<Asset>,
+}
+
+impl CosmosChain {
+    pub fn new(
+        id: ChainId,
+        prefix: impl Into<String>,
+        denom: impl Into<String>,
+        symbol: impl Into<String>,
+        decimals: u8,
+    ) -> Self {
+        CosmosChain {
+            id,
+            prefix: prefix.into(),
+            denom: denom.into(),
+            native: Asset::native(Chain::Cosmos(id), symbol, decimals),
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Names a denomination other than the native one.
+    pub fn with_token(mut self, denom: &str, symbol: &str, decimals: u8) -> Self {
+        self.tokens
+            .push(Asset::token(self.chain(), denom, symbol, decimals));
+        self
+    }
+
+    pub fn chain(&self) -> Chain {
+        Chain::Cosmos(self.id)
+    }
+
+    /// A chain walletb knows by its chain id: the Cosmos Hub
+    /// (`cosmoshub-4`), Osmosis (`osmosis-1`), Celestia (`celestia`), Juno
+    /// (`juno-1`) or Akash (`akashnet-2`).
+    pub fn known(id: &str) -> Option<CosmosChain> {
+        let (prefix, denom, symbol) = KNOWN.iter().find(|(known, ..)| *known == id)?.1;
+        let chain = CosmosChain::new(id.parse().ok()?, prefix, denom, symbol, 6);
+        Some(match id {
+            "osmosis-1" => chain.with_token(
+                "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2",
+                "ATOM",
+                6,
+            ),
+            _ => chain,
+        })
+    }
+}
+
+/// Chain id, then bech32 prefix, staking denom and symbol; all of them use
+/// six decimals.
+const KNOWN: [(&str, (&str, &str, &str)); 5] = [
+    ("cosmoshub-4", ("cosmos", "uatom", "ATOM")),
+    ("osmosis-1", ("osmo", "uosmo", "OSMO")),
+    ("celestia", ("celestia", "utia", "TIA")),
+    ("juno-1", ("juno", "ujuno", "JUNO")),
+    ("akashnet-2", ("akash", "uakt", "AKT")),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_major_chains() {
+        let hub = CosmosChain::known("cosmoshub-4").unwrap();
+        assert_eq!(hub.prefix, "cosmos");
+        assert_eq!(hub.native.symbol, "ATOM");
+        assert_eq!(hub.chain().to_string(), "cosmos:cosmoshub-4");
+
+        let osmosis = CosmosChain::known("osmosis-1").unwrap();
+        assert_eq!(osmosis.tokens[0].chain, osmosis.chain());
+        assert!(osmosis.tokens[0].contract().unwrap().starts_with("ibc/"));
+        assert!(CosmosChain::known("theta-testnet-001").is_none());
+    }
+}
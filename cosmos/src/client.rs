@@ -0,0 +1,253 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use walletb_core::{Amount, RestClient};
+
+use crate::{CosmosAddress, Error, Result};
+
+/// An amount of one denomination, in its base units.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Coin {
+    pub denom: String,
+    pub amount: Amount,
+}
+
+/// Tokens bonded to one validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delegation {
+    /// The validator's operator address, such as `cosmosvaloper1…`.
+    pub validator: String,
+    pub balance: Coin,
+}
+
+/// Tokens leaving a validator, withdrawable once the unbonding period ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unbonding {
+    pub validator: String,
+    pub creation_height: u64,
+    /// When the tokens become liquid, as an RFC 3339 timestamp.
+    pub completion_time: String,
+    /// The bond denomination's base units still to be released; less than
+    /// the initial balance if the validator was slashed meanwhile.
+    pub balance: Amount,
+}
+
+/// Staking rewards accrued with one validator and not yet withdrawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reward {
+    pub validator: String,
+    /// Rewards are tracked to 18 decimal places of a base unit; the
+    /// fraction, which cannot be withdrawn, is dropped.
+    pub coins: Vec<Coin>,
+}
+
+/// A client for a Cosmos SDK node's LCD, the REST API its gRPC gateway
+/// serves.
+#[derive(Debug)]
+pub struct LcdClient {
+    rest: RestClient,
+}
+
+impl LcdClient {
+    /// How many entries to ask for per page.
+    const PAGE_LIMIT: &'static str = "200";
+
+    pub fn new(url: impl Into<String>) -> Self {
+        LcdClient::from_rest(RestClient::new(url))
+    }
+
+    /// A client over a configured [`RestClient`], such as one that sends an
+    /// API key.
+    pub fn from_rest(rest: RestClient) -> Self {
+        LcdClient { rest }
+    }
+
+    pub fn url(&self) -> &str {
+        self.rest.url()
+    }
+
+    /// The denomination the chain stakes in, such as `uatom`.
+    pub fn bond_denom(&self) -> Result<String> {
+        let path = "/cosmos/staking/v1beta1/params";
+        let response: ParamsResponse = self.get(path, &[])?;
+        Ok(response.params.bond_denom)
+    }
+
+    /// Every denomination the address holds liquid, native or not.
+    pub fn bank_balances(&self, address: &CosmosAddress) -> Result<Vec<Coin>> {
+        let path = format!("/cosmos/bank/v1beta1/balances/{address}");
+        self.paged(&path, |page: BalancesPage| (page.balances, page.pagination))
+    }
+
+    pub fn delegations(&self, address: &CosmosAddress) -> Result<Vec<Delegation>> {
+        let path = format!("/cosmos/staking/v1beta1/delegations/{address}");
+        let responses = self.paged(&path, |page: DelegationsPage| {
+            (page.delegation_responses, page.pagination)
+        })?;
+        Ok(responses
+            .into_iter()
+            .map(|response| Delegation {
+                validator: response.delegation.validator_address,
+                balance: response.balance,
+            })
+            .collect())
+    }
+
+    /// Unbonding entries, one per undelegation still in its unbonding
+    /// period.
+    pub fn unbonding(&self, address: &CosmosAddress) -> Result<Vec<Unbonding>> {
+        let path = format!("/cosmos/staking/v1beta1/delegators/{address}/unbonding_delegations");
+        let responses = self.paged(&path, |page: UnbondingPage| {
+            (page.unbonding_responses, page.pagination)
+        })?;
+        let mut unbonding = Vec::new();
+        for response in responses {
+            for entry in response.entries {
+                unbonding.push(Unbonding {
+                    validator: response.validator_address.clone(),
+                    creation_height: number(&path, &entry.creation_height)?,
+                    completion_time: entry.completion_time,
+                    balance: entry.balance,
+                });
+            }
+        }
+        Ok(unbonding)
+    }
+
+    pub fn rewards(&self, address: &CosmosAddress) -> Result<Vec<Reward>> {
+        let path = format!("/cosmos/distribution/v1beta1/delegators/{address}/rewards");
+        let response: RewardsResponse = self.get(&path, &[])?;
+        response
+            .rewards
+            .into_iter()
+            .map(|reward| {
+                let coins = reward
+                    .reward
+                    .into_iter()
+                    .map(|coin| {
+                        let whole = coin.amount.split('.').next().unwrap_or_default();
+                        let amount = whole
+                            .parse()
+                            .map_err(|_| Error::invalid_response(&path, "reward amount"))?;
+                        Ok(Coin {
+                            denom: coin.denom,
+                            amount,
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(Reward {
+                    validator: reward.validator_address,
+                    coins,
+                })
+            })
+            .collect()
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T> {
+        let value: serde_json::Value = self.rest.get(path, query)?;
+        serde_json::from_value(value).map_err(|e| Error::invalid_response(path, e.to_string()))
+    }
+
+    /// Follows `pagination.next_key` until the last page.
+    fn paged<P, T>(
+        &self,
+        path: &str,
+        split: impl Fn(P) -> (Vec<T>, Option<Pagination>),
+    ) -> Result<Vec<T>>
+    where
+        P: DeserializeOwned,
+    {
+        let mut items = Vec::new();
+        let mut key: Option<String> = None;
+        loop {
+            let mut query = vec![("pagination.limit", Self::PAGE_LIMIT)];
+            if let Some(key) = &key {
+                query.push(("pagination.key", key));
+            }
+            let (page, pagination) = split(self.get(path, &query)?);
+            items.extend(page);
+            match pagination.and_then(|p| p.next_key) {
+                Some(next) if !next.is_empty() => key = Some(next),
+                _ => return Ok(items),
+            }
+        }
+    }
+}
+
+fn number(path: &str, s: &str) -> Result<u64> {
+    s.parse()
+        .map_err(|_| Error::invalid_response(path, format!("`{s}` is not a number")))
+}
+
+#[derive(Deserialize)]
+struct Pagination {
+    next_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ParamsResponse {
+    params: StakingParams,
+}
+
+#[derive(Deserialize)]
+struct StakingParams {
+    bond_denom: String,
+}
+
+#[derive(Deserialize)]
+struct BalancesPage {
+    balances: Vec<Coin>,
+    pagination: Option<Pagination>,
+}
+
+#[derive(Deserialize)]
+struct DelegationsPage {
+    delegation_responses: Vec<DelegationResponse>,
+    pagination: Option<Pagination>,
+}
+
+#[derive(Deserialize)]
+struct DelegationResponse {
+    delegation: DelegationInfo,
+    balance: Coin,
+}
+
+#[derive(Deserialize)]
+struct DelegationInfo {
+    validator_address: String,
+}
+
+#[derive(Deserialize)]
+struct UnbondingPage {
+    unbonding_responses: Vec<UnbondingResponse>,
+    pagination: Option<Pagination>,
+}
+
+#[derive(Deserialize)]
+struct UnbondingResponse {
+    validator_address: String,
+    entries: Vec<UnbondingEntry>,
+}
+
+#[derive(Deserialize)]
+struct UnbondingEntry {
+    creation_height: String,
+    completion_time: String,
+    balance: Amount,
+}
+
+#[derive(Deserialize)]
+struct RewardsResponse {
+    rewards: Vec<ValidatorReward>,
+}
+
+#[derive(Deserialize)]
+struct ValidatorReward {
+    validator_address: String,
+    reward: Vec<DecCoin>,
+}
+
+#[derive(Deserialize)]
+struct DecCoin {
+    denom: String,
+    amount: String,
+}
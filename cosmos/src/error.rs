@@ -0,0 +1,40 @@
+use walletb_core::RpcError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid address `{address}`: {reason}")]
+    InvalidAddress { address: String, reason: String },
+
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error(transparent)]
+    Core(#[from] walletb_core::Error),
+
+    #[error("invalid response from {path}: {reason}")]
+    InvalidResponse { path: String, reason: String },
+}
+
+impl Error {
+    pub(crate) fn invalid_response(path: &str, reason: impl Into<String>) -> Self {
+        Error::InvalidResponse {
+            path: path.to_owned(),
+            reason: reason.into(),
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<Error> for walletb_core::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::InvalidAddress { address, reason } => {
+                walletb_core::Error::InvalidAddress { address, reason }
+            }
+            Error::Rpc(err) => walletb_core::Error::Rpc(err),
+            Error::Core(err) => err,
+            other => walletb_core::Error::source(other),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! Cosmos SDK support for walletb.
+//!
+//! [`CosmosSource`] reads a chain's LCD, the REST API a node's gRPC gateway
+//! serves: bank balances in every denomination, plus the delegations,
+//! unbonding entries and pending staking rewards that make up most of what
+//! holders of a staking token own. Addresses are bech32 with the chain's
+//! prefix; [`CosmosAddress::with_prefix`] gives one key's address on
+//! another chain.
+//!
+//! The LCD serves past state only from archive nodes and only by height, so
+//! there is no [`HistoricalSource`](walletb_core::HistoricalSource) here.
+
+mod address;
+mod chain;
+mod client;
+mod error;
+mod source;
+
+pub use address::CosmosAddress;
+pub use chain::CosmosChain;
+pub use client::{Coin, Delegation, LcdClient, Reward, Unbonding};
+pub use error::{Error, Result};
+pub use source::{CosmosSource, Positions};
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use walletb_core::{Address, Amount, Asset, Balance, BalanceSource, Category, Chain};
+
+use crate::{Coin, CosmosAddress, CosmosChain, Delegation, LcdClient, Result, Reward, Unbonding};
+
+/// Everything one address holds on a chain, liquid or staked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Positions {
+    pub address: CosmosAddress,
+    /// Bank balances, in every denomination held.
+    pub liquid: Vec<Coin>,
+    pub delegations: Vec<Delegation>,
+    pub unbonding: Vec<Unbonding>,
+    /// Rewards accrued and not yet withdrawn.
+    pub rewards: Vec<Reward>,
+}
+
+/// A [`BalanceSource`] for one Cosmos SDK chain, read over its LCD.
+///
+/// On most of these chains the bulk of the native asset is staked rather
+/// than liquid, so besides bank balances, which are available, it reports
+/// delegations as staked and unbonding tokens and unclaimed rewards as
+/// pending withdrawal; [`positions`](Self::positions) has them per
+/// validator. Denominations the chain does not name are shown by an
+/// abbreviation of the denom, with no decimals.
+#[derive(Debug)]
+pub struct CosmosSource {
+    client: LcdClient,
+    chain: CosmosChain,
+}
+
+impl CosmosSource {
+    pub fn new(client: LcdClient, chain: CosmosChain) -> Self {
+        CosmosSource { client, chain }
+    }
+
+    pub fn client(&self) -> &LcdClient {
+        &self.client
+    }
+
+    pub fn cosmos_chain(&self) -> &CosmosChain {
+        &self.chain
+    }
+
+    /// Reads each address's bank balances, delegations, unbonding entries
+    /// and pending rewards.
+    pub fn positions(&self, addresses: &[Address]) -> Result<Vec<Positions>> {
+        addresses
+            .iter()
+            .map(|address| {
+                address.expect_chain(self.chain.chain())?;
+                let address =
+                    CosmosAddress::parse_with_prefix(address.as_str(), &self.chain.prefix)?;
+                Ok(Positions {
+                    liquid: self.client.bank_balances(&address)?,
+                    delegations: self.client.delegations(&address)?,
+                    unbonding: self.client.unbonding(&address)?,
+                    rewards: self.client.rewards(&address)?,
+                    address,
+                })
+            })
+            .collect()
+    }
+
+    /// Sums positions per asset and category: available native tokens
+    /// first, then other denominations and categories with a non-zero
+    /// total.
+    pub fn totals(&self, positions: &[Positions]) -> Vec<Balance> {
+        let mut totals: BTreeMap<(Category, bool, Asset), Amount> = BTreeMap::new();
+        totals.insert(
+            (Category::Available, false, self.chain.native.clone()),
+            Amount::ZERO,
+        );
+        let mut add = |category: Category, coin: &Coin| {
+            let key = if coin.denom == self.chain.denom {
+                (category, false, self.chain.native.clone())
+            } else {
+                (category, true, self.asset(&coin.denom))
+            };
+            let total = totals.entry(key).or_default();
+            *total = *total + coin.amount;
+        };
+        for position in positions {
+            for coin in &position.liquid {
+                add(Category::Available, coin);
+            }
+            for delegation in &position.delegations {
+                add(Category::Staked, &delegation.balance);
+            }
+            for entry in &position.unbonding {
+                let coin = Coin {
+                    denom: self.chain.denom.clone(),
+                    amount: entry.balance,
+                };
+                add(Category::PendingWithdrawal, &coin);
+            }
+            for coin in position.rewards.iter().flat_map(|reward| &reward.coins) {
+                add(Category::PendingWithdrawal, coin);
+            }
+        }
+        totals
+            .into_iter()
+            .filter(|((category, is_token, _), amount)| {
+                (*category == Category::Available && !is_token) || !amount.is_zero()
+            })
+            .map(|((category, _, asset), amount)| {
+                Balance::new(asset, amount).with_category(category)
+            })
+            .collect()
+    }
+
+    /// The asset a non-native denomination is counted as.
+    fn asset(&self, denom: &str) -> Asset {
+        match self
+            .chain
+            .tokens
+            .iter()
+            .find(|token| token.contract() == Some(denom))
+        {
+            Some(asset) => asset.clone(),
+            None => {
+                let symbol = match denom.len() {
+                    0..=16 => denom.to_owned(),
+                    len => format!("{}…{}", &denom[..8], &denom[len - 4..]),
+                };
+                Asset::token(self.chain.chain(), denom, symbol, 0)
+            }
+        }
+    }
+}
+
+impl BalanceSource for CosmosSource {
+    fn chain(&self) -> Chain {
+        self.chain.chain()
+    }
+
+    fn balances(&self, addresses: &[Address]) -> walletb_core::Result<Vec<Balance>> {
+        Ok(self.totals(&self.positions(addresses)?))
+    }
+}
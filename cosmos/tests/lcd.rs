@@ -0,0 +1,155 @@
+use serde_json::{json, Value};
+use walletb_core::testing::{MockRequest, MockResponse, MockServer};
+use walletb_core::{Address, Amount, Asset, BalanceSource, Category, Chain};
+use walletb_cosmos::{CosmosAddress, CosmosChain, CosmosSource, LcdClient};
+
+const ALICE: &str = "cosmos1hsk6jryyqjfhp5dhc55tc9jtckygx0eph6dd02";
+const VALIDATOR: &str = "cosmosvaloper1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u2lcnj0";
+const USDC: &str = "ibc/F663521BF1836B00F5F177680F74BFB9A8B5654A694D0D2BC249E03CF2509013";
+
+fn hub() -> Chain {
+    "cosmos:cosmoshub-4".parse().unwrap()
+}
+
+fn coin(denom: &str, amount: &str) -> Value {
+    json!({ "denom": denom, "amount": amount })
+}
+
+/// Alice's bank balances come in two pages.
+fn lcd(request: &MockRequest) -> MockResponse {
+    let path = request.path();
+    let Some(address) = path.rsplit('/').find(|part| part.starts_with("cosmos1")) else {
+        return MockResponse::status(501, r#"{"code":12,"message":"Not Implemented"}"#);
+    };
+    let alice = address == ALICE;
+    let body = if path.starts_with("/cosmos/bank/v1beta1/balances/") {
+        match (alice, request.query("pagination.key")) {
+            (true, None) => json!({
+                "balances": [coin("uatom", "1500000")],
+                "pagination": { "next_key": "dWF0b20=", "total": "0" },
+            }),
+            (true, Some(_)) => json!({
+                "balances": [coin(USDC, "2500000")],
+                "pagination": { "next_key": null, "total": "0" },
+            }),
+            (false, _) => json!({ "balances": [coin("uatom", "7")], "pagination": null }),
+        }
+    } else if path.starts_with("/cosmos/staking/v1beta1/delegations/") {
+        let delegations = if alice {
+            vec![json!({
+                "delegation": {
+                    "delegator_address": ALICE,
+                    "validator_address": VALIDATOR,
+                    "shares": "10000000.000000000000000000",
+                },
+                "balance": coin("uatom", "10000000"),
+            })]
+        } else {
+            vec![]
+        };
+        json!({ "delegation_responses": delegations, "pagination": { "next_key": null } })
+    } else if path.ends_with("/unbonding_delegations") {
+        let unbonding = if alice {
+            vec![json!({
+                "delegator_address": ALICE,
+                "validator_address": VALIDATOR,
+                "entries": [{
+                    "creation_height": "21000000",
+                    "completion_time": "2026-11-01T12:00:00Z",
+                    "initial_balance": "3000000",
+                    "balance": "2900000",
+                }],
+            })]
+        } else {
+            vec![]
+        };
+        json!({ "unbonding_responses": unbonding, "pagination": { "next_key": null } })
+    } else if path.ends_with("/rewards") {
+        let rewards = if alice {
+            vec![json!({
+                "validator_address": VALIDATOR,
+                "reward": [coin("uatom", "123456.789000000000000000")],
+            })]
+        } else {
+            vec![]
+        };
+        json!({ "rewards": rewards, "total": [] })
+    } else {
+        return MockResponse::status(501, r#"{"code":12,"message":"Not Implemented"}"#);
+    };
+    MockResponse::json(&body)
+}
+
+fn source(server: &MockServer) -> CosmosSource {
+    let chain = CosmosChain::known("cosmoshub-4")
+        .unwrap()
+        .with_token(USDC, "USDC", 6);
+    CosmosSource::new(LcdClient::new(server.url()), chain)
+}
+
+#[test]
+fn reads_liquid_and_staked_positions() {
+    let server = MockServer::http(lcd);
+    let source = source(&server);
+    let positions = source.positions(&[Address::new(hub(), ALICE)]).unwrap();
+    let alice = &positions[0];
+    assert_eq!(alice.address.to_string(), ALICE);
+    assert_eq!(alice.liquid.len(), 2);
+    assert_eq!(alice.delegations[0].validator, VALIDATOR);
+    assert_eq!(alice.unbonding[0].creation_height, 21_000_000);
+    assert_eq!(alice.unbonding[0].balance, Amount::from(2_900_000));
+    // The fraction of a base unit cannot be withdrawn.
+    assert_eq!(alice.rewards[0].coins[0].amount, Amount::from(123_456));
+
+    let second_page = server
+        .requests()
+        .into_iter()
+        .find(|r| r.query("pagination.key").is_some())
+        .unwrap();
+    assert_eq!(second_page.query("pagination.key"), Some("dWF0b20%3D"));
+}
+
+#[test]
+fn reports_staked_atom_apart() {
+    let server = MockServer::http(lcd);
+    let source = source(&server);
+    let bob = CosmosAddress::new("cosmos", &[2; 20]).unwrap().to_string();
+    assert_eq!(source.chain(), hub());
+
+    let balances = source
+        .balances(&[Address::new(hub(), ALICE), Address::new(hub(), bob)])
+        .unwrap();
+    let rows: Vec<String> = balances.iter().map(|b| b.to_string()).collect();
+    // Alice's 1.5 liquid, 10 delegated, 2.9 unbonding and 0.123456 in
+    // rewards, and Bob's 0.000007.
+    assert_eq!(
+        rows,
+        [
+            "1.500007 ATOM",
+            "2.5 USDC",
+            "10 ATOM staked",
+            "3.023456 ATOM pending_withdrawal",
+        ]
+    );
+    let usdc = Asset::token(hub(), USDC, "USDC", 6);
+    assert_eq!(balances[1].asset, usdc);
+    assert_eq!(balances[2].category, Category::Staked);
+}
+
+#[test]
+fn rejects_addresses_of_other_chains() {
+    let server = MockServer::http(lcd);
+    let source = source(&server);
+    let osmo = ALICE
+        .parse::<CosmosAddress>()
+        .unwrap()
+        .with_prefix("osmo")
+        .unwrap();
+    assert!(source
+        .balances(&[Address::new(hub(), osmo.to_string())])
+        .is_err());
+    assert!(source
+        .balances(&[Address::new(Chain::ETHEREUM, ALICE)])
+        .is_err());
+    assert!(server.requests().is_empty());
+}
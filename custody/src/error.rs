@@ -0,0 +1,60 @@
+use bitcoin::bip32::Fingerprint;
+use bitcoin::Amount;
+
+use crate::PolicyViolation;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid vault `{vault}`: {reason}")]
+    InvalidVault { vault: String, reason: String },
+
+    #[error(transparent)]
+    Policy(#[from] PolicyViolation),
+
+    #[error("invalid spend: {0}")]
+    InvalidSpend(String),
+
+    #[error("insufficient funds: need {needed}, vault has {available} confirmed")]
+    InsufficientFunds { needed: Amount, available: Amount },
+
+    #[error("invalid PSBT: {0}")]
+    Psbt(String),
+
+    #[error("{have} of {need} required signatures")]
+    NotEnoughSignatures { have: usize, need: usize },
+
+    #[error("signer {0} is not a cosigner of this vault")]
+    UnknownSigner(Fingerprint),
+
+    #[error("keystore: {0}")]
+    Keystore(String),
+
+    #[error("wrong keystore password")]
+    WrongPassword,
+
+    #[error(transparent)]
+    Bip32(#[from] bitcoin::bip32::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Bitcoin(#[from] walletb_bitcoin::Error),
+
+    #[error(transparent)]
+    Core(#[from] walletb_core::Error),
+}
+
+impl Error {
+    pub(crate) fn invalid_vault(vault: &str, reason: impl Into<String>) -> Self {
+        Error::InvalidVault {
+            vault: vault.to_owned(),
+            reason: reason.into(),
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
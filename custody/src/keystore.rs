@@ -0,0 +1,345 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use bip39::Mnemonic;
+use bitcoin::bip32::{DerivationPath, Fingerprint, Xpriv, Xpub};
+use bitcoin::psbt::Psbt;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{Network, NetworkKind};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{Cosigner, Error, KeyOrigin, Result, Signer};
+
+const FORMAT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const SEED_LEN: usize = 64;
+
+/// Argon2id cost settings for deriving the encryption key from a password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+/// The OWASP recommended minimum for Argon2id: 19 MiB, two passes.
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive_key(&self, password: &str, salt: &[u8]) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+        let params = Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(KEY_LEN),
+        )
+        .map_err(|e| Error::Keystore(format!("invalid key derivation parameters: {e}")))?;
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), salt, key.as_mut())
+            .map_err(|e| Error::Keystore(format!("key derivation failed: {e}")))?;
+        Ok(key)
+    }
+}
+
+/// A BIP39 seed encrypted at rest.
+///
+/// The seed is sealed with XChaCha20-Poly1305 under a key derived from a
+/// password with Argon2id. The network and master fingerprint are stored
+/// in the clear, so a keystore can be matched to a vault cosigner without
+/// the password, and are bound to the ciphertext as associated data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keystore {
+    version: u32,
+    name: String,
+    network: Network,
+    fingerprint: Fingerprint,
+    kdf: KdfParams,
+    #[serde(with = "hex_bytes")]
+    salt: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    nonce: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    ciphertext: Vec<u8>,
+}
+
+impl Keystore {
+    /// Encrypts the seed of `mnemonic` and the optional BIP39 `passphrase`
+    /// under `password`.
+    pub fn create(
+        name: impl Into<String>,
+        mnemonic: &Mnemonic,
+        passphrase: &str,
+        network: Network,
+        password: &str,
+        kdf: KdfParams,
+    ) -> Result<Self> {
+        let seed = Zeroizing::new(mnemonic.to_seed(passphrase));
+        // Held by a signer only so that its drop wipes the key.
+        let signer = SeedSigner {
+            master: master_key(network, seed.as_ref())?,
+        };
+        let fingerprint = Signer::fingerprint(&signer);
+        drop(signer);
+
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut keystore = Keystore {
+            version: FORMAT_VERSION,
+            name: name.into(),
+            network,
+            fingerprint,
+            kdf,
+            salt,
+            nonce: nonce.to_vec(),
+            ciphertext: Vec::new(),
+        };
+        let key = kdf.derive_key(password, &keystore.salt)?;
+        keystore.ciphertext = XChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: seed.as_ref(),
+                    aad: keystore.associated_data().as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Keystore("encryption failed".into()))?;
+        Ok(keystore)
+    }
+
+    /// Generates a new 24 word mnemonic and stores its seed. The mnemonic
+    /// is returned once so it can be backed up.
+    pub fn generate(
+        name: impl Into<String>,
+        network: Network,
+        password: &str,
+        kdf: KdfParams,
+    ) -> Result<(Self, Mnemonic)> {
+        let mut entropy = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(entropy.as_mut());
+        let mnemonic =
+            Mnemonic::from_entropy(entropy.as_ref()).map_err(|e| Error::Keystore(e.to_string()))?;
+        let keystore = Keystore::create(name, &mnemonic, "", network, password, kdf)?;
+        Ok((keystore, mnemonic))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let keystore: Keystore = serde_json::from_slice(&fs::read(path)?)?;
+        if keystore.version != FORMAT_VERSION {
+            return Err(Error::Keystore(format!(
+                "unsupported keystore version {}",
+                keystore.version
+            )));
+        }
+        Ok(keystore)
+    }
+
+    /// Writes the keystore to `path`, readable only by the owner on Unix.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        Ok(file.sync_all()?)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    /// Decrypts the seed. Fails with [`Error::WrongPassword`] if `password`
+    /// is wrong or the file was tampered with.
+    pub fn unlock(&self, password: &str) -> Result<SeedSigner> {
+        if self.nonce.len() != 24 {
+            return Err(Error::Keystore("nonce must be 24 bytes".into()));
+        }
+        let key = self.kdf.derive_key(password, &self.salt)?;
+        let seed = Zeroizing::new(
+            XChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+                .decrypt(
+                    XNonce::from_slice(&self.nonce),
+                    Payload {
+                        msg: &self.ciphertext,
+                        aad: self.associated_data().as_bytes(),
+                    },
+                )
+                .map_err(|_| Error::WrongPassword)?,
+        );
+        if seed.len() != SEED_LEN {
+            return Err(Error::Keystore("seed must be 64 bytes".into()));
+        }
+        // Wrapped before the check, so a mismatched key is wiped as well.
+        let signer = SeedSigner {
+            master: master_key(self.network, &seed)?,
+        };
+        if Signer::fingerprint(&signer) != self.fingerprint {
+            return Err(Error::Keystore("seed does not match fingerprint".into()));
+        }
+        Ok(signer)
+    }
+
+    fn associated_data(&self) -> String {
+        format!(
+            "walletb-keystore/{}/{}/{}",
+            self.version, self.network, self.fingerprint
+        )
+    }
+}
+
+/// The master key of an unlocked [`Keystore`]. The key is wiped when the
+/// signer is dropped.
+pub struct SeedSigner {
+    master: Xpriv,
+}
+
+impl SeedSigner {
+    pub fn network(&self) -> NetworkKind {
+        self.master.network
+    }
+
+    /// The cosigner entry for this key at account `path`, e.g.
+    /// `48'/0'/0'/2'` for a BIP48 native segwit multisig account.
+    pub fn cosigner(&self, name: impl Into<String>, path: &DerivationPath) -> Result<Cosigner> {
+        let secp = Secp256k1::new();
+        let mut account = self.master.derive_priv(&secp, path)?;
+        let xpub = Xpub::from_priv(&secp, &account);
+        wipe(&mut account);
+        Ok(Cosigner::new(
+            name,
+            KeyOrigin::new(self.master.fingerprint(&secp), path.clone()),
+            xpub,
+        ))
+    }
+}
+
+impl Signer for SeedSigner {
+    fn fingerprint(&self) -> Fingerprint {
+        Signer::fingerprint(&self.master)
+    }
+
+    fn sign_psbt(&self, psbt: &mut Psbt) -> Result<usize> {
+        self.master.sign_psbt(psbt)
+    }
+}
+
+impl Drop for SeedSigner {
+    fn drop(&mut self) {
+        wipe(&mut self.master);
+    }
+}
+
+/// Overwrites the private key and chain code of `key`.
+fn wipe(key: &mut Xpriv) {
+    key.private_key.non_secure_erase();
+    let chain_code: &mut [u8; 32] = key.chain_code.as_mut();
+    chain_code.zeroize();
+}
+
+/// Hides the key material from debug output.
+impl std::fmt::Debug for SeedSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SeedSigner")
+            .field("fingerprint", &Signer::fingerprint(self))
+            .finish_non_exhaustive()
+    }
+}
+
+fn master_key(network: Network, seed: &[u8]) -> Result<Xpriv> {
+    Ok(Xpriv::new_master(NetworkKind::from(network), seed)?)
+}
+
+mod hex_bytes {
+    use bitcoin::hex::{DisplayHex, FromHex};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&bytes.as_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Vec::from_hex(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    /// Cheap parameters; the defaults take a noticeable time per test.
+    const FAST: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn round_trips_through_json_and_password() {
+        let mnemonic = Mnemonic::parse(MNEMONIC).unwrap();
+        let keystore =
+            Keystore::create("hot", &mnemonic, "", Network::Bitcoin, "hunter2", FAST).unwrap();
+        // Master fingerprint of the BIP39 test vector.
+        assert_eq!(keystore.fingerprint().to_string(), "73c5da0a");
+
+        let json = serde_json::to_string(&keystore).unwrap();
+        assert!(!json.contains("abandon"));
+        let loaded: Keystore = serde_json::from_str(&json).unwrap();
+        let signer = loaded.unlock("hunter2").unwrap();
+        assert_eq!(Signer::fingerprint(&signer), keystore.fingerprint());
+        assert!(matches!(
+            loaded.unlock("hunter3"),
+            Err(Error::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn passphrase_changes_the_wallet() {
+        let mnemonic = Mnemonic::parse(MNEMONIC).unwrap();
+        let plain = Keystore::create("a", &mnemonic, "", Network::Bitcoin, "pw", FAST).unwrap();
+        let salted =
+            Keystore::create("b", &mnemonic, "TREZOR", Network::Bitcoin, "pw", FAST).unwrap();
+        assert_ne!(plain.fingerprint(), salted.fingerprint());
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let (keystore, mnemonic) = Keystore::generate("new", Network::Testnet, "pw", FAST).unwrap();
+        assert_eq!(mnemonic.word_count(), 24);
+
+        let mut moved = keystore.clone();
+        moved.network = Network::Bitcoin;
+        assert!(matches!(moved.unlock("pw"), Err(Error::WrongPassword)));
+
+        let mut flipped = keystore;
+        flipped.ciphertext[0] ^= 1;
+        assert!(matches!(flipped.unlock("pw"), Err(Error::WrongPassword)));
+    }
+}
@@ -0,0 +1,36 @@
+//! Custody arrangements for walletb: the vaults funds are held in and the
+//! policies that govern spending from them.
+//!
+//! A [`Vault`] is an M-of-N set of [`Cosigner`]s, each identified by the
+//! origin of its key, plus a [`SpendingPolicy`]. The vault's watch-only
+//! descriptor is derived from the cosigners, so balances can be read with
+//! any [`walletb_bitcoin`] backend and reported per vault with
+//! [`vault_balances`].
+//!
+//! Spending goes through PSBTs: [`build_spend`] selects vault coins and
+//! returns a [`VaultPsbt`] that is exported to each cosigner's signer,
+//! combined as signatures come back and finalized once the quorum and the
+//! policy are satisfied. Both BIP174 and BIP370 encodings are supported.
+//!
+//! For hot and warm signing without an external wallet, a [`Keystore`]
+//! keeps a BIP39 seed encrypted on disk; once unlocked it is a [`Signer`]
+//! that [`VaultPsbt::sign`] accepts.
+
+mod error;
+mod keystore;
+mod policy;
+mod psbt;
+mod psbt_v2;
+mod report;
+mod signer;
+mod spend;
+mod vault;
+
+pub use error::{Error, Result};
+pub use keystore::{KdfParams, Keystore, SeedSigner};
+pub use policy::{PolicyViolation, SpendingPolicy};
+pub use psbt::{PsbtVersion, SigningStatus, VaultPsbt};
+pub use report::{vault_balances, VaultBalance};
+pub use signer::Signer;
+pub use spend::{build_spend, Payment, SpendRequest};
+pub use vault::{Cosigner, KeyOrigin, Vault, VaultScript, MAX_COSIGNERS};
@@ -0,0 +1,143 @@
+use bitcoin::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Rules a spend from a vault must satisfy on top of reaching the signing
+/// threshold. Every rule is optional; the default policy allows anything
+/// the quorum signs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpendingPolicy {
+    /// Largest amount one transaction may send out of the vault, change
+    /// excluded.
+    #[serde(with = "bitcoin::amount::serde::as_sat::opt")]
+    pub max_per_transaction: Option<Amount>,
+    /// Largest amount that may leave the vault in any 24 hour window.
+    #[serde(with = "bitcoin::amount::serde::as_sat::opt")]
+    pub daily_limit: Option<Amount>,
+    /// If not empty, the only addresses the vault may pay to.
+    pub allowed_destinations: Vec<String>,
+    /// Cosigners, by name, who must sign every spend.
+    pub required_cosigners: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PolicyViolation {
+    #[error("spend of {amount} exceeds the per-transaction limit of {limit}")]
+    TransactionLimit { amount: Amount, limit: Amount },
+
+    #[error("spend of {amount} after {spent} today exceeds the daily limit of {limit}")]
+    DailyLimit {
+        amount: Amount,
+        spent: Amount,
+        limit: Amount,
+    },
+
+    #[error("destination `{0}` is not on the vault's allow list")]
+    DestinationNotAllowed(String),
+
+    #[error("required cosigner `{0}` has not signed")]
+    MissingCosigner(String),
+}
+
+impl SpendingPolicy {
+    /// Checks the external outputs of a proposed spend. `spent_last_24h` is
+    /// what already left the vault in the current window.
+    pub fn check_outputs(
+        &self,
+        outputs: &[(String, Amount)],
+        spent_last_24h: Amount,
+    ) -> Result<(), PolicyViolation> {
+        if !self.allowed_destinations.is_empty() {
+            if let Some((address, _)) = outputs
+                .iter()
+                .find(|(address, _)| !self.allowed_destinations.contains(address))
+            {
+                return Err(PolicyViolation::DestinationNotAllowed(address.clone()));
+            }
+        }
+        let amount: Amount = outputs.iter().map(|(_, value)| *value).sum();
+        if let Some(limit) = self.max_per_transaction {
+            if amount > limit {
+                return Err(PolicyViolation::TransactionLimit { amount, limit });
+            }
+        }
+        if let Some(limit) = self.daily_limit {
+            if spent_last_24h + amount > limit {
+                return Err(PolicyViolation::DailyLimit {
+                    amount,
+                    spent: spent_last_24h,
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every required cosigner is among `signed_by`.
+    pub fn check_signers<S: AsRef<str>>(&self, signed_by: &[S]) -> Result<(), PolicyViolation> {
+        match self
+            .required_cosigners
+            .iter()
+            .find(|required| !signed_by.iter().any(|s| s.as_ref() == required.as_str()))
+        {
+            Some(missing) => Err(PolicyViolation::MissingCosigner(missing.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn out(address: &str, sats: u64) -> (String, Amount) {
+        (address.to_owned(), Amount::from_sat(sats))
+    }
+
+    #[test]
+    fn default_policy_allows_everything() {
+        let policy = SpendingPolicy::default();
+        assert!(policy
+            .check_outputs(&[out("anywhere", u64::MAX / 2)], Amount::ZERO)
+            .is_ok());
+        assert!(policy.check_signers::<&str>(&[]).is_ok());
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let policy = SpendingPolicy {
+            max_per_transaction: Some(Amount::from_sat(1_000)),
+            daily_limit: Some(Amount::from_sat(1_500)),
+            ..Default::default()
+        };
+        assert!(policy
+            .check_outputs(&[out("a", 600), out("b", 400)], Amount::ZERO)
+            .is_ok());
+        assert!(matches!(
+            policy.check_outputs(&[out("a", 1_001)], Amount::ZERO),
+            Err(PolicyViolation::TransactionLimit { .. })
+        ));
+        assert!(matches!(
+            policy.check_outputs(&[out("a", 600)], Amount::from_sat(1_000)),
+            Err(PolicyViolation::DailyLimit { .. })
+        ));
+    }
+
+    #[test]
+    fn enforces_allow_list_and_required_signers() {
+        let policy = SpendingPolicy {
+            allowed_destinations: vec!["exchange".into()],
+            required_cosigners: vec!["cfo".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.check_outputs(&[out("exchange", 1), out("elsewhere", 1)], Amount::ZERO),
+            Err(PolicyViolation::DestinationNotAllowed("elsewhere".into()))
+        );
+        assert!(policy.check_signers(&["ceo", "cfo"]).is_ok());
+        assert_eq!(
+            policy.check_signers(&["ceo"]),
+            Err(PolicyViolation::MissingCosigner("cfo".into()))
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use bitcoin::base64::prelude::{Engine, BASE64_STANDARD};
+use bitcoin::bip32::ChildNumber;
+use bitcoin::psbt::Psbt;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{Address, Amount, Transaction};
+use serde::{Deserialize, Serialize};
+use walletb_bitcoin::miniscript::psbt::PsbtExt;
+use walletb_bitcoin::{Keychain, Keychains};
+
+use crate::{psbt_v2, Error, Result, Signer, Vault};
+
+/// PSBT serialization versions understood by [`VaultPsbt`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PsbtVersion {
+    /// BIP174, accepted by every signer.
+    #[default]
+    V0,
+    /// BIP370, which drops the global unsigned transaction.
+    V2,
+}
+
+impl FromStr for PsbtVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "0" | "v0" | "bip174" => Ok(PsbtVersion::V0),
+            "2" | "v2" | "bip370" => Ok(PsbtVersion::V2),
+            _ => Err(Error::Psbt(format!("unknown PSBT version `{s}`"))),
+        }
+    }
+}
+
+/// How far a vault spend is from being broadcastable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SigningStatus {
+    pub threshold: usize,
+    /// Fewest cosigner signatures on any input.
+    pub signatures: usize,
+    /// Cosigners who have signed every input, in vault order.
+    pub signed_by: Vec<String>,
+}
+
+impl SigningStatus {
+    pub fn is_complete(&self) -> bool {
+        self.signatures >= self.threshold
+    }
+}
+
+/// A spend from a vault on its way through the cosigners.
+///
+/// Created by [`build_spend`](crate::build_spend) or imported from a signer.
+/// Cosigners either [`sign`] locally or sign an exported copy that is
+/// merged back with [`combine`]. Once the quorum has signed and the vault
+/// policy is met, [`finalize`] turns it into a transaction. The policy is
+/// checked again there, as an imported PSBT need not come from
+/// [`build_spend`](crate::build_spend).
+///
+/// [`sign`]: VaultPsbt::sign
+/// [`combine`]: VaultPsbt::combine
+/// [`finalize`]: VaultPsbt::finalize
+#[derive(Debug, Clone, PartialEq)]
+pub struct VaultPsbt {
+    vault: Vault,
+    psbt: Psbt,
+}
+
+impl VaultPsbt {
+    pub fn new(vault: Vault, psbt: Psbt) -> Self {
+        VaultPsbt { vault, psbt }
+    }
+
+    /// Parses a base64 PSBT of either version for `vault`.
+    pub fn import(vault: Vault, base64: &str) -> Result<Self> {
+        let psbt = parse_psbt(base64)?;
+        Ok(VaultPsbt { vault, psbt })
+    }
+
+    /// The base64 encoding of the PSBT, to hand to a signer.
+    pub fn export(&self, version: PsbtVersion) -> Result<String> {
+        match version {
+            PsbtVersion::V0 => Ok(self.psbt.to_string()),
+            PsbtVersion::V2 => Ok(BASE64_STANDARD.encode(psbt_v2::serialize_v2(&self.psbt)?)),
+        }
+    }
+
+    pub fn vault(&self) -> &Vault {
+        &self.vault
+    }
+
+    pub fn psbt(&self) -> &Psbt {
+        &self.psbt
+    }
+
+    pub fn psbt_mut(&mut self) -> &mut Psbt {
+        &mut self.psbt
+    }
+
+    pub fn into_psbt(self) -> Psbt {
+        self.psbt
+    }
+
+    pub fn fee(&self) -> Result<Amount> {
+        self.psbt.fee().map_err(|e| Error::Psbt(e.to_string()))
+    }
+
+    /// Merges the signatures from a cosigner's copy of the same spend.
+    pub fn combine(&mut self, other: Psbt) -> Result<()> {
+        self.psbt
+            .combine(other)
+            .map_err(|e| Error::Psbt(e.to_string()))
+    }
+
+    /// Adds `signer`'s signatures, returning how many were added. The
+    /// signer must be one of the vault's cosigners.
+    pub fn sign<S: Signer + ?Sized>(&mut self, signer: &S) -> Result<usize> {
+        let fingerprint = signer.fingerprint();
+        if self.vault.cosigner_by_fingerprint(fingerprint).is_none() {
+            return Err(Error::UnknownSigner(fingerprint));
+        }
+        signer.sign_psbt(&mut self.psbt)
+    }
+
+    /// Parses and merges a base64 PSBT returned by a cosigner.
+    pub fn combine_base64(&mut self, base64: &str) -> Result<()> {
+        self.combine(parse_psbt(base64)?)
+    }
+
+    pub fn status(&self) -> SigningStatus {
+        let mut signatures: Option<usize> = None;
+        let mut signed_everywhere: Option<BTreeSet<usize>> = None;
+        for input in &self.psbt.inputs {
+            let signers: BTreeSet<usize> = input
+                .partial_sigs
+                .keys()
+                .filter_map(|key| input.bip32_derivation.get(&key.inner))
+                .filter_map(|(fingerprint, _)| {
+                    self.vault
+                        .cosigners()
+                        .iter()
+                        .position(|c| c.origin.fingerprint == *fingerprint)
+                })
+                .collect();
+            signatures = Some(signatures.map_or(signers.len(), |n| n.min(signers.len())));
+            signed_everywhere = Some(match signed_everywhere {
+                Some(seen) => seen.intersection(&signers).copied().collect(),
+                None => signers,
+            });
+        }
+        let cosigners = self.vault.cosigners();
+        SigningStatus {
+            threshold: self.vault.threshold(),
+            signatures: signatures.unwrap_or(0),
+            signed_by: signed_everywhere
+                .unwrap_or_default()
+                .into_iter()
+                .map(|i| cosigners[i].name.clone())
+                .collect(),
+        }
+    }
+
+    /// The outputs that leave the vault, by address: all but those paying
+    /// one of its change addresses. An output counts as change only if the
+    /// vault derives its script at the index its key origins name, so a
+    /// payment cannot pass for change.
+    pub fn external_outputs(&self) -> Result<Vec<(String, Amount)>> {
+        let descriptor = self.vault.descriptor()?;
+        let mut external = Vec::new();
+        for (txout, output) in self.psbt.unsigned_tx.output.iter().zip(&self.psbt.outputs) {
+            let mut indexes: Vec<u32> = output
+                .bip32_derivation
+                .values()
+                .filter_map(|(_, path)| match path.as_ref().last() {
+                    Some(ChildNumber::Normal { index }) => Some(*index),
+                    _ => None,
+                })
+                .collect();
+            indexes.sort_unstable();
+            indexes.dedup();
+            let mut change = false;
+            for index in indexes {
+                if descriptor
+                    .derive(Keychain::Internal, index)?
+                    .script_pubkey()
+                    == txout.script_pubkey
+                {
+                    change = true;
+                    break;
+                }
+            }
+            if !change {
+                let address = Address::from_script(&txout.script_pubkey, self.vault.network())
+                    .map(|address| address.to_string())
+                    .unwrap_or_else(|_| txout.script_pubkey.to_hex_string());
+                external.push((address, txout.value));
+            }
+        }
+        Ok(external)
+    }
+
+    /// Finalizes every input and extracts the signed transaction.
+    ///
+    /// Fails unless each input carries at least the vault threshold of
+    /// signatures, every cosigner the policy requires has signed and the
+    /// [`external_outputs`](Self::external_outputs) keep to the policy's
+    /// limits and allow list. `spent_last_24h` is what already left the
+    /// vault in the current window. The signatures are verified by running
+    /// the finalized scripts.
+    pub fn finalize(mut self, spent_last_24h: Amount) -> Result<Transaction> {
+        let status = self.status();
+        if !status.is_complete() {
+            return Err(Error::NotEnoughSignatures {
+                have: status.signatures,
+                need: status.threshold,
+            });
+        }
+        let policy = self.vault.policy();
+        policy.check_signers(&status.signed_by)?;
+        policy.check_outputs(&self.external_outputs()?, spent_last_24h)?;
+        let secp = Secp256k1::verification_only();
+        self.psbt.finalize_mut(&secp).map_err(|errors| {
+            let reasons: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            Error::Psbt(reasons.join("; "))
+        })?;
+        self.psbt
+            .extract(&secp)
+            .map_err(|e| Error::Psbt(e.to_string()))
+    }
+}
+
+fn parse_psbt(base64: &str) -> Result<Psbt> {
+    let bytes = BASE64_STANDARD
+        .decode(base64.trim())
+        .map_err(|e| Error::Psbt(format!("invalid base64: {e}")))?;
+    match psbt_v2::version(&bytes)? {
+        0 => Psbt::deserialize(&bytes).map_err(|e| Error::Psbt(e.to_string())),
+        2 => psbt_v2::deserialize_v2(&bytes),
+        other => Err(Error::Psbt(format!("unsupported PSBT version {other}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{OutPoint, ScriptBuf, Sequence, TxIn, TxOut, Txid, Witness};
+
+    use super::*;
+
+    fn psbt() -> Psbt {
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::from_consensus(800_000),
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_byte_array([7; 32]), 3),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(12_345),
+                script_pubkey: ScriptBuf::from_bytes(vec![0x00, 0x14, 0xab]),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(20_000),
+            script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+        });
+        psbt
+    }
+
+    #[test]
+    fn converts_between_v0_and_v2() {
+        let original = psbt();
+        let v2 = psbt_v2::serialize_v2(&original).unwrap();
+        assert_eq!(psbt_v2::version(&v2).unwrap(), 2);
+        assert_eq!(psbt_v2::version(&original.serialize()).unwrap(), 0);
+        assert_eq!(psbt_v2::deserialize_v2(&v2).unwrap(), original);
+        assert!(psbt_v2::deserialize_v2(&original.serialize()).is_err());
+    }
+
+    #[test]
+    fn parses_either_version_from_base64() {
+        let original = psbt();
+        assert_eq!(parse_psbt(&original.to_string()).unwrap(), original);
+        let v2 = BASE64_STANDARD.encode(psbt_v2::serialize_v2(&original).unwrap());
+        assert_eq!(parse_psbt(&v2).unwrap(), original);
+        assert!(parse_psbt("not base64!").is_err());
+        assert_eq!("bip370".parse::<PsbtVersion>().unwrap(), PsbtVersion::V2);
+    }
+}
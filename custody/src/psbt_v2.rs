@@ -0,0 +1,309 @@
+//! Conversion between BIP174 (version 0) and BIP370 (version 2) PSBTs.
+//!
+//! rust-bitcoin only models version 0, where the unsigned transaction sits
+//! in the global map. Version 2 drops that field and spreads the same data
+//! over per-input and per-output fields instead. Every other field has the
+//! same encoding in both versions, so the conversion is done on the raw
+//! key-value maps and leaves unknown and proprietary fields untouched.
+
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hashes::Hash;
+use bitcoin::psbt::Psbt;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+
+use crate::{Error, Result};
+
+const MAGIC: &[u8] = b"psbt\xff";
+
+const GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const GLOBAL_TX_VERSION: u8 = 0x02;
+const GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+const GLOBAL_INPUT_COUNT: u8 = 0x04;
+const GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+const GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+const GLOBAL_VERSION: u8 = 0xfb;
+
+const IN_PREVIOUS_TXID: u8 = 0x0e;
+const IN_OUTPUT_INDEX: u8 = 0x0f;
+const IN_SEQUENCE: u8 = 0x10;
+const IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+const IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+
+const OUT_AMOUNT: u8 = 0x03;
+const OUT_SCRIPT: u8 = 0x04;
+
+/// One key-value pair. The key includes its type byte.
+type Pair = (Vec<u8>, Vec<u8>);
+
+/// A PSBT split into its maps, without interpreting them.
+struct RawPsbt {
+    global: Vec<Pair>,
+    inputs: Vec<Vec<Pair>>,
+    outputs: Vec<Vec<Pair>>,
+}
+
+/// The version declared in the global map; 0 when absent.
+pub(crate) fn version(bytes: &[u8]) -> Result<u32> {
+    let mut reader = Reader::new(bytes)?;
+    let global = reader.map()?;
+    match find(&global, GLOBAL_VERSION) {
+        Some(value) => read_u32(value),
+        None => Ok(0),
+    }
+}
+
+/// Serializes `psbt` as a version 2 PSBT.
+pub(crate) fn serialize_v2(psbt: &Psbt) -> Result<Vec<u8>> {
+    let v0 = psbt.serialize();
+    let mut reader = Reader::new(&v0)?;
+    let global = reader.map()?;
+    let tx = &psbt.unsigned_tx;
+    let mut raw = RawPsbt {
+        global,
+        inputs: (0..tx.input.len())
+            .map(|_| reader.map())
+            .collect::<Result<_>>()?,
+        outputs: (0..tx.output.len())
+            .map(|_| reader.map())
+            .collect::<Result<_>>()?,
+    };
+
+    raw.global
+        .retain(|(key, _)| key[0] != GLOBAL_UNSIGNED_TX && key[0] != GLOBAL_VERSION);
+    raw.global.extend([
+        pair(GLOBAL_TX_VERSION, tx.version.0.to_le_bytes().to_vec()),
+        pair(
+            GLOBAL_FALLBACK_LOCKTIME,
+            tx.lock_time.to_consensus_u32().to_le_bytes().to_vec(),
+        ),
+        pair(GLOBAL_INPUT_COUNT, compact_size(tx.input.len() as u64)),
+        pair(GLOBAL_OUTPUT_COUNT, compact_size(tx.output.len() as u64)),
+        pair(GLOBAL_VERSION, 2u32.to_le_bytes().to_vec()),
+    ]);
+    for (map, input) in raw.inputs.iter_mut().zip(&tx.input) {
+        map.extend([
+            pair(
+                IN_PREVIOUS_TXID,
+                input.previous_output.txid.to_byte_array().to_vec(),
+            ),
+            pair(
+                IN_OUTPUT_INDEX,
+                input.previous_output.vout.to_le_bytes().to_vec(),
+            ),
+            pair(IN_SEQUENCE, input.sequence.0.to_le_bytes().to_vec()),
+        ]);
+    }
+    for (map, output) in raw.outputs.iter_mut().zip(&tx.output) {
+        map.extend([
+            pair(OUT_AMOUNT, output.value.to_sat().to_le_bytes().to_vec()),
+            pair(OUT_SCRIPT, output.script_pubkey.to_bytes()),
+        ]);
+    }
+    Ok(raw.serialize())
+}
+
+/// Parses a version 2 PSBT into rust-bitcoin's version 0 model.
+pub(crate) fn deserialize_v2(bytes: &[u8]) -> Result<Psbt> {
+    let mut reader = Reader::new(bytes)?;
+    let global = reader.map()?;
+    if find(&global, GLOBAL_VERSION).map(read_u32).transpose()? != Some(2) {
+        return Err(invalid("not a version 2 PSBT"));
+    }
+    let tx_version = read_u32(required(&global, GLOBAL_TX_VERSION)?)? as i32;
+    let fallback = find(&global, GLOBAL_FALLBACK_LOCKTIME)
+        .map(read_u32)
+        .transpose()?
+        .unwrap_or(0);
+    let input_count = read_compact_size(required(&global, GLOBAL_INPUT_COUNT)?)?;
+    let output_count = read_compact_size(required(&global, GLOBAL_OUTPUT_COUNT)?)?;
+    let mut raw = RawPsbt {
+        global,
+        inputs: (0..input_count)
+            .map(|_| reader.map())
+            .collect::<Result<_>>()?,
+        outputs: (0..output_count)
+            .map(|_| reader.map())
+            .collect::<Result<_>>()?,
+    };
+
+    let mut input = Vec::with_capacity(raw.inputs.len());
+    let (mut height_lock, mut time_lock) = (None::<u32>, None::<u32>);
+    for map in &raw.inputs {
+        let txid: [u8; 32] = required(map, IN_PREVIOUS_TXID)?
+            .try_into()
+            .map_err(|_| invalid("previous txid is not 32 bytes"))?;
+        let vout = read_u32(required(map, IN_OUTPUT_INDEX)?)?;
+        let sequence = find(map, IN_SEQUENCE)
+            .map(read_u32)
+            .transpose()?
+            .unwrap_or(u32::MAX);
+        if let Some(height) = find(map, IN_REQUIRED_HEIGHT_LOCKTIME) {
+            height_lock = height_lock.max(Some(read_u32(height)?));
+        }
+        if let Some(time) = find(map, IN_REQUIRED_TIME_LOCKTIME) {
+            time_lock = time_lock.max(Some(read_u32(time)?));
+        }
+        input.push(TxIn {
+            previous_output: OutPoint::new(Txid::from_byte_array(txid), vout),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence(sequence),
+            witness: Witness::new(),
+        });
+    }
+    let mut output = Vec::with_capacity(raw.outputs.len());
+    for map in &raw.outputs {
+        let amount: [u8; 8] = required(map, OUT_AMOUNT)?
+            .try_into()
+            .map_err(|_| invalid("output amount is not 8 bytes"))?;
+        output.push(TxOut {
+            value: Amount::from_sat(i64::from_le_bytes(amount) as u64),
+            script_pubkey: ScriptBuf::from_bytes(required(map, OUT_SCRIPT)?.to_vec()),
+        });
+    }
+    // BIP370 prefers a height lock when inputs allow either kind.
+    let lock_time = height_lock.or(time_lock).unwrap_or(fallback);
+    let tx = Transaction {
+        version: Version(tx_version),
+        lock_time: LockTime::from_consensus(lock_time),
+        input,
+        output,
+    };
+
+    raw.global.retain(|(key, _)| {
+        !matches!(
+            key[0],
+            GLOBAL_TX_VERSION
+                | GLOBAL_FALLBACK_LOCKTIME
+                | GLOBAL_INPUT_COUNT
+                | GLOBAL_OUTPUT_COUNT
+                | GLOBAL_TX_MODIFIABLE
+                | GLOBAL_VERSION
+        )
+    });
+    raw.global.push(pair(GLOBAL_UNSIGNED_TX, serialize(&tx)));
+    for map in &mut raw.inputs {
+        map.retain(|(key, _)| !(IN_PREVIOUS_TXID..=IN_REQUIRED_HEIGHT_LOCKTIME).contains(&key[0]));
+    }
+    for map in &mut raw.outputs {
+        map.retain(|(key, _)| key[0] != OUT_AMOUNT && key[0] != OUT_SCRIPT);
+    }
+    Psbt::deserialize(&raw.serialize()).map_err(|e| Error::Psbt(e.to_string()))
+}
+
+impl RawPsbt {
+    fn serialize(mut self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        for map in std::iter::once(&mut self.global)
+            .chain(&mut self.inputs)
+            .chain(&mut self.outputs)
+        {
+            map.sort();
+            for (key, value) in map.iter() {
+                out.extend(compact_size(key.len() as u64));
+                out.extend(key);
+                out.extend(compact_size(value.len() as u64));
+                out.extend(value);
+            }
+            out.push(0x00);
+        }
+        out
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        match bytes.strip_prefix(MAGIC) {
+            Some(bytes) => Ok(Reader { bytes }),
+            None => Err(invalid("missing PSBT magic bytes")),
+        }
+    }
+
+    fn map(&mut self) -> Result<Vec<Pair>> {
+        let mut pairs = Vec::new();
+        loop {
+            let key_len = self.compact_size()?;
+            if key_len == 0 {
+                return Ok(pairs);
+            }
+            let key = self.take(key_len)?.to_vec();
+            let value_len = self.compact_size()?;
+            let value = self.take(value_len)?.to_vec();
+            pairs.push((key, value));
+        }
+    }
+
+    fn compact_size(&mut self) -> Result<u64> {
+        let (value, used) = decode_compact_size(self.bytes)?;
+        self.bytes = &self.bytes[used..];
+        Ok(value)
+    }
+
+    fn take(&mut self, len: u64) -> Result<&'a [u8]> {
+        let len = usize::try_from(len).map_err(|_| invalid("field too large"))?;
+        if len > self.bytes.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+}
+
+fn pair(key_type: u8, value: Vec<u8>) -> Pair {
+    (vec![key_type], value)
+}
+
+/// The value of the field with a bare `key_type` key.
+fn find(map: &[Pair], key_type: u8) -> Option<&[u8]> {
+    map.iter()
+        .find(|(key, _)| key.as_slice() == [key_type])
+        .map(|(_, value)| value.as_slice())
+}
+
+fn required(map: &[Pair], key_type: u8) -> Result<&[u8]> {
+    find(map, key_type).ok_or_else(|| invalid(format!("missing field {key_type:#04x}")))
+}
+
+fn read_u32(value: &[u8]) -> Result<u32> {
+    let bytes: [u8; 4] = value
+        .try_into()
+        .map_err(|_| invalid("expected a 4 byte integer"))?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_compact_size(value: &[u8]) -> Result<u64> {
+    let (n, used) = decode_compact_size(value)?;
+    if used != value.len() {
+        return Err(invalid("trailing bytes after count"));
+    }
+    Ok(n)
+}
+
+fn compact_size(n: u64) -> Vec<u8> {
+    serialize(&bitcoin::VarInt(n))
+}
+
+fn decode_compact_size(bytes: &[u8]) -> Result<(u64, usize)> {
+    let used = match bytes.first() {
+        None => return Err(invalid("unexpected end of data")),
+        Some(0xfd) => 3,
+        Some(0xfe) => 5,
+        Some(0xff) => 9,
+        Some(_) => 1,
+    };
+    let bytes = bytes
+        .get(..used)
+        .ok_or_else(|| invalid("unexpected end of data"))?;
+    let n: bitcoin::VarInt = deserialize(bytes).map_err(|e| invalid(e.to_string()))?;
+    Ok((n.0, used))
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::Psbt(reason.into())
+}
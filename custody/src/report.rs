@@ -0,0 +1,60 @@
+use serde::Serialize;
+use walletb_bitcoin::{BitcoinSource, Keychain, UtxoBackend};
+use walletb_core::{Balance, BalanceSource, Category};
+
+use crate::{Result, Vault};
+
+/// The holdings of one vault.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VaultBalance {
+    pub vault_id: String,
+    pub vault_name: String,
+    pub quorum: String,
+    pub balances: Vec<Balance>,
+    /// Number of addresses with activity across both keychains.
+    pub used_addresses: usize,
+    /// Index of the next unused receive address.
+    pub next_receive_index: u32,
+}
+
+impl VaultBalance {
+    /// Balances the vault's quorum can spend now.
+    pub fn spendable(&self) -> impl Iterator<Item = &Balance> {
+        self.balances
+            .iter()
+            .filter(|b| b.category == Category::Available)
+    }
+
+    /// Balances held but not spendable yet: staked, locked or pending
+    /// withdrawal.
+    pub fn unspendable(&self) -> impl Iterator<Item = &Balance> {
+        self.balances
+            .iter()
+            .filter(|b| b.category != Category::Available)
+    }
+}
+
+/// Reads the balance of each vault through `source`, discovering vault
+/// addresses up to `gap_limit`.
+pub fn vault_balances<B: UtxoBackend>(
+    vaults: &[Vault],
+    source: &BitcoinSource<B>,
+    gap_limit: u32,
+) -> Result<Vec<VaultBalance>> {
+    vaults
+        .iter()
+        .map(|vault| {
+            let descriptor = vault.descriptor()?;
+            let discovery = source.discover(&descriptor, gap_limit)?;
+            let balances = source.balances(&discovery.used_addresses())?;
+            Ok(VaultBalance {
+                vault_id: vault.id().to_owned(),
+                vault_name: vault.name().to_owned(),
+                quorum: vault.quorum(),
+                balances,
+                used_addresses: discovery.used().count(),
+                next_receive_index: discovery.next_index(Keychain::External),
+            })
+        })
+        .collect()
+}
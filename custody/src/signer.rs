@@ -0,0 +1,62 @@
+use bitcoin::bip32::{Fingerprint, Xpriv};
+use bitcoin::psbt::{Psbt, SigningKeys};
+use bitcoin::secp256k1::Secp256k1;
+
+use crate::{Error, Result};
+
+/// Something that holds a cosigner's private key and can sign PSBTs with
+/// it, such as an unlocked [`Keystore`](crate::Keystore).
+pub trait Signer {
+    /// Fingerprint of the master key, matching the cosigner's
+    /// [`KeyOrigin`](crate::KeyOrigin).
+    fn fingerprint(&self) -> Fingerprint;
+
+    /// Signs every input that has a key derived from this signer and
+    /// returns the number of signatures added.
+    fn sign_psbt(&self, psbt: &mut Psbt) -> Result<usize>;
+}
+
+impl<S: Signer + ?Sized> Signer for &S {
+    fn fingerprint(&self) -> Fingerprint {
+        (**self).fingerprint()
+    }
+
+    fn sign_psbt(&self, psbt: &mut Psbt) -> Result<usize> {
+        (**self).sign_psbt(psbt)
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for Box<S> {
+    fn fingerprint(&self) -> Fingerprint {
+        (**self).fingerprint()
+    }
+
+    fn sign_psbt(&self, psbt: &mut Psbt) -> Result<usize> {
+        (**self).sign_psbt(psbt)
+    }
+}
+
+/// Signs with a master private key, using the BIP32 derivations recorded
+/// in the PSBT to find the child keys.
+impl Signer for Xpriv {
+    fn fingerprint(&self) -> Fingerprint {
+        Xpriv::fingerprint(self, &Secp256k1::signing_only())
+    }
+
+    fn sign_psbt(&self, psbt: &mut Psbt) -> Result<usize> {
+        let signed = psbt.sign(self, &Secp256k1::new()).map_err(|(_, errors)| {
+            let reasons: Vec<String> = errors
+                .iter()
+                .map(|(input, e)| format!("input {input}: {e}"))
+                .collect();
+            Error::Psbt(reasons.join("; "))
+        })?;
+        Ok(signed
+            .values()
+            .map(|keys| match keys {
+                SigningKeys::Ecdsa(keys) => keys.len(),
+                SigningKeys::Schnorr(keys) => keys.len(),
+            })
+            .sum())
+    }
+}
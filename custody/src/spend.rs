@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::psbt::Psbt;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, FeeRate, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Weight, Witness};
+use walletb_bitcoin::miniscript::psbt::PsbtExt;
+use walletb_bitcoin::{parse_address, BitcoinSource, Keychain, Keychains, Utxo, UtxoBackend};
+
+use crate::{Error, Result, Vault, VaultPsbt};
+
+/// One output of a spend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payment {
+    pub address: String,
+    pub amount: Amount,
+}
+
+impl Payment {
+    pub fn new(address: impl Into<String>, amount: Amount) -> Self {
+        Payment {
+            address: address.into(),
+            amount,
+        }
+    }
+}
+
+/// What to pay out of a vault and at what fee rate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendRequest {
+    pub payments: Vec<Payment>,
+    pub fee_rate: FeeRate,
+    /// Amount already sent out of the vault in the current 24 hour window,
+    /// checked against the policy's daily limit.
+    pub spent_last_24h: Amount,
+}
+
+impl SpendRequest {
+    pub fn new(payments: Vec<Payment>, fee_rate: FeeRate) -> Self {
+        SpendRequest {
+            payments,
+            fee_rate,
+            spent_last_24h: Amount::ZERO,
+        }
+    }
+
+    pub fn with_spent_last_24h(mut self, spent: Amount) -> Self {
+        self.spent_last_24h = spent;
+        self
+    }
+}
+
+/// Weight of a transaction with no inputs or outputs: version, lock time,
+/// both counts and the segwit marker and flag.
+const TX_OVERHEAD: Weight = Weight::from_wu(4 * (4 + 4 + 1 + 1) + 2);
+
+/// Weight of an unsigned input: outpoint, sequence and an empty script.
+const TXIN_BASE: Weight = Weight::from_wu(4 * (36 + 4 + 1));
+
+/// A vault output that can fund the spend.
+struct Candidate {
+    utxo: Utxo,
+    keychain: Keychain,
+    index: u32,
+    /// Weight the input adds once signed.
+    weight: Weight,
+}
+
+/// Builds an unsigned PSBT paying `request` out of `vault`.
+///
+/// Vault addresses are discovered through `source` up to `gap_limit` and
+/// only confirmed outputs are spent, largest first, until the payments and
+/// fee are covered. Change goes to the vault's next unused change address
+/// unless it would be dust, in which case it is left to the fee. Inputs
+/// and the change output carry the witness script and every cosigner's
+/// key origin, which is all an external signer needs.
+pub fn build_spend<B: UtxoBackend>(
+    vault: &Vault,
+    source: &BitcoinSource<B>,
+    request: &SpendRequest,
+    gap_limit: u32,
+) -> Result<VaultPsbt> {
+    if request.payments.is_empty() {
+        return Err(Error::InvalidSpend("no payments".into()));
+    }
+    let mut outputs = Vec::with_capacity(request.payments.len() + 1);
+    for payment in &request.payments {
+        let (address, _) = parse_address(&payment.address, vault.network())?;
+        let script_pubkey = address.script_pubkey();
+        if payment.amount < script_pubkey.minimal_non_dust() {
+            return Err(Error::InvalidSpend(format!(
+                "payment of {} to {} is dust",
+                payment.amount, payment.address
+            )));
+        }
+        outputs.push(TxOut {
+            value: payment.amount,
+            script_pubkey,
+        });
+    }
+    let destinations: Vec<(String, Amount)> = request
+        .payments
+        .iter()
+        .map(|p| (p.address.clone(), p.amount))
+        .collect();
+    vault
+        .policy()
+        .check_outputs(&destinations, request.spent_last_24h)?;
+
+    let descriptor = vault.descriptor()?;
+    let discovery = source.discover(&descriptor, gap_limit)?;
+    let owners: HashMap<ScriptBuf, (Keychain, u32)> = discovery
+        .used()
+        .map(|a| (a.address.script_pubkey(), (a.keychain, a.index)))
+        .collect();
+    let scripts: Vec<ScriptBuf> = owners.keys().cloned().collect();
+    let mut candidates = Vec::new();
+    for utxo in source.backend().unspent(&scripts)? {
+        let Some(&(keychain, index)) = owners.get(&utxo.script_pubkey) else {
+            continue;
+        };
+        if !utxo.is_confirmed() {
+            continue;
+        }
+        let satisfaction = descriptor
+            .at_index(keychain, index)?
+            .max_weight_to_satisfy()
+            .map_err(|e| Error::InvalidSpend(e.to_string()))?;
+        candidates.push(Candidate {
+            utxo,
+            keychain,
+            index,
+            weight: TXIN_BASE + satisfaction,
+        });
+    }
+    candidates.sort_by(|a, b| {
+        b.utxo
+            .value
+            .cmp(&a.utxo.value)
+            .then(a.utxo.outpoint.cmp(&b.utxo.outpoint))
+    });
+
+    let change_index = discovery.next_index(Keychain::Internal);
+    let change_script = descriptor
+        .derive(Keychain::Internal, change_index)?
+        .script_pubkey();
+    let change_weight = TxOut {
+        value: Amount::ZERO,
+        script_pubkey: change_script.clone(),
+    }
+    .weight();
+
+    let target: Amount = outputs.iter().map(|o| o.value).sum();
+    let mut weight = outputs.iter().fold(TX_OVERHEAD, |w, o| w + o.weight());
+    let fee = |weight: Weight| {
+        request
+            .fee_rate
+            .fee_wu(weight)
+            .ok_or_else(|| Error::InvalidSpend("fee overflows".into()))
+    };
+    let available: Amount = candidates.iter().map(|c| c.utxo.value).sum();
+    let mut selected = 0;
+    let mut total = Amount::ZERO;
+    while total < target + fee(weight)? {
+        let Some(candidate) = candidates.get(selected) else {
+            return Err(Error::InsufficientFunds {
+                needed: target + fee(weight)?,
+                available,
+            });
+        };
+        total += candidate.utxo.value;
+        weight += candidate.weight;
+        selected += 1;
+    }
+    let inputs = &candidates[..selected];
+
+    let fee_with_change = fee(weight + change_weight)?;
+    let change = (total - target)
+        .checked_sub(fee_with_change)
+        .filter(|change| *change >= change_script.minimal_non_dust());
+    if let Some(value) = change {
+        outputs.push(TxOut {
+            value,
+            script_pubkey: change_script,
+        });
+    }
+
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|c| TxIn {
+                previous_output: c.utxo.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: outputs,
+    };
+    let mut psbt = Psbt::from_unsigned_tx(tx).map_err(|e| Error::Psbt(e.to_string()))?;
+    for cosigner in vault.cosigners() {
+        psbt.xpub.insert(
+            cosigner.xpub,
+            (cosigner.origin.fingerprint, cosigner.origin.path.clone()),
+        );
+    }
+    for (i, candidate) in inputs.iter().enumerate() {
+        psbt.inputs[i].witness_utxo = Some(TxOut {
+            value: candidate.utxo.value,
+            script_pubkey: candidate.utxo.script_pubkey.clone(),
+        });
+        let definite = descriptor.at_index(candidate.keychain, candidate.index)?;
+        psbt.update_input_with_descriptor(i, &definite)
+            .map_err(|e| Error::Psbt(e.to_string()))?;
+    }
+    if change.is_some() {
+        let definite = descriptor.at_index(Keychain::Internal, change_index)?;
+        let last = psbt.outputs.len() - 1;
+        psbt.update_output_with_descriptor(last, &definite)
+            .map_err(|e| Error::Psbt(e.to_string()))?;
+    }
+    Ok(VaultPsbt::new(vault.clone(), psbt))
+}
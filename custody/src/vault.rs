@@ -0,0 +1,367 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+use bitcoin::{Network, NetworkKind};
+use serde::{Deserialize, Serialize};
+use walletb_bitcoin::WalletDescriptor;
+
+use crate::{Error, Result, SpendingPolicy};
+
+/// Most keys `OP_CHECKMULTISIG` accepts, and so the most a `sortedmulti`
+/// vault can have.
+pub const MAX_COSIGNERS: usize = 20;
+
+/// Where a cosigner's key sits in its own wallet: the master key
+/// fingerprint and the path from it to the xpub.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeyOrigin {
+    pub fingerprint: Fingerprint,
+    pub path: DerivationPath,
+}
+
+impl KeyOrigin {
+    pub fn new(fingerprint: Fingerprint, path: DerivationPath) -> Self {
+        KeyOrigin { fingerprint, path }
+    }
+}
+
+/// Displays as a descriptor key origin, e.g. `[d34db33f/48'/0'/0'/2']`.
+impl fmt::Display for KeyOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "[{}]", self.fingerprint)
+        } else {
+            write!(f, "[{}/{}]", self.fingerprint, self.path)
+        }
+    }
+}
+
+/// One key holder in a vault.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cosigner {
+    pub name: String,
+    pub origin: KeyOrigin,
+    pub xpub: Xpub,
+}
+
+impl Cosigner {
+    pub fn new(name: impl Into<String>, origin: KeyOrigin, xpub: Xpub) -> Self {
+        Cosigner {
+            name: name.into(),
+            origin,
+            xpub,
+        }
+    }
+
+    /// The key expression used in the vault descriptor, covering both the
+    /// receive and change chains.
+    fn key_expression(&self) -> String {
+        format!("{}{}/<0;1>/*", self.origin, self.xpub)
+    }
+}
+
+/// How the vault's multisig script is wrapped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultScript {
+    /// Native segwit `wsh(sortedmulti(...))`.
+    #[default]
+    Wsh,
+    /// Nested segwit `sh(wsh(sortedmulti(...)))`, for counterparties that
+    /// cannot pay to bech32 addresses.
+    ShWsh,
+}
+
+/// An M-of-N multisig vault and the rules for spending from it.
+///
+/// Construction and deserialization both validate the quorum and the
+/// cosigner set, so a `Vault` value is always usable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "VaultDef", into = "VaultDef")]
+pub struct Vault {
+    id: String,
+    name: String,
+    network: Network,
+    threshold: usize,
+    cosigners: Vec<Cosigner>,
+    script: VaultScript,
+    policy: SpendingPolicy,
+}
+
+impl Vault {
+    pub fn new(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        network: Network,
+        threshold: usize,
+        cosigners: Vec<Cosigner>,
+    ) -> Result<Self> {
+        let vault = Vault {
+            id: id.into(),
+            name: name.into(),
+            network,
+            threshold,
+            cosigners,
+            script: VaultScript::default(),
+            policy: SpendingPolicy::default(),
+        };
+        vault.validate()?;
+        Ok(vault)
+    }
+
+    pub fn with_script(mut self, script: VaultScript) -> Self {
+        self.script = script;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: SpendingPolicy) -> Result<Self> {
+        self.policy = policy;
+        self.validate()?;
+        Ok(self)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn cosigners(&self) -> &[Cosigner] {
+        &self.cosigners
+    }
+
+    pub fn script(&self) -> VaultScript {
+        self.script
+    }
+
+    pub fn policy(&self) -> &SpendingPolicy {
+        &self.policy
+    }
+
+    /// The quorum in `M-of-N` form.
+    pub fn quorum(&self) -> String {
+        format!("{}-of-{}", self.threshold, self.cosigners.len())
+    }
+
+    pub fn cosigner_by_fingerprint(&self, fingerprint: Fingerprint) -> Option<&Cosigner> {
+        self.cosigners
+            .iter()
+            .find(|c| c.origin.fingerprint == fingerprint)
+    }
+
+    /// The watch-only multipath descriptor for the vault, without checksum.
+    pub fn descriptor_string(&self) -> String {
+        let keys: Vec<String> = self
+            .cosigners
+            .iter()
+            .map(Cosigner::key_expression)
+            .collect();
+        let multi = format!("sortedmulti({},{})", self.threshold, keys.join(","));
+        match self.script {
+            VaultScript::Wsh => format!("wsh({multi})"),
+            VaultScript::ShWsh => format!("sh(wsh({multi}))"),
+        }
+    }
+
+    pub fn descriptor(&self) -> Result<WalletDescriptor> {
+        Ok(WalletDescriptor::parse(
+            &self.descriptor_string(),
+            self.network,
+        )?)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(Error::invalid_vault(&self.id, reason));
+        if self.id.is_empty() {
+            return invalid("vault id must not be empty".into());
+        }
+        let n = self.cosigners.len();
+        if n == 0 || n > MAX_COSIGNERS {
+            return invalid(format!("needs 1 to {MAX_COSIGNERS} cosigners, got {n}"));
+        }
+        if self.threshold == 0 || self.threshold > n {
+            return invalid(format!(
+                "threshold {} is not within 1..={n}",
+                self.threshold
+            ));
+        }
+        let mut names = HashSet::new();
+        let mut keys = HashSet::new();
+        let mut fingerprints = HashSet::new();
+        for cosigner in &self.cosigners {
+            if !names.insert(cosigner.name.as_str()) {
+                return invalid(format!("duplicate cosigner name `{}`", cosigner.name));
+            }
+            if !keys.insert(cosigner.xpub) {
+                return invalid(format!("cosigner `{}` reuses another key", cosigner.name));
+            }
+            // Signers and PSBT origins name cosigners by master fingerprint.
+            if !fingerprints.insert(cosigner.origin.fingerprint) {
+                return invalid(format!(
+                    "cosigner `{}` shares master fingerprint {} with another",
+                    cosigner.name, cosigner.origin.fingerprint
+                ));
+            }
+            if cosigner.xpub.network != NetworkKind::from(self.network) {
+                return invalid(format!(
+                    "cosigner `{}` key is not for {}",
+                    cosigner.name, self.network
+                ));
+            }
+        }
+        for required in &self.policy.required_cosigners {
+            if !names.contains(required.as_str()) {
+                return invalid(format!("policy requires unknown cosigner `{required}`"));
+            }
+        }
+        if self.policy.required_cosigners.len() > self.threshold {
+            return invalid("policy requires more cosigners than the threshold".into());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}, {})", self.name, self.id, self.quorum())
+    }
+}
+
+/// The serialized form of a [`Vault`], validated on the way in.
+#[derive(Serialize, Deserialize)]
+struct VaultDef {
+    id: String,
+    name: String,
+    network: Network,
+    threshold: usize,
+    cosigners: Vec<Cosigner>,
+    #[serde(default)]
+    script: VaultScript,
+    #[serde(default)]
+    policy: SpendingPolicy,
+}
+
+impl TryFrom<VaultDef> for Vault {
+    type Error = Error;
+
+    fn try_from(def: VaultDef) -> Result<Self> {
+        Vault::new(def.id, def.name, def.network, def.threshold, def.cosigners)?
+            .with_script(def.script)
+            .with_policy(def.policy)
+    }
+}
+
+impl From<Vault> for VaultDef {
+    fn from(vault: Vault) -> Self {
+        VaultDef {
+            id: vault.id,
+            name: vault.name,
+            network: vault.network,
+            threshold: vault.threshold,
+            cosigners: vault.cosigners,
+            script: vault.script,
+            policy: vault.policy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::Secp256k1;
+
+    use super::*;
+
+    fn cosigner(name: &str, seed: u8, network: NetworkKind) -> Cosigner {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(network, &[seed; 32]).unwrap();
+        let path: DerivationPath = "48'/0'/0'/2'".parse().unwrap();
+        let account = master.derive_priv(&secp, &path).unwrap();
+        Cosigner::new(
+            name,
+            KeyOrigin::new(master.fingerprint(&secp), path),
+            Xpub::from_priv(&secp, &account),
+        )
+    }
+
+    fn cosigners(n: u8) -> Vec<Cosigner> {
+        (0..n)
+            .map(|i| cosigner(&format!("key{i}"), i + 1, NetworkKind::Main))
+            .collect()
+    }
+
+    #[test]
+    fn builds_sortedmulti_descriptor_with_origins() {
+        let vault = Vault::new("treasury", "Treasury", Network::Bitcoin, 2, cosigners(3)).unwrap();
+        let descriptor = vault.descriptor_string();
+        assert!(descriptor.starts_with("wsh(sortedmulti(2,["));
+        assert!(descriptor.contains("/48'/0'/0'/2']xpub"));
+        assert!(vault.descriptor().is_ok());
+        assert_eq!(vault.quorum(), "2-of-3");
+
+        let nested = vault.with_script(VaultScript::ShWsh);
+        assert!(nested
+            .descriptor_string()
+            .starts_with("sh(wsh(sortedmulti(2,"));
+    }
+
+    #[test]
+    fn rejects_bad_quorums_and_duplicate_keys() {
+        let net = Network::Bitcoin;
+        assert!(Vault::new("v", "V", net, 0, cosigners(2)).is_err());
+        assert!(Vault::new("v", "V", net, 3, cosigners(2)).is_err());
+        assert!(Vault::new("v", "V", net, 1, Vec::new()).is_err());
+        assert!(Vault::new("v", "V", net, 2, cosigners(21)).is_err());
+
+        let mut dup = cosigners(2);
+        dup[1].xpub = dup[0].xpub;
+        assert!(Vault::new("v", "V", net, 2, dup).is_err());
+
+        let testnet = vec![cosigner("t", 9, NetworkKind::Test)];
+        assert!(Vault::new("v", "V", net, 1, testnet).is_err());
+    }
+
+    #[test]
+    fn rejects_cosigners_sharing_a_fingerprint() {
+        // Two accounts of one seed: distinct keys, one master fingerprint.
+        let mut shared = cosigners(2);
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(NetworkKind::Main, &[1; 32]).unwrap();
+        let path: DerivationPath = "48'/0'/1'/2'".parse().unwrap();
+        let account = master.derive_priv(&secp, &path).unwrap();
+        shared[1] = Cosigner::new(
+            "key1",
+            KeyOrigin::new(master.fingerprint(&secp), path),
+            Xpub::from_priv(&secp, &account),
+        );
+        let err = Vault::new("v", "V", Network::Bitcoin, 2, shared).unwrap_err();
+        assert!(
+            err.to_string().contains("shares master fingerprint"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn deserialization_validates() {
+        let vault = Vault::new("cold", "Cold", Network::Bitcoin, 2, cosigners(3)).unwrap();
+        let mut json = serde_json::to_value(&vault).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Vault>(json.clone()).unwrap(),
+            vault
+        );
+
+        json["threshold"] = 4.into();
+        assert!(serde_json::from_value::<Vault>(json).is_err());
+    }
+}
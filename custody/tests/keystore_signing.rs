@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use bitcoin::bip32::DerivationPath;
+use bitcoin::hashes::Hash;
+use bitcoin::{Amount, FeeRate, Network, OutPoint, Txid};
+use walletb_bitcoin::{BitcoinSource, Keychain, Keychains, MemoryUtxoSet, Utxo};
+use walletb_custody::{build_spend, Error, KdfParams, Keystore, Payment, SpendRequest, Vault};
+
+const FAST: KdfParams = KdfParams {
+    memory_kib: 64,
+    iterations: 1,
+    parallelism: 1,
+};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("walletb-{}-{name}.json", std::process::id()))
+}
+
+#[test]
+fn unlocked_keystores_sign_vault_spends() {
+    let account: DerivationPath = "48'/1'/0'/2'".parse().unwrap();
+    let mut signers = Vec::new();
+    let mut cosigners = Vec::new();
+    for name in ["hot", "warm", "outsider"] {
+        let (keystore, _mnemonic) =
+            Keystore::generate(name, Network::Regtest, "correct horse", FAST).unwrap();
+        let path = temp_path(name);
+        keystore.save(&path).unwrap();
+        let loaded = Keystore::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, keystore);
+
+        let signer = loaded.unlock("correct horse").unwrap();
+        cosigners.push(signer.cosigner(name, &account).unwrap());
+        signers.push(signer);
+    }
+    let outsider = signers.pop().unwrap();
+    cosigners.pop();
+    let vault = Vault::new("petty", "Petty cash", Network::Regtest, 2, cosigners).unwrap();
+
+    let address = vault
+        .descriptor()
+        .unwrap()
+        .derive(Keychain::External, 0)
+        .unwrap();
+    let set: MemoryUtxoSet = [Utxo {
+        outpoint: OutPoint::new(Txid::from_byte_array([9; 32]), 1),
+        script_pubkey: address.script_pubkey(),
+        value: Amount::from_sat(80_000),
+        height: Some(5),
+    }]
+    .into_iter()
+    .collect();
+    let source = BitcoinSource::new(Network::Regtest, set);
+    let request = SpendRequest::new(
+        vec![Payment::new(
+            "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+            Amount::from_sat(30_000),
+        )],
+        FeeRate::from_sat_per_vb(2).unwrap(),
+    );
+    let mut spend = build_spend(&vault, &source, &request, 20).unwrap();
+
+    assert!(matches!(
+        spend.sign(&outsider),
+        Err(Error::UnknownSigner(_))
+    ));
+    assert_eq!(spend.sign(&signers[0]).unwrap(), 1);
+    assert_eq!(spend.sign(&signers[1]).unwrap(), 1);
+    let tx = spend.finalize(Amount::ZERO).unwrap();
+    assert_eq!(tx.input[0].witness.len(), 4);
+}
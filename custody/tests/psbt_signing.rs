@@ -0,0 +1,234 @@
+use bitcoin::bip32::{DerivationPath, Xpriv, Xpub};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{Amount, FeeRate, Network, NetworkKind, OutPoint, Txid};
+use walletb_bitcoin::{BitcoinSource, Keychain, Keychains, MemoryUtxoSet, Utxo};
+use walletb_custody::{
+    build_spend, Cosigner, Error, KeyOrigin, Payment, PolicyViolation, PsbtVersion, SpendRequest,
+    SpendingPolicy, Vault, VaultPsbt,
+};
+
+const PAYEE: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+fn master(seed: u8) -> Xpriv {
+    Xpriv::new_master(NetworkKind::Main, &[seed; 32]).unwrap()
+}
+
+fn cosigner(name: &str, seed: u8) -> Cosigner {
+    let secp = Secp256k1::new();
+    let master = master(seed);
+    let path: DerivationPath = "48'/0'/0'/2'".parse().unwrap();
+    let account = master.derive_priv(&secp, &path).unwrap();
+    Cosigner::new(
+        name,
+        KeyOrigin::new(master.fingerprint(&secp), path),
+        Xpub::from_priv(&secp, &account),
+    )
+}
+
+fn vault() -> Vault {
+    Vault::new(
+        "cold",
+        "Cold storage",
+        Network::Bitcoin,
+        2,
+        vec![
+            cosigner("alice", 1),
+            cosigner("bob", 2),
+            cosigner("carol", 3),
+        ],
+    )
+    .unwrap()
+}
+
+fn funded(vault: &Vault, coins: &[(Keychain, u32, u64)]) -> BitcoinSource<MemoryUtxoSet> {
+    let descriptor = vault.descriptor().unwrap();
+    let set = coins
+        .iter()
+        .enumerate()
+        .map(|(n, &(keychain, index, sats))| Utxo {
+            outpoint: OutPoint::new(Txid::from_byte_array([n as u8 + 1; 32]), 0),
+            script_pubkey: descriptor.derive(keychain, index).unwrap().script_pubkey(),
+            value: Amount::from_sat(sats),
+            height: Some(100),
+        })
+        .collect();
+    BitcoinSource::new(Network::Bitcoin, set)
+}
+
+/// Signs the exported PSBT the way an external signer would.
+fn sign(spend: &VaultPsbt, seed: u8, version: PsbtVersion) -> String {
+    let mut copy = VaultPsbt::import(spend.vault().clone(), &spend.export(version).unwrap())
+        .unwrap()
+        .into_psbt();
+    copy.sign(&master(seed), &Secp256k1::new()).unwrap();
+    copy.to_string()
+}
+
+#[test]
+fn builds_signs_and_finalizes_a_two_of_three_spend() {
+    let vault = vault();
+    let source = funded(
+        &vault,
+        &[
+            (Keychain::External, 0, 300_000),
+            (Keychain::External, 1, 50_000),
+            (Keychain::Internal, 0, 200_000),
+        ],
+    );
+    let request = SpendRequest::new(
+        vec![Payment::new(PAYEE, Amount::from_sat(400_000))],
+        FeeRate::from_sat_per_vb(10).unwrap(),
+    );
+    let mut spend = build_spend(&vault, &source, &request, 20).unwrap();
+
+    let tx = &spend.psbt().unsigned_tx;
+    assert_eq!(tx.input.len(), 2, "largest coins are selected first");
+    assert_eq!(tx.output.len(), 2);
+    let change = vault
+        .descriptor()
+        .unwrap()
+        .derive(Keychain::Internal, 1)
+        .unwrap();
+    assert_eq!(tx.output[1].script_pubkey, change.script_pubkey());
+    assert_eq!(spend.psbt().outputs[1].bip32_derivation.len(), 3);
+    assert!(spend.psbt().inputs[0].witness_script.is_some());
+    let fee = spend.fee().unwrap();
+    assert!(fee > Amount::from_sat(2_000) && fee < Amount::from_sat(5_000));
+
+    assert_eq!(spend.status().signatures, 0);
+    assert!(matches!(
+        spend.clone().finalize(Amount::ZERO),
+        Err(Error::NotEnoughSignatures { have: 0, need: 2 })
+    ));
+
+    spend
+        .combine_base64(&sign(&spend, 1, PsbtVersion::V0))
+        .unwrap();
+    spend
+        .combine_base64(&sign(&spend, 3, PsbtVersion::V2))
+        .unwrap();
+    let status = spend.status();
+    assert!(status.is_complete());
+    assert_eq!(status.signed_by, ["alice", "carol"]);
+
+    let signed = spend.finalize(Amount::ZERO).unwrap();
+    assert!(signed.input.iter().all(|input| input.witness.len() == 4));
+    assert_eq!(signed.output[0].value, Amount::from_sat(400_000));
+}
+
+#[test]
+fn enforces_funds_and_policy() {
+    let vault = vault()
+        .with_policy(SpendingPolicy {
+            max_per_transaction: Some(Amount::from_sat(500_000)),
+            required_cosigners: vec!["bob".into()],
+            ..Default::default()
+        })
+        .unwrap();
+    let source = funded(&vault, &[(Keychain::External, 0, 100_000)]);
+    let fee_rate = FeeRate::from_sat_per_vb(5).unwrap();
+
+    let too_much = SpendRequest::new(
+        vec![Payment::new(PAYEE, Amount::from_sat(600_000))],
+        fee_rate,
+    );
+    assert!(matches!(
+        build_spend(&vault, &source, &too_much, 20),
+        Err(Error::Policy(PolicyViolation::TransactionLimit { .. }))
+    ));
+
+    let unfunded = SpendRequest::new(
+        vec![Payment::new(PAYEE, Amount::from_sat(100_000))],
+        fee_rate,
+    );
+    assert!(matches!(
+        build_spend(&vault, &source, &unfunded, 20),
+        Err(Error::InsufficientFunds { .. })
+    ));
+
+    let dust = SpendRequest::new(vec![Payment::new(PAYEE, Amount::from_sat(100))], fee_rate);
+    assert!(matches!(
+        build_spend(&vault, &source, &dust, 20),
+        Err(Error::InvalidSpend(_))
+    ));
+
+    // Everything but the fee goes out, so the change would be dust.
+    let sweep = SpendRequest::new(
+        vec![Payment::new(PAYEE, Amount::from_sat(99_000))],
+        fee_rate,
+    );
+    let mut spend = build_spend(&vault, &source, &sweep, 20).unwrap();
+    assert_eq!(spend.psbt().unsigned_tx.output.len(), 1);
+
+    spend
+        .combine_base64(&sign(&spend, 1, PsbtVersion::V0))
+        .unwrap();
+    spend
+        .combine_base64(&sign(&spend, 3, PsbtVersion::V0))
+        .unwrap();
+    assert!(matches!(
+        spend.finalize(Amount::ZERO),
+        Err(Error::Policy(PolicyViolation::MissingCosigner(name))) if name == "bob"
+    ));
+}
+
+#[test]
+fn finalizing_checks_imported_spends_against_the_policy() {
+    // Built and signed under no policy, as an outside tool might.
+    let open = vault();
+    let source = funded(
+        &open,
+        &[
+            (Keychain::External, 0, 300_000),
+            (Keychain::Internal, 0, 200_000),
+        ],
+    );
+    let request = SpendRequest::new(
+        vec![Payment::new(PAYEE, Amount::from_sat(400_000))],
+        FeeRate::from_sat_per_vb(10).unwrap(),
+    );
+    let mut spend = build_spend(&open, &source, &request, 20).unwrap();
+    spend
+        .combine_base64(&sign(&spend, 1, PsbtVersion::V0))
+        .unwrap();
+    spend
+        .combine_base64(&sign(&spend, 2, PsbtVersion::V0))
+        .unwrap();
+    let under = |policy: SpendingPolicy| {
+        let vault = vault().with_policy(policy).unwrap();
+        VaultPsbt::import(vault, &spend.export(PsbtVersion::V0).unwrap()).unwrap()
+    };
+
+    // The change back to the vault does not count against the limits.
+    let limited = SpendingPolicy {
+        max_per_transaction: Some(Amount::from_sat(400_000)),
+        daily_limit: Some(Amount::from_sat(500_000)),
+        ..Default::default()
+    };
+    assert_eq!(
+        under(limited.clone()).external_outputs().unwrap(),
+        [(PAYEE.to_owned(), Amount::from_sat(400_000))]
+    );
+    assert!(under(limited.clone()).finalize(Amount::ZERO).is_ok());
+    assert!(matches!(
+        under(limited).finalize(Amount::from_sat(100_001)),
+        Err(Error::Policy(PolicyViolation::DailyLimit { .. }))
+    ));
+    assert!(matches!(
+        under(SpendingPolicy {
+            max_per_transaction: Some(Amount::from_sat(399_999)),
+            ..Default::default()
+        })
+        .finalize(Amount::ZERO),
+        Err(Error::Policy(PolicyViolation::TransactionLimit { .. }))
+    ));
+    assert!(matches!(
+        under(SpendingPolicy {
+            allowed_destinations: vec!["bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh".into()],
+            ..Default::default()
+        })
+        .finalize(Amount::ZERO),
+        Err(Error::Policy(PolicyViolation::DestinationNotAllowed(address))) if address == PAYEE
+    ));
+}
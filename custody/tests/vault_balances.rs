@@ -0,0 +1,75 @@
+use bitcoin::bip32::{DerivationPath, Xpriv, Xpub};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{Amount, Network, NetworkKind, OutPoint, Txid};
+use walletb_bitcoin::{BitcoinSource, Keychain, Keychains, MemoryUtxoSet, Utxo};
+use walletb_custody::{vault_balances, Cosigner, KeyOrigin, Vault, VaultScript};
+
+fn cosigner(name: &str, seed: u8) -> Cosigner {
+    let secp = Secp256k1::new();
+    let master = Xpriv::new_master(NetworkKind::Main, &[seed; 32]).unwrap();
+    let path: DerivationPath = "48'/0'/0'/2'".parse().unwrap();
+    let account = master.derive_priv(&secp, &path).unwrap();
+    Cosigner::new(
+        name,
+        KeyOrigin::new(master.fingerprint(&secp), path),
+        Xpub::from_priv(&secp, &account),
+    )
+}
+
+fn fund(set: &mut MemoryUtxoSet, vault: &Vault, keychain: Keychain, index: u32, n: u8, sats: u64) {
+    let address = vault.descriptor().unwrap().derive(keychain, index).unwrap();
+    set.insert(Utxo {
+        outpoint: OutPoint::new(Txid::from_byte_array([n; 32]), 0),
+        script_pubkey: address.script_pubkey(),
+        value: Amount::from_sat(sats),
+        height: Some(10),
+    });
+}
+
+#[test]
+fn reports_balances_grouped_by_vault() {
+    let cold = Vault::new(
+        "cold",
+        "Cold storage",
+        Network::Bitcoin,
+        2,
+        vec![
+            cosigner("alice", 1),
+            cosigner("bob", 2),
+            cosigner("carol", 3),
+        ],
+    )
+    .unwrap();
+    let ops = Vault::new(
+        "ops",
+        "Operations",
+        Network::Bitcoin,
+        1,
+        vec![cosigner("alice", 1), cosigner("dave", 4)],
+    )
+    .unwrap()
+    .with_script(VaultScript::ShWsh);
+
+    let mut set = MemoryUtxoSet::new();
+    fund(&mut set, &cold, Keychain::External, 0, 1, 5_000_000);
+    fund(&mut set, &cold, Keychain::External, 2, 2, 2_500_000);
+    fund(&mut set, &cold, Keychain::Internal, 0, 3, 100_000);
+    fund(&mut set, &ops, Keychain::External, 0, 4, 40_000);
+    let source = BitcoinSource::new(Network::Bitcoin, set);
+
+    let report = vault_balances(&[cold, ops], &source, 20).unwrap();
+    assert_eq!(report.len(), 2);
+
+    assert_eq!(report[0].vault_id, "cold");
+    assert_eq!(report[0].quorum, "2-of-3");
+    assert_eq!(report[0].balances[0].confirmed.to_u64(), Some(7_600_000));
+    assert_eq!(report[0].spendable().count(), 1);
+    assert_eq!(report[0].unspendable().count(), 0);
+    assert_eq!(report[0].used_addresses, 3);
+    assert_eq!(report[0].next_receive_index, 3);
+
+    assert_eq!(report[1].vault_id, "ops");
+    assert_eq!(report[1].quorum, "1-of-2");
+    assert_eq!(report[1].balances[0].confirmed.to_u64(), Some(40_000));
+}
@@ -0,0 +1,189 @@
+//! Just enough Solidity ABI encoding to call token view functions and
+//! read token transfer events.
+
+use sha3::{Digest, Keccak256};
+use walletb_core::U256;
+
+use crate::address::hex;
+use crate::EthAddress;
+
+pub(crate) const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+pub(crate) const DECIMALS: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+pub(crate) const SYMBOL: [u8; 4] = [0x95, 0xd8, 0x9b, 0x41];
+pub(crate) const NAME: [u8; 4] = [0x06, 0xfd, 0xde, 0x03];
+/// ERC-721 `ownerOf(uint256)`.
+pub(crate) const OWNER_OF: [u8; 4] = [0x63, 0x52, 0x21, 0x1e];
+/// ERC-1155 `balanceOf(address,uint256)`.
+pub(crate) const BALANCE_OF_ID: [u8; 4] = [0x00, 0xfd, 0xd5, 0x8e];
+
+/// ERC-20 and ERC-721 `Transfer`; ERC-721 indexes the token id as well.
+pub(crate) const TRANSFER: &str = "Transfer(address,address,uint256)";
+pub(crate) const TRANSFER_SINGLE: &str = "TransferSingle(address,address,address,uint256,uint256)";
+pub(crate) const TRANSFER_BATCH: &str =
+    "TransferBatch(address,address,address,uint256[],uint256[])";
+
+/// The selector of the function `signature`, such as `"decimals()"`.
+pub(crate) fn selector(signature: &str) -> [u8; 4] {
+    let hash = event_topic(signature);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// The first topic of logs emitted for the event `signature`.
+pub(crate) fn event_topic(signature: &str) -> [u8; 32] {
+    Keccak256::digest(signature.as_bytes()).into()
+}
+
+pub(crate) fn address_word(address: &EthAddress) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(address.as_bytes());
+    word
+}
+
+pub(crate) fn uint_word(value: U256) -> [u8; 32] {
+    value.to_be_bytes()
+}
+
+/// The address in the low 20 bytes of a word, as in an indexed topic.
+pub(crate) fn word_address(word: &[u8; 32]) -> EthAddress {
+    let mut bytes = [0u8; 20];
+    bytes.copy_from_slice(&word[12..]);
+    EthAddress::from_bytes(bytes)
+}
+
+/// `0x`-prefixed hex of a 32-byte word.
+pub(crate) fn word_hex(word: &[u8; 32]) -> String {
+    format!("0x{}", hex(word))
+}
+
+/// `0x`-prefixed call data for `selector(args...)` with static arguments.
+pub(crate) fn encode_call(selector: [u8; 4], args: &[[u8; 32]]) -> String {
+    let mut data = selector.to_vec();
+    for arg in args {
+        data.extend_from_slice(arg);
+    }
+    format!("0x{}", hex(&data))
+}
+
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let digits = s.strip_prefix("0x")?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Parses a JSON-RPC hex quantity such as `"0x1bc16d674ec80000"`.
+pub(crate) fn parse_quantity(s: &str) -> Option<U256> {
+    let digits = s.strip_prefix("0x")?;
+    if digits.is_empty() {
+        return None;
+    }
+    U256::from_str_radix(digits, 16).ok()
+}
+
+pub(crate) fn decode_uint(data: &[u8]) -> Option<U256> {
+    U256::try_from_be_slice(data.get(..32)?)
+}
+
+/// Decodes a dynamic `uint256[]` whose offset is in the word at `head`.
+pub(crate) fn decode_uint_array(data: &[u8], head: usize) -> Option<Vec<U256>> {
+    let offset = usize::try_from(decode_uint(data.get(head..)?)?).ok()?;
+    let len = usize::try_from(decode_uint(data.get(offset..)?)?).ok()?;
+    (0..len)
+        .map(|i| decode_uint(data.get(offset.checked_add(32 * (i + 1))?..)?))
+        .collect()
+}
+
+/// Decodes an ABI `string` return value, falling back to the `bytes32`
+/// encoding some older tokens (MKR, SAI) use for `symbol()` and `name()`.
+pub(crate) fn decode_string(data: &[u8]) -> Option<String> {
+    if data.len() == 32 {
+        let end = data.iter().position(|&b| b == 0).unwrap_or(32);
+        return String::from_utf8(data[..end].to_vec()).ok();
+    }
+    let offset = usize::try_from(decode_uint(data)?).ok()?;
+    let len = usize::try_from(decode_uint(data.get(offset..)?)?).ok()?;
+    let start = offset.checked_add(32)?;
+    let bytes = data.get(start..start.checked_add(len)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_balance_of() {
+        let holder: EthAddress = "0x00000000000000000000000000000000000000ff"
+            .parse()
+            .unwrap();
+        let data = encode_call(BALANCE_OF, &[address_word(&holder)]);
+        assert_eq!(data.len(), 2 + 8 + 64);
+        assert!(data.starts_with("0x70a08231"));
+        assert!(data.ends_with("00ff"));
+    }
+
+    #[test]
+    fn decodes_dynamic_and_bytes32_strings() {
+        let mut dynamic = vec![0u8; 96];
+        dynamic[31] = 0x20;
+        dynamic[63] = 4;
+        dynamic[64..68].copy_from_slice(b"USDC");
+        assert_eq!(decode_string(&dynamic).as_deref(), Some("USDC"));
+
+        let mut fixed = [0u8; 32];
+        fixed[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_string(&fixed).as_deref(), Some("MKR"));
+    }
+
+    #[test]
+    fn hashes_event_signatures() {
+        assert_eq!(
+            word_hex(&event_topic(TRANSFER)),
+            "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+        assert_eq!(
+            word_hex(&event_topic(TRANSFER_SINGLE)),
+            "0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62"
+        );
+        assert_eq!(
+            word_hex(&event_topic(TRANSFER_BATCH)),
+            "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb"
+        );
+    }
+
+    #[test]
+    fn derives_function_selectors() {
+        assert_eq!(selector("balanceOf(address)"), BALANCE_OF);
+        assert_eq!(selector("decimals()"), DECIMALS);
+        assert_eq!(selector("balanceOf(address,uint256)"), BALANCE_OF_ID);
+    }
+
+    #[test]
+    fn decodes_uint_arrays() {
+        // (uint256[] ids, uint256[] values) = ([7, 9], [1, 5])
+        let words: [u128; 8] = [0x40, 0xa0, 2, 7, 9, 2, 1, 5];
+        let data: Vec<u8> = words
+            .iter()
+            .flat_map(|w| uint_word(U256::from(*w)))
+            .collect();
+        let ids = decode_uint_array(&data, 0).unwrap();
+        let values = decode_uint_array(&data, 32).unwrap();
+        assert_eq!(ids, [U256::from(7u8), U256::from(9u8)]);
+        assert_eq!(values, [U256::from(1u8), U256::from(5u8)]);
+        assert_eq!(decode_uint_array(&data[..150], 0), None);
+    }
+
+    #[test]
+    fn parses_quantities() {
+        assert_eq!(parse_quantity("0x0"), Some(U256::ZERO));
+        assert_eq!(
+            parse_quantity("0x1bc16d674ec80000"),
+            Some(U256::from(2_000_000_000_000_000_000u64))
+        );
+        assert_eq!(parse_quantity("12"), None);
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+use std::str::FromStr;
+
+use sha3::{Digest, Keccak256};
+
+use crate::{Error, Result};
+
+/// A 20-byte Ethereum account or contract address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EthAddress([u8; 20]);
+
+impl EthAddress {
+    pub const fn from_bytes(bytes: [u8; 20]) -> Self {
+        EthAddress(bytes)
+    }
+
+    pub const fn as_bytes(&self) -> &[u8; 20] {
+        &self.0
+    }
+
+    /// Lower-case `0x`-prefixed hex, the form used in JSON-RPC requests.
+    pub fn to_lower_hex(&self) -> String {
+        format!("0x{}", hex(&self.0))
+    }
+
+    /// The EIP-55 mixed-case checksum encoding.
+    pub fn to_checksum(&self) -> String {
+        let lower = hex(&self.0);
+        let hash = Keccak256::digest(lower.as_bytes());
+        let mut out = String::with_capacity(42);
+        out.push_str("0x");
+        for (i, c) in lower.chars().enumerate() {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            out.push(if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            });
+        }
+        out
+    }
+}
+
+impl FromStr for EthAddress {
+    type Err = Error;
+
+    /// Parses a `0x`-prefixed address. All-lower or all-upper case input is
+    /// accepted as is; mixed case must carry a valid EIP-55 checksum.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidAddress {
+            address: s.to_owned(),
+            reason: reason.to_owned(),
+        };
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .ok_or_else(|| invalid("missing 0x prefix"))?;
+        if digits.len() != 40 {
+            return Err(invalid("expected 40 hex digits"));
+        }
+        let mut bytes = [0u8; 20];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&digits[2 * i..2 * i + 2], 16)
+                .map_err(|_| invalid("not hexadecimal"))?;
+        }
+        let address = EthAddress(bytes);
+        let has_lower = digits.chars().any(|c| c.is_ascii_lowercase());
+        let has_upper = digits.chars().any(|c| c.is_ascii_uppercase());
+        if has_lower && has_upper && address.to_checksum()[2..] != *digits {
+            return Err(invalid("bad EIP-55 checksum"));
+        }
+        Ok(address)
+    }
+}
+
+/// Displays the EIP-55 checksum form.
+impl fmt::Display for EthAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_checksum())
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from EIP-55.
+    const CHECKSUMMED: [&str; 4] = [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn produces_eip55_checksums() {
+        for s in CHECKSUMMED {
+            let address: EthAddress = s.parse().unwrap();
+            assert_eq!(address.to_checksum(), s);
+        }
+    }
+
+    #[test]
+    fn accepts_single_case_and_rejects_bad_checksum() {
+        let lower = CHECKSUMMED[0].to_lowercase();
+        assert!(lower.parse::<EthAddress>().is_ok());
+        assert!(format!("0x{}", lower[2..].to_uppercase())
+            .parse::<EthAddress>()
+            .is_ok());
+
+        let tampered = CHECKSUMMED[0].replace("aA", "Aa");
+        assert!(tampered.parse::<EthAddress>().is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+            .parse::<EthAddress>()
+            .is_err());
+        assert!("0x1234".parse::<EthAddress>().is_err());
+        assert!("0xzzzzb6053f3e94c9b9a09f33669435e7ef1beaed"
+            .parse::<EthAddress>()
+            .is_err());
+    }
+}
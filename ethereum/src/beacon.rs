@@ -0,0 +1,363 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use walletb_core::{Address, Amount, Balance, BalanceSource, Category, Chain, RestClient, U256};
+
+use crate::{eth, Error, EthAddress, Result};
+
+/// How a wallet names a validator: by index, by public key, or by the
+/// execution address its withdrawals are paid to, which stands for every
+/// validator paying there.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValidatorKey {
+    Index(u64),
+    /// A `0x`-prefixed, lowercase 48-byte BLS public key.
+    Pubkey(String),
+    WithdrawalAddress(EthAddress),
+}
+
+impl FromStr for ValidatorKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return s
+                .parse()
+                .map(ValidatorKey::Index)
+                .map_err(|e| Error::InvalidAddress {
+                    address: s.to_owned(),
+                    reason: e.to_string(),
+                });
+        }
+        let digits = s.strip_prefix("0x").unwrap_or_default();
+        if digits.len() == 96 && digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Ok(ValidatorKey::Pubkey(s.to_ascii_lowercase()));
+        }
+        s.parse()
+            .map(ValidatorKey::WithdrawalAddress)
+            .map_err(|_| Error::InvalidAddress {
+                address: s.to_owned(),
+                reason: "not a validator index, public key or withdrawal address".into(),
+            })
+    }
+}
+
+impl fmt::Display for ValidatorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidatorKey::Index(index) => write!(f, "{index}"),
+            ValidatorKey::Pubkey(pubkey) => f.write_str(pubkey),
+            ValidatorKey::WithdrawalAddress(address) => write!(f, "{address}"),
+        }
+    }
+}
+
+/// Where a validator is in its life cycle, as the Beacon API reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidatorStatus {
+    /// Deposited, waiting for the deposit to be processed.
+    PendingInitialized,
+    /// Waiting in the activation queue.
+    PendingQueued,
+    ActiveOngoing,
+    /// Active, with an exit requested.
+    ActiveExiting,
+    /// Active, slashed and being forced out.
+    ActiveSlashed,
+    ExitedUnslashed,
+    ExitedSlashed,
+    /// Exited; the balance is paid out at the next withdrawal sweep.
+    WithdrawalPossible,
+    WithdrawalDone,
+}
+
+/// A validator and its balance at the state read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validator {
+    pub index: u64,
+    pub pubkey: String,
+    pub status: ValidatorStatus,
+    /// In gwei, rewards included.
+    pub balance: u64,
+    /// In gwei: the stake rewards are paid on, a multiple of 1 ETH.
+    pub effective_balance: u64,
+    /// The address withdrawals are paid to; `None` while the validator
+    /// still has BLS withdrawal credentials.
+    pub withdrawal_address: Option<EthAddress>,
+    pub slashed: bool,
+}
+
+impl Validator {
+    /// Deposits waiting for activation are locked; an active validator is
+    /// staked; one exiting, exited or being slashed is pending withdrawal.
+    pub fn category(&self) -> Category {
+        match self.status {
+            ValidatorStatus::PendingInitialized | ValidatorStatus::PendingQueued => {
+                Category::Locked
+            }
+            ValidatorStatus::ActiveOngoing => Category::Staked,
+            _ => Category::PendingWithdrawal,
+        }
+    }
+
+    /// The balance in wei.
+    pub fn balance_wei(&self) -> Amount {
+        Amount::from_base_units(U256::from(self.balance) * U256::from(1_000_000_000u64))
+    }
+}
+
+/// A client for a consensus-layer node's Beacon API.
+#[derive(Debug)]
+pub struct BeaconClient {
+    rest: RestClient,
+}
+
+impl BeaconClient {
+    /// How many validators to ask for per request, to keep URLs short.
+    const IDS_PER_REQUEST: usize = 64;
+
+    pub fn new(url: impl Into<String>) -> Self {
+        BeaconClient::from_rest(RestClient::new(url))
+    }
+
+    /// A client over a configured [`RestClient`], such as one that sends an
+    /// API key.
+    pub fn from_rest(rest: RestClient) -> Self {
+        BeaconClient { rest }
+    }
+
+    pub fn url(&self) -> &str {
+        self.rest.url()
+    }
+
+    /// The validators with the given indices or public keys at `state`
+    /// (`head`, `finalized` or a slot). Unknown ones are left out.
+    pub fn validators(&self, state: &str, ids: &[String]) -> Result<Vec<Validator>> {
+        let mut validators = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(Self::IDS_PER_REQUEST) {
+            validators.extend(self.read(state, &[("id", &chunk.join(","))])?);
+        }
+        Ok(validators)
+    }
+
+    /// Every validator at `state`. The Beacon API cannot filter by
+    /// withdrawal address, so finding a wallet's validators means reading
+    /// them all: well over a million on mainnet.
+    pub fn all_validators(&self, state: &str) -> Result<Vec<Validator>> {
+        self.read(state, &[])
+    }
+
+    fn read(&self, state: &str, query: &[(&str, &str)]) -> Result<Vec<Validator>> {
+        let path = format!("/eth/v1/beacon/states/{state}/validators");
+        let response: ValidatorsResponse = self.rest.get(&path, query)?;
+        response
+            .data
+            .into_iter()
+            .map(|entry| entry.parse(&path))
+            .collect()
+    }
+}
+
+/// A [`BalanceSource`] for ether staked on the beacon chain.
+///
+/// Wallet addresses are [`ValidatorKey`]s. Each validator's balance is
+/// reported as locked, staked or pending withdrawal by its
+/// [`category`](Validator::category); nothing on the beacon chain is
+/// spendable until it is withdrawn to an execution address, so there is no
+/// available balance.
+///
+/// The validators paying to a withdrawal address are looked up once, with
+/// a read of every validator, and remembered for the life of the source.
+#[derive(Debug)]
+pub struct BeaconSource {
+    client: BeaconClient,
+    state: String,
+    /// Validator indices per withdrawal address.
+    by_withdrawal: Mutex<HashMap<EthAddress, Vec<u64>>>,
+}
+
+impl BeaconSource {
+    pub fn new(client: BeaconClient) -> Self {
+        BeaconSource {
+            client,
+            state: "head".to_owned(),
+            by_withdrawal: Mutex::default(),
+        }
+    }
+
+    /// Reads at `state`, such as `finalized` or a slot, instead of `head`.
+    pub fn at_state(mut self, state: impl Into<String>) -> Self {
+        self.state = state.into();
+        self
+    }
+
+    pub fn client(&self) -> &BeaconClient {
+        &self.client
+    }
+
+    /// Every validator the addresses name, each once, by index.
+    pub fn validators(&self, addresses: &[Address]) -> Result<Vec<Validator>> {
+        let keys = addresses
+            .iter()
+            .map(|a| {
+                a.expect_chain(Chain::ETHEREUM)?;
+                a.as_str().parse()
+            })
+            .collect::<Result<Vec<ValidatorKey>>>()?;
+        let mut ids = Vec::new();
+        let mut withdrawal = Vec::new();
+        for key in keys {
+            match key {
+                ValidatorKey::WithdrawalAddress(address) => withdrawal.push(address),
+                key => ids.push(key.to_string()),
+            }
+        }
+        ids.extend(
+            self.resolve_withdrawal(&withdrawal)?
+                .into_iter()
+                .map(|i| i.to_string()),
+        );
+        let validators: BTreeMap<u64, Validator> = self
+            .client
+            .validators(&self.state, &ids)?
+            .into_iter()
+            .map(|v| (v.index, v))
+            .collect();
+        Ok(validators.into_values().collect())
+    }
+
+    /// Sums validator balances per category.
+    pub fn totals(&self, validators: &[Validator]) -> Vec<Balance> {
+        let mut totals: BTreeMap<Category, Amount> = BTreeMap::new();
+        for validator in validators {
+            let total = totals.entry(validator.category()).or_default();
+            *total = *total + validator.balance_wei();
+        }
+        totals
+            .into_iter()
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|(category, amount)| Balance::new(eth(), amount).with_category(category))
+            .collect()
+    }
+
+    /// The indices of validators paying to `addresses`, reading every
+    /// validator if any address was not looked up before.
+    fn resolve_withdrawal(&self, addresses: &[EthAddress]) -> Result<Vec<u64>> {
+        let mut known = self.by_withdrawal.lock().unwrap();
+        if addresses.iter().any(|a| !known.contains_key(a)) {
+            let all = self.client.all_validators(&self.state)?;
+            for address in addresses {
+                let indices = all
+                    .iter()
+                    .filter(|v| v.withdrawal_address == Some(*address))
+                    .map(|v| v.index)
+                    .collect();
+                known.insert(*address, indices);
+            }
+        }
+        Ok(addresses
+            .iter()
+            .flat_map(|a| known[a].iter().copied())
+            .collect())
+    }
+}
+
+impl BalanceSource for BeaconSource {
+    fn chain(&self) -> Chain {
+        Chain::ETHEREUM
+    }
+
+    fn balances(&self, addresses: &[Address]) -> walletb_core::Result<Vec<Balance>> {
+        Ok(self.totals(&self.validators(addresses)?))
+    }
+}
+
+#[derive(Deserialize)]
+struct ValidatorsResponse {
+    data: Vec<ValidatorEntry>,
+}
+
+#[derive(Deserialize)]
+struct ValidatorEntry {
+    index: String,
+    balance: String,
+    status: ValidatorStatus,
+    validator: ValidatorInfo,
+}
+
+#[derive(Deserialize)]
+struct ValidatorInfo {
+    pubkey: String,
+    withdrawal_credentials: String,
+    effective_balance: String,
+    slashed: bool,
+}
+
+impl ValidatorEntry {
+    fn parse(self, path: &str) -> Result<Validator> {
+        let number = |s: &str| {
+            s.parse::<u64>()
+                .map_err(|_| Error::invalid_response(path, format!("`{s}` is not a number")))
+        };
+        Ok(Validator {
+            index: number(&self.index)?,
+            pubkey: self.validator.pubkey,
+            status: self.status,
+            balance: number(&self.balance)?,
+            effective_balance: number(&self.validator.effective_balance)?,
+            withdrawal_address: withdrawal_address(&self.validator.withdrawal_credentials),
+            slashed: self.validator.slashed,
+        })
+    }
+}
+
+/// The execution address in `0x01` or `0x02` withdrawal credentials: the
+/// prefix byte, 11 zero bytes and the address.
+fn withdrawal_address(credentials: &str) -> Option<EthAddress> {
+    let digits = credentials.strip_prefix("0x")?;
+    if digits.len() != 64 || !matches!(&digits[..2], "01" | "02") {
+        return None;
+    }
+    format!("0x{}", &digits[24..]).parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_validator_keys() {
+        assert_eq!(
+            "42".parse::<ValidatorKey>().unwrap(),
+            ValidatorKey::Index(42)
+        );
+        let pubkey = format!("0x{}", "AB".repeat(48));
+        assert_eq!(
+            pubkey.parse::<ValidatorKey>().unwrap(),
+            ValidatorKey::Pubkey(pubkey.to_ascii_lowercase())
+        );
+        let address = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        assert!(matches!(
+            address.parse::<ValidatorKey>().unwrap(),
+            ValidatorKey::WithdrawalAddress(_)
+        ));
+        assert!("0x1234".parse::<ValidatorKey>().is_err());
+        assert!("-1".parse::<ValidatorKey>().is_err());
+    }
+
+    #[test]
+    fn reads_execution_withdrawal_credentials() {
+        let address = "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+        let eth1 = format!("0x01{}{address}", "00".repeat(11));
+        assert_eq!(
+            withdrawal_address(&eth1).unwrap().to_lower_hex(),
+            format!("0x{address}")
+        );
+        let bls = format!("0x00{}", "ab".repeat(31));
+        assert_eq!(withdrawal_address(&bls), None);
+    }
+}
@@ -0,0 +1,504 @@
+use serde_json::{json, Value};
+use walletb_core::rpc::RpcCall;
+use walletb_core::{JsonRpcClient, RpcError, U256};
+
+use crate::abi::{self, BALANCE_OF, BALANCE_OF_ID, DECIMALS, NAME, OWNER_OF, SYMBOL};
+use crate::{Error, EthAddress, Result};
+
+/// Most blocks one `eth_getLogs` request covers unless overridden; hosted
+/// providers commonly cap ranges near this.
+pub const DEFAULT_MAX_LOG_RANGE: u64 = 10_000;
+
+/// One balance read in an [`EthClient::balances`] batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceQuery {
+    /// `eth_getBalance(holder)`.
+    Native { holder: EthAddress },
+    /// `eth_getTransactionCount(holder)`: how many transactions it has
+    /// sent.
+    Nonce { holder: EthAddress },
+    /// `token.balanceOf(holder)` through `eth_call`.
+    Token {
+        token: EthAddress,
+        holder: EthAddress,
+    },
+    /// 1 if `token.ownerOf(id)` is `holder`, else 0. Burned items, whose
+    /// `ownerOf` reverts, count as 0.
+    Erc721 {
+        token: EthAddress,
+        holder: EthAddress,
+        id: U256,
+    },
+    /// `token.balanceOf(holder, id)` on an ERC-1155 contract.
+    Erc1155 {
+        token: EthAddress,
+        holder: EthAddress,
+        id: U256,
+    },
+    /// A view function of `token` taking no arguments and returning a
+    /// uint, such as a liquid-staking token's redemption rate.
+    Rate {
+        token: EthAddress,
+        selector: [u8; 4],
+    },
+}
+
+/// An `eth_getLogs` query over an inclusive block range. Each entry of
+/// `topics` matches any of its values at that position; an empty entry
+/// matches anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Emitting contracts; empty for any.
+    pub addresses: Vec<EthAddress>,
+    pub topics: Vec<Vec<[u8; 32]>>,
+}
+
+/// An event log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Log {
+    pub address: EthAddress,
+    pub topics: Vec<[u8; 32]>,
+    pub data: Vec<u8>,
+    pub block_number: u64,
+    pub transaction_hash: String,
+    pub log_index: u64,
+}
+
+/// A block and the transactions it includes, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub number: u64,
+    pub timestamp: u64,
+    pub transactions: Vec<BlockTransaction>,
+}
+
+/// A transaction as a block lists it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockTransaction {
+    pub hash: String,
+    pub from: EthAddress,
+    /// `None` for a contract creation.
+    pub to: Option<EthAddress>,
+    /// Wei sent along.
+    pub value: U256,
+}
+
+/// The outcome of a mined transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub transaction_hash: String,
+    /// False if the transaction reverted, moving no value but still paying
+    /// its fee.
+    pub success: bool,
+    /// Gas used times the effective gas price, plus the L1 data fee on
+    /// rollups that report one.
+    pub fee: U256,
+}
+
+/// What an ERC-20 contract reports about itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub name: String,
+    pub decimals: u8,
+}
+
+/// Typed wrapper over the Ethereum JSON-RPC methods walletb needs.
+#[derive(Debug)]
+pub struct EthClient {
+    rpc: JsonRpcClient,
+    max_log_range: u64,
+}
+
+impl EthClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        EthClient::from_rpc(JsonRpcClient::new(url))
+    }
+
+    pub fn from_rpc(rpc: JsonRpcClient) -> Self {
+        EthClient {
+            rpc,
+            max_log_range: DEFAULT_MAX_LOG_RANGE,
+        }
+    }
+
+    /// Caps how many blocks one `eth_getLogs` request covers; longer
+    /// ranges are split.
+    pub fn with_max_log_range(mut self, blocks: u64) -> Self {
+        self.max_log_range = blocks.max(1);
+        self
+    }
+
+    pub fn rpc(&self) -> &JsonRpcClient {
+        &self.rpc
+    }
+
+    pub fn block_number(&self) -> Result<u64> {
+        let hex: String = self.rpc.call("eth_blockNumber", json!([]))?;
+        abi::parse_quantity(&hex)
+            .and_then(|n| u64::try_from(n).ok())
+            .ok_or_else(|| Error::invalid_response("eth_blockNumber", hex))
+    }
+
+    /// The header timestamp of block `block`, in seconds since the Unix
+    /// epoch.
+    pub fn block_timestamp(&self, block: u64) -> Result<u64> {
+        let header: Value = self
+            .rpc
+            .call("eth_getBlockByNumber", json!([block_tag(block), false]))?;
+        header["timestamp"]
+            .as_str()
+            .and_then(abi::parse_quantity)
+            .and_then(|t| u64::try_from(t).ok())
+            .ok_or_else(|| {
+                Error::invalid_response(
+                    "eth_getBlockByNumber",
+                    format!("no timestamp for block {block}: {header}"),
+                )
+            })
+    }
+
+    /// The last block mined at or before `time`, found by bisecting header
+    /// timestamps, which takes about log2(chain height) requests.
+    pub fn block_at_time(&self, time: u64) -> Result<u64> {
+        let latest = self.block_number()?;
+        if self.block_timestamp(latest)? <= time {
+            return Ok(latest);
+        }
+        if latest == 0 || self.block_timestamp(0)? > time {
+            return Err(Error::BeforeGenesis { time });
+        }
+        // Invariant: block `low` is at or before `time`, `high` is after it.
+        let (mut low, mut high) = (0, latest);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if self.block_timestamp(mid)? <= time {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    /// The blocks numbered `from` through `to`, with their transactions, in
+    /// as few HTTP requests as the client's batch size allows.
+    pub fn blocks(&self, from: u64, to: u64) -> Result<Vec<Block>> {
+        self.blocks_numbered(&(from..=to).collect::<Vec<_>>())
+    }
+
+    /// The blocks `numbers`, with their transactions, in order.
+    pub fn blocks_numbered(&self, numbers: &[u64]) -> Result<Vec<Block>> {
+        let method = "eth_getBlockByNumber";
+        let calls: Vec<RpcCall> = numbers
+            .iter()
+            .map(|block| RpcCall::new(method, json!([block_tag(*block), true])))
+            .collect();
+        self.rpc
+            .batch(&calls)?
+            .into_iter()
+            .zip(numbers)
+            .map(|(result, number)| {
+                let block = result?;
+                parse_block(&block).ok_or_else(|| {
+                    Error::invalid_response(method, format!("block {number}: {block}"))
+                })
+            })
+            .collect()
+    }
+
+    /// The receipts of the transactions `hashes`, in order.
+    pub fn receipts(&self, hashes: &[String]) -> Result<Vec<Receipt>> {
+        let method = "eth_getTransactionReceipt";
+        let calls: Vec<RpcCall> = hashes
+            .iter()
+            .map(|hash| RpcCall::new(method, json!([hash])))
+            .collect();
+        self.rpc
+            .batch(&calls)?
+            .into_iter()
+            .zip(hashes)
+            .map(|(result, hash)| {
+                let receipt = result?;
+                parse_receipt(&receipt).ok_or_else(|| {
+                    Error::invalid_response(method, format!("transaction {hash}: {receipt}"))
+                })
+            })
+            .collect()
+    }
+
+    /// Runs every query against block `block` in as few HTTP requests as the
+    /// client's batch size allows, returning raw base-unit values in query
+    /// order.
+    pub fn balances(&self, queries: &[BalanceQuery], block: u64) -> Result<Vec<U256>> {
+        let tag = block_tag(block);
+        let calls: Vec<RpcCall> = queries
+            .iter()
+            .map(|q| match q {
+                BalanceQuery::Native { holder } => {
+                    RpcCall::new("eth_getBalance", json!([holder.to_lower_hex(), tag]))
+                }
+                BalanceQuery::Nonce { holder } => RpcCall::new(
+                    "eth_getTransactionCount",
+                    json!([holder.to_lower_hex(), tag]),
+                ),
+                BalanceQuery::Token { token, holder } => eth_call(
+                    token,
+                    abi::encode_call(BALANCE_OF, &[abi::address_word(holder)]),
+                    &tag,
+                ),
+                BalanceQuery::Erc721 { token, id, .. } => eth_call(
+                    token,
+                    abi::encode_call(OWNER_OF, &[abi::uint_word(*id)]),
+                    &tag,
+                ),
+                BalanceQuery::Erc1155 { token, holder, id } => eth_call(
+                    token,
+                    abi::encode_call(
+                        BALANCE_OF_ID,
+                        &[abi::address_word(holder), abi::uint_word(*id)],
+                    ),
+                    &tag,
+                ),
+                BalanceQuery::Rate { token, selector } => {
+                    eth_call(token, abi::encode_call(*selector, &[]), &tag)
+                }
+            })
+            .collect();
+        self.rpc
+            .batch(&calls)?
+            .into_iter()
+            .zip(queries)
+            .map(|(result, query)| {
+                if let BalanceQuery::Erc721 { holder, .. } = query {
+                    let owner = result
+                        .ok()
+                        .and_then(|value| call_data(&value))
+                        .filter(|data| data.len() >= 32)
+                        .map(|data| abi::word_address(data[..32].try_into().unwrap()));
+                    return Ok(U256::from(u8::from(owner == Some(*holder))));
+                }
+                let value = result?;
+                match query {
+                    BalanceQuery::Native { .. } => {
+                        value.as_str().and_then(abi::parse_quantity).ok_or_else(|| {
+                            Error::invalid_response("eth_getBalance", value.to_string())
+                        })
+                    }
+                    BalanceQuery::Nonce { .. } => {
+                        value.as_str().and_then(abi::parse_quantity).ok_or_else(|| {
+                            Error::invalid_response("eth_getTransactionCount", value.to_string())
+                        })
+                    }
+                    BalanceQuery::Erc721 { .. } => unreachable!("answered above"),
+                    BalanceQuery::Token { token, .. } | BalanceQuery::Erc1155 { token, .. } => {
+                        call_data(&value)
+                            .and_then(|data| abi::decode_uint(&data))
+                            .ok_or_else(|| {
+                                Error::invalid_response(
+                                    "eth_call",
+                                    format!("balanceOf on {token} returned {value}"),
+                                )
+                            })
+                    }
+                    BalanceQuery::Rate { token, .. } => call_data(&value)
+                        .and_then(|data| abi::decode_uint(&data))
+                        .ok_or_else(|| {
+                            Error::invalid_response(
+                                "eth_call",
+                                format!("rate of {token} returned {value}"),
+                            )
+                        }),
+                }
+            })
+            .collect()
+    }
+
+    /// Reads `symbol()`, `name()` and `decimals()` from an ERC-20 contract in
+    /// one batch.
+    pub fn token_metadata(&self, token: &EthAddress, block: u64) -> Result<TokenMetadata> {
+        let tag = block_tag(block);
+        let calls =
+            [SYMBOL, NAME, DECIMALS].map(|sel| eth_call(token, abi::encode_call(sel, &[]), &tag));
+        let results = self.rpc.batch(&calls)?;
+        let data = |i: usize| results[i].as_ref().ok().and_then(call_data);
+        let bad = |what: &str| {
+            Error::invalid_response("eth_call", format!("no usable {what}() on {token}"))
+        };
+        let symbol = data(0)
+            .and_then(|d| abi::decode_string(&d))
+            .ok_or_else(|| bad("symbol"))?;
+        let decimals = data(2)
+            .and_then(|d| abi::decode_uint(&d))
+            .and_then(|d| u8::try_from(d).ok())
+            .ok_or_else(|| bad("decimals"))?;
+        // `name()` is optional in ERC-20; fall back to the symbol.
+        let name = data(1)
+            .and_then(|d| abi::decode_string(&d))
+            .unwrap_or_else(|| symbol.clone());
+        Ok(TokenMetadata {
+            symbol,
+            name,
+            decimals,
+        })
+    }
+
+    /// `symbol()` of each contract, or `None` where it has none, as NFT
+    /// contracts may not.
+    pub fn token_symbols(&self, tokens: &[EthAddress], block: u64) -> Result<Vec<Option<String>>> {
+        let tag = block_tag(block);
+        let calls: Vec<RpcCall> = tokens
+            .iter()
+            .map(|token| eth_call(token, abi::encode_call(SYMBOL, &[]), &tag))
+            .collect();
+        Ok(self
+            .rpc
+            .batch(&calls)?
+            .into_iter()
+            .map(|result| {
+                result
+                    .ok()
+                    .and_then(|value| call_data(&value))
+                    .and_then(|data| abi::decode_string(&data))
+            })
+            .collect())
+    }
+
+    /// Every log matching `filter`, oldest first.
+    ///
+    /// The range is queried in chunks of at most the client's maximum log
+    /// range. A chunk the node refuses, usually for returning too many
+    /// logs, is halved and retried down to single blocks.
+    pub fn logs(&self, filter: &LogFilter) -> Result<Vec<Log>> {
+        let mut logs = Vec::new();
+        let mut from = filter.from_block;
+        while from <= filter.to_block {
+            let to = filter
+                .to_block
+                .min(from.saturating_add(self.max_log_range - 1));
+            self.logs_in(filter, from, to, &mut logs)?;
+            from = to + 1;
+        }
+        Ok(logs)
+    }
+
+    fn logs_in(&self, filter: &LogFilter, from: u64, to: u64, logs: &mut Vec<Log>) -> Result<()> {
+        let mut query = json!({ "fromBlock": block_tag(from), "toBlock": block_tag(to) });
+        if !filter.addresses.is_empty() {
+            let addresses: Vec<String> = filter
+                .addresses
+                .iter()
+                .map(EthAddress::to_lower_hex)
+                .collect();
+            query["address"] = json!(addresses);
+        }
+        let topics: Vec<Value> = filter
+            .topics
+            .iter()
+            .map(|any_of| match any_of.as_slice() {
+                [] => Value::Null,
+                words => json!(words.iter().map(abi::word_hex).collect::<Vec<_>>()),
+            })
+            .collect();
+        query["topics"] = json!(topics);
+        match self.rpc.call::<Vec<Value>>("eth_getLogs", json!([query])) {
+            Ok(found) => {
+                for log in found {
+                    logs.push(
+                        parse_log(&log).ok_or_else(|| {
+                            Error::invalid_response("eth_getLogs", log.to_string())
+                        })?,
+                    );
+                }
+                Ok(())
+            }
+            Err(RpcError::Server { .. }) if from < to => {
+                let mid = from + (to - from) / 2;
+                self.logs_in(filter, from, mid, logs)?;
+                self.logs_in(filter, mid + 1, to, logs)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn parse_log(log: &Value) -> Option<Log> {
+    let quantity = |key: &str| {
+        log[key]
+            .as_str()
+            .and_then(abi::parse_quantity)
+            .and_then(|n| u64::try_from(n).ok())
+    };
+    let topics = log["topics"]
+        .as_array()?
+        .iter()
+        .map(|t| abi::decode_hex(t.as_str()?)?.try_into().ok())
+        .collect::<Option<Vec<[u8; 32]>>>()?;
+    Some(Log {
+        address: log["address"].as_str()?.parse().ok()?,
+        topics,
+        data: abi::decode_hex(log["data"].as_str()?)?,
+        block_number: quantity("blockNumber")?,
+        transaction_hash: log["transactionHash"].as_str()?.to_owned(),
+        log_index: quantity("logIndex")?,
+    })
+}
+
+fn parse_block(block: &Value) -> Option<Block> {
+    let quantity = |value: &Value| value.as_str().and_then(abi::parse_quantity);
+    let transactions = block["transactions"]
+        .as_array()?
+        .iter()
+        .map(|tx| {
+            Some(BlockTransaction {
+                hash: tx["hash"].as_str()?.to_owned(),
+                from: tx["from"].as_str()?.parse().ok()?,
+                to: match tx["to"].as_str() {
+                    Some(to) => Some(to.parse().ok()?),
+                    None => None,
+                },
+                value: quantity(&tx["value"])?,
+            })
+        })
+        .collect::<Option<_>>()?;
+    Some(Block {
+        number: u64::try_from(quantity(&block["number"])?).ok()?,
+        timestamp: u64::try_from(quantity(&block["timestamp"])?).ok()?,
+        transactions,
+    })
+}
+
+fn parse_receipt(receipt: &Value) -> Option<Receipt> {
+    let quantity = |key: &str| receipt[key].as_str().and_then(abi::parse_quantity);
+    let gas = quantity("gasUsed")?.checked_mul(quantity("effectiveGasPrice")?)?;
+    let l1_fee = receipt["l1Fee"]
+        .as_str()
+        .map_or(Some(U256::ZERO), abi::parse_quantity)?;
+    Some(Receipt {
+        transaction_hash: receipt["transactionHash"].as_str()?.to_owned(),
+        success: quantity("status")? == U256::from(1),
+        fee: gas.checked_add(l1_fee)?,
+    })
+}
+
+fn block_tag(block: u64) -> String {
+    format!("0x{block:x}")
+}
+
+fn eth_call(to: &EthAddress, data: String, tag: &str) -> RpcCall {
+    RpcCall::new(
+        "eth_call",
+        json!([{ "to": to.to_lower_hex(), "data": data }, tag]),
+    )
+}
+
+/// Return data of an `eth_call`, or `None` if it is empty (no contract at
+/// the address) or malformed.
+fn call_data(value: &Value) -> Option<Vec<u8>> {
+    value
+        .as_str()
+        .and_then(abi::decode_hex)
+        .filter(|data| !data.is_empty())
+}
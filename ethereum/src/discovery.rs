@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use walletb_core::U256;
+
+use crate::abi::{self, TRANSFER, TRANSFER_BATCH, TRANSFER_SINGLE};
+use crate::{EthAddress, EthClient, Log, LogFilter, Result};
+
+/// The token interface a contract was seen using.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TokenStandard {
+    Erc20,
+    Erc721,
+    Erc1155,
+}
+
+/// A token that one of the scanned addresses received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FoundToken {
+    pub holder: EthAddress,
+    pub contract: EthAddress,
+    pub standard: TokenStandard,
+    /// The item, for ERC-721 and ERC-1155 tokens.
+    pub token_id: Option<U256>,
+}
+
+/// The tokens a log scan found, each with the block it was first received
+/// in, and the last block scanned.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenDiscovery {
+    pub found: BTreeMap<FoundToken, u64>,
+    pub scanned_to: Option<u64>,
+}
+
+impl TokenDiscovery {
+    /// The tokens received at or before `block`.
+    pub fn tokens_at(&self, block: u64) -> impl Iterator<Item = &FoundToken> {
+        self.found
+            .iter()
+            .filter(move |(_, first)| **first <= block)
+            .map(|(token, _)| token)
+    }
+
+    /// Adds a later scan's findings, keeping the earliest block for tokens
+    /// found by both.
+    pub fn merge(&mut self, later: TokenDiscovery) {
+        for (token, block) in later.found {
+            let first = self.found.entry(token).or_insert(block);
+            *first = (*first).min(block);
+        }
+        self.scanned_to = self.scanned_to.max(later.scanned_to);
+    }
+}
+
+/// Finds every token paid to `holders` between `from_block` and `to_block`
+/// from `Transfer` (ERC-20 and ERC-721), `TransferSingle` and
+/// `TransferBatch` (ERC-1155) logs.
+///
+/// Minting is a transfer from the zero address, so it is found too. Only
+/// incoming transfers are scanned: a token that was never received cannot
+/// be held. Whether it is still held is for a balance read to tell.
+pub fn discover_tokens(
+    client: &EthClient,
+    holders: &[EthAddress],
+    from_block: u64,
+    to_block: u64,
+) -> Result<TokenDiscovery> {
+    let mut discovery = TokenDiscovery {
+        found: BTreeMap::new(),
+        scanned_to: Some(to_block),
+    };
+    if holders.is_empty() || from_block > to_block {
+        return Ok(discovery);
+    }
+    let holders: Vec<[u8; 32]> = holders.iter().map(abi::address_word).collect();
+    let filter = |topics| LogFilter {
+        from_block,
+        to_block,
+        addresses: Vec::new(),
+        topics,
+    };
+    // The recipient is the second indexed argument of `Transfer` and the
+    // third of the ERC-1155 events.
+    let transfers = client.logs(&filter(vec![
+        vec![abi::event_topic(TRANSFER)],
+        Vec::new(),
+        holders.clone(),
+    ]))?;
+    let multi = client.logs(&filter(vec![
+        vec![
+            abi::event_topic(TRANSFER_SINGLE),
+            abi::event_topic(TRANSFER_BATCH),
+        ],
+        Vec::new(),
+        Vec::new(),
+        holders,
+    ]))?;
+    for log in transfers.iter().chain(&multi) {
+        for token in tokens_in(log) {
+            let first = discovery.found.entry(token).or_insert(log.block_number);
+            *first = (*first).min(log.block_number);
+        }
+    }
+    Ok(discovery)
+}
+
+/// The tokens a transfer log pays out. Logs that do not decode, such as
+/// ones from contracts that reuse the event names with other layouts, pay
+/// nothing.
+fn tokens_in(log: &Log) -> Vec<FoundToken> {
+    let found = |holder: &[u8; 32], standard, token_id| FoundToken {
+        holder: abi::word_address(holder),
+        contract: log.address,
+        standard,
+        token_id,
+    };
+    let transfer = abi::event_topic(TRANSFER);
+    let single = abi::event_topic(TRANSFER_SINGLE);
+    let batch = abi::event_topic(TRANSFER_BATCH);
+    match log.topics.as_slice() {
+        [topic, _, to] if *topic == transfer => vec![found(to, TokenStandard::Erc20, None)],
+        [topic, _, to, id] if *topic == transfer => vec![found(
+            to,
+            TokenStandard::Erc721,
+            Some(U256::from_be_bytes(*id)),
+        )],
+        [topic, _, _, to] if *topic == single => abi::decode_uint(&log.data)
+            .map(|id| found(to, TokenStandard::Erc1155, Some(id)))
+            .into_iter()
+            .collect(),
+        [topic, _, _, to] if *topic == batch => abi::decode_uint_array(&log.data, 0)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|id| found(to, TokenStandard::Erc1155, Some(id)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,49 @@
+use walletb_core::RpcError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid address `{address}`: {reason}")]
+    InvalidAddress { address: String, reason: String },
+
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error(transparent)]
+    Core(#[from] walletb_core::Error),
+
+    #[error("no block at or before unix time {time}")]
+    BeforeGenesis { time: u64 },
+
+    #[error("block {0} means a different block on each chain; read at a date instead")]
+    HeightAcrossChains(u64),
+
+    #[error("listing transactions needs a start: a date or block to list from, or token discovery's first block")]
+    NoStartBlock,
+
+    #[error("invalid response to {method}: {reason}")]
+    InvalidResponse { method: String, reason: String },
+}
+
+impl Error {
+    pub(crate) fn invalid_response(method: &str, reason: impl Into<String>) -> Self {
+        Error::InvalidResponse {
+            method: method.to_owned(),
+            reason: reason.into(),
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<Error> for walletb_core::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::InvalidAddress { address, reason } => {
+                walletb_core::Error::InvalidAddress { address, reason }
+            }
+            Error::Rpc(err) => walletb_core::Error::Rpc(err),
+            Error::Core(err) => err,
+            other => walletb_core::Error::source(other),
+        }
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use walletb_core::{
+    Address, Amount, Asset, BalanceSource, LedgerEntry, Period, PointInTime, TransactionSource,
+    U256,
+};
+
+use crate::abi::{self, TRANSFER, TRANSFER_BATCH, TRANSFER_SINGLE};
+use crate::multichain::on_chain;
+use crate::{
+    BalanceQuery, Error, EthAddress, EthereumSource, FoundToken, Log, LogFilter, MultiChainSource,
+    Result, TokenStandard,
+};
+
+/// Blocks fetched per round of a transaction scan.
+const SCAN_CHUNK: usize = 1_000;
+
+/// One movement of value inside a transaction.
+struct Transfer {
+    /// `None` for the native asset.
+    token: Option<FoundToken>,
+    from: EthAddress,
+    to: EthAddress,
+    amount: U256,
+}
+
+/// What one transaction did, gathered from its block, receipt and logs.
+#[derive(Default)]
+struct Activity {
+    transfers: Vec<Transfer>,
+    /// Whether one of the holders sent it, and so paid its fee.
+    sent: bool,
+    fee: U256,
+    /// The address a holder's transaction called, named when it moved
+    /// nothing but its fee.
+    called: Option<EthAddress>,
+}
+
+impl EthereumSource {
+    /// The first and last block of `period`: the one after `after`, or
+    /// token discovery's first block, through `until`, or the latest.
+    pub fn blocks_in(&self, period: Period) -> Result<(u64, u64)> {
+        let from = match period.after {
+            Some(after) => self.block_for(after)? + 1,
+            None => self.discover_from.ok_or(Error::NoStartBlock)?,
+        };
+        let to = match period.until {
+            Some(until) => self.block_for(until)?,
+            None => self.resolve_block()?,
+        };
+        Ok((from, to))
+    }
+
+    /// The ledger of `addresses` from block `from` through `to`.
+    ///
+    /// `Transfer` logs give the addresses' ERC-20 and ERC-721 movements and
+    /// `TransferSingle` and `TransferBatch` logs their ERC-1155 ones. The
+    /// transactions they sent or were sent ether in are found by bisecting
+    /// the range on their ether balances and nonces, so only blocks where
+    /// those changed are read. Ether that contracts pay out in internal
+    /// calls leaves neither trace, so it shows in balances but not here;
+    /// that takes a tracing node.
+    pub fn transactions_between(
+        &self,
+        addresses: &[Address],
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<LedgerEntry>> {
+        let holders = addresses
+            .iter()
+            .map(|a| {
+                a.expect_chain(self.chain())?;
+                a.as_str().parse()
+            })
+            .collect::<Result<HashSet<EthAddress>>>()?;
+        if holders.is_empty() || from > to {
+            return Ok(Vec::new());
+        }
+        // Position of each transaction in the chain, and block times.
+        let mut order: HashMap<String, (u64, usize)> = HashMap::new();
+        let mut times: HashMap<u64, u64> = HashMap::new();
+        let mut activity: HashMap<String, Activity> = HashMap::new();
+        let logs = self.transfer_logs(&holders, from, to)?;
+        let mut blocks = self.ether_blocks(&holders, from, to)?;
+        blocks.extend(logs.iter().map(|log| log.block_number));
+        let blocks: Vec<u64> = blocks.into_iter().collect();
+        for chunk in blocks.chunks(SCAN_CHUNK) {
+            for block in self.client.blocks_numbered(chunk)? {
+                times.insert(block.number, block.timestamp);
+                for (index, tx) in block.transactions.into_iter().enumerate() {
+                    order.insert(tx.hash.clone(), (block.number, index));
+                    let sent = holders.contains(&tx.from);
+                    let paid = tx.to.is_some_and(|to| holders.contains(&to));
+                    if !sent && !paid {
+                        continue;
+                    }
+                    let entry = activity.entry(tx.hash).or_default();
+                    if sent {
+                        entry.sent = true;
+                        entry.called = tx.to;
+                    }
+                    if let (Some(to), false) = (tx.to, tx.value.is_zero()) {
+                        entry.transfers.push(Transfer {
+                            token: None,
+                            from: tx.from,
+                            to,
+                            amount: tx.value,
+                        });
+                    }
+                }
+            }
+        }
+
+        let hashes: Vec<String> = activity.keys().cloned().collect();
+        for receipt in self.client.receipts(&hashes)? {
+            let Some(entry) = activity.get_mut(&receipt.transaction_hash) else {
+                continue;
+            };
+            if entry.sent {
+                entry.fee = receipt.fee;
+            }
+            if !receipt.success {
+                entry.transfers.clear();
+            }
+        }
+
+        for log in logs {
+            let transfers = transfers(&log, &holders);
+            if transfers.is_empty() {
+                continue;
+            }
+            activity
+                .entry(log.transaction_hash.clone())
+                .or_default()
+                .transfers
+                .extend(transfers);
+            order
+                .entry(log.transaction_hash)
+                .or_insert((log.block_number, usize::MAX));
+        }
+
+        let tokens: Vec<FoundToken> = activity
+            .values()
+            .flat_map(|a| &a.transfers)
+            .filter_map(|t| t.token)
+            .collect();
+        let assets = self.token_assets(&tokens, to)?;
+
+        let mut sorted: Vec<(String, Activity)> = activity.into_iter().collect();
+        sorted.sort_by_key(|(hash, _)| order.get(hash).copied().unwrap_or((u64::MAX, 0)));
+        let mut entries = Vec::new();
+        for (hash, activity) in sorted {
+            let (height, _) = order[&hash];
+            let time = times.get(&height).copied().unwrap_or_default();
+            // Native asset first, then tokens by asset.
+            let mut moves: BTreeMap<(bool, Asset), Vec<&Transfer>> = BTreeMap::new();
+            for transfer in &activity.transfers {
+                let asset = match &transfer.token {
+                    None => self.native.clone(),
+                    Some(token) => match assets.get(token) {
+                        Some(asset) => asset.clone(),
+                        // A contract without usable metadata, usually spam.
+                        None => continue,
+                    },
+                };
+                moves
+                    .entry((!asset.is_native(), asset))
+                    .or_default()
+                    .push(transfer);
+            }
+            if !activity.fee.is_zero() {
+                moves.entry((false, self.native.clone())).or_default();
+            }
+            for ((_, asset), transfers) in moves {
+                let mut received = U256::ZERO;
+                let mut sent = U256::ZERO;
+                for transfer in &transfers {
+                    if holders.contains(&transfer.to) {
+                        received = received.saturating_add(transfer.amount);
+                    }
+                    if holders.contains(&transfer.from) {
+                        sent = sent.saturating_add(transfer.amount);
+                    }
+                }
+                let fee = if asset.is_native() {
+                    activity.fee
+                } else {
+                    U256::ZERO
+                };
+                let (received, sent) = if received >= sent {
+                    (received - sent, U256::ZERO)
+                } else {
+                    (U256::ZERO, sent - received)
+                };
+                if received.is_zero() && sent.is_zero() && fee.is_zero() {
+                    continue;
+                }
+                let counterparty = transfers
+                    .iter()
+                    .find_map(|t| {
+                        let (ours, theirs) = if received.is_zero() {
+                            (t.from, t.to)
+                        } else {
+                            (t.to, t.from)
+                        };
+                        (holders.contains(&ours) && !holders.contains(&theirs)).then_some(theirs)
+                    })
+                    .or(activity.called.filter(|_| transfers.is_empty()))
+                    .map(|address| address.to_checksum());
+                entries.push(LedgerEntry {
+                    txid: hash.clone(),
+                    height,
+                    time,
+                    asset,
+                    received: Amount::from_base_units(received),
+                    sent: Amount::from_base_units(sent),
+                    fee: Amount::from_base_units(fee),
+                    counterparty,
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The blocks from `from` through `to` in which a holder's ether
+    /// balance or nonce changed.
+    ///
+    /// A span whose ends agree on every balance and nonce is skipped whole:
+    /// a holder's ether only leaves in transactions it sends, which raise
+    /// its nonce, so nothing was sent or received in between. A contract
+    /// holder paying ether back out in internal calls can hide its receipts
+    /// this way, as it hides the payouts themselves.
+    fn ether_blocks(
+        &self,
+        holders: &HashSet<EthAddress>,
+        from: u64,
+        to: u64,
+    ) -> Result<BTreeSet<u64>> {
+        let queries: Vec<BalanceQuery> = holders
+            .iter()
+            .flat_map(|&holder| {
+                [
+                    BalanceQuery::Native { holder },
+                    BalanceQuery::Nonce { holder },
+                ]
+            })
+            .collect();
+        // Genesis has no transactions, so its state stands in for the one
+        // before it.
+        let low = from.saturating_sub(1);
+        let mut blocks = BTreeSet::new();
+        let mut spans = vec![(
+            low,
+            self.client.balances(&queries, low)?,
+            to,
+            self.client.balances(&queries, to)?,
+        )];
+        while let Some((low, before, high, after)) = spans.pop() {
+            if before == after {
+                continue;
+            }
+            if high - low == 1 {
+                blocks.insert(high);
+                continue;
+            }
+            let mid = low + (high - low) / 2;
+            let state = self.client.balances(&queries, mid)?;
+            spans.push((low, before, mid, state.clone()));
+            spans.push((mid, state, high, after));
+        }
+        Ok(blocks)
+    }
+
+    /// `Transfer`, `TransferSingle` and `TransferBatch` logs from or to
+    /// `holders`, each once.
+    fn transfer_logs(&self, holders: &HashSet<EthAddress>, from: u64, to: u64) -> Result<Vec<Log>> {
+        let words: Vec<[u8; 32]> = holders.iter().map(abi::address_word).collect();
+        let filter = |topics| LogFilter {
+            from_block: from,
+            to_block: to,
+            addresses: Vec::new(),
+            topics,
+        };
+        let transfer = vec![abi::event_topic(TRANSFER)];
+        // The ERC-1155 events name the operator first.
+        let multi = vec![
+            abi::event_topic(TRANSFER_SINGLE),
+            abi::event_topic(TRANSFER_BATCH),
+        ];
+        let mut logs = Vec::new();
+        for topics in [
+            vec![transfer.clone(), words.clone()],
+            vec![transfer, Vec::new(), words.clone()],
+            vec![multi.clone(), Vec::new(), words.clone()],
+            vec![multi, Vec::new(), Vec::new(), words],
+        ] {
+            logs.extend(self.client.logs(&filter(topics))?);
+        }
+        let mut seen = HashSet::new();
+        logs.retain(|log| seen.insert((log.transaction_hash.clone(), log.log_index)));
+        Ok(logs)
+    }
+
+    /// The asset each token is counted as: a configured token, or one read
+    /// from the contract as discovery would.
+    fn token_assets(
+        &self,
+        tokens: &[FoundToken],
+        block: u64,
+    ) -> Result<HashMap<FoundToken, Asset>> {
+        let mut assets = HashMap::new();
+        let mut unknown = Vec::new();
+        for token in tokens {
+            let configured = self.tokens.iter().find(|asset| {
+                asset
+                    .contract()
+                    .and_then(|c| c.parse::<EthAddress>().ok())
+                    .is_some_and(|contract| contract == token.contract)
+            });
+            match configured {
+                Some(asset) if token.token_id.is_none() => {
+                    assets.insert(*token, asset.clone());
+                }
+                _ => unknown.push(*token),
+            }
+        }
+        let found = self.found_assets(&unknown, block)?;
+        for (token, asset) in unknown.into_iter().zip(found) {
+            if let Some(asset) = asset {
+                assets.insert(token, asset);
+            }
+        }
+        Ok(assets)
+    }
+}
+
+/// The movements a transfer log records: an ERC-20 amount, one ERC-721
+/// item, or amounts of ERC-1155 items. Logs that do not decode move
+/// nothing.
+fn transfers(log: &Log, holders: &HashSet<EthAddress>) -> Vec<Transfer> {
+    let moved = |from: &[u8; 32], to: &[u8; 32], standard, token_id, amount| {
+        let (from, to) = (abi::word_address(from), abi::word_address(to));
+        Transfer {
+            token: Some(FoundToken {
+                holder: if holders.contains(&to) { to } else { from },
+                contract: log.address,
+                standard,
+                token_id,
+            }),
+            from,
+            to,
+            amount,
+        }
+    };
+    let transfer = abi::event_topic(TRANSFER);
+    let single = abi::event_topic(TRANSFER_SINGLE);
+    let batch = abi::event_topic(TRANSFER_BATCH);
+    match log.topics.as_slice() {
+        [topic, from, to] if *topic == transfer => abi::decode_uint(&log.data)
+            .map(|amount| moved(from, to, TokenStandard::Erc20, None, amount))
+            .into_iter()
+            .collect(),
+        [topic, from, to, id] if *topic == transfer => vec![moved(
+            from,
+            to,
+            TokenStandard::Erc721,
+            Some(U256::from_be_bytes(*id)),
+            U256::from(1),
+        )],
+        [topic, _, from, to] if *topic == single => {
+            let id = abi::decode_uint(&log.data);
+            let amount = log.data.get(32..).and_then(abi::decode_uint);
+            id.zip(amount)
+                .map(|(id, amount)| moved(from, to, TokenStandard::Erc1155, Some(id), amount))
+                .into_iter()
+                .collect()
+        }
+        [topic, _, from, to] if *topic == batch => {
+            let ids = abi::decode_uint_array(&log.data, 0).unwrap_or_default();
+            let amounts = abi::decode_uint_array(&log.data, 32).unwrap_or_default();
+            if ids.len() != amounts.len() {
+                return Vec::new();
+            }
+            ids.into_iter()
+                .zip(amounts)
+                .map(|(id, amount)| moved(from, to, TokenStandard::Erc1155, Some(id), amount))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+impl TransactionSource for EthereumSource {
+    fn transactions(
+        &self,
+        addresses: &[Address],
+        period: Period,
+    ) -> walletb_core::Result<Vec<LedgerEntry>> {
+        let (from, to) = self.blocks_in(period)?;
+        Ok(self.transactions_between(addresses, from, to)?)
+    }
+}
+
+/// Lists every chain's transactions over a period of dates, as block
+/// heights differ from chain to chain.
+impl TransactionSource for MultiChainSource {
+    fn transactions(
+        &self,
+        addresses: &[Address],
+        period: Period,
+    ) -> walletb_core::Result<Vec<LedgerEntry>> {
+        for at in [period.after, period.until].into_iter().flatten() {
+            if let PointInTime::Height(height) = at {
+                return Err(Error::HeightAcrossChains(height).into());
+            }
+        }
+        let mut entries = Vec::new();
+        for source in self.sources() {
+            entries.extend(source.transactions(&on_chain(addresses, source.chain())?, period)?);
+        }
+        entries.sort_by_key(|entry| entry.time);
+        Ok(entries)
+    }
+}
@@ -0,0 +1,56 @@
+//! Ethereum support for walletb.
+//!
+//! [`EthereumSource`] reads native ether and ERC-20 balances over JSON-RPC.
+//! Every read in a snapshot is pinned to one block number and sent as a
+//! single batched request, so the balances are mutually consistent even
+//! while the chain advances.
+//!
+//! With [`with_token_discovery`](EthereumSource::with_token_discovery) the
+//! source also finds the ERC-20, ERC-721 and ERC-1155 tokens an address has
+//! received by scanning transfer logs, so they need not be listed by hand.
+//!
+//! Any EVM chain is read the same way. [`ChainRegistry`] knows the native
+//! asset, endpoints and major tokens of the common ones, and
+//! [`MultiChainSource`] reads one set of addresses on several chains at
+//! once, keeping a balance per chain.
+//!
+//! The same reads work at any past block or date through
+//! [`HistoricalSource`](walletb_core::HistoricalSource), given an archive
+//! node.
+//!
+//! Staked ether is reported apart from spendable ether: [`BeaconSource`]
+//! reads validator balances from a consensus node's Beacon API, and
+//! [`LiquidStakingToken`]s such as stETH and rETH carry their value in
+//! ether.
+
+mod abi;
+mod address;
+mod beacon;
+mod client;
+mod discovery;
+mod error;
+mod ledger;
+mod multichain;
+mod registry;
+mod source;
+mod staking;
+
+pub use address::EthAddress;
+pub use beacon::{BeaconClient, BeaconSource, Validator, ValidatorKey, ValidatorStatus};
+pub use client::{
+    BalanceQuery, Block, BlockTransaction, EthClient, Log, LogFilter, Receipt, TokenMetadata,
+    DEFAULT_MAX_LOG_RANGE,
+};
+pub use discovery::{discover_tokens, FoundToken, TokenDiscovery, TokenStandard};
+pub use error::{Error, Result};
+pub use multichain::MultiChainSource;
+pub use registry::{ChainRegistry, EvmChain};
+pub use source::{EthereumSource, Holding, Snapshot};
+pub use staking::{LiquidStakingToken, Redemption};
+
+use walletb_core::{Asset, Chain};
+
+/// Ether on Ethereum mainnet, denominated in wei.
+pub fn eth() -> Asset {
+    Asset::native(Chain::ETHEREUM, "ETH", 18)
+}
@@ -0,0 +1,92 @@
+use walletb_core::{Address, Balance, BalanceSource, Chain, HistoricalSource, PointInTime};
+
+use crate::{Error, EthereumSource, Result, Snapshot};
+
+/// A [`BalanceSource`] that reads the same addresses on several EVM chains.
+///
+/// An EVM address is the same key on every chain, so addresses may be
+/// tagged with any EVM chain; each chain's source sees them as its own.
+/// Balances keep the chain of their asset, so ether on Ethereum and ether
+/// on Arbitrum stay apart.
+#[derive(Debug, Default)]
+pub struct MultiChainSource {
+    sources: Vec<EthereumSource>,
+}
+
+impl MultiChainSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chain. The first one added is the chain the source reports
+    /// as its own.
+    pub fn with_source(mut self, source: EthereumSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn sources(&self) -> &[EthereumSource] {
+        &self.sources
+    }
+
+    pub fn chains(&self) -> Vec<Chain> {
+        self.sources.iter().map(|s| s.chain()).collect()
+    }
+
+    /// Reads a snapshot on every chain, each at its own latest block.
+    pub fn snapshots(&self, addresses: &[Address]) -> Result<Vec<Snapshot>> {
+        self.sources
+            .iter()
+            .map(|source| source.snapshot(&on_chain(addresses, source.chain())?))
+            .collect()
+    }
+}
+
+/// `addresses` re-tagged for `chain`.
+pub(crate) fn on_chain(addresses: &[Address], chain: Chain) -> Result<Vec<Address>> {
+    addresses
+        .iter()
+        .map(|a| match a.chain {
+            Chain::Evm(_) => Ok(Address::new(chain, a.as_str())),
+            actual => Err(walletb_core::Error::ChainMismatch {
+                address: a.to_string(),
+                expected: chain,
+                actual,
+            }
+            .into()),
+        })
+        .collect()
+}
+
+impl BalanceSource for MultiChainSource {
+    fn chain(&self) -> Chain {
+        self.sources.first().map_or(Chain::ETHEREUM, |s| s.chain())
+    }
+
+    fn balances(&self, addresses: &[Address]) -> walletb_core::Result<Vec<Balance>> {
+        Ok(self
+            .snapshots(addresses)?
+            .iter()
+            .flat_map(Snapshot::totals)
+            .collect())
+    }
+}
+
+/// Reads every chain as of a date. Block heights differ from chain to
+/// chain, so a height is refused.
+impl HistoricalSource for MultiChainSource {
+    fn balances_at(
+        &self,
+        addresses: &[Address],
+        at: PointInTime,
+    ) -> walletb_core::Result<Vec<Balance>> {
+        if let PointInTime::Height(height) = at {
+            return Err(Error::HeightAcrossChains(height).into());
+        }
+        let mut balances = Vec::new();
+        for source in &self.sources {
+            balances.extend(source.balances_at(&on_chain(addresses, source.chain())?, at)?);
+        }
+        Ok(balances)
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+
+use walletb_core::{Asset, Chain};
+
+use crate::{EthClient, EthereumSource, LiquidStakingToken, Redemption};
+
+/// What walletb knows about one EVM chain: its native asset, where to reach
+/// it and the tokens worth reading on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvmChain {
+    /// The EIP-155 chain id.
+    pub id: u64,
+    pub name: String,
+    pub native: Asset,
+    /// JSON-RPC endpoints, preferred first.
+    pub rpc_urls: Vec<String>,
+    /// ERC-20 tokens read for every address on the chain.
+    pub tokens: Vec<Asset>,
+    /// Liquid-staking tokens read for every address, valued in the native
+    /// asset.
+    pub liquid_staking: Vec<LiquidStakingToken>,
+}
+
+impl EvmChain {
+    /// A chain with no endpoints or tokens yet.
+    pub fn new(id: u64, name: impl Into<String>, symbol: impl Into<String>, decimals: u8) -> Self {
+        EvmChain {
+            id,
+            name: name.into(),
+            native: Asset::native(Chain::Evm(id), symbol, decimals),
+            rpc_urls: Vec::new(),
+            tokens: Vec::new(),
+            liquid_staking: Vec::new(),
+        }
+    }
+
+    pub fn with_rpc_url(mut self, url: impl Into<String>) -> Self {
+        self.rpc_urls.push(url.into());
+        self
+    }
+
+    /// Adds an ERC-20 token, given its checksummed contract address.
+    pub fn with_token(mut self, contract: &str, symbol: &str, decimals: u8) -> Self {
+        self.tokens
+            .push(Asset::token(self.chain(), contract, symbol, decimals));
+        self
+    }
+
+    /// Adds a token for ether staked through a staking service, redeemable
+    /// for the native asset.
+    pub fn with_liquid_staking(
+        mut self,
+        contract: &str,
+        symbol: &str,
+        decimals: u8,
+        redemption: Redemption,
+    ) -> Self {
+        self.liquid_staking.push(LiquidStakingToken::new(
+            Asset::token(self.chain(), contract, symbol, decimals),
+            self.native.clone(),
+            redemption,
+        ));
+        self
+    }
+
+    pub fn chain(&self) -> Chain {
+        Chain::Evm(self.id)
+    }
+
+    /// A source for this chain's native asset, known tokens and
+    /// liquid-staking tokens, read through `client`.
+    pub fn source(&self, client: EthClient) -> EthereumSource {
+        let source = self.tokens.iter().cloned().fold(
+            EthereumSource::new(client).with_native_asset(self.native.clone()),
+            EthereumSource::with_token,
+        );
+        self.liquid_staking
+            .iter()
+            .cloned()
+            .fold(source, EthereumSource::with_liquid_staking)
+    }
+
+    /// A client for the preferred endpoint, if the chain has one.
+    pub fn client(&self) -> Option<EthClient> {
+        self.rpc_urls.first().cloned().map(EthClient::new)
+    }
+}
+
+/// EVM chains by chain id.
+///
+/// [`builtin`](Self::builtin) knows the chains walletb supports out of the
+/// box, with public endpoints that suit occasional reads; point busy or
+/// historical reads at a node of your own with
+/// [`with_rpc_url`](EvmChain::with_rpc_url) on a fresh [`EvmChain`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainRegistry {
+    chains: BTreeMap<u64, EvmChain>,
+}
+
+impl ChainRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ethereum, Optimism, BNB Smart Chain, Polygon PoS, Base, Arbitrum One
+    /// and the Avalanche C-chain, each with its major stablecoins, and
+    /// Ethereum with the main liquid-staking tokens.
+    pub fn builtin() -> Self {
+        let mut registry = ChainRegistry::new();
+        for chain in [
+            EvmChain::new(1, "ethereum", "ETH", 18)
+                .with_rpc_url("https://ethereum-rpc.publicnode.com")
+                .with_token("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "USDC", 6)
+                .with_token("0xdAC17F958D2ee523a2206206994597C13D831ec7", "USDT", 6)
+                .with_token("0x6B175474E89094C44Da98b954EedeAC495271d0F", "DAI", 18)
+                .with_token("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", "WETH", 18)
+                .with_liquid_staking(
+                    "0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84",
+                    "stETH",
+                    18,
+                    Redemption::OneToOne,
+                )
+                .with_liquid_staking(
+                    "0x7f39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0",
+                    "wstETH",
+                    18,
+                    Redemption::Rate("stEthPerToken()".into()),
+                )
+                .with_liquid_staking(
+                    "0xae78736Cd615f374D3085123A210448E74Fc6393",
+                    "rETH",
+                    18,
+                    Redemption::Rate("getExchangeRate()".into()),
+                )
+                .with_liquid_staking(
+                    "0xBe9895146f7AF43049ca1c1AE358B0541Ea49704",
+                    "cbETH",
+                    18,
+                    Redemption::Rate("exchangeRate()".into()),
+                ),
+            EvmChain::new(10, "optimism", "ETH", 18)
+                .with_rpc_url("https://mainnet.optimism.io")
+                .with_token("0x0b2C639c533813f4Aa9D7837CAf62653d097Ff85", "USDC", 6)
+                .with_token("0x4200000000000000000000000000000000000042", "OP", 18),
+            EvmChain::new(56, "bsc", "BNB", 18)
+                .with_rpc_url("https://bsc-dataseed.bnbchain.org")
+                .with_token("0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", "USDC", 18)
+                .with_token("0x55d398326f99059fF775485246999027B3197955", "USDT", 18),
+            EvmChain::new(137, "polygon", "POL", 18)
+                .with_rpc_url("https://polygon-rpc.com")
+                .with_token("0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359", "USDC", 6)
+                .with_token("0xc2132D05D31c914a87C6611C10748AEb04B58e8F", "USDT", 6),
+            EvmChain::new(8453, "base", "ETH", 18)
+                .with_rpc_url("https://mainnet.base.org")
+                .with_token("0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", "USDC", 6),
+            EvmChain::new(42161, "arbitrum", "ETH", 18)
+                .with_rpc_url("https://arb1.arbitrum.io/rpc")
+                .with_token("0xaf88d065e77c8cC2239327C5EDb3A432268e5831", "USDC", 6)
+                .with_token("0x912CE59144191C1204E64559FE8253a0e49E6548", "ARB", 18),
+            EvmChain::new(43114, "avalanche", "AVAX", 18)
+                .with_rpc_url("https://api.avax.network/ext/bc/C/rpc")
+                .with_token("0xB97EF9Ef8734C71904D8002F8b6Bc66Dd9c48a6E", "USDC", 6),
+        ] {
+            registry.insert(chain);
+        }
+        registry
+    }
+
+    /// Adds or replaces a chain, returning the entry it replaced.
+    pub fn insert(&mut self, chain: EvmChain) -> Option<EvmChain> {
+        self.chains.insert(chain.id, chain)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&EvmChain> {
+        self.chains.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut EvmChain> {
+        self.chains.get_mut(&id)
+    }
+
+    /// Looks a chain up by name, or by chain id written as a number or as
+    /// `evm:<id>`.
+    pub fn find(&self, name: &str) -> Option<&EvmChain> {
+        let id = name
+            .strip_prefix("evm:")
+            .unwrap_or(name)
+            .parse()
+            .ok()
+            .or_else(|| self.chains.values().find(|c| c.name == name).map(|c| c.id))?;
+        self.get(id)
+    }
+
+    /// Every chain, by ascending chain id.
+    pub fn iter(&self) -> impl Iterator<Item = &EvmChain> {
+        self.chains.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EthAddress;
+
+    #[test]
+    fn builtin_chains_are_consistent() {
+        let registry = ChainRegistry::builtin();
+        assert_eq!(registry.len(), 7);
+        for chain in registry.iter() {
+            // Core displays the same names, so output and config agree.
+            assert_eq!(chain.chain().to_string(), chain.name);
+            assert_eq!(chain.native.chain, chain.chain());
+            assert!(chain.client().is_some(), "{}", chain.name);
+            let staking = chain.liquid_staking.iter().map(|lst| &lst.token);
+            for token in chain.tokens.iter().chain(staking) {
+                assert_eq!(token.chain, chain.chain());
+                let contract: EthAddress = token.contract().unwrap().parse().unwrap();
+                assert_eq!(contract.to_checksum(), token.contract().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn finds_chains_by_name_or_id() {
+        let mut registry = ChainRegistry::builtin();
+        assert_eq!(registry.find("polygon").unwrap().native.symbol, "POL");
+        assert_eq!(registry.find("42161").unwrap().name, "arbitrum");
+        assert_eq!(registry.find("evm:56").unwrap().name, "bsc");
+        assert!(registry.find("zksync").is_none());
+
+        registry.insert(EvmChain::new(324, "zksync", "ETH", 18));
+        assert_eq!(registry.find("zksync").unwrap().chain(), Chain::Evm(324));
+        assert!(registry.find("zksync").unwrap().client().is_none());
+    }
+}
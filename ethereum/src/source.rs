@@ -0,0 +1,428 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use walletb_core::{
+    Address, Amount, Asset, Balance, BalanceSource, Chain, HistoricalSource, PointInTime, U256,
+};
+
+use crate::staking::underlying_amount;
+use crate::{
+    discover_tokens, eth, BalanceQuery, EthAddress, EthClient, FoundToken, LiquidStakingToken,
+    Result, TokenDiscovery, TokenStandard,
+};
+
+/// One asset held by one address in a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holding {
+    pub address: EthAddress,
+    pub asset: Asset,
+    pub amount: Amount,
+}
+
+/// Balances read at a single block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub block_number: u64,
+    pub holdings: Vec<Holding>,
+    /// Each liquid-staking token with the underlying base units one whole
+    /// token redeemed for at the block.
+    pub rates: Vec<(LiquidStakingToken, U256)>,
+}
+
+impl Snapshot {
+    /// Sums holdings per asset. The native asset always comes first; tokens
+    /// with a zero total are dropped. Liquid-staking tokens carry their
+    /// value in the underlying asset.
+    pub fn totals(&self) -> Vec<Balance> {
+        let mut totals: BTreeMap<(bool, &Asset), Amount> = BTreeMap::new();
+        for holding in &self.holdings {
+            let total = totals
+                .entry((!holding.asset.is_native(), &holding.asset))
+                .or_default();
+            *total = *total + holding.amount;
+        }
+        totals
+            .into_iter()
+            .filter(|((is_token, _), amount)| !is_token || !amount.is_zero())
+            .map(|((_, asset), amount)| {
+                let balance = Balance::new(asset.clone(), amount);
+                match self.rates.iter().find(|(lst, _)| lst.token == *asset) {
+                    Some((lst, rate)) => match underlying_amount(asset, amount, *rate) {
+                        Some(value) => balance.with_underlying(lst.underlying.clone(), value),
+                        None => balance,
+                    },
+                    None => balance,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A [`BalanceSource`] for ether and a configured list of ERC-20 tokens.
+///
+/// Each call to [`snapshot`](Self::snapshot) resolves one block number
+/// (the latest, unless pinned with [`at_block`](Self::at_block)) and issues
+/// every `eth_getBalance` and `balanceOf` against that block in one batch.
+///
+/// As a [`HistoricalSource`] it reads the same state at a past block, so
+/// contract and internal transfers are reflected without replaying
+/// transactions. That needs an archive node: a pruned node only keeps
+/// recent state and rejects older blocks.
+///
+/// Liquid-staking tokens added with
+/// [`with_liquid_staking`](Self::with_liquid_staking) are read like other
+/// tokens, and their redemption rates in the same batch.
+///
+/// With token discovery on, every token an address has received is read
+/// as well. Log scans and token metadata are kept for the life of the
+/// source, so later snapshots only scan blocks mined since.
+#[derive(Debug)]
+pub struct EthereumSource {
+    pub(crate) client: EthClient,
+    pub(crate) native: Asset,
+    pub(crate) tokens: Vec<Asset>,
+    liquid_staking: Vec<LiquidStakingToken>,
+    block: Option<u64>,
+    pub(crate) discover_from: Option<u64>,
+    discovered: Mutex<Discovered>,
+}
+
+#[derive(Debug, Default)]
+struct Discovered {
+    /// Log scan results per holder.
+    scans: HashMap<EthAddress, TokenDiscovery>,
+    /// Metadata per discovered contract, as a token asset; `None` for
+    /// ERC-20 contracts whose metadata cannot be read, which are skipped.
+    assets: HashMap<EthAddress, Option<Asset>>,
+}
+
+impl EthereumSource {
+    /// A source for Ethereum mainnet ether.
+    pub fn new(client: EthClient) -> Self {
+        EthereumSource {
+            client,
+            native: eth(),
+            tokens: Vec::new(),
+            liquid_staking: Vec::new(),
+            block: None,
+            discover_from: None,
+            discovered: Mutex::default(),
+        }
+    }
+
+    /// Replaces the native asset, and with it the chain addresses and tokens
+    /// must belong to.
+    pub fn with_native_asset(mut self, native: Asset) -> Self {
+        self.native = native;
+        self
+    }
+
+    /// Adds an ERC-20 token to read for every address. The asset's contract
+    /// is checked when the next snapshot is taken.
+    pub fn with_token(mut self, token: Asset) -> Self {
+        self.tokens.push(token);
+        self
+    }
+
+    /// Adds a liquid-staking token, read for every address like
+    /// [`with_token`](Self::with_token) and valued in its underlying asset.
+    pub fn with_liquid_staking(mut self, lst: LiquidStakingToken) -> Self {
+        if !self.tokens.contains(&lst.token) {
+            self.tokens.push(lst.token.clone());
+        }
+        self.liquid_staking.push(lst);
+        self
+    }
+
+    /// Also reads every token the addresses received from `from_block` on,
+    /// found in transfer logs. Start at the block the oldest address was
+    /// first used, or 0 to scan the whole chain.
+    pub fn with_token_discovery(mut self, from_block: u64) -> Self {
+        self.discover_from = Some(from_block);
+        self
+    }
+
+    /// Reads every snapshot at `block` instead of the chain tip.
+    pub fn at_block(mut self, block: u64) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    pub fn client(&self) -> &EthClient {
+        &self.client
+    }
+
+    pub fn tokens(&self) -> &[Asset] {
+        &self.tokens
+    }
+
+    /// Looks up `contract`'s metadata and returns it as an [`Asset`] on this
+    /// source's chain.
+    pub fn fetch_token(&self, contract: &EthAddress) -> Result<Asset> {
+        let block = self.resolve_block()?;
+        let meta = self.client.token_metadata(contract, block)?;
+        Ok(Asset::token(
+            self.chain(),
+            contract.to_checksum(),
+            meta.symbol,
+            meta.decimals,
+        ))
+    }
+
+    pub fn snapshot(&self, addresses: &[Address]) -> Result<Snapshot> {
+        self.read(addresses, self.block)
+    }
+
+    /// Reads a snapshot at `block`, regardless of any pinned block.
+    pub fn snapshot_at(&self, addresses: &[Address], block: u64) -> Result<Snapshot> {
+        self.read(addresses, Some(block))
+    }
+
+    /// The tokens `holders` had received by `block`, scanning the logs of
+    /// blocks not scanned before. Empty unless token discovery is on.
+    pub fn discover_tokens(&self, holders: &[EthAddress], block: u64) -> Result<Vec<FoundToken>> {
+        let Some(from_block) = self.discover_from else {
+            return Ok(Vec::new());
+        };
+        let mut discovered = self.discovered.lock().unwrap();
+        // Holders scanned up to the same block resume together.
+        let mut pending: BTreeMap<u64, Vec<EthAddress>> = BTreeMap::new();
+        for holder in holders {
+            let start = discovered
+                .scans
+                .get(holder)
+                .and_then(|scan| scan.scanned_to)
+                .map_or(from_block, |scanned| scanned + 1);
+            if start <= block {
+                pending.entry(start).or_default().push(*holder);
+            }
+        }
+        for (start, group) in pending {
+            let found = discover_tokens(&self.client, &group, start, block)?;
+            for holder in group {
+                discovered
+                    .scans
+                    .entry(holder)
+                    .or_default()
+                    .merge(TokenDiscovery {
+                        found: found
+                            .found
+                            .iter()
+                            .filter(|(token, _)| token.holder == holder)
+                            .map(|(token, first)| (*token, *first))
+                            .collect(),
+                        scanned_to: found.scanned_to,
+                    });
+            }
+        }
+        Ok(holders
+            .iter()
+            .filter_map(|holder| discovered.scans.get(holder))
+            .flat_map(|scan| scan.tokens_at(block).copied())
+            .collect())
+    }
+
+    /// The block that `at` refers to: the height itself, or the last block
+    /// mined at or before the timestamp.
+    pub fn block_for(&self, at: PointInTime) -> Result<u64> {
+        match at {
+            PointInTime::Height(height) => Ok(height),
+            PointInTime::Time(time) => self.client.block_at_time(time),
+        }
+    }
+
+    fn read(&self, addresses: &[Address], block: Option<u64>) -> Result<Snapshot> {
+        let holders = addresses
+            .iter()
+            .map(|a| {
+                a.expect_chain(self.chain())?;
+                a.as_str().parse()
+            })
+            .collect::<Result<Vec<EthAddress>>>()?;
+        let tokens = self
+            .tokens
+            .iter()
+            .map(|t| {
+                let contract = t.contract().unwrap_or_default();
+                Ok((contract.parse::<EthAddress>()?, t))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut queries = Vec::with_capacity(holders.len() * (tokens.len() + 1));
+        let mut assets = Vec::with_capacity(queries.capacity());
+        for holder in &holders {
+            queries.push(BalanceQuery::Native { holder: *holder });
+            assets.push((*holder, &self.native));
+            for (token, asset) in &tokens {
+                queries.push(BalanceQuery::Token {
+                    token: *token,
+                    holder: *holder,
+                });
+                assets.push((*holder, *asset));
+            }
+        }
+
+        let block_number = match block {
+            Some(block) => block,
+            None => self.client.block_number()?,
+        };
+        let configured: Vec<EthAddress> = tokens.iter().map(|(contract, _)| *contract).collect();
+        let found: Vec<FoundToken> = self
+            .discover_tokens(&holders, block_number)?
+            .into_iter()
+            .filter(|t| !configured.contains(&t.contract))
+            .collect();
+        let found_assets = self.found_assets(&found, block_number)?;
+        let mut discovered_assets = Vec::new();
+        for (token, asset) in found.iter().zip(found_assets) {
+            let Some(asset) = asset else { continue };
+            let (contract, holder) = (token.contract, token.holder);
+            queries.push(match (token.standard, token.token_id) {
+                (TokenStandard::Erc721, Some(id)) => BalanceQuery::Erc721 {
+                    token: contract,
+                    holder,
+                    id,
+                },
+                (TokenStandard::Erc1155, Some(id)) => BalanceQuery::Erc1155 {
+                    token: contract,
+                    holder,
+                    id,
+                },
+                _ => BalanceQuery::Token {
+                    token: contract,
+                    holder,
+                },
+            });
+            discovered_assets.push((holder, asset));
+        }
+        let assets = assets
+            .into_iter()
+            .map(|(holder, asset)| (holder, asset.clone()))
+            .chain(discovered_assets);
+        let balance_reads = queries.len();
+        let mut rate_reads = Vec::new();
+        for lst in &self.liquid_staking {
+            if let Some(selector) = lst.rate_selector() {
+                let token = lst.token.contract().unwrap_or_default().parse()?;
+                queries.push(BalanceQuery::Rate { token, selector });
+                rate_reads.push(lst);
+            }
+        }
+        let mut values = self.client.balances(&queries, block_number)?;
+        let read_rates = values.split_off(balance_reads);
+        let rates = self
+            .liquid_staking
+            .iter()
+            .map(|lst| {
+                let read = rate_reads
+                    .iter()
+                    .position(|read| *read == lst)
+                    .map(|i| read_rates[i]);
+                (lst.clone(), lst.rate(read))
+            })
+            .collect();
+        let holdings = assets
+            .zip(values)
+            .map(|((address, asset), value)| Holding {
+                address,
+                asset,
+                amount: Amount::from_base_units(value),
+            })
+            .collect();
+        Ok(Snapshot {
+            block_number,
+            holdings,
+            rates,
+        })
+    }
+
+    /// The asset each discovered token is read as, or `None` for ERC-20
+    /// contracts without usable metadata. Metadata is fetched once per
+    /// contract.
+    pub(crate) fn found_assets(
+        &self,
+        found: &[FoundToken],
+        block: u64,
+    ) -> Result<Vec<Option<Asset>>> {
+        let mut discovered = self.discovered.lock().unwrap();
+        let mut nfts = Vec::new();
+        for token in found {
+            if discovered.assets.contains_key(&token.contract) {
+                continue;
+            }
+            if token.standard == TokenStandard::Erc20 {
+                let asset = self
+                    .client
+                    .token_metadata(&token.contract, block)
+                    .ok()
+                    .map(|meta| {
+                        Asset::token(
+                            self.chain(),
+                            token.contract.to_checksum(),
+                            meta.symbol,
+                            meta.decimals,
+                        )
+                    });
+                discovered.assets.insert(token.contract, asset);
+            } else if !nfts.contains(&(token.contract, token.standard)) {
+                nfts.push((token.contract, token.standard));
+            }
+        }
+        let contracts: Vec<EthAddress> = nfts.iter().map(|(contract, _)| *contract).collect();
+        let symbols = self.client.token_symbols(&contracts, block)?;
+        for ((contract, standard), symbol) in nfts.into_iter().zip(symbols) {
+            let symbol = symbol.unwrap_or_else(|| {
+                match standard {
+                    TokenStandard::Erc1155 => "ERC1155",
+                    _ => "ERC721",
+                }
+                .to_owned()
+            });
+            let asset = Asset::token(self.chain(), contract.to_checksum(), symbol, 0);
+            discovered.assets.insert(contract, Some(asset));
+        }
+        Ok(found
+            .iter()
+            .map(|token| {
+                let asset = discovered.assets[&token.contract].as_ref()?;
+                Some(match token.token_id {
+                    Some(id) => Asset::nft(
+                        self.chain(),
+                        token.contract.to_checksum(),
+                        id.to_string(),
+                        &asset.symbol,
+                    ),
+                    None => asset.clone(),
+                })
+            })
+            .collect())
+    }
+
+    pub(crate) fn resolve_block(&self) -> Result<u64> {
+        match self.block {
+            Some(block) => Ok(block),
+            None => self.client.block_number(),
+        }
+    }
+}
+
+impl BalanceSource for EthereumSource {
+    fn chain(&self) -> Chain {
+        self.native.chain
+    }
+
+    fn balances(&self, addresses: &[Address]) -> walletb_core::Result<Vec<Balance>> {
+        Ok(self.snapshot(addresses)?.totals())
+    }
+}
+
+impl HistoricalSource for EthereumSource {
+    fn balances_at(
+        &self,
+        addresses: &[Address],
+        at: PointInTime,
+    ) -> walletb_core::Result<Vec<Balance>> {
+        let block = self.block_for(at)?;
+        Ok(self.snapshot_at(addresses, block)?.totals())
+    }
+}
@@ -0,0 +1,87 @@
+use walletb_core::{Amount, Asset, U256};
+
+use crate::abi;
+
+/// How a liquid-staking token's worth in its underlying asset is read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redemption {
+    /// A rebasing token worth one unit of the underlying asset, as stETH.
+    OneToOne,
+    /// A token with a view function, named by its signature such as
+    /// `"stEthPerToken()"`, returning the base units of the underlying
+    /// asset one whole token redeems for.
+    Rate(String),
+}
+
+/// A token standing for staked funds, such as wstETH for ether staked
+/// through Lido. Its balances are reported with their value in the
+/// underlying asset, which is what a price source usually knows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiquidStakingToken {
+    pub token: Asset,
+    pub underlying: Asset,
+    pub redemption: Redemption,
+}
+
+impl LiquidStakingToken {
+    pub fn new(token: Asset, underlying: Asset, redemption: Redemption) -> Self {
+        LiquidStakingToken {
+            token,
+            underlying,
+            redemption,
+        }
+    }
+
+    /// The selector of the rate function, if the token has one.
+    pub(crate) fn rate_selector(&self) -> Option<[u8; 4]> {
+        match &self.redemption {
+            Redemption::OneToOne => None,
+            Redemption::Rate(signature) => Some(abi::selector(signature)),
+        }
+    }
+
+    /// The base units of the underlying asset one whole token redeems for,
+    /// given what the rate function returned, if it has one.
+    pub(crate) fn rate(&self, read: Option<U256>) -> U256 {
+        read.unwrap_or_else(|| U256::from(10u8).pow(U256::from(self.underlying.decimals)))
+    }
+}
+
+/// `amount` of a token worth `rate` underlying base units per whole token,
+/// in underlying base units; `None` on overflow.
+pub(crate) fn underlying_amount(token: &Asset, amount: Amount, rate: U256) -> Option<Amount> {
+    let one = U256::from(10u8).checked_pow(U256::from(token.decimals))?;
+    let value = amount.base_units().checked_mul(rate)?.checked_div(one)?;
+    Some(Amount::from_base_units(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use walletb_core::Chain;
+
+    use super::*;
+    use crate::eth;
+
+    #[test]
+    fn values_tokens_at_their_rate() {
+        let wsteth = Asset::token(Chain::ETHEREUM, "0x7f39…", "wstETH", 18);
+        let lst = LiquidStakingToken::new(
+            wsteth.clone(),
+            eth(),
+            Redemption::Rate("stEthPerToken()".into()),
+        );
+        assert_eq!(lst.rate_selector(), Some([0x03, 0x5f, 0xaf, 0x82]));
+        // 2 wstETH at 1.2 ETH each.
+        let rate = lst.rate(Some(U256::from(1_200_000_000_000_000_000u64)));
+        let amount = Amount::from_decimal_str("2", 18).unwrap();
+        let value = underlying_amount(&wsteth, amount, rate).unwrap();
+        assert_eq!(value.to_decimal_string(18), "2.4");
+
+        let steth = LiquidStakingToken::new(wsteth, eth(), Redemption::OneToOne);
+        assert_eq!(steth.rate_selector(), None);
+        assert_eq!(
+            steth.rate(None),
+            Amount::from_decimal_str("1", 18).unwrap().base_units()
+        );
+    }
+}
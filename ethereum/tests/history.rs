@@ -0,0 +1,85 @@
+use serde_json::json;
+use walletb_core::testing::{MockServer, RpcFailure};
+use walletb_core::{Address, Amount, Chain, HistoricalSource, PointInTime};
+use walletb_ethereum::{Error, EthClient, EthereumSource};
+
+const ALICE: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+// A 1000-block chain whose genesis is at 2024-01-01T00:00:00Z with one
+// block every 12 seconds.
+const GENESIS: u64 = 1_704_067_200;
+const TIP: u64 = 1_000;
+
+fn parse_block(tag: &serde_json::Value) -> u64 {
+    u64::from_str_radix(tag.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+/// An archive node where Alice receives 1 ETH in block 500 and another in
+/// block 800.
+fn archive_node() -> MockServer {
+    MockServer::json_rpc(|method, params| match method {
+        "eth_blockNumber" => Ok(json!(format!("0x{TIP:x}"))),
+        "eth_getBlockByNumber" => {
+            let block = parse_block(&params[0]);
+            if block > TIP {
+                return Ok(json!(null));
+            }
+            Ok(json!({
+                "number": format!("0x{block:x}"),
+                "timestamp": format!("0x{:x}", GENESIS + 12 * block),
+            }))
+        }
+        "eth_getBalance" => {
+            let block = parse_block(&params[1]);
+            let ether = u64::from(block >= 500) + u64::from(block >= 800);
+            Ok(json!(format!("0x{:x}", u128::from(ether) * 10u128.pow(18))))
+        }
+        _ => Err(RpcFailure::method_not_found(method)),
+    })
+}
+
+fn ether(balances: &[walletb_core::Balance]) -> String {
+    assert!(balances[0].unconfirmed.is_zero());
+    balances[0].confirmed.to_decimal_string(18)
+}
+
+#[test]
+fn finds_the_last_block_before_a_timestamp() {
+    let server = archive_node();
+    let client = EthClient::new(server.url());
+    assert_eq!(client.block_at_time(GENESIS).unwrap(), 0);
+    assert_eq!(client.block_at_time(GENESIS + 12 * 500 - 1).unwrap(), 499);
+    assert_eq!(client.block_at_time(GENESIS + 12 * 500).unwrap(), 500);
+    assert_eq!(client.block_at_time(GENESIS + 12 * 500 + 11).unwrap(), 500);
+    assert_eq!(client.block_at_time(u64::MAX).unwrap(), TIP);
+    assert!(matches!(
+        client.block_at_time(GENESIS - 1),
+        Err(Error::BeforeGenesis { time }) if time == GENESIS - 1
+    ));
+}
+
+#[test]
+fn reads_balances_at_a_height_or_date() {
+    let server = archive_node();
+    let source = EthereumSource::new(EthClient::new(server.url()));
+    let alice = [Address::new(Chain::ETHEREUM, ALICE)];
+
+    let at = |at: PointInTime| ether(&source.balances_at(&alice, at).unwrap());
+    assert_eq!(at(PointInTime::Height(499)), "0");
+    assert_eq!(at(PointInTime::Height(500)), "1");
+    assert_eq!(at(PointInTime::Time(GENESIS + 12 * 800 - 1)), "1");
+    assert_eq!(at("2024-01-01".parse().unwrap()), "2");
+
+    let snapshot = source.snapshot_at(&alice, 650).unwrap();
+    assert_eq!(snapshot.block_number, 650);
+    assert_eq!(snapshot.holdings[0].amount, Amount::from_u64(10u64.pow(18)));
+}
+
+#[test]
+fn bisects_in_logarithmic_requests() {
+    let server = archive_node();
+    let client = EthClient::new(server.url());
+    client.block_at_time(GENESIS + 12 * 321 + 5).unwrap();
+    // Tip number, tip and genesis headers, then ten halvings of 1000.
+    assert!(server.requests().len() <= 14, "{}", server.requests().len());
+}
@@ -0,0 +1,124 @@
+use serde_json::json;
+use walletb_core::testing::{MockServer, RpcFailure};
+use walletb_core::{Address, Amount, Asset, BalanceSource, Chain, U256};
+use walletb_ethereum::{eth, EthAddress, EthClient, EthereumSource};
+
+const ALICE: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+const BOB: &str = "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359";
+const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+const DAI: &str = "0x6B175474E89094C44Da98b954EedeAC495271d0F";
+
+fn word(value: u128) -> String {
+    format!("0x{value:064x}")
+}
+
+/// ABI encoding of a short (< 32 byte) `string` return value.
+fn abi_string(s: &str) -> String {
+    let text: String = s.bytes().map(|b| format!("{b:02x}")).collect();
+    format!("0x{:064x}{:064x}{text:0<64}", 0x20, s.len())
+}
+
+fn holder_of(data: &str) -> String {
+    format!("0x{}", &data[data.len() - 40..])
+}
+
+/// A node at block 0x10 where Alice holds 1.5 ETH and 2500.5 USDC and Bob
+/// holds 3 wei and no tokens. Every read must name block 0x10.
+fn node() -> MockServer {
+    let alice = ALICE.to_lowercase();
+    let usdc = USDC.to_lowercase();
+    MockServer::json_rpc(move |method, params| {
+        let block = |i: usize| params[i].as_str().unwrap_or_default().to_owned();
+        match method {
+            "eth_blockNumber" => Ok(json!("0x10")),
+            "eth_getBalance" => {
+                assert_eq!(block(1), "0x10");
+                if params[0] == alice.as_str() {
+                    Ok(json!("0x14d1120d7b160000"))
+                } else {
+                    Ok(json!("0x3"))
+                }
+            }
+            "eth_call" => {
+                assert_eq!(block(1), "0x10");
+                let to = params[0]["to"].as_str().unwrap();
+                let data = params[0]["data"].as_str().unwrap();
+                match &data[..10] {
+                    "0x70a08231" if to == usdc && holder_of(data) == alice => {
+                        Ok(json!(word(2_500_500_000)))
+                    }
+                    "0x70a08231" => Ok(json!(word(0))),
+                    "0x313ce567" => Ok(json!(word(6))),
+                    "0x95d89b41" => Ok(json!(abi_string("USDC"))),
+                    _ => Err(RpcFailure::new(3, "execution reverted")),
+                }
+            }
+            _ => Err(RpcFailure::method_not_found(method)),
+        }
+    })
+}
+
+fn addr(s: &str) -> Address {
+    Address::new(Chain::ETHEREUM, s)
+}
+
+fn usdc() -> Asset {
+    Asset::token(Chain::ETHEREUM, USDC, "USDC", 6)
+}
+
+#[test]
+fn batches_all_reads_at_one_block() {
+    let server = node();
+    let source = EthereumSource::new(EthClient::new(server.url()))
+        .with_token(usdc())
+        .with_token(Asset::token(Chain::ETHEREUM, DAI, "DAI", 18));
+
+    let snapshot = source.snapshot(&[addr(ALICE), addr(BOB)]).unwrap();
+    assert_eq!(snapshot.block_number, 16);
+    assert_eq!(snapshot.holdings.len(), 6);
+
+    // One request for the block number, one batch for all six reads.
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].json().as_array().map(Vec::len), Some(6));
+
+    let totals = snapshot.totals();
+    assert_eq!(totals.len(), 2, "zero DAI total is dropped");
+    assert_eq!(totals[0].asset, eth());
+    assert_eq!(
+        totals[0].confirmed,
+        Amount::from_u64(1_500_000_000_000_000_003)
+    );
+    assert_eq!(totals[1].asset, usdc());
+    assert_eq!(totals[1].to_string(), "2500.5 USDC");
+}
+
+#[test]
+fn pinned_block_skips_block_number_lookup() {
+    let server = node();
+    let source = EthereumSource::new(EthClient::new(server.url())).at_block(16);
+    let balances = source.balances(&[addr(BOB)]).unwrap();
+    assert_eq!(balances[0].confirmed.base_units(), U256::from(3u8));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn fetches_token_metadata() {
+    let server = node();
+    let source = EthereumSource::new(EthClient::new(server.url()));
+    let asset = source
+        .fetch_token(&USDC.parse::<EthAddress>().unwrap())
+        .unwrap();
+    assert_eq!(asset, usdc());
+}
+
+#[test]
+fn rejects_foreign_addresses() {
+    let server = node();
+    let source = EthereumSource::new(EthClient::new(server.url()));
+    assert!(source
+        .balances(&[Address::new(Chain::Bitcoin, ALICE)])
+        .is_err());
+    assert!(source.balances(&[addr("0x1234")]).is_err());
+    assert!(server.requests().is_empty());
+}
@@ -0,0 +1,73 @@
+use serde_json::json;
+use walletb_core::testing::{MockServer, RpcFailure};
+use walletb_core::{Address, BalanceSource, Chain, HistoricalSource, PointInTime};
+use walletb_ethereum::{ChainRegistry, EthClient, MultiChainSource};
+
+const ALICE: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+/// A node where every address holds `native` wei and `token` base units of
+/// every ERC-20 token.
+fn node(native: u128, token: u128) -> MockServer {
+    MockServer::json_rpc(move |method, _| match method {
+        "eth_blockNumber" => Ok(json!("0x64")),
+        "eth_getBalance" => Ok(json!(format!("0x{native:x}"))),
+        "eth_call" => Ok(json!(format!("0x{token:064x}"))),
+        _ => Err(RpcFailure::method_not_found(method)),
+    })
+}
+
+fn source(nodes: &[(&str, &MockServer)]) -> MultiChainSource {
+    let registry = ChainRegistry::builtin();
+    nodes
+        .iter()
+        .fold(MultiChainSource::new(), |multi, (name, server)| {
+            let chain = registry.find(name).unwrap();
+            multi.with_source(chain.source(EthClient::new(server.url())))
+        })
+}
+
+#[test]
+fn reads_one_address_on_every_chain() {
+    let (mainnet, arbitrum, polygon) = (node(10u128.pow(18), 0), node(0, 0), node(0, 5_000_000));
+    let source = source(&[
+        ("ethereum", &mainnet),
+        ("arbitrum", &arbitrum),
+        ("polygon", &polygon),
+    ]);
+    assert_eq!(source.chain(), Chain::ETHEREUM);
+
+    let balances = source
+        .balances(&[Address::new(Chain::ETHEREUM, ALICE)])
+        .unwrap();
+    let rows: Vec<String> = balances
+        .iter()
+        .map(|b| format!("{} {}", b.asset.chain, b))
+        .collect();
+    // Native assets are always listed; empty tokens are not.
+    assert_eq!(
+        rows,
+        [
+            "ethereum 1 ETH",
+            "arbitrum 0 ETH",
+            "polygon 0 POL",
+            "polygon 5 USDC",
+            "polygon 5 USDT",
+        ]
+    );
+    assert_ne!(balances[0].asset, balances[1].asset);
+}
+
+#[test]
+fn refuses_heights_and_foreign_addresses() {
+    let (mainnet, base) = (node(0, 0), node(0, 0));
+    let source = source(&[("ethereum", &mainnet), ("base", &base)]);
+    let alice = [Address::new(Chain::BASE, ALICE)];
+    assert!(source
+        .balances_at(&alice, PointInTime::Height(100))
+        .unwrap_err()
+        .to_string()
+        .contains("read at a date"));
+    assert!(source
+        .balances(&[Address::new(Chain::Bitcoin, ALICE)])
+        .is_err());
+}
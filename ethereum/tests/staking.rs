@@ -0,0 +1,158 @@
+use serde_json::{json, Value};
+use walletb_core::testing::{MockRequest, MockResponse, MockServer, RpcFailure};
+use walletb_core::{Address, Asset, BalanceSource, Category, Chain};
+use walletb_ethereum::{
+    eth, BeaconClient, BeaconSource, ChainRegistry, EthClient, ValidatorStatus,
+};
+
+const ALICE: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+const WSTETH: &str = "0x7f39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0";
+const RETH: &str = "0xae78736Cd615f374D3085123A210448E74Fc6393";
+
+fn addr(s: &str) -> Address {
+    Address::new(Chain::ETHEREUM, s)
+}
+
+fn word(value: u128) -> String {
+    format!("0x{value:064x}")
+}
+
+/// Alice holds 2 wstETH, at 1.2 stETH each, and 1 rETH, at 1.1 ETH; every
+/// other balance is zero.
+#[test]
+fn values_liquid_staking_tokens_in_ether() {
+    let node = MockServer::json_rpc(|method, params| match method {
+        "eth_blockNumber" => Ok(json!("0x10")),
+        "eth_getBalance" => Ok(json!("0x0")),
+        "eth_call" => {
+            let to = params[0]["to"].as_str().unwrap();
+            let data = params[0]["data"].as_str().unwrap();
+            Ok(json!(match (&data[..10], to) {
+                ("0x70a08231", t) if t == WSTETH.to_lowercase() => word(2 * 10u128.pow(18)),
+                ("0x70a08231", t) if t == RETH.to_lowercase() => word(10u128.pow(18)),
+                ("0x70a08231", _) => word(0),
+                // stEthPerToken(), getExchangeRate() and cbETH's
+                // exchangeRate().
+                ("0x035faf82", _) => word(12 * 10u128.pow(17)),
+                ("0xe6aa216c", _) => word(11 * 10u128.pow(17)),
+                ("0x3ba0b9a9", _) => word(109 * 10u128.pow(16)),
+                _ => return Err(RpcFailure::new(3, "execution reverted")),
+            }))
+        }
+        _ => Err(RpcFailure::method_not_found(method)),
+    });
+    let registry = ChainRegistry::builtin();
+    let source = registry.get(1).unwrap().source(EthClient::new(node.url()));
+    let balances = source.balances(&[addr(ALICE)]).unwrap();
+    let rows: Vec<String> = balances.iter().map(|b| b.to_string()).collect();
+    assert_eq!(
+        rows,
+        ["0 ETH", "2 wstETH (= 2.4 ETH)", "1 rETH (= 1.1 ETH)"]
+    );
+    assert_eq!(balances[2].underlying.as_ref().unwrap().asset, eth());
+    assert!(balances.iter().all(|b| b.category == Category::Available));
+
+    // Balances and both rates in one batch.
+    let calls = node.requests();
+    assert_eq!(calls.len(), 2);
+}
+
+fn validator(index: u64, status: &str, gwei: u64, credentials: String) -> Value {
+    json!({
+        "index": index.to_string(),
+        "balance": gwei.to_string(),
+        "status": status,
+        "validator": {
+            "pubkey": format!("0x{}", format!("{index:02x}").repeat(48)),
+            "withdrawal_credentials": credentials,
+            "effective_balance": "32000000000",
+            "slashed": false,
+            "activation_eligibility_epoch": "0",
+            "activation_epoch": "0",
+            "exit_epoch": "18446744073709551615",
+            "withdrawable_epoch": "18446744073709551615",
+        },
+    })
+}
+
+/// Validators 1 and 2 pay Alice, 1 active and 2 exiting; 3 is queued with
+/// BLS credentials.
+fn beacon(request: &MockRequest) -> MockResponse {
+    let alice = format!("0x01{}{}", "00".repeat(11), &ALICE[2..].to_lowercase());
+    let all = [
+        validator(1, "active_ongoing", 32_012_345_678, alice.clone()),
+        validator(2, "active_exiting", 31_900_000_000, alice),
+        validator(
+            3,
+            "pending_queued",
+            32_000_000_000,
+            format!("0x00{}", "ab".repeat(31)),
+        ),
+    ];
+    if request.path() != "/eth/v1/beacon/states/head/validators" {
+        return MockResponse::status(404, r#"{"code":404,"message":"not found"}"#);
+    }
+    let data: Vec<&Value> = match request.query("id") {
+        None => all.iter().collect(),
+        Some(ids) => {
+            let ids: Vec<&str> = ids.split("%2C").collect();
+            all.iter()
+                .filter(|v| {
+                    ids.contains(&v["index"].as_str().unwrap())
+                        || ids.contains(&v["validator"]["pubkey"].as_str().unwrap())
+                })
+                .collect()
+        }
+    };
+    MockResponse::json(&json!({ "execution_optimistic": false, "finalized": false, "data": data }))
+}
+
+#[test]
+fn reads_validators_by_index_or_withdrawal_address() {
+    let server = MockServer::http(beacon);
+    let source = BeaconSource::new(BeaconClient::new(server.url()));
+    let validators = source
+        .validators(&[addr("3"), addr(ALICE), addr("1")])
+        .unwrap();
+    let indices: Vec<u64> = validators.iter().map(|v| v.index).collect();
+    assert_eq!(indices, [1, 2, 3]);
+    assert_eq!(validators[1].status, ValidatorStatus::ActiveExiting);
+    assert_eq!(
+        validators[0].withdrawal_address.unwrap().to_checksum(),
+        ALICE
+    );
+    assert_eq!(validators[2].withdrawal_address, None);
+
+    let balances = source.totals(&validators);
+    let rows: Vec<String> = balances.iter().map(|b| b.to_string()).collect();
+    assert_eq!(
+        rows,
+        [
+            "32.012345678 ETH staked",
+            "32 ETH locked",
+            "31.9 ETH pending_withdrawal",
+        ]
+    );
+    assert_eq!(balances[0].asset, Asset::native(Chain::ETHEREUM, "ETH", 18));
+
+    // Alice's validators are looked up once; later reads go by index.
+    source.balances(&[addr(ALICE)]).unwrap();
+    let full_reads = server
+        .requests()
+        .iter()
+        .filter(|r| r.query("id").is_none())
+        .count();
+    assert_eq!(full_reads, 1);
+}
+
+#[test]
+fn rejects_keys_that_name_no_validator() {
+    let server = MockServer::http(beacon);
+    let source = BeaconSource::new(BeaconClient::new(server.url()));
+    assert!(source.balances(&[addr("0x1234")]).is_err());
+    assert!(source
+        .balances(&[Address::new(Chain::Solana, "1")])
+        .is_err());
+    assert!(server.requests().is_empty());
+    assert!(source.balances(&[addr("99")]).unwrap().is_empty());
+}
@@ -0,0 +1,262 @@
+use serde_json::{json, Value};
+use walletb_core::testing::{MockServer, RpcFailure};
+use walletb_core::{Address, Asset, BalanceSource, Chain};
+use walletb_ethereum::{discover_tokens, EthAddress, EthClient, EthereumSource, TokenStandard};
+
+const ALICE: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+const BOB: &str = "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359";
+const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+const SPAM: &str = "0x1111111111111111111111111111111111111111";
+const PUNKS: &str = "0xb47e3cd837dDF8e4c57F05d70Ab865de6e193BBB";
+const GAME: &str = "0x76BE3b62873462d2142405439777e971754E8E77";
+
+const TRANSFER: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+const TRANSFER_SINGLE: &str = "0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62";
+const TRANSFER_BATCH: &str = "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb";
+
+/// Blocks a `eth_getLogs` request may span before the node refuses it.
+const NODE_LOG_LIMIT: u64 = 5;
+
+fn word(value: u128) -> String {
+    format!("0x{value:064x}")
+}
+
+fn address_topic(address: &str) -> String {
+    format!("0x{:0>64}", address.trim_start_matches("0x").to_lowercase())
+}
+
+fn abi_string(s: &str) -> String {
+    let text: String = s.bytes().map(|b| format!("{b:02x}")).collect();
+    format!("0x{:064x}{:064x}{text:0<64}", 0x20, s.len())
+}
+
+fn quantity(value: &Value) -> u64 {
+    u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+fn log(block: u64, contract: &str, topics: &[String], data: String) -> Value {
+    json!({
+        "address": contract.to_lowercase(),
+        "topics": topics,
+        "data": data,
+        "blockNumber": format!("0x{block:x}"),
+        "transactionHash": word(block.into()),
+        "logIndex": "0x0",
+    })
+}
+
+/// Alice's token history on a chain at block 0x10:
+///
+/// - block 5: 1000 USDC, and an airdrop from a contract with no metadata;
+/// - block 7: punk #42, sent on to Bob at block 12;
+/// - block 8: punk #43;
+/// - block 9: a batch of 10 of game item 1 and 5 of item 2, of which she
+///   no longer holds any of item 2;
+/// - block 10: one of game item 3, also gone.
+fn node() -> MockServer {
+    let zero = address_topic("0x0000000000000000000000000000000000000000");
+    let (alice, bob) = (address_topic(ALICE), address_topic(BOB));
+    let logs = [
+        log(
+            5,
+            USDC,
+            &[TRANSFER.into(), zero.clone(), alice.clone()],
+            word(1_000_000_000),
+        ),
+        log(
+            5,
+            SPAM,
+            &[TRANSFER.into(), zero.clone(), alice.clone()],
+            word(1),
+        ),
+        log(
+            7,
+            PUNKS,
+            &[TRANSFER.into(), zero.clone(), alice.clone(), word(42)],
+            "0x".into(),
+        ),
+        log(
+            8,
+            PUNKS,
+            &[TRANSFER.into(), zero.clone(), alice.clone(), word(43)],
+            "0x".into(),
+        ),
+        log(
+            9,
+            GAME,
+            &[
+                TRANSFER_BATCH.into(),
+                alice.clone(),
+                zero.clone(),
+                alice.clone(),
+            ],
+            format!(
+                "0x{}",
+                [0x40, 0xa0, 2, 1, 2, 2, 10, 5]
+                    .map(|w: u128| format!("{w:064x}"))
+                    .concat()
+            ),
+        ),
+        log(
+            10,
+            GAME,
+            &[
+                TRANSFER_SINGLE.into(),
+                bob.clone(),
+                bob.clone(),
+                alice.clone(),
+            ],
+            format!("0x{:064x}{:064x}", 3, 1),
+        ),
+        log(
+            12,
+            PUNKS,
+            &[TRANSFER.into(), alice.clone(), bob.clone(), word(42)],
+            "0x".into(),
+        ),
+    ];
+    MockServer::json_rpc(move |method, params| match method {
+        "eth_blockNumber" => Ok(json!("0x10")),
+        "eth_getLogs" => {
+            let filter = &params[0];
+            let (from, to) = (quantity(&filter["fromBlock"]), quantity(&filter["toBlock"]));
+            if to - from >= NODE_LOG_LIMIT {
+                return Err(RpcFailure::new(-32005, "query exceeds max block range"));
+            }
+            let topics = filter["topics"].as_array().unwrap();
+            let matches = |log: &Value| {
+                let block = quantity(&log["blockNumber"]);
+                let log_topics = log["topics"].as_array().unwrap();
+                (from..=to).contains(&block)
+                    && topics.iter().enumerate().all(|(i, any_of)| {
+                        any_of.is_null()
+                            || log_topics
+                                .get(i)
+                                .is_some_and(|t| any_of.as_array().unwrap().contains(t))
+                    })
+            };
+            Ok(Value::Array(
+                logs.iter().filter(|l| matches(l)).cloned().collect(),
+            ))
+        }
+        "eth_getBalance" => Ok(json!("0x0")),
+        "eth_call" => {
+            let to = params[0]["to"].as_str().unwrap();
+            let data = params[0]["data"].as_str().unwrap();
+            let arg = |i: usize| u128::from_str_radix(&data[10 + 64 * i..10 + 64 * (i + 1)], 16);
+            let reverted = Err(RpcFailure::new(3, "execution reverted"));
+            match (to, &data[..10]) {
+                (t, "0x95d89b41") if t == USDC.to_lowercase() => Ok(json!(abi_string("USDC"))),
+                (t, "0x06fdde03") if t == USDC.to_lowercase() => Ok(json!(abi_string("USD Coin"))),
+                (t, "0x313ce567") if t == USDC.to_lowercase() => Ok(json!(word(6))),
+                (t, "0x70a08231") if t == USDC.to_lowercase() => Ok(json!(word(1_000_000_000))),
+                (t, "0x95d89b41") if t == PUNKS.to_lowercase() => Ok(json!(abi_string("PUNK"))),
+                (t, "0x6352211e") if t == PUNKS.to_lowercase() => match arg(0).unwrap() {
+                    42 => Ok(json!(address_topic(BOB))),
+                    43 => Ok(json!(address_topic(ALICE))),
+                    _ => reverted,
+                },
+                (t, "0x00fdd58e") if t == GAME.to_lowercase() => match arg(1).unwrap() {
+                    1 => Ok(json!(word(10))),
+                    _ => Ok(json!(word(0))),
+                },
+                _ => reverted,
+            }
+        }
+        _ => Err(RpcFailure::method_not_found(method)),
+    })
+}
+
+fn alice() -> EthAddress {
+    ALICE.parse().unwrap()
+}
+
+fn source(server: &MockServer) -> EthereumSource {
+    EthereumSource::new(EthClient::new(server.url()).with_max_log_range(8)).with_token_discovery(0)
+}
+
+#[test]
+fn classifies_transfers_by_standard() {
+    let server = node();
+    let client = EthClient::new(server.url()).with_max_log_range(4);
+    let discovery = discover_tokens(&client, &[alice()], 0, 16).unwrap();
+    assert_eq!(discovery.scanned_to, Some(16));
+
+    let mut found: Vec<(String, TokenStandard, Option<u64>, u64)> = discovery
+        .found
+        .iter()
+        .map(|(t, first)| {
+            (
+                t.contract.to_checksum(),
+                t.standard,
+                t.token_id.map(|id| u64::try_from(id).unwrap()),
+                *first,
+            )
+        })
+        .collect();
+    let mut expected = vec![
+        (USDC.to_owned(), TokenStandard::Erc20, None, 5),
+        (SPAM.to_owned(), TokenStandard::Erc20, None, 5),
+        (PUNKS.to_owned(), TokenStandard::Erc721, Some(42), 7),
+        (PUNKS.to_owned(), TokenStandard::Erc721, Some(43), 8),
+        (GAME.to_owned(), TokenStandard::Erc1155, Some(1), 9),
+        (GAME.to_owned(), TokenStandard::Erc1155, Some(2), 9),
+        (GAME.to_owned(), TokenStandard::Erc1155, Some(3), 10),
+    ];
+    found.sort();
+    expected.sort();
+    assert_eq!(found, expected);
+    // Punk #42 leaving for Bob is not a transfer to Alice.
+    assert!(discovery.found.keys().all(|t| t.holder == alice()));
+}
+
+#[test]
+fn reads_every_discovered_token() {
+    let server = node();
+    let source = source(&server);
+    let balances = source
+        .balances(&[Address::new(Chain::ETHEREUM, ALICE)])
+        .unwrap();
+    let held: Vec<String> = balances.iter().skip(1).map(|b| b.to_string()).collect();
+    assert_eq!(held.len(), 3, "{held:?}");
+    assert!(held.contains(&"1000 USDC".to_owned()));
+    assert!(balances.iter().any(
+        |b| b.asset == Asset::nft(Chain::ETHEREUM, PUNKS, "43", "PUNK")
+            && b.confirmed.to_u64() == Some(1)
+    ));
+    assert!(balances.iter().any(
+        |b| b.asset == Asset::nft(Chain::ETHEREUM, GAME, "1", "ERC1155")
+            && b.confirmed.to_u64() == Some(10)
+    ));
+
+    // The node refuses ranges of more than five blocks, so each eight-block
+    // chunk was split in two.
+    let log_requests = |server: &MockServer| {
+        server
+            .requests()
+            .iter()
+            .filter(|r| r.json()["method"] == "eth_getLogs")
+            .count()
+    };
+    let scanned = log_requests(&server);
+    assert!(scanned > 6, "{scanned}");
+
+    // Scanned blocks and metadata are remembered.
+    let again = source
+        .balances(&[Address::new(Chain::ETHEREUM, ALICE)])
+        .unwrap();
+    assert_eq!(again, balances);
+    assert_eq!(log_requests(&server), scanned);
+}
+
+#[test]
+fn reads_past_blocks_with_the_tokens_held_then() {
+    let server = node();
+    let source = source(&server);
+    let snapshot = source
+        .snapshot_at(&[Address::new(Chain::ETHEREUM, ALICE)], 6)
+        .unwrap();
+    let assets: Vec<&Asset> = snapshot.holdings.iter().map(|h| &h.asset).collect();
+    assert_eq!(assets.len(), 2, "ether and USDC: {assets:?}");
+    assert_eq!(assets[1].symbol, "USDC");
+}
@@ -0,0 +1,283 @@
+use serde_json::{json, Value};
+use walletb_core::testing::{MockServer, RpcFailure};
+use walletb_core::{Address, Asset, Chain, Period, PointInTime, TransactionSource};
+use walletb_ethereum::{Error, EthClient, EthereumSource, MultiChainSource};
+
+const ALICE: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+const BOB: &str = "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359";
+const CAROL: &str = "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB";
+const USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+const GAME: &str = "0x76BE3b62873462d2142405439777e971754E8E77";
+const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+const TRANSFER_SINGLE: &str = "0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62";
+const TRANSFER_BATCH: &str = "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb";
+const GENESIS: u64 = 1_704_067_200;
+const GWEI: u64 = 1_000_000_000;
+
+/// Transaction, asset symbol, received, sent, fee and counterparty.
+type Row<'a> = (&'a str, &'a str, String, String, String, Option<&'a str>);
+
+fn quantity(tag: &Value) -> u64 {
+    u64::from_str_radix(tag.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+}
+
+fn word(address: &str) -> String {
+    format!("0x{:0>64}", address[2..].to_lowercase())
+}
+
+fn tx(hash: &str, from: &str, to: &str, wei: u64) -> Value {
+    json!({ "hash": hash, "from": from.to_lowercase(), "to": to.to_lowercase(), "value": format!("0x{wei:x}") })
+}
+
+/// Ten blocks, one every 12 seconds. Bob pays Alice 1 ETH in block 3;
+/// Alice pays Bob 0.25 ETH in block 5 and 100 USDC in block 7; Carol's
+/// swap pays Alice 50 USDC in block 8; Alice's payment of 1 ETH to Bob
+/// reverts in block 9.
+fn node() -> MockServer {
+    // Alice's ether balance and nonce step at each block she is in.
+    let steps = |blocks: &[u64], tag: &Value| {
+        let count = blocks.iter().filter(|&&b| b <= quantity(tag)).count();
+        Ok(json!(format!("0x{count:x}")))
+    };
+    MockServer::json_rpc(move |method, params| match method {
+        "eth_getBalance" => steps(&[3, 5, 7, 9], &params[1]),
+        "eth_getTransactionCount" => steps(&[5, 7, 9], &params[1]),
+        "eth_getBlockByNumber" => {
+            let number = quantity(&params[0]);
+            let transactions = match number {
+                3 => vec![tx("0xa1", BOB, ALICE, 10u64.pow(18))],
+                5 => vec![
+                    tx("0xb0", BOB, CAROL, 5),
+                    tx("0xa2", ALICE, BOB, 25 * 10u64.pow(16)),
+                ],
+                7 => vec![tx("0xa3", ALICE, USDC, 0)],
+                8 => vec![tx("0xa4", CAROL, CAROL, 0)],
+                9 => vec![tx("0xa5", ALICE, BOB, 10u64.pow(18))],
+                _ => Vec::new(),
+            };
+            Ok(json!({
+                "number": format!("0x{number:x}"),
+                "timestamp": format!("0x{:x}", GENESIS + 12 * number),
+                "transactions": transactions,
+            }))
+        }
+        "eth_getTransactionReceipt" => {
+            let hash = params[0].as_str().unwrap();
+            Ok(json!({
+                "transactionHash": hash,
+                "status": if hash == "0xa5" { "0x0" } else { "0x1" },
+                "gasUsed": format!("0x{:x}", 21_000),
+                "effectiveGasPrice": format!("0x{:x}", 2 * GWEI),
+            }))
+        }
+        "eth_getLogs" => {
+            let topics = &params[0]["topics"];
+            let log = |hash: &str, block: u64, from: &str, to: &str, units: u64| {
+                json!({
+                    "address": USDC.to_lowercase(),
+                    "topics": [TRANSFER_TOPIC, word(from), word(to)],
+                    "data": format!("0x{units:064x}"),
+                    "blockNumber": format!("0x{block:x}"),
+                    "transactionHash": hash,
+                    "logIndex": "0x0",
+                })
+            };
+            let logs = if topics[1].is_null() {
+                vec![log("0xa4", 8, CAROL, ALICE, 50_000_000)]
+            } else {
+                vec![log("0xa3", 7, ALICE, BOB, 100_000_000)]
+            };
+            Ok(json!(logs))
+        }
+        _ => Err(RpcFailure::method_not_found(method)),
+    })
+}
+
+/// The blocks `server` was asked for, in order.
+fn fetched_blocks(server: &MockServer) -> Vec<u64> {
+    server
+        .requests()
+        .iter()
+        .flat_map(|request| match request.json() {
+            Value::Array(calls) => calls,
+            call => vec![call],
+        })
+        .filter(|call| call["method"] == "eth_getBlockByNumber")
+        .map(|call| quantity(&call["params"][0]))
+        .collect()
+}
+
+fn source(server: &MockServer) -> EthereumSource {
+    EthereumSource::new(EthClient::new(server.url())).with_token(Asset::token(
+        Chain::ETHEREUM,
+        USDC,
+        "USDC",
+        6,
+    ))
+}
+
+#[test]
+fn lists_ether_and_token_movements_with_fees() {
+    let server = node();
+    let alice = [Address::new(Chain::ETHEREUM, ALICE)];
+    let blocks = Period {
+        after: Some(PointInTime::Height(0)),
+        until: Some(PointInTime::Height(10)),
+    };
+    let entries = source(&server).transactions(&alice, blocks).unwrap();
+    let rows: Vec<Row> = entries
+        .iter()
+        .map(|e| {
+            let decimals = e.asset.decimals;
+            (
+                e.txid.as_str(),
+                e.asset.symbol.as_str(),
+                e.received.to_decimal_string(decimals),
+                e.sent.to_decimal_string(decimals),
+                e.fee.to_decimal_string(decimals),
+                e.counterparty.as_deref(),
+            )
+        })
+        .collect();
+    let fee = "0.000042".to_owned();
+    let zero = || "0".to_owned();
+    assert_eq!(
+        rows,
+        [
+            ("0xa1", "ETH", "1".to_owned(), zero(), zero(), Some(BOB)),
+            (
+                "0xa2",
+                "ETH",
+                zero(),
+                "0.25".to_owned(),
+                fee.clone(),
+                Some(BOB)
+            ),
+            // A token payment costs its sender ether.
+            ("0xa3", "ETH", zero(), zero(), fee.clone(), Some(USDC)),
+            ("0xa3", "USDC", zero(), "100".to_owned(), zero(), Some(BOB)),
+            ("0xa4", "USDC", "50".to_owned(), zero(), zero(), Some(CAROL)),
+            // A reverted payment moves nothing but still costs its fee.
+            ("0xa5", "ETH", zero(), zero(), fee, Some(BOB)),
+        ]
+    );
+    assert_eq!(entries[0].height, 3);
+    assert_eq!(entries[0].time, GENESIS + 36);
+    // Only blocks where Alice's balance or nonce moved, or her tokens did.
+    assert_eq!(fetched_blocks(&server), [3, 5, 7, 8, 9]);
+}
+
+/// Bob's game sends Alice two of item 3 in block 4; Alice sends Bob five
+/// of item 1 and one of item 2 in a batch in block 6.
+fn game_node() -> MockServer {
+    let log = |hash: &str, block: u64, topics: [&str; 4], words: &[u64]| {
+        json!({
+            "address": GAME.to_lowercase(),
+            "topics": topics.map(|t| if t.len() == 42 { word(t) } else { t.to_owned() }),
+            "data": format!("0x{}", words.iter().map(|w| format!("{w:064x}")).collect::<String>()),
+            "blockNumber": format!("0x{block:x}"),
+            "transactionHash": hash,
+            "logIndex": "0x0",
+        })
+    };
+    let logs = [
+        log("0xc1", 4, [TRANSFER_SINGLE, BOB, BOB, ALICE], &[3, 2]),
+        log(
+            "0xc2",
+            6,
+            [TRANSFER_BATCH, ALICE, ALICE, BOB],
+            &[0x40, 0xa0, 2, 1, 2, 2, 5, 1],
+        ),
+    ];
+    MockServer::json_rpc(move |method, params| match method {
+        "eth_getBalance" | "eth_getTransactionCount" => Ok(json!("0x0")),
+        "eth_getBlockByNumber" => {
+            let number = quantity(&params[0]);
+            Ok(json!({
+                "number": format!("0x{number:x}"),
+                "timestamp": format!("0x{:x}", GENESIS + 12 * number),
+                "transactions": [],
+            }))
+        }
+        "eth_getLogs" => {
+            let topics = params[0]["topics"].as_array().unwrap();
+            let matches = |log: &Value| {
+                topics.iter().enumerate().all(|(i, any_of)| {
+                    any_of.is_null() || any_of.as_array().unwrap().contains(&log["topics"][i])
+                })
+            };
+            Ok(Value::Array(
+                logs.iter().filter(|l| matches(l)).cloned().collect(),
+            ))
+        }
+        _ => Err(RpcFailure::method_not_found(method)),
+    })
+}
+
+#[test]
+fn lists_erc1155_transfers_by_item() {
+    let server = game_node();
+    let alice = [Address::new(Chain::ETHEREUM, ALICE)];
+    let blocks = Period {
+        after: Some(PointInTime::Height(0)),
+        until: Some(PointInTime::Height(10)),
+    };
+    let entries = source(&server).transactions(&alice, blocks).unwrap();
+    let rows: Vec<_> = entries
+        .iter()
+        .map(|e| {
+            (
+                e.txid.as_str(),
+                e.asset.clone(),
+                e.received.to_decimal_string(0),
+                e.sent.to_decimal_string(0),
+                e.counterparty.as_deref(),
+            )
+        })
+        .collect();
+    // Without a symbol the items are named after their standard.
+    let item = |id: &str| Asset::nft(Chain::ETHEREUM, GAME, id, "ERC1155");
+    assert_eq!(
+        rows,
+        [
+            ("0xc1", item("3"), "2".to_owned(), "0".to_owned(), Some(BOB)),
+            ("0xc2", item("1"), "0".to_owned(), "5".to_owned(), Some(BOB)),
+            ("0xc2", item("2"), "0".to_owned(), "1".to_owned(), Some(BOB)),
+        ]
+    );
+    assert_eq!(fetched_blocks(&server), [4, 6]);
+}
+
+#[test]
+fn needs_a_start_and_dates_across_chains() {
+    let server = node();
+    let alice = [Address::new(Chain::ETHEREUM, ALICE)];
+    let open = Period {
+        after: None,
+        until: Some(PointInTime::Height(10)),
+    };
+    let err = source(&server).transactions(&alice, open).unwrap_err();
+    assert!(err.to_string().contains("needs a start"), "{err}");
+
+    // Token discovery's first block is where the addresses' history starts.
+    let entries = source(&server)
+        .with_token_discovery(6)
+        .transactions(&alice, open)
+        .unwrap();
+    assert_eq!(entries.first().map(|e| e.txid.as_str()), Some("0xa3"));
+
+    let multi = MultiChainSource::new().with_source(source(&server));
+    let err = multi
+        .transactions(
+            &alice,
+            Period {
+                after: Some(PointInTime::Height(0)),
+                until: None,
+            },
+        )
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        walletb_core::Error::Source(ref e) if matches!(e.downcast_ref(), Some(Error::HeightAcrossChains(0)))
+    ));
+}
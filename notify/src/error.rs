@@ -0,0 +1,32 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unknown event type `{0}`")]
+    UnknownEvent(String),
+
+    #[error("webhook {url} failed after {attempts} attempts: {reason}")]
+    Delivery {
+        url: String,
+        attempts: u32,
+        reason: String,
+    },
+
+    #[error("webhook {url} rejected the request with status {status}")]
+    Rejected { url: String, status: u16 },
+
+    #[error("invalid duration `{0}`; use whole seconds, minutes, hours or days such as `90s`, `30m`, `1h` or `7d`")]
+    InvalidDuration(String),
+
+    #[error("cannot mail through {relay}: {reason}")]
+    Smtp { relay: String, reason: String },
+
+    #[error("cannot listen on {addr}: {reason}")]
+    Bind { addr: String, reason: String },
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
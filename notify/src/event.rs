@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use walletb_core::{Amount, Asset, Balance, Category};
+
+use crate::{Error, Result};
+
+/// What happened to a watched wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// A payment to the wallet appeared in the mempool.
+    Pending,
+    /// A payment was included in a block.
+    Confirmed,
+    /// A payment is buried under the configured number of blocks.
+    ConfirmationsReached,
+    /// A payment left the block it was confirmed in, or dropped out of the
+    /// mempool, after a reorganization or replacement.
+    Reorged,
+    /// The total held of one asset in one category changed.
+    BalanceChanged,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 5] = [
+        EventKind::Pending,
+        EventKind::Confirmed,
+        EventKind::ConfirmationsReached,
+        EventKind::Reorged,
+        EventKind::BalanceChanged,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Pending => "pending",
+            EventKind::Confirmed => "confirmed",
+            EventKind::ConfirmationsReached => "confirmations_reached",
+            EventKind::Reorged => "reorged",
+            EventKind::BalanceChanged => "balance_changed",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        EventKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| Error::UnknownEvent(s.to_owned()))
+    }
+}
+
+/// One change to a watched wallet, as delivered to webhooks and streams.
+///
+/// `id` is the same for every delivery of an event, so receivers can drop
+/// duplicates left by retries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: EventKind,
+    pub wallet: String,
+    pub asset: Asset,
+    /// Unix time the change was observed at.
+    pub time: u64,
+    /// The payment, for every kind but [`EventKind::BalanceChanged`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment: Option<Payment>,
+    /// The new totals, for [`EventKind::BalanceChanged`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance: Option<BalanceChange>,
+}
+
+/// A transaction output paying the wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Payment {
+    pub txid: String,
+    pub vout: u32,
+    pub amount: Amount,
+    /// Height of the confirming block, or `None` in the mempool. For
+    /// [`EventKind::Reorged`], the height it was confirmed at before.
+    pub height: Option<u32>,
+    /// Blocks on top of and including the confirming one, when the source
+    /// knows its tip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmations: Option<u32>,
+}
+
+/// The holdings of one asset in one category, before and after a change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub category: Category,
+    pub confirmed: Amount,
+    pub unconfirmed: Amount,
+    pub previous_confirmed: Amount,
+    pub previous_unconfirmed: Amount,
+}
+
+/// An output paying the wallet that is still unspent, as a source reports
+/// it. Change a wallet pays itself is a receipt too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Receipt {
+    pub txid: String,
+    pub vout: u32,
+    pub asset: Asset,
+    pub amount: Amount,
+    pub height: Option<u32>,
+    /// Whether the transaction paying it spends the wallet's own outputs,
+    /// making it change rather than a payment. Sources that cannot tell
+    /// leave it `false`.
+    pub change: bool,
+}
+
+/// One poll of a wallet: its balances and, from sources that see
+/// individual outputs, what makes them up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Observation {
+    pub balances: Vec<Balance>,
+    /// Unspent outputs, or `None` from account-based sources, which only
+    /// report balance changes.
+    pub receipts: Option<Vec<Receipt>>,
+    /// Height of the best block, to count confirmations.
+    pub tip: Option<u32>,
+    /// Every transaction touching the wallet's addresses, confirmed or in
+    /// the mempool. Lets a receipt that disappeared be told apart as spent
+    /// (its transaction is still known) or reorganized out (it is not).
+    /// Without it, disappearing receipts are taken as spent.
+    pub known_txids: Option<HashSet<String>>,
+}
+
+impl Observation {
+    pub fn new(balances: Vec<Balance>) -> Self {
+        Observation {
+            balances,
+            ..Observation::default()
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Events about watched wallets, for systems that must react to deposits
+//! as they happen instead of polling balances.
+//!
+//! A [`Tracker`] compares successive [`Observation`]s of each wallet and
+//! reports what changed as [`Event`]s: a payment seen in the mempool,
+//! confirmed, buried under enough blocks, or reorganized out, and changes
+//! of any balance. Payment events need a source that sees individual
+//! outputs (the Bitcoin sources); the others only report balance changes.
+//!
+//! Events go to [`Sink`]s: [`Webhook`]s, which sign each request and retry
+//! with backoff, and an [`EventStream`] that WebSocket clients connect to.
+//!
+//! [`Rules`] watch the same polls for conditions worth a person's attention,
+//! such as a hot wallet running low or a cold one paying out, and raise
+//! [`Alert`]s through [`Notifier`]s: [`Email`], [`Webhook`] or [`Stdout`].
+
+mod error;
+mod event;
+mod notifier;
+mod rules;
+mod stream;
+mod tracker;
+mod webhook;
+
+pub use error::{Error, Result};
+pub use event::{BalanceChange, Event, EventKind, Observation, Payment, Receipt};
+pub use notifier::{Email, Stdout};
+pub use rules::{parse_duration, Alert, Condition, Rule, Rules};
+pub use stream::EventStream;
+pub use tracker::Tracker;
+pub use webhook::{sign, verify, Retry, Webhook, WebhookQueue};
+
+/// Somewhere events are delivered to.
+pub trait Sink {
+    fn send(&self, event: &Event) -> Result<()>;
+}
+
+/// Somewhere alerts are delivered to.
+pub trait Notifier {
+    fn notify(&self, alert: &Alert) -> Result<()>;
+}
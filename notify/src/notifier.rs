@@ -0,0 +1,179 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use walletb_core::PointInTime;
+
+use crate::{Alert, Error, Notifier, Result};
+
+/// Prints each alert as one line on standard output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stdout;
+
+impl Notifier for Stdout {
+    fn notify(&self, alert: &Alert) -> Result<()> {
+        let mut out = io::stdout().lock();
+        writeln!(out, "{}", line(alert))?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Mails each alert through an SMTP relay.
+///
+/// This is a minimal client for a relay on a trusted network, such as a
+/// local MTA or a mail catcher: it speaks plain SMTP, without TLS or
+/// authentication.
+#[derive(Debug, Clone)]
+pub struct Email {
+    relay: String,
+    from: String,
+    to: Vec<String>,
+    timeout: Duration,
+}
+
+impl Email {
+    /// Sends from `from` to every address in `to` through the relay at
+    /// `host:port`.
+    pub fn new(relay: impl Into<String>, from: impl Into<String>, to: Vec<String>) -> Self {
+        Email {
+            relay: relay.into(),
+            from: from.into(),
+            to,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The message for `alert`, headers included, before dot-stuffing.
+    fn message(&self, alert: &Alert) -> String {
+        let asset = alert
+            .asset
+            .as_ref()
+            .map(|asset| format!("Asset: {asset}\r\n"))
+            .unwrap_or_default();
+        format!(
+            "From: <{from}>\r\nTo: {to}\r\nSubject: [walletb] {rule}: {message}\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\r\n\
+             {message}\r\n\r\nRule: {rule}\r\nWallet: {wallet}\r\n{asset}Time: {time}\r\n",
+            from = self.from,
+            to = self
+                .to
+                .iter()
+                .map(|to| format!("<{to}>"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            rule = alert.rule,
+            message = alert.message,
+            wallet = alert.wallet,
+            time = PointInTime::Time(alert.time),
+        )
+    }
+
+    fn send(&self, message: &str) -> io::Result<std::result::Result<(), String>> {
+        let stream = TcpStream::connect(&self.relay)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut smtp = Smtp {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+        let mut dialog = vec![
+            (None, 220),
+            (Some("EHLO walletb".to_owned()), 250),
+            (Some(format!("MAIL FROM:<{}>", self.from)), 250),
+        ];
+        dialog.extend(
+            self.to
+                .iter()
+                .map(|to| (Some(format!("RCPT TO:<{to}>")), 250)),
+        );
+        dialog.push((Some("DATA".to_owned()), 354));
+        for (command, expected) in dialog {
+            if let Err(reply) = smtp.exchange(command.as_deref(), expected)? {
+                return Ok(Err(reply));
+            }
+        }
+        let mut data = String::new();
+        for line in message.split("\r\n") {
+            // A line starting with a dot would otherwise end the message.
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push('.');
+        if let Err(reply) = smtp.exchange(Some(&data), 250)? {
+            return Ok(Err(reply));
+        }
+        let _ = smtp.exchange(Some("QUIT"), 221);
+        Ok(Ok(()))
+    }
+}
+
+impl Notifier for Email {
+    fn notify(&self, alert: &Alert) -> Result<()> {
+        let smtp = |reason: String| Error::Smtp {
+            relay: self.relay.clone(),
+            reason,
+        };
+        self.send(&self.message(alert))
+            .map_err(|e| smtp(e.to_string()))?
+            .map_err(smtp)
+    }
+}
+
+struct Smtp {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Smtp {
+    /// Sends `command`, if any, and reads the reply, returning it as an
+    /// error unless its code is `expected`. `251` counts as `250`.
+    fn exchange(
+        &mut self,
+        command: Option<&str>,
+        expected: u16,
+    ) -> io::Result<std::result::Result<(), String>> {
+        if let Some(command) = command {
+            write!(self.writer, "{command}\r\n")?;
+            self.writer.flush()?;
+        }
+        // Multi-line replies repeat the code with a `-` until the last line.
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(Err("connection closed".to_owned()));
+            }
+            reply.push_str(line.trim_end());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                break;
+            }
+            reply.push(' ');
+        }
+        let code: u16 = reply.get(..3).and_then(|c| c.parse().ok()).unwrap_or(0);
+        if code == expected || (expected == 250 && code == 251) {
+            Ok(Ok(()))
+        } else {
+            Ok(Err(reply))
+        }
+    }
+}
+
+/// `<time> <rule> <wallet>: <message>`.
+fn line(alert: &Alert) -> String {
+    format!(
+        "{} {} {}: {}",
+        PointInTime::Time(alert.time),
+        alert.rule,
+        alert.wallet,
+        alert.message
+    )
+}
@@ -0,0 +1,475 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::time::Duration;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use walletb_core::{Amount, Asset, Balance, Category};
+
+use crate::tracker::asset_id;
+use crate::{Error, Observation, Result};
+
+/// When a [`Rule`] fires, measured in whole units of an asset (BTC, not
+/// satoshi).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// The amount held is under the threshold.
+    Below(Decimal),
+    /// The amount fell by more than `percent` from its highest point within
+    /// `window`.
+    Drop { percent: Decimal, window: Duration },
+    /// Outputs the wallet held at the previous poll were spent.
+    ///
+    /// Sources that report no outputs, such as account-based chains, only
+    /// show the balance, so there a fall in the amount since the previous
+    /// poll is taken as the payment out instead. That also fires on fees
+    /// and slashing, and misses a payment out that a payment in made up
+    /// for between two polls.
+    Outgoing,
+}
+
+/// A condition on the holdings of some wallets.
+///
+/// A rule measures every asset a wallet holds separately, or only those
+/// with the symbol `asset`, summing the categories it holds them in unless
+/// `category` picks one. `Below` and `Drop` fire when the condition starts
+/// to hold and again only after it stopped holding; `Outgoing` fires on
+/// every spend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub name: String,
+    /// The wallet id the rule watches; every wallet if `None`.
+    pub wallet: Option<String>,
+    /// The asset symbol the rule watches; every asset if `None`.
+    pub asset: Option<String>,
+    pub category: Option<Category>,
+    pub condition: Condition,
+}
+
+impl Rule {
+    pub fn new(name: impl Into<String>, condition: Condition) -> Self {
+        Rule {
+            name: name.into(),
+            wallet: None,
+            asset: None,
+            category: None,
+            condition,
+        }
+    }
+
+    pub fn with_wallet(mut self, wallet: impl Into<String>) -> Self {
+        self.wallet = Some(wallet.into());
+        self
+    }
+
+    pub fn with_asset(mut self, symbol: impl Into<String>) -> Self {
+        self.asset = Some(symbol.into());
+        self
+    }
+
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    fn watches(&self, wallet: &str) -> bool {
+        self.wallet.as_deref().map_or(true, |id| id == wallet)
+    }
+
+    fn counts(&self, balance: &Balance) -> bool {
+        self.asset.as_deref().map_or(true, |symbol| {
+            balance.asset.symbol.eq_ignore_ascii_case(symbol)
+        }) && self.category.map_or(true, |c| c == balance.category)
+    }
+
+    /// Records `amount`, measured at `time`, and the `outputs` making it
+    /// up if the source reports them, and says why the rule fires, if it
+    /// does.
+    fn check(
+        &self,
+        state: &mut State,
+        label: &str,
+        amount: Decimal,
+        outputs: Option<Outputs>,
+        time: u64,
+    ) -> Option<String> {
+        let (holds, message) = match &self.condition {
+            Condition::Below(threshold) => (
+                amount < *threshold,
+                format!("{label} is {amount}, below {threshold}"),
+            ),
+            Condition::Drop { percent, window } => {
+                state.samples.push_back((time, amount));
+                while state
+                    .samples
+                    .front()
+                    .is_some_and(|(at, _)| at + window.as_secs() < time)
+                {
+                    state.samples.pop_front();
+                }
+                let peak = state
+                    .samples
+                    .iter()
+                    .map(|(_, amount)| *amount)
+                    .max()
+                    .unwrap_or(amount);
+                let fell = if peak.is_zero() {
+                    Decimal::ZERO
+                } else {
+                    (peak - amount) / peak * Decimal::ONE_HUNDRED
+                };
+                (
+                    fell > *percent,
+                    format!(
+                        "{label} fell {}% from {peak} to {amount} within {}",
+                        fell.round_dp(2).normalize(),
+                        format_duration(*window)
+                    ),
+                )
+            }
+            Condition::Outgoing => {
+                let before = state.previous.replace(amount);
+                let Some(outputs) = outputs else {
+                    let fell = before.filter(|&before| amount < before);
+                    return fell.map(|before| format!("{label} fell from {before} to {amount}"));
+                };
+                let previous = state.unspent.replace(outputs.unspent)?;
+                let unspent = state.unspent.as_ref()?;
+                // Gone since the last poll: spent, unless the transaction
+                // that paid the wallet is gone too.
+                let spent: Decimal = previous
+                    .iter()
+                    .filter(|(key, _)| !unspent.contains_key(*key))
+                    .filter(|((txid, _), _)| outputs.known_txids.map_or(true, |k| k.contains(txid)))
+                    .map(|(_, amount)| *amount)
+                    .sum();
+                return (!spent.is_zero())
+                    .then(|| format!("{spent} {label} was spent, leaving {amount}"));
+            }
+        };
+        let fires = holds && !state.firing;
+        state.firing = holds;
+        fires.then_some(message)
+    }
+}
+
+/// A rule that fired.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: String,
+    pub rule: String,
+    pub wallet: String,
+    /// The asset measured, unless the wallet holds none of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<Asset>,
+    /// The amount held, in whole units.
+    pub amount: Decimal,
+    pub message: String,
+    /// Unix time of the poll that fired the rule.
+    pub time: u64,
+}
+
+/// Evaluates [`Rule`]s against each poll of the wallets they watch.
+#[derive(Debug)]
+pub struct Rules {
+    rules: Vec<Rule>,
+    state: HashMap<(usize, String, Option<Asset>), State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Whether the condition held at the last poll.
+    firing: bool,
+    previous: Option<Decimal>,
+    /// Unspent outputs at the last poll, for `Outgoing`, if the source
+    /// reported them.
+    unspent: Option<HashMap<(String, u32), Decimal>>,
+    /// Amounts of recent polls, oldest first, for `Drop`.
+    samples: VecDeque<(u64, Decimal)>,
+}
+
+/// The outputs making up one asset's amount at a poll.
+struct Outputs<'a> {
+    unspent: HashMap<(String, u32), Decimal>,
+    known_txids: Option<&'a HashSet<String>>,
+}
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Rules {
+            rules,
+            state: HashMap::new(),
+        }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Checks every rule watching `wallet` against its `balances`, read at
+    /// unix `time`, returning the alerts that fire. For sources that report
+    /// outputs, use [`observe`](Self::observe).
+    pub fn evaluate(&mut self, wallet: &str, balances: &[Balance], time: u64) -> Vec<Alert> {
+        self.observe(wallet, &Observation::new(balances.to_vec()), time)
+    }
+
+    /// Like [`evaluate`](Self::evaluate), telling outgoing payments by the
+    /// outputs of `observation` that were spent when it has them.
+    pub fn observe(&mut self, wallet: &str, observation: &Observation, time: u64) -> Vec<Alert> {
+        let balances = &observation.balances;
+        let mut alerts = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !rule.watches(wallet) {
+                continue;
+            }
+            let mut held: BTreeMap<Option<Asset>, Decimal> = BTreeMap::new();
+            for balance in balances.iter().filter(|b| rule.counts(b)) {
+                *held.entry(Some(balance.asset.clone())).or_default() +=
+                    units(balance.total(), &balance.asset);
+            }
+            // An asset the source stopped reporting is held no more.
+            for (rule_index, id, asset) in self.state.keys() {
+                if *rule_index == i && id == wallet && asset.is_some() {
+                    held.entry(asset.clone()).or_default();
+                }
+            }
+            // A threshold on an asset the wallet never held still applies.
+            if held.is_empty() && matches!(rule.condition, Condition::Below(_)) {
+                held.insert(None, Decimal::ZERO);
+            }
+            for (asset, amount) in held {
+                let label = match (&asset, &rule.asset) {
+                    (Some(asset), _) => asset.label(),
+                    (None, Some(symbol)) => symbol.clone(),
+                    (None, None) => "the balance".to_owned(),
+                };
+                let outputs = match (&observation.receipts, &asset) {
+                    // Outputs are spendable, so they make up the available
+                    // amount only.
+                    (Some(receipts), Some(asset))
+                        if rule.category.map_or(true, |c| c == Category::Available) =>
+                    {
+                        Some(Outputs {
+                            unspent: receipts
+                                .iter()
+                                .filter(|r| r.asset == *asset)
+                                .map(|r| ((r.txid.clone(), r.vout), units(r.amount, &r.asset)))
+                                .collect(),
+                            known_txids: observation.known_txids.as_ref(),
+                        })
+                    }
+                    _ => None,
+                };
+                let state = self
+                    .state
+                    .entry((i, wallet.to_owned(), asset.clone()))
+                    .or_default();
+                let Some(message) = rule.check(state, &label, amount, outputs, time) else {
+                    continue;
+                };
+                let id = match &asset {
+                    Some(asset) => format!("{}:{wallet}:{}:{time}", rule.name, asset_id(asset)),
+                    None => format!("{}:{wallet}:{time}", rule.name),
+                };
+                alerts.push(Alert {
+                    id,
+                    rule: rule.name.clone(),
+                    wallet: wallet.to_owned(),
+                    asset,
+                    amount,
+                    message,
+                    time,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+/// `amount` in whole units of `asset`, saturating at the largest decimal.
+fn units(amount: Amount, asset: &Asset) -> Decimal {
+    Decimal::from_str(&amount.to_decimal_string(asset.decimals))
+        .map(|d| d.normalize())
+        .unwrap_or(Decimal::MAX)
+}
+
+/// Parses a duration of whole seconds, minutes, hours or days, such as
+/// `90s`, `30m`, `1h` or `7d`.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let invalid = || Error::InvalidDuration(s.to_owned());
+    let unit = match s.chars().last().ok_or_else(invalid)? {
+        's' => 1,
+        'm' => 60,
+        'h' => 3_600,
+        'd' => 86_400,
+        _ => return Err(invalid()),
+    };
+    let count: u64 = s[..s.len() - 1].parse().map_err(|_| invalid())?;
+    if count == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(
+        count.checked_mul(unit).ok_or_else(invalid)?,
+    ))
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        _ if secs % 86_400 == 0 => format!("{}d", secs / 86_400),
+        _ if secs % 3_600 == 0 => format!("{}h", secs / 3_600),
+        _ if secs % 60 == 0 => format!("{}m", secs / 60),
+        _ => format!("{secs}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use walletb_core::Chain;
+
+    use super::*;
+    use crate::Receipt;
+
+    fn btc(amount: &str) -> Balance {
+        let asset = Asset::native(Chain::Bitcoin, "BTC", 8);
+        Balance::new(asset, Amount::from_decimal_str(amount, 8).unwrap())
+    }
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn messages(alerts: &[Alert]) -> Vec<&str> {
+        alerts.iter().map(|a| a.message.as_str()).collect()
+    }
+
+    #[test]
+    fn fires_once_when_a_balance_falls_below_a_threshold() {
+        let mut rules = Rules::new(vec![Rule::new("hot-low", Condition::Below(dec("0.5")))
+            .with_wallet("hot")
+            .with_asset("btc")]);
+        assert!(rules.evaluate("hot", &[btc("0.6")], 1).is_empty());
+        let alerts = rules.evaluate("hot", &[btc("0.4")], 2);
+        assert_eq!(messages(&alerts), ["BTC is 0.4, below 0.5"]);
+        assert_eq!(alerts[0].id, "hot-low:hot:bitcoin:BTC:2");
+        assert_eq!(alerts[0].amount, dec("0.4"));
+        // Still low: no repeat until it recovers and falls again.
+        assert!(rules.evaluate("hot", &[btc("0.3")], 3).is_empty());
+        assert!(rules.evaluate("hot", &[btc("0.7")], 4).is_empty());
+        assert_eq!(rules.evaluate("hot", &[btc("0.1")], 5).len(), 1);
+        // Other wallets are not watched.
+        assert!(rules.evaluate("cold", &[btc("0")], 5).is_empty());
+
+        // A wallet that holds none of the asset is below any threshold.
+        let mut rules = Rules::new(vec![
+            Rule::new("low", Condition::Below(dec("1"))).with_asset("ETH")
+        ]);
+        let alerts = rules.evaluate("hot", &[btc("2")], 1);
+        assert_eq!(messages(&alerts), ["ETH is 0, below 1"]);
+        assert_eq!(alerts[0].asset, None);
+    }
+
+    #[test]
+    fn fires_on_drops_within_a_window() {
+        let window = Duration::from_secs(3_600);
+        let mut rules = Rules::new(vec![Rule::new(
+            "vault-drop",
+            Condition::Drop {
+                percent: dec("5"),
+                window,
+            },
+        )]);
+        assert!(rules.evaluate("vault", &[btc("10")], 0).is_empty());
+        assert!(rules.evaluate("vault", &[btc("9.8")], 600).is_empty());
+        let alerts = rules.evaluate("vault", &[btc("9.4")], 1_200);
+        assert_eq!(messages(&alerts), ["BTC fell 6% from 10 to 9.4 within 1h"]);
+        assert!(rules.evaluate("vault", &[btc("9.3")], 1_800).is_empty());
+
+        // The same slide spread over more than the window does not fire.
+        let mut rules = Rules::new(vec![Rule::new(
+            "vault-drop",
+            Condition::Drop {
+                percent: dec("5"),
+                window,
+            },
+        )]);
+        for (i, amount) in ["10", "9.7", "9.4", "9.1"].into_iter().enumerate() {
+            let time = i as u64 * 3_000;
+            assert!(
+                rules.evaluate("vault", &[btc(amount)], time).is_empty(),
+                "{amount}"
+            );
+        }
+    }
+
+    #[test]
+    fn fires_on_every_outgoing_payment() {
+        let mut rules = Rules::new(vec![
+            Rule::new("cold-out", Condition::Outgoing).with_wallet("cold")
+        ]);
+        assert!(rules.evaluate("cold", &[btc("2")], 1).is_empty());
+        assert!(rules.evaluate("cold", &[btc("2.5")], 2).is_empty());
+        assert_eq!(
+            messages(&rules.evaluate("cold", &[btc("1.5")], 3)),
+            ["BTC fell from 2.5 to 1.5"]
+        );
+        assert_eq!(rules.evaluate("cold", &[btc("1")], 4).len(), 1);
+        // Emptied and no longer reported.
+        assert_eq!(
+            messages(&rules.evaluate("cold", &[], 5)),
+            ["BTC fell from 1 to 0"]
+        );
+    }
+
+    #[test]
+    fn tells_outgoing_payments_by_spent_outputs() {
+        let mut rules = Rules::new(vec![Rule::new("cold-out", Condition::Outgoing)]);
+        let poll = |outputs: &[(&str, &str)], known: &[&str]| {
+            let receipts: Vec<Receipt> = outputs
+                .iter()
+                .map(|(txid, amount)| Receipt {
+                    txid: txid.to_string(),
+                    vout: 0,
+                    asset: btc("0").asset,
+                    amount: btc(amount).confirmed,
+                    height: Some(1),
+                    change: false,
+                })
+                .collect();
+            let total = receipts.iter().fold(Amount::ZERO, |sum, r| sum + r.amount);
+            let mut observation = Observation::new(vec![Balance::new(btc("0").asset, total)]);
+            observation.receipts = Some(receipts);
+            observation.known_txids = Some(known.iter().map(|t| t.to_string()).collect());
+            observation
+        };
+        let known = ["a", "b", "c", "d"];
+        assert!(rules
+            .observe("cold", &poll(&[("a", "1"), ("b", "1.5")], &known), 1)
+            .is_empty());
+        // A payment in that outweighs the payment out still counts.
+        assert_eq!(
+            messages(&rules.observe("cold", &poll(&[("b", "1.5"), ("c", "1.2")], &known), 2)),
+            ["1 BTC was spent, leaving 2.7"]
+        );
+        // Reorganized out, not spent.
+        assert!(rules
+            .observe("cold", &poll(&[("c", "1.2")], &["a", "c", "d"]), 3)
+            .is_empty());
+        // Spent with change back: the balance barely moves.
+        assert_eq!(
+            messages(&rules.observe("cold", &poll(&[("d", "1.1")], &known), 4)),
+            ["1.2 BTC was spent, leaving 1.1"]
+        );
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1_800));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        for invalid in ["", "h", "1", "0h", "1.5h", "1w", "-1h", "300000000000000d"] {
+            assert!(parse_duration(invalid).is_err(), "{invalid}");
+        }
+        assert_eq!(format_duration(Duration::from_secs(5_400)), "90m");
+    }
+}
@@ -0,0 +1,148 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message, WebSocket};
+
+use crate::{Error, Event, Result, Sink};
+
+/// A WebSocket endpoint that sends every event, as a JSON text message, to
+/// each connected client.
+///
+/// The stream only goes one way: what clients send is never read. When
+/// keys are set, a client must present one while connecting, as
+/// `Authorization: Bearer <key>` or a `?key=<key>` query, or it is turned
+/// away with `401`. A client that stops reading is dropped once a send to
+/// it times out.
+pub struct EventStream {
+    addr: SocketAddr,
+    clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EventStream {
+    /// Listens on `addr`, accepting clients that present one of `keys`, or
+    /// anyone if there are none.
+    pub fn bind(addr: &str, keys: Vec<String>) -> Result<Self> {
+        let listener = TcpListener::bind(addr).map_err(|e| Error::Bind {
+            addr: addr.to_owned(),
+            reason: e.to_string(),
+        })?;
+        let addr = listener.local_addr()?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let clients = Arc::clone(&clients);
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    // A client stalling its handshake would hold up every
+                    // other one.
+                    let timeout = Some(Duration::from_secs(5));
+                    if stream.set_read_timeout(timeout).is_err()
+                        || stream.set_write_timeout(timeout).is_err()
+                    {
+                        continue;
+                    }
+                    if let Ok(socket) = tungstenite::accept_hdr(stream, Authorize(&keys)) {
+                        clients.lock().unwrap().push(socket);
+                    }
+                }
+            })
+        };
+        Ok(EventStream {
+            addr,
+            clients,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Number of clients connected.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+}
+
+impl Sink for EventStream {
+    fn send(&self, event: &Event) -> Result<()> {
+        let text = serde_json::to_string(event)?;
+        self.clients
+            .lock()
+            .unwrap()
+            .retain_mut(|client| client.send(Message::Text(text.clone())).is_ok());
+        Ok(())
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        for mut client in self.clients.lock().unwrap().drain(..) {
+            let _ = client.close(None);
+            let _ = client.flush();
+        }
+    }
+}
+
+/// Turns away handshakes that do not present a known key.
+struct Authorize<'a>(&'a [String]);
+
+impl Callback for Authorize<'_> {
+    fn on_request(
+        self,
+        request: &Request,
+        response: Response,
+    ) -> std::result::Result<Response, ErrorResponse> {
+        if self.0.is_empty() || authorized(self.0, request) {
+            return Ok(response);
+        }
+        let mut denied = ErrorResponse::new(Some("missing or unknown key".to_owned()));
+        *denied.status_mut() = StatusCode::UNAUTHORIZED;
+        Err(denied)
+    }
+}
+
+fn authorized(keys: &[String], request: &Request) -> bool {
+    let bearer = request
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+    let query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(name, _)| *name == "key")
+            .map(|(_, key)| key)
+    });
+    let presented = bearer.or(query).unwrap_or_default();
+    // Compare against every key, so timing does not tell which one a guess
+    // was close to.
+    keys.iter().fold(false, |found, key| {
+        same(key.as_bytes(), presented.as_bytes()) | found
+    })
+}
+
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
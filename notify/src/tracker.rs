@@ -0,0 +1,486 @@
+use std::collections::{BTreeMap, HashMap};
+
+use walletb_core::{Amount, Asset, Category};
+
+use crate::{BalanceChange, Event, EventKind, Observation, Payment, Receipt};
+
+/// Turns successive [`Observation`]s of wallets into [`Event`]s.
+///
+/// The first observation of a wallet only sets the baseline: payments
+/// already there are not announced again, so restarting a watcher does not
+/// replay a wallet's whole history.
+///
+/// Receipts marked as change are not payments to the wallet: they show
+/// only in [`EventKind::BalanceChanged`] events.
+#[derive(Debug)]
+pub struct Tracker {
+    confirmations: u32,
+    wallets: HashMap<String, WalletState>,
+}
+
+#[derive(Debug, Default)]
+struct WalletState {
+    receipts: BTreeMap<(String, u32), Tracked>,
+    /// Confirmed and unconfirmed totals.
+    balances: BTreeMap<(Asset, Category), (Amount, Amount)>,
+}
+
+#[derive(Debug)]
+struct Tracked {
+    receipt: Receipt,
+    /// Whether [`EventKind::ConfirmationsReached`] was sent for the
+    /// receipt's current height.
+    reached: bool,
+}
+
+impl Tracker {
+    /// Announces a payment once `confirmations` blocks confirm it.
+    pub fn new(confirmations: u32) -> Self {
+        Tracker {
+            confirmations,
+            wallets: HashMap::new(),
+        }
+    }
+
+    pub fn confirmations(&self) -> u32 {
+        self.confirmations
+    }
+
+    /// Compares `observation` with the previous one of `wallet`, returning
+    /// what changed as of unix `time`.
+    pub fn observe(&mut self, wallet: &str, observation: &Observation, time: u64) -> Vec<Event> {
+        let baseline = !self.wallets.contains_key(wallet);
+        let state = self.wallets.entry(wallet.to_owned()).or_default();
+        let mut events = Vec::new();
+        let mut emit = |kind: EventKind, id: String, asset: &Asset, payment, balance| {
+            if !baseline {
+                events.push(Event {
+                    id: format!("{wallet}:{kind}:{id}"),
+                    kind,
+                    wallet: wallet.to_owned(),
+                    asset: asset.clone(),
+                    time,
+                    payment,
+                    balance,
+                });
+            }
+        };
+
+        if let Some(receipts) = &observation.receipts {
+            let mut previous = std::mem::take(&mut state.receipts);
+            for receipt in receipts {
+                let key = (receipt.txid.clone(), receipt.vout);
+                if receipt.change {
+                    let receipt = receipt.clone();
+                    state.receipts.insert(
+                        key,
+                        Tracked {
+                            receipt,
+                            reached: true,
+                        },
+                    );
+                    continue;
+                }
+                let outpoint = format!("{}:{}", receipt.txid, receipt.vout);
+                let confirmations = confirmations(receipt.height, observation.tip);
+                let payment = Payment {
+                    txid: receipt.txid.clone(),
+                    vout: receipt.vout,
+                    amount: receipt.amount,
+                    height: receipt.height,
+                    confirmations,
+                };
+                let before = previous.remove(&key);
+                let was = before.as_ref().map(|tracked| tracked.receipt.height);
+                match (was, receipt.height) {
+                    (None, None) => {
+                        emit(
+                            EventKind::Pending,
+                            outpoint.clone(),
+                            &receipt.asset,
+                            Some(payment.clone()),
+                            None,
+                        );
+                    }
+                    (Some(Some(old)), new) if new != Some(old) => {
+                        let moved = Payment {
+                            height: Some(old),
+                            confirmations: None,
+                            ..payment.clone()
+                        };
+                        emit(
+                            EventKind::Reorged,
+                            format!("{outpoint}:{old}"),
+                            &receipt.asset,
+                            Some(moved),
+                            None,
+                        );
+                        if let Some(height) = new {
+                            emit(
+                                EventKind::Confirmed,
+                                format!("{outpoint}:{height}"),
+                                &receipt.asset,
+                                Some(payment.clone()),
+                                None,
+                            );
+                        }
+                    }
+                    (None | Some(None), Some(height)) => {
+                        emit(
+                            EventKind::Confirmed,
+                            format!("{outpoint}:{height}"),
+                            &receipt.asset,
+                            Some(payment.clone()),
+                            None,
+                        );
+                    }
+                    _ => {}
+                }
+                let mut reached = before.is_some_and(|tracked| {
+                    tracked.reached && tracked.receipt.height == receipt.height
+                });
+                if !reached && confirmations.is_some_and(|n| n >= self.confirmations) {
+                    reached = true;
+                    let height = receipt.height.unwrap_or_default();
+                    emit(
+                        EventKind::ConfirmationsReached,
+                        format!("{outpoint}:{height}"),
+                        &receipt.asset,
+                        Some(payment),
+                        None,
+                    );
+                }
+                state.receipts.insert(
+                    key,
+                    Tracked {
+                        receipt: receipt.clone(),
+                        reached,
+                    },
+                );
+            }
+            // Gone since the last poll: spent, unless the transaction that
+            // paid the wallet is gone too.
+            for ((txid, vout), tracked) in previous {
+                if tracked.receipt.change {
+                    continue;
+                }
+                let dropped = observation
+                    .known_txids
+                    .as_ref()
+                    .is_some_and(|known| !known.contains(&txid));
+                if !dropped {
+                    continue;
+                }
+                let receipt = tracked.receipt;
+                let at = receipt
+                    .height
+                    .map_or("mempool".to_owned(), |h| h.to_string());
+                let payment = Payment {
+                    txid: receipt.txid.clone(),
+                    vout,
+                    amount: receipt.amount,
+                    height: receipt.height,
+                    confirmations: None,
+                };
+                emit(
+                    EventKind::Reorged,
+                    format!("{txid}:{vout}:{at}"),
+                    &receipt.asset,
+                    Some(payment),
+                    None,
+                );
+            }
+        }
+
+        let mut current: BTreeMap<(Asset, Category), (Amount, Amount)> = BTreeMap::new();
+        for balance in &observation.balances {
+            let totals = current
+                .entry((balance.asset.clone(), balance.category))
+                .or_insert((Amount::ZERO, Amount::ZERO));
+            totals.0 = totals.0 + balance.confirmed;
+            totals.1 = totals.1 + balance.unconfirmed;
+        }
+        let zero = (Amount::ZERO, Amount::ZERO);
+        let mut keys: Vec<&(Asset, Category)> = current.keys().collect();
+        keys.extend(
+            state
+                .balances
+                .keys()
+                .filter(|key| !current.contains_key(*key)),
+        );
+        keys.sort();
+        for key in keys {
+            let before = state.balances.get(key).copied().unwrap_or(zero);
+            let after = current.get(key).copied().unwrap_or(zero);
+            if before == after {
+                continue;
+            }
+            let (asset, category) = key;
+            let change = BalanceChange {
+                category: *category,
+                confirmed: after.0,
+                unconfirmed: after.1,
+                previous_confirmed: before.0,
+                previous_unconfirmed: before.1,
+            };
+            emit(
+                EventKind::BalanceChanged,
+                format!("{}:{category}:{time}", asset_id(asset)),
+                asset,
+                None,
+                Some(change),
+            );
+        }
+        state.balances = current;
+        events
+    }
+}
+
+/// Blocks on top of and including `height`.
+fn confirmations(height: Option<u32>, tip: Option<u32>) -> Option<u32> {
+    match (height, tip) {
+        (None, _) => Some(0),
+        (Some(height), Some(tip)) => Some(tip.saturating_sub(height) + 1),
+        (Some(_), None) => None,
+    }
+}
+
+/// Names an asset within its chain: its contract, or symbol if native.
+pub(crate) fn asset_id(asset: &Asset) -> String {
+    let id = match (asset.contract(), asset.token_id()) {
+        (Some(contract), Some(token)) => format!("{contract}#{token}"),
+        (Some(contract), None) => contract.to_owned(),
+        (None, _) => asset.symbol.clone(),
+    };
+    format!("{}:{id}", asset.chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use walletb_core::{Balance, Chain};
+
+    use super::*;
+
+    fn btc() -> Asset {
+        Asset::native(Chain::Bitcoin, "BTC", 8)
+    }
+
+    fn receipt(txid: &str, sats: u64, height: Option<u32>) -> Receipt {
+        Receipt {
+            txid: txid.to_owned(),
+            vout: 0,
+            asset: btc(),
+            amount: Amount::from_u64(sats),
+            height,
+            change: false,
+        }
+    }
+
+    fn observation(receipts: Vec<Receipt>, tip: u32) -> Observation {
+        let confirmed = receipts.iter().filter(|r| r.height.is_some());
+        let unconfirmed = receipts.iter().filter(|r| r.height.is_none());
+        let sum = |rs: &mut dyn Iterator<Item = &Receipt>| {
+            rs.fold(Amount::ZERO, |total, r| total + r.amount)
+        };
+        let balance = Balance::new(btc(), sum(&mut confirmed.into_iter()))
+            .with_unconfirmed(sum(&mut unconfirmed.into_iter()));
+        let known_txids = receipts.iter().map(|r| r.txid.clone()).collect();
+        Observation {
+            balances: vec![balance],
+            receipts: Some(receipts),
+            tip: Some(tip),
+            known_txids: Some(known_txids),
+        }
+    }
+
+    fn kinds(events: &[Event]) -> Vec<(EventKind, &str)> {
+        events.iter().map(|e| (e.kind, e.id.as_str())).collect()
+    }
+
+    #[test]
+    fn follows_a_deposit_until_it_is_final() {
+        let mut tracker = Tracker::new(3);
+        let old = receipt("aa", 1_000, Some(90));
+        assert!(tracker
+            .observe("vault", &observation(vec![old.clone()], 100), 1)
+            .is_empty());
+
+        let pending = receipt("bb", 50_000, None);
+        let events = tracker.observe("vault", &observation(vec![old.clone(), pending], 100), 2);
+        assert_eq!(
+            kinds(&events),
+            [
+                (EventKind::Pending, "vault:pending:bb:0"),
+                (
+                    EventKind::BalanceChanged,
+                    "vault:balance_changed:bitcoin:BTC:available:2"
+                ),
+            ]
+        );
+        assert_eq!(events[0].payment.as_ref().unwrap().confirmations, Some(0));
+        let change = events[1].balance.as_ref().unwrap();
+        assert_eq!(change.unconfirmed, Amount::from_u64(50_000));
+        assert_eq!(change.previous_unconfirmed, Amount::ZERO);
+
+        let confirmed = receipt("bb", 50_000, Some(101));
+        let events = tracker.observe(
+            "vault",
+            &observation(vec![old.clone(), confirmed.clone()], 101),
+            3,
+        );
+        assert_eq!(events[0].kind, EventKind::Confirmed);
+        assert_eq!(events[0].id, "vault:confirmed:bb:0:101");
+        assert_eq!(events[1].kind, EventKind::BalanceChanged);
+
+        assert!(tracker
+            .observe(
+                "vault",
+                &observation(vec![old.clone(), confirmed.clone()], 102),
+                4
+            )
+            .is_empty());
+        let events = tracker.observe(
+            "vault",
+            &observation(vec![old.clone(), confirmed.clone()], 103),
+            5,
+        );
+        assert_eq!(
+            kinds(&events),
+            [(
+                EventKind::ConfirmationsReached,
+                "vault:confirmations_reached:bb:0:101"
+            )]
+        );
+        assert_eq!(events[0].payment.as_ref().unwrap().confirmations, Some(3));
+        assert!(tracker
+            .observe("vault", &observation(vec![old, confirmed], 104), 6)
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_payments_reorganized_out() {
+        let mut tracker = Tracker::new(6);
+        tracker.observe(
+            "vault",
+            &observation(vec![receipt("aa", 1_000, Some(100))], 100),
+            1,
+        );
+
+        // Back in the mempool after the block was replaced.
+        let events = tracker.observe(
+            "vault",
+            &observation(vec![receipt("aa", 1_000, None)], 100),
+            2,
+        );
+        assert_eq!(events[0].kind, EventKind::Reorged);
+        assert_eq!(events[0].id, "vault:reorged:aa:0:100");
+        assert_eq!(events[0].payment.as_ref().unwrap().height, Some(100));
+
+        // Confirmed again in another block, then gone altogether.
+        let events = tracker.observe(
+            "vault",
+            &observation(vec![receipt("aa", 1_000, Some(101))], 101),
+            3,
+        );
+        assert_eq!(events[0].id, "vault:confirmed:aa:0:101");
+        let events = tracker.observe("vault", &observation(Vec::new(), 101), 4);
+        assert_eq!(
+            kinds(&events),
+            [
+                (EventKind::Reorged, "vault:reorged:aa:0:101"),
+                (
+                    EventKind::BalanceChanged,
+                    "vault:balance_changed:bitcoin:BTC:available:4"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn takes_receipts_of_known_transactions_as_spent() {
+        let mut tracker = Tracker::new(6);
+        tracker.observe(
+            "vault",
+            &observation(vec![receipt("aa", 1_000, Some(100))], 100),
+            1,
+        );
+        let mut spent = observation(Vec::new(), 101);
+        spent.known_txids = Some(HashSet::from(["aa".to_owned(), "cc".to_owned()]));
+        let events = tracker.observe("vault", &spent, 2);
+        assert_eq!(
+            kinds(&events),
+            [(
+                EventKind::BalanceChanged,
+                "vault:balance_changed:bitcoin:BTC:available:2"
+            )]
+        );
+    }
+
+    #[test]
+    fn announces_no_payment_for_change() {
+        let mut tracker = Tracker::new(1);
+        tracker.observe(
+            "vault",
+            &observation(vec![receipt("aa", 100_000, Some(100))], 100),
+            1,
+        );
+        // "bb" spends "aa", paying 30_000 back to the wallet.
+        let change = Receipt {
+            change: true,
+            ..receipt("bb", 30_000, None)
+        };
+        let known = HashSet::from(["aa".to_owned(), "bb".to_owned()]);
+        let mut spent = observation(vec![change.clone()], 100);
+        spent.known_txids = Some(known.clone());
+        let events = tracker.observe("vault", &spent, 2);
+        assert_eq!(
+            kinds(&events),
+            [(
+                EventKind::BalanceChanged,
+                "vault:balance_changed:bitcoin:BTC:available:2"
+            )]
+        );
+        let confirmed = Receipt {
+            height: Some(101),
+            ..change
+        };
+        let mut mined = observation(vec![confirmed], 101);
+        mined.known_txids = Some(known);
+        let events = tracker.observe("vault", &mined, 3);
+        assert!(events.iter().all(|e| e.kind == EventKind::BalanceChanged));
+    }
+
+    #[test]
+    fn reports_balance_changes_of_account_sources() {
+        let eth = Asset::native(Chain::ETHEREUM, "ETH", 18);
+        let staked = |wei| {
+            Observation::new(vec![
+                Balance::new(eth.clone(), Amount::from_u64(wei)).with_category(Category::Staked)
+            ])
+        };
+        let mut tracker = Tracker::new(6);
+        assert!(tracker.observe("a", &staked(5), 1).is_empty());
+        // Wallets are tracked separately.
+        assert!(tracker.observe("b", &staked(7), 1).is_empty());
+        assert!(tracker.observe("a", &staked(5), 2).is_empty());
+        let events = tracker.observe("a", &staked(8), 3);
+        assert_eq!(
+            kinds(&events),
+            [(
+                EventKind::BalanceChanged,
+                "a:balance_changed:ethereum:ETH:staked:3"
+            )]
+        );
+        let change = events[0].balance.as_ref().unwrap();
+        assert_eq!(
+            (change.previous_confirmed, change.confirmed),
+            (Amount::from_u64(5), Amount::from_u64(8))
+        );
+
+        // An asset no longer reported counts as emptied.
+        let events = tracker.observe("a", &Observation::new(Vec::new()), 4);
+        assert_eq!(events[0].balance.as_ref().unwrap().confirmed, Amount::ZERO);
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{Alert, Error, Event, EventKind, Notifier, Result, Sink};
+
+/// How often, and how patiently, a failed delivery is retried.
+///
+/// The wait doubles after every attempt, from `initial` up to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    /// Attempts in all, including the first.
+    pub attempts: u32,
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            attempts: 6,
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Retry {
+    /// The wait after failed attempt `attempt`, counting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// An HTTPS endpoint that events are POSTed to as JSON.
+///
+/// Each request is signed so the receiver can check it came from us and
+/// was not replayed: `X-Walletb-Timestamp` holds the unix time it was sent
+/// at and `X-Walletb-Signature` is `sha256=` followed by the hex
+/// HMAC-SHA256, keyed with the shared secret, of the timestamp, a `.` and
+/// the body. [`verify`] checks one. `X-Walletb-Event` names the event type
+/// and `X-Walletb-Delivery` holds the event id, the same across retries.
+///
+/// A delivery is retried on connection failures, `408`, `429` and `5xx`
+/// answers; any other `4xx` means the receiver will never take it.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+    secret: String,
+    retry: Retry,
+    /// Event kinds to deliver; every kind if empty.
+    events: Vec<EventKind>,
+    agent: ureq::Agent,
+}
+
+impl Webhook {
+    pub fn new(url: impl Into<String>, secret: impl Into<String>) -> Self {
+        Webhook {
+            url: url.into(),
+            secret: secret.into(),
+            retry: Retry::default(),
+            events: Vec::new(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(10))
+                .build(),
+        }
+    }
+
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Delivers only events of these kinds.
+    pub fn with_events(mut self, events: impl IntoIterator<Item = EventKind>) -> Self {
+        self.events = events.into_iter().collect();
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Whether the webhook subscribes to `event`'s kind.
+    pub fn wants(&self, event: &Event) -> bool {
+        self.events.is_empty() || self.events.contains(&event.kind)
+    }
+
+    /// POSTs `event`, retrying until it is accepted or the attempts run
+    /// out. Returns the number of attempts it took.
+    pub fn deliver(&self, event: &Event) -> Result<u32> {
+        self.post(&Post::event(event)?)
+    }
+
+    fn post(&self, post: &Post) -> Result<u32> {
+        let mut attempt = 1;
+        loop {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let sent = self
+                .agent
+                .post(&self.url)
+                .set("Content-Type", "application/json")
+                .set("X-Walletb-Event", &post.kind)
+                .set("X-Walletb-Delivery", &post.id)
+                .set("X-Walletb-Timestamp", &timestamp.to_string())
+                .set(
+                    "X-Walletb-Signature",
+                    &sign(&self.secret, timestamp, &post.body),
+                )
+                .send_string(&post.body);
+            let reason = match sent {
+                Ok(_) => return Ok(attempt),
+                Err(ureq::Error::Status(status, _))
+                    if status != 408 && status != 429 && status < 500 =>
+                {
+                    return Err(Error::Rejected {
+                        url: self.url.clone(),
+                        status,
+                    });
+                }
+                Err(ureq::Error::Status(status, _)) => format!("status {status}"),
+                Err(ureq::Error::Transport(e)) => e.to_string(),
+            };
+            if attempt >= self.retry.attempts {
+                return Err(Error::Delivery {
+                    url: self.url.clone(),
+                    attempts: attempt,
+                    reason,
+                });
+            }
+            thread::sleep(self.retry.delay(attempt));
+            attempt += 1;
+        }
+    }
+
+    /// Moves delivery to a background thread, so a slow or failing
+    /// receiver does not hold up the caller. `on_failure` is called on that
+    /// thread with the id of each event or alert that could not be
+    /// delivered, and why. Dropping the queue waits for the events already
+    /// queued.
+    pub fn spawn<F>(self, mut on_failure: F) -> WebhookQueue
+    where
+        F: FnMut(&str, Error) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Post>();
+        let webhook = self.clone();
+        let thread = thread::spawn(move || {
+            for post in receiver {
+                if let Err(err) = webhook.post(&post) {
+                    on_failure(&post.id, err);
+                }
+            }
+        });
+        WebhookQueue {
+            webhook: self,
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Sink for Webhook {
+    fn send(&self, event: &Event) -> Result<()> {
+        if self.wants(event) {
+            self.deliver(event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Alerts are sent whatever event types the webhook subscribes to, with
+/// `X-Walletb-Event: alert`.
+impl Notifier for Webhook {
+    fn notify(&self, alert: &Alert) -> Result<()> {
+        self.post(&Post::alert(alert)?).map(drop)
+    }
+}
+
+/// One request body, with the headers naming it.
+struct Post {
+    kind: String,
+    id: String,
+    body: String,
+}
+
+impl Post {
+    fn event(event: &Event) -> Result<Self> {
+        Ok(Post {
+            kind: event.kind.to_string(),
+            id: event.id.clone(),
+            body: serde_json::to_string(event)?,
+        })
+    }
+
+    fn alert(alert: &Alert) -> Result<Self> {
+        Ok(Post {
+            kind: "alert".to_owned(),
+            id: alert.id.clone(),
+            body: serde_json::to_string(alert)?,
+        })
+    }
+}
+
+/// A [`Webhook`] delivering from a background thread, in order.
+pub struct WebhookQueue {
+    webhook: Webhook,
+    sender: Option<Sender<Post>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WebhookQueue {
+    fn queue(&self, post: Post) -> Result<()> {
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send(post).ok())
+            .ok_or_else(|| Error::Delivery {
+                url: self.webhook.url.clone(),
+                attempts: 0,
+                reason: "the delivery thread stopped".to_owned(),
+            })
+    }
+}
+
+impl Sink for WebhookQueue {
+    fn send(&self, event: &Event) -> Result<()> {
+        if !self.webhook.wants(event) {
+            return Ok(());
+        }
+        self.queue(Post::event(event)?)
+    }
+}
+
+impl Notifier for WebhookQueue {
+    fn notify(&self, alert: &Alert) -> Result<()> {
+        self.queue(Post::alert(alert)?)
+    }
+}
+
+impl Drop for WebhookQueue {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The `X-Walletb-Signature` value of `body` sent at unix `timestamp`.
+pub fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let digest = mac(secret, timestamp, body).finalize().into_bytes();
+    format!("sha256={}", hex::encode(digest))
+}
+
+/// Checks an `X-Walletb-Signature` value in constant time. Receivers
+/// should also reject timestamps too far from their own clock.
+pub fn verify(secret: &str, timestamp: u64, body: &str, signature: &str) -> bool {
+    let Some(digest) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex| hex::decode(hex).ok())
+    else {
+        return false;
+    };
+    mac(secret, timestamp, body).verify_slice(&digest).is_ok()
+}
+
+fn mac(secret: &str, timestamp: u64, body: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_maximum() {
+        let retry = Retry {
+            attempts: 10,
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(5),
+        };
+        let delays: Vec<u128> = (1..=6).map(|n| retry.delay(n).as_millis()).collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000, 5000, 5000]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::str::FromStr;
+use std::thread;
+
+use rust_decimal::Decimal;
+use walletb_core::testing::{MockResponse, MockServer};
+use walletb_core::{Amount, Asset, Balance, Chain};
+use walletb_notify::{verify, Alert, Condition, Email, Error, Notifier, Rule, Rules, Webhook};
+
+fn low_hot_wallet() -> Alert {
+    let mut rules = Rules::new(vec![Rule::new(
+        "hot-low",
+        Condition::Below(Decimal::from_str("0.5").unwrap()),
+    )
+    .with_wallet("hot")
+    .with_asset("BTC")]);
+    let balance = Balance::new(
+        Asset::native(Chain::Bitcoin, "BTC", 8),
+        Amount::from_u64(40_000_000),
+    );
+    rules.evaluate("hot", &[balance], 1_717_243_200).remove(0)
+}
+
+/// A relay that accepts one message, answering every command with the
+/// next of `replies` after its greeting, and returns the conversation.
+fn relay(replies: &'static [&'static str]) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let thread = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        writer.write_all(b"220 mock ESMTP\r\n").unwrap();
+        let mut lines = Vec::new();
+        let mut replies = replies.iter();
+        let mut in_data = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let line = line.trim_end_matches("\r\n").to_owned();
+            let answer = in_data && line != ".";
+            lines.push(line.clone());
+            if answer {
+                continue;
+            }
+            let Some(reply) = replies.next() else { break };
+            in_data = reply.starts_with("354");
+            writer.write_all(format!("{reply}\r\n").as_bytes()).unwrap();
+        }
+        lines
+    });
+    (addr, thread)
+}
+
+#[test]
+fn mails_alerts_through_a_relay() {
+    let (addr, conversation) = relay(&[
+        "250-mock\r\n250 8BITMIME",
+        "250 ok",
+        "250 ok",
+        "251 forwarded",
+        "354 go ahead",
+        "250 queued",
+        "221 bye",
+    ]);
+    let email = Email::new(
+        addr,
+        "walletb@example.com",
+        vec!["ops@example.com".to_owned(), "cfo@example.com".to_owned()],
+    );
+    email.notify(&low_hot_wallet()).unwrap();
+    let lines = conversation.join().unwrap();
+    assert_eq!(
+        lines[..5],
+        [
+            "EHLO walletb",
+            "MAIL FROM:<walletb@example.com>",
+            "RCPT TO:<ops@example.com>",
+            "RCPT TO:<cfo@example.com>",
+            "DATA",
+        ]
+    );
+    assert!(lines.contains(&"To: <ops@example.com>, <cfo@example.com>".to_owned()));
+    assert!(lines.contains(&"Subject: [walletb] hot-low: BTC is 0.4, below 0.5".to_owned()));
+    assert!(lines.contains(&"Time: 2024-06-01T12:00:00Z".to_owned()));
+    assert_eq!(lines[lines.len() - 2..], [".", "QUIT"]);
+}
+
+#[test]
+fn reports_a_relay_refusing_the_message() {
+    let (addr, conversation) = relay(&["250 mock", "550 sender rejected"]);
+    let err = Email::new(
+        addr,
+        "walletb@example.com",
+        vec!["ops@example.com".to_owned()],
+    )
+    .notify(&low_hot_wallet())
+    .unwrap_err();
+    assert!(
+        matches!(&err, Error::Smtp { reason, .. } if reason == "550 sender rejected"),
+        "{err}"
+    );
+    conversation.join().unwrap();
+}
+
+#[test]
+fn posts_signed_alerts_to_webhooks() {
+    let receiver = MockServer::http(|_| MockResponse::status(200, "{}"));
+    Webhook::new(receiver.url(), "whsec-0123456789abcdef")
+        .notify(&low_hot_wallet())
+        .unwrap();
+    let request = &receiver.requests()[0];
+    assert_eq!(request.header("X-Walletb-Event"), Some("alert"));
+    assert_eq!(
+        request.header("X-Walletb-Delivery"),
+        Some("hot-low:hot:bitcoin:BTC:1717243200")
+    );
+    assert!(verify(
+        "whsec-0123456789abcdef",
+        request
+            .header("X-Walletb-Timestamp")
+            .unwrap()
+            .parse()
+            .unwrap(),
+        &request.body,
+        request.header("X-Walletb-Signature").unwrap(),
+    ));
+    let alert = request.json();
+    assert_eq!(alert["rule"], "hot-low");
+    assert_eq!(alert["amount"], "0.4");
+    assert_eq!(alert["message"], "BTC is 0.4, below 0.5");
+}
@@ -0,0 +1,191 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tungstenite::client::IntoClientRequest;
+use tungstenite::Message;
+use walletb_core::testing::{MockResponse, MockServer};
+use walletb_core::{Amount, Asset, Chain};
+use walletb_notify::{
+    sign, verify, Error, Event, EventKind, EventStream, Payment, Retry, Sink, Webhook,
+};
+
+const SECRET: &str = "whsec-0123456789abcdef";
+
+fn deposit() -> Event {
+    Event {
+        id: "vault:pending:aa:0".to_owned(),
+        kind: EventKind::Pending,
+        wallet: "vault".to_owned(),
+        asset: Asset::native(Chain::Bitcoin, "BTC", 8),
+        time: 1_717_000_000,
+        payment: Some(Payment {
+            txid: "aa".to_owned(),
+            vout: 0,
+            amount: Amount::from_u64(50_000),
+            height: None,
+            confirmations: Some(0),
+        }),
+        balance: None,
+    }
+}
+
+fn quick() -> Retry {
+    Retry {
+        attempts: 3,
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(20),
+    }
+}
+
+#[test]
+fn signs_each_delivery() {
+    let receiver = MockServer::http(|_| MockResponse::status(204, ""));
+    let attempts = Webhook::new(receiver.url(), SECRET)
+        .deliver(&deposit())
+        .unwrap();
+    assert_eq!(attempts, 1);
+
+    let requests = receiver.requests();
+    let request = &requests[0];
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.header("X-Walletb-Event"), Some("pending"));
+    assert_eq!(
+        request.header("X-Walletb-Delivery"),
+        Some("vault:pending:aa:0")
+    );
+    let body = request.json();
+    assert_eq!(body["type"], "pending");
+    assert_eq!(body["payment"]["amount"], "50000");
+    assert_eq!(body["asset"]["symbol"], "BTC");
+
+    let timestamp: u64 = request
+        .header("X-Walletb-Timestamp")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let signature = request.header("X-Walletb-Signature").unwrap();
+    assert_eq!(signature, sign(SECRET, timestamp, &request.body));
+    assert!(verify(SECRET, timestamp, &request.body, signature));
+    assert!(!verify(
+        "another-secret-0123",
+        timestamp,
+        &request.body,
+        signature
+    ));
+    assert!(!verify(SECRET, timestamp + 1, &request.body, signature));
+    assert!(!verify(SECRET, timestamp, &request.body, "sha256=zz"));
+}
+
+#[test]
+fn retries_until_the_receiver_takes_the_event() {
+    let calls = AtomicUsize::new(0);
+    let receiver = MockServer::http(move |_| match calls.fetch_add(1, Ordering::SeqCst) {
+        0 => MockResponse::status(503, "busy"),
+        1 => MockResponse::status(429, "slow down"),
+        _ => MockResponse::status(200, "{}"),
+    });
+    let webhook = Webhook::new(receiver.url(), SECRET).with_retry(quick());
+    assert_eq!(webhook.deliver(&deposit()).unwrap(), 3);
+    let requests = receiver.requests();
+    assert_eq!(requests.len(), 3);
+    // Every attempt carries the same delivery id.
+    assert!(requests
+        .iter()
+        .all(|r| r.header("X-Walletb-Delivery") == Some("vault:pending:aa:0")));
+
+    let down = MockServer::http(|_| MockResponse::status(500, "down"));
+    let err = Webhook::new(down.url(), SECRET)
+        .with_retry(quick())
+        .deliver(&deposit())
+        .unwrap_err();
+    assert!(matches!(err, Error::Delivery { attempts: 3, .. }), "{err}");
+    assert_eq!(down.requests().len(), 3);
+
+    let gone = MockServer::http(|_| MockResponse::status(410, "gone"));
+    let err = Webhook::new(gone.url(), SECRET)
+        .with_retry(quick())
+        .deliver(&deposit())
+        .unwrap_err();
+    assert!(matches!(err, Error::Rejected { status: 410, .. }), "{err}");
+    assert_eq!(gone.requests().len(), 1);
+}
+
+#[test]
+fn queues_only_subscribed_events() {
+    let receiver = MockServer::http(|_| MockResponse::status(200, "{}"));
+    {
+        let queue = Webhook::new(receiver.url(), SECRET)
+            .with_events([EventKind::Confirmed, EventKind::Reorged])
+            .spawn(|id, err| panic!("{id}: {err}"));
+        queue.send(&deposit()).unwrap();
+        let confirmed = Event {
+            id: "vault:confirmed:aa:0:100".to_owned(),
+            kind: EventKind::Confirmed,
+            ..deposit()
+        };
+        queue.send(&confirmed).unwrap();
+        // Dropping the queue waits for the delivery.
+    }
+    let requests = receiver.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].header("X-Walletb-Event"), Some("confirmed"));
+}
+
+#[test]
+fn hands_failed_deliveries_back_to_the_caller() {
+    let gone = MockServer::http(|_| MockResponse::status(410, "gone"));
+    let (failures, failed) = mpsc::channel();
+    Webhook::new(gone.url(), SECRET)
+        .with_retry(quick())
+        .spawn(move |id, err| failures.send((id.to_owned(), err)).unwrap())
+        .send(&deposit())
+        .unwrap();
+    let (id, err) = failed.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(id, "vault:pending:aa:0");
+    assert!(matches!(err, Error::Rejected { status: 410, .. }), "{err}");
+}
+
+#[test]
+fn streams_events_to_clients_with_a_key() {
+    let stream = EventStream::bind("127.0.0.1:0", vec!["ops-0123456789abcdef".to_owned()]).unwrap();
+    let url = format!("ws://{}/", stream.local_addr());
+
+    match tungstenite::connect(url.as_str()) {
+        Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), 401),
+        other => panic!("connected without a key: {:?}", other.map(|_| ())),
+    }
+
+    let mut request = url.as_str().into_client_request().unwrap();
+    request.headers_mut().insert(
+        "Authorization",
+        "Bearer ops-0123456789abcdef".parse().unwrap(),
+    );
+    let (mut bearer, _) = tungstenite::connect(request).unwrap();
+    let (mut query, _) = tungstenite::connect(format!("{url}?key=ops-0123456789abcdef")).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while stream.clients() < 2 {
+        assert!(Instant::now() < deadline, "clients never registered");
+        thread::sleep(Duration::from_millis(10));
+    }
+    stream.send(&deposit()).unwrap();
+    for client in [&mut bearer, &mut query] {
+        let Message::Text(text) = client.read().unwrap() else {
+            panic!("expected a text message");
+        };
+        let event: Event = serde_json::from_str(&text).unwrap();
+        assert_eq!(event, deposit());
+    }
+
+    // A client that went away is dropped once a send to it fails, which
+    // may take the connection reset that answers the first one.
+    drop(query);
+    while stream.clients() > 1 {
+        assert!(Instant::now() < deadline, "closed client never dropped");
+        stream.send(&deposit()).unwrap();
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(matches!(bearer.read().unwrap(), Message::Text(_)));
+}
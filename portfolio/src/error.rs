@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use walletb_core::Asset;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("wallet `{wallet}`: {source}")]
+    Wallet {
+        wallet: String,
+        #[source]
+        source: walletb_core::Error,
+    },
+
+    #[error("{}:{line}: {reason}", path.display())]
+    PriceFile {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+
+    #[error("value of {asset} does not fit in a decimal")]
+    Overflow { asset: Asset },
+
+    #[error("unknown cost basis method `{0}`; expected fifo, lifo, hifo or average")]
+    UnknownCostMethod(String),
+
+    #[error(transparent)]
+    Core(#[from] walletb_core::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,542 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use walletb_core::{Amount, Asset};
+
+use crate::report::whole_units;
+use crate::{Error, Flow, Movement, PriceSource, Result};
+
+/// How a disposal picks the lots it draws on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostMethod {
+    /// First in, first out: the oldest lots go first.
+    #[default]
+    Fifo,
+    /// Last in, first out: the newest lots go first.
+    Lifo,
+    /// Highest in, first out: the lots that cost most per unit go first,
+    /// which keeps realized gains lowest.
+    Hifo,
+    /// Every lot of an asset is pooled at their average cost.
+    Average,
+}
+
+impl CostMethod {
+    pub const ALL: [CostMethod; 4] = [
+        CostMethod::Fifo,
+        CostMethod::Lifo,
+        CostMethod::Hifo,
+        CostMethod::Average,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CostMethod::Fifo => "fifo",
+            CostMethod::Lifo => "lifo",
+            CostMethod::Hifo => "hifo",
+            CostMethod::Average => "average",
+        }
+    }
+}
+
+impl fmt::Display for CostMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CostMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        CostMethod::ALL
+            .into_iter()
+            .find(|method| method.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::UnknownCostMethod(s.to_owned()))
+    }
+}
+
+/// Units of an asset acquired together, and what they cost.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Lot {
+    pub txid: String,
+    /// When the lot was acquired; under average cost, when the pool was
+    /// first.
+    pub acquired: u64,
+    /// What is left of the lot, in base units.
+    pub amount: Amount,
+    /// What is left of the lot cost.
+    pub cost: Decimal,
+}
+
+/// Units of an asset that left the wallets, and the gain they realized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Disposal {
+    pub wallet: String,
+    pub txid: String,
+    pub time: u64,
+    pub asset: Asset,
+    /// Base units disposed of, the fee paid in the asset included.
+    pub amount: Amount,
+    /// What the units sent were worth on the day. A fee earns nothing and
+    /// so only adds to the cost.
+    pub proceeds: Decimal,
+    pub cost: Decimal,
+    pub gain: Decimal,
+    /// When the earliest lot drawn on was acquired, to tell short-term
+    /// holdings from long-term ones.
+    pub acquired: Option<u64>,
+    /// Units beyond every lot held, counted at no cost: they were acquired
+    /// before the history read starts.
+    pub unmatched: Amount,
+}
+
+/// What is left of an asset's lots, valued at one price.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Holding {
+    pub asset: Asset,
+    pub amount: Amount,
+    pub cost: Decimal,
+    pub price: Option<Decimal>,
+    pub value: Option<Decimal>,
+    /// `value - cost`, when there is a price.
+    pub gain: Option<Decimal>,
+    pub lots: Vec<Lot>,
+}
+
+/// A movement counted at no value for want of a price on its day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MissingPrice {
+    pub txid: String,
+    pub time: u64,
+    pub asset: Asset,
+}
+
+/// Realized gains per disposal and unrealized gains on what is held, in
+/// one currency.
+///
+/// Prices missing on the day of a movement make it count at no value, and
+/// [`missing`](GainsReport::missing) lists each one so a figure is never
+/// silently wrong.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GainsReport {
+    pub currency: String,
+    pub method: CostMethod,
+    pub realized: Decimal,
+    pub unrealized: Decimal,
+    pub disposals: Vec<Disposal>,
+    pub holdings: Vec<Holding>,
+    pub missing: Vec<MissingPrice>,
+}
+
+impl GainsReport {
+    /// The report with only the disposals after `time`, such as those of
+    /// one tax year; the lots they drew on still come from the whole
+    /// history.
+    pub fn disposed_after(mut self, time: u64) -> Result<Self> {
+        self.disposals.retain(|d| d.time > time);
+        self.realized = total(self.disposals.iter().map(|d| (&d.asset, d.gain)))?;
+        Ok(self)
+    }
+}
+
+/// Matches the disposals in `movements`, oldest first, against the lots
+/// acquired before them with `method`.
+///
+/// Lots are pooled per asset across every wallet, so moves between the
+/// wallets keep their lots and only their fees are disposals. Income is
+/// acquired at its price on the day of `prices`'s
+/// [`price_at`](PriceSource::price_at). What is left is valued at the
+/// price on the day of `valued_at`, or at the current price if `None`.
+pub fn gains<P: PriceSource + ?Sized>(
+    movements: &[Movement],
+    method: CostMethod,
+    prices: &P,
+    currency: &str,
+    valued_at: Option<u64>,
+) -> Result<GainsReport> {
+    let currency = currency.to_ascii_uppercase();
+    let mut ordered: Vec<&Movement> = movements.iter().collect();
+    ordered.sort_by_key(|m| m.entry.time);
+
+    let mut lots: BTreeMap<Asset, Vec<Lot>> = BTreeMap::new();
+    let mut disposals = Vec::new();
+    let mut missing = Vec::new();
+    let mut quotes: HashMap<(Asset, u64), Option<Decimal>> = HashMap::new();
+    for movement in ordered {
+        let entry = &movement.entry;
+        let asset = &entry.asset;
+        // `movements` splits off what went to someone else in a transaction
+        // that also paid another wallet, so an internal movement is only
+        // ever what the wallets passed among themselves.
+        let (received, sent) = match movement.flow {
+            Flow::Internal => (Amount::default(), Amount::default()),
+            Flow::Income | Flow::Expense => (entry.received, entry.sent),
+        };
+        let disposed = sent.checked_add(entry.fee).ok_or_else(|| overflow(asset))?;
+        if disposed.is_zero() && received.is_zero() {
+            continue;
+        }
+        // A fee alone is worth nothing to the wallets and needs no price.
+        let priced = !sent.is_zero() || !received.is_zero();
+        let day = entry.time / DAY;
+        let price = match quotes.get(&(asset.clone(), day)) {
+            _ if !priced => None,
+            Some(price) => *price,
+            None => {
+                let price = prices.price_at(asset, &currency, entry.time)?;
+                quotes.insert((asset.clone(), day), price);
+                price
+            }
+        };
+        if priced && price.is_none() {
+            missing.push(MissingPrice {
+                txid: entry.txid.clone(),
+                time: entry.time,
+                asset: asset.clone(),
+            });
+        }
+        let worth = |amount: Amount| -> Result<Decimal> {
+            let price = price.unwrap_or_default();
+            units(asset, amount)?
+                .checked_mul(price)
+                .ok_or_else(|| overflow(asset))
+        };
+        let held = lots.entry(asset.clone()).or_default();
+
+        if !disposed.is_zero() {
+            let (cost, acquired, unmatched) = dispose(held, method, asset, disposed)?;
+            let proceeds = worth(sent)?;
+            disposals.push(Disposal {
+                wallet: movement.wallet.clone(),
+                txid: entry.txid.clone(),
+                time: entry.time,
+                asset: asset.clone(),
+                amount: disposed,
+                proceeds,
+                cost,
+                gain: proceeds.checked_sub(cost).ok_or_else(|| overflow(asset))?,
+                acquired,
+                unmatched,
+            });
+        }
+        if !received.is_zero() {
+            let lot = Lot {
+                txid: entry.txid.clone(),
+                acquired: entry.time,
+                amount: received,
+                cost: worth(received)?,
+            };
+            match (method, held.first_mut()) {
+                (CostMethod::Average, Some(pool)) => {
+                    pool.amount = pool
+                        .amount
+                        .checked_add(lot.amount)
+                        .ok_or_else(|| overflow(asset))?;
+                    pool.cost = pool
+                        .cost
+                        .checked_add(lot.cost)
+                        .ok_or_else(|| overflow(asset))?;
+                }
+                _ => held.push(lot),
+            }
+        }
+    }
+
+    let mut holdings = Vec::new();
+    for (asset, lots) in lots {
+        if lots.is_empty() {
+            continue;
+        }
+        let amount: Amount = lots.iter().map(|lot| lot.amount).sum();
+        let cost = total(lots.iter().map(|lot| (&asset, lot.cost)))?;
+        let price = match valued_at {
+            Some(time) => prices.price_at(&asset, &currency, time)?,
+            None => prices.price(&asset, &currency)?,
+        };
+        let value = price
+            .map(|price| {
+                units(&asset, amount)?
+                    .checked_mul(price)
+                    .ok_or_else(|| overflow(&asset))
+            })
+            .transpose()?;
+        let gain = value
+            .map(|value| value.checked_sub(cost).ok_or_else(|| overflow(&asset)))
+            .transpose()?;
+        holdings.push(Holding {
+            asset,
+            amount,
+            cost,
+            price,
+            value,
+            gain,
+            lots,
+        });
+    }
+
+    Ok(GainsReport {
+        currency,
+        method,
+        realized: total(disposals.iter().map(|d| (&d.asset, d.gain)))?,
+        unrealized: total(
+            holdings
+                .iter()
+                .filter_map(|h| h.gain.map(|gain| (&h.asset, gain))),
+        )?,
+        disposals,
+        holdings,
+        missing,
+    })
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Takes `amount` out of `lots` in the order `method` picks them, and
+/// returns its cost, when the earliest lot used was acquired and how much
+/// no lot covered.
+fn dispose(
+    lots: &mut Vec<Lot>,
+    method: CostMethod,
+    asset: &Asset,
+    amount: Amount,
+) -> Result<(Decimal, Option<u64>, Amount)> {
+    let mut left = amount;
+    let mut cost = Decimal::ZERO;
+    let mut acquired: Option<u64> = None;
+    while !left.is_zero() && !lots.is_empty() {
+        let index = match method {
+            CostMethod::Fifo | CostMethod::Average => 0,
+            CostMethod::Lifo => lots.len() - 1,
+            CostMethod::Hifo => {
+                let mut highest = (0, Decimal::MIN);
+                for (i, lot) in lots.iter().enumerate() {
+                    let per_unit = lot
+                        .cost
+                        .checked_div(units(asset, lot.amount)?)
+                        .unwrap_or_default();
+                    if per_unit > highest.1 {
+                        highest = (i, per_unit);
+                    }
+                }
+                highest.0
+            }
+        };
+        let lot = &mut lots[index];
+        acquired = Some(acquired.map_or(lot.acquired, |a| a.min(lot.acquired)));
+        let taken = if left >= lot.amount {
+            let taken = lot.cost;
+            left = left.saturating_sub(lot.amount);
+            lots.remove(index);
+            taken
+        } else {
+            let taken = lot
+                .cost
+                .checked_mul(units(asset, left)?)
+                .and_then(|c| c.checked_div(units(asset, lot.amount).ok()?))
+                .ok_or_else(|| overflow(asset))?;
+            lot.amount = lot.amount.saturating_sub(left);
+            lot.cost -= taken;
+            left = Amount::default();
+            taken
+        };
+        cost = cost.checked_add(taken).ok_or_else(|| overflow(asset))?;
+    }
+    Ok((cost, acquired, left))
+}
+
+fn units(asset: &Asset, amount: Amount) -> Result<Decimal> {
+    whole_units(asset, amount).ok_or_else(|| overflow(asset))
+}
+
+fn total<'a>(mut gains: impl Iterator<Item = (&'a Asset, Decimal)>) -> Result<Decimal> {
+    gains.try_fold(Decimal::ZERO, |sum, (asset, gain)| {
+        sum.checked_add(gain).ok_or_else(|| overflow(asset))
+    })
+}
+
+fn overflow(asset: &Asset) -> Error {
+    Error::Overflow {
+        asset: asset.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use walletb_core::{Address, Chain, LedgerEntry};
+
+    use super::*;
+    use crate::{movements, PriceTable, WalletLedger};
+
+    const JAN_1: u64 = 1_704_067_200;
+
+    fn btc() -> Asset {
+        Asset::native(Chain::Bitcoin, "BTC", 8)
+    }
+
+    fn movement(txid: &str, day: u64, flow: Flow, received: u64, sent: u64) -> Movement {
+        Movement {
+            wallet: "cold".into(),
+            entry: LedgerEntry {
+                txid: txid.into(),
+                height: day,
+                time: JAN_1 + day * DAY + 3600,
+                asset: btc(),
+                received: Amount::from_u64(received),
+                sent: Amount::from_u64(sent),
+                fee: Amount::default(),
+                counterparty: None,
+            },
+            flow,
+        }
+    }
+
+    /// 1 BTC bought at 10,000, 30,000 and 20,000 on the first three days of
+    /// 2024, and 1.5 BTC sold at 40,000 on the fourth; BTC is now 50,000.
+    fn history() -> (Vec<Movement>, PriceTable) {
+        const BTC: u64 = 100_000_000;
+        let movements = vec![
+            movement("buy-1", 0, Flow::Income, BTC, 0),
+            movement("buy-2", 1, Flow::Income, BTC, 0),
+            movement("buy-3", 2, Flow::Income, BTC, 0),
+            movement("sell", 3, Flow::Expense, 0, BTC + BTC / 2),
+        ];
+        let prices = PriceTable::from_csv(
+            "symbol,currency,price,date\n\
+             BTC,USD,10000,2024-01-01\n\
+             BTC,USD,30000,2024-01-02\n\
+             BTC,USD,20000,2024-01-03\n\
+             BTC,USD,40000,2024-01-04\n\
+             BTC,USD,50000,\n",
+        )
+        .unwrap();
+        (movements, prices)
+    }
+
+    #[test]
+    fn matches_disposals_by_each_method() {
+        let (movements, prices) = history();
+        let rows: Vec<(CostMethod, i64, i64, i64, i64)> = CostMethod::ALL
+            .into_iter()
+            .map(|method| {
+                let report = gains(&movements, method, &prices, "usd", None).unwrap();
+                let sold = &report.disposals[0];
+                let held = &report.holdings[0];
+                let whole = |d: Decimal| i64::try_from(d).unwrap();
+                (
+                    method,
+                    whole(sold.cost),
+                    whole(report.realized),
+                    whole(held.cost),
+                    whole(report.unrealized),
+                )
+            })
+            .collect();
+        // Proceeds of 60,000, and 1.5 BTC left worth 75,000.
+        assert_eq!(
+            rows,
+            [
+                (CostMethod::Fifo, 25_000, 35_000, 35_000, 40_000),
+                (CostMethod::Lifo, 35_000, 25_000, 25_000, 50_000),
+                (CostMethod::Hifo, 40_000, 20_000, 20_000, 55_000),
+                (CostMethod::Average, 30_000, 30_000, 30_000, 45_000),
+            ]
+        );
+
+        let fifo = gains(&movements, CostMethod::Fifo, &prices, "USD", None).unwrap();
+        assert_eq!(fifo.disposals[0].acquired, Some(JAN_1 + 3600));
+        assert_eq!(fifo.holdings[0].lots.len(), 2);
+        assert!(fifo.missing.is_empty());
+        assert_eq!("HIFO".parse::<CostMethod>().unwrap(), CostMethod::Hifo);
+        assert!("lofi".parse::<CostMethod>().is_err());
+    }
+
+    #[test]
+    fn keeps_lots_across_internal_moves() {
+        let (mut movements, prices) = history();
+        // A move to another wallet costs only its fee, which is disposed of
+        // for nothing.
+        let mut moved = movement("move", 3, Flow::Internal, 0, 50_000_000);
+        moved.entry.fee = Amount::from_u64(1_000_000);
+        movements.insert(3, moved);
+        // Sold before any lot was bought.
+        let mut early = movement("early", 0, Flow::Expense, 0, 100_000_000);
+        early.entry.time = JAN_1;
+        movements.push(early);
+        // Received with no price.
+        let mut gift = movement("gift", 9, Flow::Income, 100_000_000, 0);
+        gift.entry.asset = Asset::native(Chain::Solana, "SOL", 9);
+        movements.push(gift);
+
+        let report = gains(&movements, CostMethod::Fifo, &prices, "USD", None).unwrap();
+        let rows: Vec<(&str, i64, i64, u64)> = report
+            .disposals
+            .iter()
+            .map(|d| {
+                let whole = |d: Decimal| i64::try_from(d).unwrap();
+                (
+                    d.txid.as_str(),
+                    whole(d.proceeds),
+                    whole(d.cost),
+                    d.unmatched.to_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("early", 10_000, 0, 100_000_000),
+                ("move", 0, 100, 0),
+                ("sell", 60_000, 9_900 + 15_300, 0),
+            ]
+        );
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].txid, "gift");
+
+        let year = report.disposed_after(JAN_1 + 3 * DAY).unwrap();
+        assert_eq!(year.disposals.len(), 2);
+        assert_eq!(year.realized, Decimal::from(-100 + 60_000 - 25_200));
+    }
+
+    #[test]
+    fn disposes_of_what_a_move_between_wallets_paid_out() {
+        let (history, prices) = history();
+        // Bought for 10,000, then 0.4 BTC sent to the spending wallet and
+        // 0.2 BTC to a shop in one transaction, at 40,000.
+        let bought = history[0].entry.clone();
+        let paid = LedgerEntry {
+            counterparty: Some("bc1qshop".into()),
+            ..movement("pay", 3, Flow::Expense, 0, 60_000_000).entry
+        };
+        let ledgers = [
+            WalletLedger {
+                id: "savings".into(),
+                addresses: vec![Address::new(Chain::Bitcoin, "bc1qsavings")],
+                entries: vec![bought, paid.clone()],
+            },
+            WalletLedger {
+                id: "spending".into(),
+                addresses: vec![Address::new(Chain::Bitcoin, "bc1qspending")],
+                entries: vec![LedgerEntry {
+                    received: Amount::from_u64(40_000_000),
+                    sent: Amount::default(),
+                    counterparty: None,
+                    ..paid
+                }],
+            },
+        ];
+        let report = gains(&movements(&ledgers), CostMethod::Fifo, &prices, "USD", None).unwrap();
+        assert_eq!(report.disposals.len(), 1);
+        let sold = &report.disposals[0];
+        assert_eq!(sold.amount, Amount::from_u64(20_000_000));
+        assert_eq!(sold.proceeds, Decimal::from(8_000));
+        assert_eq!(sold.cost, Decimal::from(2_000));
+        // The 0.4 BTC moved keeps its cost.
+        assert_eq!(report.holdings[0].amount, Amount::from_u64(80_000_000));
+        assert_eq!(report.holdings[0].cost, Decimal::from(8_000));
+    }
+}
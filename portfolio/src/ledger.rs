@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+use walletb_core::{Address, Amount, Asset, Chain, LedgerEntry};
+
+/// What a movement means to the owner of every wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flow {
+    /// Value arriving from outside.
+    Income,
+    /// Value leaving, or only a fee paid.
+    Expense,
+    /// Value moving between two of the owner's wallets. Any fee it cost
+    /// is still in the entry's `fee`.
+    Internal,
+}
+
+impl Flow {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Flow::Income => "income",
+            Flow::Expense => "expense",
+            Flow::Internal => "internal",
+        }
+    }
+}
+
+impl fmt::Display for Flow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The transactions of one wallet, as its
+/// [`TransactionSource`](walletb_core::TransactionSource) listed them for
+/// its addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletLedger {
+    pub id: String,
+    pub addresses: Vec<Address>,
+    pub entries: Vec<LedgerEntry>,
+}
+
+/// One ledger entry of one wallet, and what it means across all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Movement {
+    pub wallet: String,
+    #[serde(flatten)]
+    pub entry: LedgerEntry,
+    pub flow: Flow,
+}
+
+/// Merges the ledgers of every wallet, oldest first, and tells income and
+/// expense from transfers between the wallets.
+///
+/// What another wallet has an entry for in the same transaction and asset,
+/// going the other way, is internal. This catches moves whose counterparty
+/// the chain does not tell, such as a Bitcoin transaction paying several
+/// outputs, and splits a transaction paying both another wallet and
+/// someone else in two: the part the other wallet received is internal,
+/// and the rest, with the fee, an expense. Failing such an entry, one
+/// whose counterparty is an address of any of the wallets is internal as
+/// a whole.
+pub fn movements(ledgers: &[WalletLedger]) -> Vec<Movement> {
+    let ours = |chain: Chain, address: &str| {
+        ledgers.iter().flat_map(|l| &l.addresses).any(|own| {
+            own.chain == chain
+                && match chain {
+                    // Hex addresses come checksummed or not.
+                    Chain::Evm(_) => own.as_str().eq_ignore_ascii_case(address),
+                    _ => own.as_str() == address,
+                }
+        })
+    };
+    // What each wallet received and sent in each transaction, per asset.
+    type Sides<'a> = HashMap<&'a str, (Amount, Amount)>;
+    let mut sides: HashMap<(Chain, &str, &Asset), Sides> = HashMap::new();
+    for ledger in ledgers {
+        for entry in &ledger.entries {
+            let side = sides
+                .entry((entry.asset.chain, entry.txid.as_str(), &entry.asset))
+                .or_default()
+                .entry(ledger.id.as_str())
+                .or_default();
+            *side = (side.0 + entry.received, side.1 + entry.sent);
+        }
+    }
+
+    let mut movements = Vec::new();
+    for ledger in ledgers {
+        for entry in &ledger.entries {
+            let chain = entry.asset.chain;
+            // What the other wallets took in or paid out of this transaction.
+            let (others_received, others_sent) = sides
+                .get(&(chain, entry.txid.as_str(), &entry.asset))
+                .into_iter()
+                .flatten()
+                .filter(|(wallet, _)| **wallet != ledger.id)
+                .fold(
+                    (Amount::default(), Amount::default()),
+                    |(r, s), (_, side)| (r + side.0, s + side.1),
+                );
+            let outside = if incoming(entry) {
+                Flow::Income
+            } else {
+                Flow::Expense
+            };
+            let (moved, matched) = if incoming(entry) {
+                (entry.received, others_sent)
+            } else {
+                (entry.sent, others_received)
+            };
+            let internal = moved.min(matched);
+            let counterparty_ours = entry
+                .counterparty
+                .as_deref()
+                .is_some_and(|c| ours(chain, c));
+            let movement = |flow: Flow, entry: LedgerEntry| Movement {
+                wallet: ledger.id.clone(),
+                entry,
+                flow,
+            };
+            if moved.is_zero() {
+                movements.push(movement(outside, entry.clone()));
+            } else if internal == moved || (internal.is_zero() && counterparty_ours) {
+                movements.push(movement(Flow::Internal, entry.clone()));
+            } else if internal.is_zero() {
+                movements.push(movement(outside, entry.clone()));
+            } else {
+                let part = |amount: Amount, fee: Amount, counterparty: Option<String>| {
+                    let (received, sent) = if incoming(entry) {
+                        (amount, Amount::default())
+                    } else {
+                        (Amount::default(), amount)
+                    };
+                    LedgerEntry {
+                        received,
+                        sent,
+                        fee,
+                        counterparty,
+                        ..entry.clone()
+                    }
+                };
+                let (theirs, mine) = if counterparty_ours {
+                    (None, entry.counterparty.clone())
+                } else {
+                    (entry.counterparty.clone(), None)
+                };
+                movements.push(movement(
+                    Flow::Internal,
+                    part(internal, Amount::default(), mine),
+                ));
+                movements.push(movement(
+                    outside,
+                    part(moved.saturating_sub(internal), entry.fee, theirs),
+                ));
+            }
+        }
+    }
+    movements.sort_by_key(|m| m.entry.time);
+    movements
+}
+
+fn incoming(entry: &LedgerEntry) -> bool {
+    !entry.received.is_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(txid: &str, time: u64, asset: &Asset, received: u64, sent: u64) -> LedgerEntry {
+        LedgerEntry {
+            txid: txid.into(),
+            height: time,
+            time,
+            asset: asset.clone(),
+            received: Amount::from_u64(received),
+            sent: Amount::from_u64(sent),
+            fee: Amount::from_u64(0),
+            counterparty: None,
+        }
+    }
+
+    #[test]
+    fn tells_moves_between_wallets_from_income_and_expense() {
+        let btc = Asset::native(Chain::Bitcoin, "BTC", 8);
+        let eth = Asset::native(Chain::ETHEREUM, "ETH", 18);
+        let hot = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let with = |mut e: LedgerEntry, counterparty: &str, fee: u64| {
+            e.counterparty = Some(counterparty.into());
+            e.fee = Amount::from_u64(fee);
+            e
+        };
+        let ledgers = [
+            WalletLedger {
+                id: "savings".into(),
+                addresses: vec![
+                    Address::new(Chain::Bitcoin, "bc1qsavings"),
+                    Address::new(
+                        Chain::ETHEREUM,
+                        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+                    ),
+                ],
+                entries: vec![
+                    with(entry("a", 1, &btc, 100, 0), "bc1qexchange", 0),
+                    // The chain named no counterparty.
+                    LedgerEntry {
+                        fee: Amount::from_u64(5),
+                        ..entry("b", 3, &btc, 0, 40)
+                    },
+                    // Sent to a lowercased address of the hot wallet.
+                    with(entry("c", 4, &eth, 0, 7), &hot.to_lowercase(), 2),
+                ],
+            },
+            WalletLedger {
+                id: "spending".into(),
+                addresses: vec![
+                    Address::new(Chain::Bitcoin, "bc1qspending"),
+                    Address::new(Chain::ETHEREUM, hot),
+                ],
+                entries: vec![
+                    entry("b", 3, &btc, 40, 0),
+                    with(
+                        entry("c", 4, &eth, 7, 0),
+                        "0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359",
+                        0,
+                    ),
+                    with(entry("d", 5, &eth, 0, 0), "0xContract", 2),
+                    with(entry("e", 2, &btc, 0, 10), "bc1qshop", 1),
+                ],
+            },
+        ];
+        let movements = movements(&ledgers);
+        let flows: Vec<(&str, &str, Flow)> = movements
+            .iter()
+            .map(|m| (m.wallet.as_str(), m.entry.txid.as_str(), m.flow))
+            .collect();
+        assert_eq!(
+            flows,
+            [
+                ("savings", "a", Flow::Income),
+                ("spending", "e", Flow::Expense),
+                ("savings", "b", Flow::Internal),
+                ("spending", "b", Flow::Internal),
+                ("savings", "c", Flow::Internal),
+                ("spending", "c", Flow::Internal),
+                // Only a fee, which is always an expense.
+                ("spending", "d", Flow::Expense),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_payments_to_a_wallet_and_someone_else() {
+        let btc = Asset::native(Chain::Bitcoin, "BTC", 8);
+        let ledgers = [
+            WalletLedger {
+                id: "savings".into(),
+                addresses: vec![Address::new(Chain::Bitcoin, "bc1qsavings")],
+                // 40 to the spending wallet and 20 to a shop, for a fee of 5.
+                entries: vec![LedgerEntry {
+                    fee: Amount::from_u64(5),
+                    counterparty: Some("bc1qshop".into()),
+                    ..entry("f", 1, &btc, 0, 60)
+                }],
+            },
+            WalletLedger {
+                id: "spending".into(),
+                addresses: vec![Address::new(Chain::Bitcoin, "bc1qspending")],
+                entries: vec![entry("f", 1, &btc, 40, 0)],
+            },
+        ];
+        let movements = movements(&ledgers);
+        let rows: Vec<(&str, Flow, [u64; 3])> = movements
+            .iter()
+            .map(|m| {
+                let units = |amount: Amount| amount.to_u64().unwrap();
+                let entry = &m.entry;
+                let amounts = [units(entry.received), units(entry.sent), units(entry.fee)];
+                (m.wallet.as_str(), m.flow, amounts)
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("savings", Flow::Internal, [0, 40, 0]),
+                ("savings", Flow::Expense, [0, 20, 5]),
+                ("spending", Flow::Internal, [40, 0, 0]),
+            ]
+        );
+        assert_eq!(movements[0].entry.counterparty, None);
+        assert_eq!(movements[1].entry.counterparty.as_deref(), Some("bc1qshop"));
+    }
+}
@@ -0,0 +1,30 @@
+//! Portfolio aggregation for walletb.
+//!
+//! A [`Portfolio`] is a list of named wallets, each backed by a
+//! [`WalletSource`]: a [`BalanceSource`](walletb_core::BalanceSource) with a
+//! fixed set of addresses, or anything else that can produce balances, such
+//! as an xpub scan. [`Portfolio::report`] reads every wallet and values the
+//! result in one fiat currency through a [`PriceSource`], giving a total
+//! plus per-asset and per-wallet breakdowns.
+//!
+//! [`PriceFile`] is an offline [`PriceSource`] read from CSV or JSON.
+//!
+//! [`movements`] merges the transaction ledgers of several wallets and
+//! tells income and expense from moves between them, and [`gains`] works
+//! out the cost basis of those movements, FIFO, LIFO, HIFO or at average
+//! cost, for realized and unrealized gains.
+
+mod error;
+mod gains;
+mod ledger;
+mod portfolio;
+mod price;
+mod report;
+
+pub use error::{Error, Result};
+pub use gains::{gains, CostMethod, Disposal, GainsReport, Holding, Lot, MissingPrice};
+pub use ledger::{movements, Flow, Movement, WalletLedger};
+pub use portfolio::{AddressWallet, Portfolio, WalletBalances, WalletSource};
+pub use price::{PriceEntry, PriceFile, PriceSource, PriceTable};
+pub use report::{value_balances, AssetValuation, PortfolioReport, WalletValuation};
+pub use rust_decimal::Decimal;
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use walletb_core::{Address, Balance, BalanceSource};
+
+use crate::{value_balances, Error, PortfolioReport, PriceSource, Result};
+
+/// Produces the current balances of one wallet.
+///
+/// Implemented by [`AddressWallet`] and by any closure returning balances,
+/// so wallets that need discovery first (xpubs, descriptors, vaults) can be
+/// added with a `move ||` that runs the scan.
+pub trait WalletSource {
+    fn balances(&self) -> walletb_core::Result<Vec<Balance>>;
+}
+
+impl<F> WalletSource for F
+where
+    F: Fn() -> walletb_core::Result<Vec<Balance>>,
+{
+    fn balances(&self) -> walletb_core::Result<Vec<Balance>> {
+        self()
+    }
+}
+
+/// A fixed list of addresses read through one [`BalanceSource`].
+#[derive(Debug, Clone)]
+pub struct AddressWallet<S> {
+    source: S,
+    addresses: Vec<Address>,
+}
+
+impl<S: BalanceSource> AddressWallet<S> {
+    pub fn new(source: S, addresses: Vec<Address>) -> Self {
+        AddressWallet { source, addresses }
+    }
+}
+
+impl<S: BalanceSource> WalletSource for AddressWallet<S> {
+    fn balances(&self) -> walletb_core::Result<Vec<Balance>> {
+        self.source.balances(&self.addresses)
+    }
+}
+
+/// The balances read from one wallet of a [`Portfolio`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WalletBalances {
+    pub id: String,
+    pub name: String,
+    pub balances: Vec<Balance>,
+}
+
+struct Wallet {
+    id: String,
+    name: String,
+    source: Box<dyn WalletSource>,
+}
+
+/// Every wallet whose holdings count towards the total.
+#[derive(Default)]
+pub struct Portfolio {
+    wallets: Vec<Wallet>,
+}
+
+impl Portfolio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_wallet(
+        mut self,
+        id: impl Into<String>,
+        name: impl Into<String>,
+        source: impl WalletSource + 'static,
+    ) -> Self {
+        self.add_wallet(id, name, source);
+        self
+    }
+
+    pub fn add_wallet(
+        &mut self,
+        id: impl Into<String>,
+        name: impl Into<String>,
+        source: impl WalletSource + 'static,
+    ) {
+        self.wallets.push(Wallet {
+            id: id.into(),
+            name: name.into(),
+            source: Box::new(source),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.wallets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wallets.is_empty()
+    }
+
+    /// Reads every wallet, in the order they were added. A failing wallet
+    /// fails the whole read, since a partial total would be misleading.
+    pub fn balances(&self) -> Result<Vec<WalletBalances>> {
+        self.wallets
+            .iter()
+            .map(|wallet| {
+                let balances = wallet.source.balances().map_err(|source| Error::Wallet {
+                    wallet: wallet.id.clone(),
+                    source,
+                })?;
+                Ok(WalletBalances {
+                    id: wallet.id.clone(),
+                    name: wallet.name.clone(),
+                    balances,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads every wallet and values the holdings in `currency`.
+    pub fn report<P: PriceSource + ?Sized>(
+        &self,
+        prices: &P,
+        currency: &str,
+    ) -> Result<PortfolioReport> {
+        value_balances(&self.balances()?, prices, currency)
+    }
+}
@@ -0,0 +1,444 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use walletb_core::{Asset, Chain, PointInTime};
+
+use crate::{Error, Result};
+
+/// Supplies the fiat price of assets.
+pub trait PriceSource {
+    /// The price of one whole unit of `asset` in `currency` (an ISO 4217
+    /// code such as `USD`), or `None` if the source has no price for it.
+    fn price(&self, asset: &Asset, currency: &str) -> Result<Option<Decimal>>;
+
+    /// The price of `asset` on the UTC day of `time`, in seconds since the
+    /// Unix epoch, or `None` if the source has none that old. Sources
+    /// without history know no past prices.
+    fn price_at(&self, asset: &Asset, currency: &str, time: u64) -> Result<Option<Decimal>> {
+        let _ = (asset, currency, time);
+        Ok(None)
+    }
+}
+
+impl<P: PriceSource + ?Sized> PriceSource for &P {
+    fn price(&self, asset: &Asset, currency: &str) -> Result<Option<Decimal>> {
+        (**self).price(asset, currency)
+    }
+
+    fn price_at(&self, asset: &Asset, currency: &str, time: u64) -> Result<Option<Decimal>> {
+        (**self).price_at(asset, currency, time)
+    }
+}
+
+impl<P: PriceSource + ?Sized> PriceSource for Box<P> {
+    fn price(&self, asset: &Asset, currency: &str) -> Result<Option<Decimal>> {
+        (**self).price(asset, currency)
+    }
+
+    fn price_at(&self, asset: &Asset, currency: &str, time: u64) -> Result<Option<Decimal>> {
+        (**self).price_at(asset, currency, time)
+    }
+}
+
+/// One row of a price table.
+///
+/// Native assets are matched by `symbol`, and by `chain` too when it is
+/// given. Tokens are only ever matched by `contract`, because anyone can
+/// deploy a token with a well-known symbol.
+///
+/// An entry with a `date` is the price on that UTC day, for valuing past
+/// transactions; one without is the current price.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PriceEntry {
+    pub symbol: String,
+    #[serde(default)]
+    pub chain: Option<Chain>,
+    #[serde(default)]
+    pub contract: Option<String>,
+    pub currency: String,
+    pub price: Decimal,
+    #[serde(default)]
+    pub date: Option<PointInTime>,
+}
+
+impl PriceEntry {
+    /// The day the price was quoted on, counted from the Unix epoch.
+    fn day(&self) -> Option<u64> {
+        match self.date {
+            Some(PointInTime::Time(time)) => Some(time / DAY),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, asset: &Asset, currency: &str) -> bool {
+        if !self.currency.eq_ignore_ascii_case(currency) {
+            return false;
+        }
+        if self.chain.is_some_and(|chain| chain != asset.chain) {
+            return false;
+        }
+        match (asset.contract(), &self.contract) {
+            (None, None) => self.symbol.eq_ignore_ascii_case(&asset.symbol),
+            (Some(token), Some(contract)) => same_contract(token, contract),
+            _ => false,
+        }
+    }
+}
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// A chain-qualified entry wins over one that applies to any chain.
+fn preferred<'a>(entries: &[&'a PriceEntry]) -> Option<&'a PriceEntry> {
+    entries
+        .iter()
+        .find(|e| e.chain.is_some())
+        .or(entries.first())
+        .copied()
+}
+
+/// EVM addresses compare case-insensitively; other chains' identifiers
+/// (base58, bech32) are compared as written.
+fn same_contract(a: &str, b: &str) -> bool {
+    if a.starts_with("0x") && b.starts_with("0x") {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+/// Prices held in memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PriceTable {
+    entries: Vec<PriceEntry>,
+}
+
+impl PriceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, entry: PriceEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[PriceEntry] {
+        &self.entries
+    }
+
+    /// Parses `symbol,chain,contract,currency,price,date` rows after a
+    /// header line naming those columns. Only `symbol`, `currency` and
+    /// `price` are required; columns may come in any order. Blank lines
+    /// and lines starting with `#` are ignored.
+    pub fn from_csv(text: &str) -> Result<Self> {
+        Self::parse_csv(text, Path::new("<csv>"))
+    }
+
+    /// Parses a JSON array of [`PriceEntry`] objects. Prices may be given
+    /// as strings or numbers.
+    pub fn from_json(text: &str) -> Result<Self> {
+        Self::parse_json(text, Path::new("<json>"))
+    }
+
+    fn parse_csv(text: &str, path: &Path) -> Result<Self> {
+        let invalid = |line: usize, reason: String| Error::PriceFile {
+            path: path.to_owned(),
+            line,
+            reason,
+        };
+        let mut rows = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let Some((header_line, header)) = rows.next() else {
+            return Ok(Self::new());
+        };
+        let columns: Vec<String> = header
+            .split(',')
+            .map(|c| c.trim().to_ascii_lowercase())
+            .collect();
+        let column = |name: &str| columns.iter().position(|c| c == name);
+        let (Some(symbol), Some(currency), Some(price)) =
+            (column("symbol"), column("currency"), column("price"))
+        else {
+            return Err(invalid(
+                header_line,
+                "header must name symbol, currency and price columns".into(),
+            ));
+        };
+        let (chain, contract, date) = (column("chain"), column("contract"), column("date"));
+
+        let mut table = Self::new();
+        for (line, row) in rows {
+            let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+            if fields.len() != columns.len() {
+                return Err(invalid(
+                    line,
+                    format!("expected {} fields, got {}", columns.len(), fields.len()),
+                ));
+            }
+            let optional = |i: Option<usize>| i.map(|i| fields[i]).filter(|f| !f.is_empty());
+            let entry = PriceEntry {
+                symbol: fields[symbol].to_owned(),
+                chain: optional(chain)
+                    .map(Chain::from_str)
+                    .transpose()
+                    .map_err(|e| invalid(line, e.to_string()))?,
+                contract: optional(contract).map(str::to_owned),
+                currency: fields[currency].to_owned(),
+                price: Decimal::from_str(fields[price])
+                    .map_err(|e| invalid(line, format!("price `{}`: {e}", fields[price])))?,
+                date: optional(date)
+                    .map(PointInTime::from_str)
+                    .transpose()
+                    .map_err(|e| invalid(line, e.to_string()))?,
+            };
+            check_date(&entry).map_err(|reason| invalid(line, reason))?;
+            table.insert(entry);
+        }
+        Ok(table)
+    }
+
+    fn parse_json(text: &str, path: &Path) -> Result<Self> {
+        let entries = serde_json::from_str(text).map_err(|e| Error::PriceFile {
+            path: path.to_owned(),
+            line: e.line(),
+            reason: e.to_string(),
+        })?;
+        let table = PriceTable { entries };
+        for entry in &table.entries {
+            check_date(entry).map_err(|reason| Error::PriceFile {
+                path: path.to_owned(),
+                line: 0,
+                reason,
+            })?;
+        }
+        Ok(table)
+    }
+}
+
+fn check_date(entry: &PriceEntry) -> std::result::Result<(), String> {
+    match entry.date {
+        Some(PointInTime::Height(_)) => Err(format!(
+            "{} price dated by block height; use a date",
+            entry.symbol
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl FromIterator<PriceEntry> for PriceTable {
+    fn from_iter<I: IntoIterator<Item = PriceEntry>>(iter: I) -> Self {
+        PriceTable {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl PriceSource for PriceTable {
+    /// An undated entry, or failing that the latest dated one.
+    fn price(&self, asset: &Asset, currency: &str) -> Result<Option<Decimal>> {
+        let matching: Vec<&PriceEntry> = self
+            .entries
+            .iter()
+            .filter(|e| e.matches(asset, currency))
+            .collect();
+        let latest = matching.iter().filter_map(|e| e.day()).max();
+        let current: Vec<&PriceEntry> = matching
+            .iter()
+            .filter(|e| e.day().is_none())
+            .copied()
+            .collect();
+        let dated: Vec<&PriceEntry> = matching
+            .iter()
+            .filter(|e| latest.is_some() && e.day() == latest)
+            .copied()
+            .collect();
+        Ok(preferred(&current).or(preferred(&dated)).map(|e| e.price))
+    }
+
+    /// The entry of that day or, for days without one, the closest before.
+    fn price_at(&self, asset: &Asset, currency: &str, time: u64) -> Result<Option<Decimal>> {
+        let day = time / DAY;
+        let matching: Vec<&PriceEntry> = self
+            .entries
+            .iter()
+            .filter(|e| e.matches(asset, currency) && e.day().is_some_and(|d| d <= day))
+            .collect();
+        let Some(latest) = matching.iter().filter_map(|e| e.day()).max() else {
+            return Ok(None);
+        };
+        let dated: Vec<&PriceEntry> = matching
+            .into_iter()
+            .filter(|e| e.day() == Some(latest))
+            .collect();
+        Ok(preferred(&dated).map(|e| e.price))
+    }
+}
+
+/// Prices read from a local file, for offline valuation.
+///
+/// Files ending in `.json` are read with [`PriceTable::from_json`], anything
+/// else with [`PriceTable::from_csv`].
+#[derive(Debug, Clone)]
+pub struct PriceFile {
+    path: PathBuf,
+    table: PriceTable,
+}
+
+impl PriceFile {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let table = Self::load(&path)?;
+        Ok(PriceFile { path, table })
+    }
+
+    /// Re-reads the file, e.g. after a nightly price export.
+    pub fn reload(&mut self) -> Result<()> {
+        self.table = Self::load(&self.path)?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn table(&self) -> &PriceTable {
+        &self.table
+    }
+
+    fn load(path: &Path) -> Result<PriceTable> {
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            PriceTable::parse_json(&text, path)
+        } else {
+            PriceTable::parse_csv(&text, path)
+        }
+    }
+}
+
+impl PriceSource for PriceFile {
+    fn price(&self, asset: &Asset, currency: &str) -> Result<Option<Decimal>> {
+        self.table.price(asset, currency)
+    }
+
+    fn price_at(&self, asset: &Asset, currency: &str, time: u64) -> Result<Option<Decimal>> {
+        self.table.price_at(asset, currency, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usdc() -> Asset {
+        Asset::token(
+            Chain::ETHEREUM,
+            "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+            "USDC",
+            6,
+        )
+    }
+
+    #[test]
+    fn parses_csv_with_optional_columns() {
+        let table = PriceTable::from_csv(
+            "# exported 2024-05-01\n\
+             symbol, currency, price, chain, contract\n\
+             BTC, USD, 64000.50,,\n\
+             ETH, usd, 3000, evm:10,\n\
+             ETH, USD, 3100, ,\n\
+             USDC, USD, 0.9998, ethereum, 0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48\n",
+        )
+        .unwrap();
+        assert_eq!(table.entries().len(), 4);
+
+        let btc = Asset::native(Chain::Bitcoin, "BTC", 8);
+        assert_eq!(
+            table.price(&btc, "USD").unwrap(),
+            Some(Decimal::from_str("64000.50").unwrap())
+        );
+        assert_eq!(table.price(&btc, "EUR").unwrap(), None);
+
+        let optimism_eth = Asset::native(Chain::Evm(10), "ETH", 18);
+        let mainnet_eth = Asset::native(Chain::ETHEREUM, "ETH", 18);
+        assert_eq!(
+            table.price(&optimism_eth, "USD").unwrap(),
+            Some(Decimal::from(3000))
+        );
+        assert_eq!(
+            table.price(&mainnet_eth, "USD").unwrap(),
+            Some(Decimal::from(3100))
+        );
+        assert_eq!(
+            table.price(&usdc(), "USD").unwrap(),
+            Some(Decimal::from_str("0.9998").unwrap())
+        );
+    }
+
+    #[test]
+    fn tokens_never_match_by_symbol() {
+        let table = PriceTable::from_json(
+            r#"[{"symbol": "USDC", "currency": "USD", "price": "1"},
+                {"symbol": "ETH", "chain": "ethereum", "currency": "USD", "price": 3100.25}]"#,
+        )
+        .unwrap();
+        assert_eq!(table.price(&usdc(), "USD").unwrap(), None);
+        assert_eq!(
+            table
+                .price(&Asset::native(Chain::ETHEREUM, "ETH", 18), "USD")
+                .unwrap(),
+            Some(Decimal::from_str("3100.25").unwrap())
+        );
+    }
+
+    #[test]
+    fn looks_up_past_prices_by_day() {
+        let table = PriceTable::from_csv(
+            "symbol,currency,price,date\n\
+             BTC,USD,42000,2024-01-01\n\
+             BTC,USD,43000,2024-01-03\n\
+             ETH,USD,2300,2024-01-02\n",
+        )
+        .unwrap();
+        let btc = Asset::native(Chain::Bitcoin, "BTC", 8);
+        let day = |date: &str| match date.parse().unwrap() {
+            PointInTime::Time(time) => time,
+            PointInTime::Height(_) => unreachable!(),
+        };
+        assert_eq!(
+            table
+                .price_at(&btc, "USD", day("2024-01-01T00:00:00Z"))
+                .unwrap(),
+            Some(Decimal::from(42_000))
+        );
+        // A day without a quote takes the last one before it.
+        assert_eq!(
+            table.price_at(&btc, "USD", day("2024-01-02")).unwrap(),
+            Some(Decimal::from(42_000))
+        );
+        assert_eq!(
+            table.price_at(&btc, "USD", day("2023-12-31")).unwrap(),
+            None
+        );
+        // With no undated entry the latest quote is the current price.
+        assert_eq!(
+            table.price(&btc, "USD").unwrap(),
+            Some(Decimal::from(43_000))
+        );
+
+        let err = PriceTable::from_csv("symbol,currency,price,date\nBTC,USD,1,height:5\n");
+        assert!(matches!(err, Err(Error::PriceFile { line: 2, .. })));
+    }
+
+    #[test]
+    fn reports_bad_rows_with_line_numbers() {
+        let err = PriceTable::from_csv("symbol,currency,price\nBTC,USD,lots\n").unwrap_err();
+        assert!(matches!(err, Error::PriceFile { line: 2, .. }), "{err}");
+        assert!(PriceTable::from_csv("symbol,price\n").is_err());
+        assert!(PriceTable::from_csv("symbol,currency,price\nBTC,USD\n").is_err());
+    }
+}
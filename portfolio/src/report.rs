@@ -0,0 +1,319 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+use walletb_core::{Amount, Asset, Balance, Category, Underlying};
+
+use crate::{Error, PriceSource, Result, WalletBalances};
+
+/// The holding of one asset in one category and what it is worth.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AssetValuation {
+    pub asset: Asset,
+    pub category: Category,
+    /// Confirmed plus unconfirmed, in base units.
+    pub amount: Amount,
+    /// What a liquid-staking token's amount is worth in the asset it
+    /// stakes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underlying: Option<Underlying>,
+    /// Price of one whole unit, if the price source knows it or, failing
+    /// that, the underlying asset's.
+    pub price: Option<Decimal>,
+    pub value: Option<Decimal>,
+}
+
+/// The holdings of one wallet and their total value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WalletValuation {
+    pub id: String,
+    pub name: String,
+    pub total: Decimal,
+    /// The total split by category, for every category held.
+    pub categories: BTreeMap<Category, Decimal>,
+    pub assets: Vec<AssetValuation>,
+}
+
+/// Net worth across every wallet in one currency.
+///
+/// Assets without a price are listed with no value and left out of the
+/// totals; [`unpriced`](PortfolioReport::unpriced) names them so a total
+/// is never silently short. Liquid-staking tokens without a price of their
+/// own are valued at their underlying asset's.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortfolioReport {
+    pub currency: String,
+    pub total: Decimal,
+    /// The total split by category, so spendable funds can be told from
+    /// staked ones.
+    pub categories: BTreeMap<Category, Decimal>,
+    /// Holdings summed across wallets per asset and category, most
+    /// valuable first.
+    pub assets: Vec<AssetValuation>,
+    pub wallets: Vec<WalletValuation>,
+}
+
+impl PortfolioReport {
+    /// Assets with no price, each once.
+    pub fn unpriced(&self) -> impl Iterator<Item = &Asset> {
+        self.assets
+            .iter()
+            .enumerate()
+            .filter(|(i, a)| {
+                a.price.is_none() && !self.assets[..*i].iter().any(|b| b.asset == a.asset)
+            })
+            .map(|(_, a)| &a.asset)
+    }
+
+    /// The value of what can be spent now.
+    pub fn available(&self) -> Decimal {
+        self.categories
+            .get(&Category::Available)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Values already fetched wallet balances in `currency`.
+pub fn value_balances<P: PriceSource + ?Sized>(
+    wallets: &[WalletBalances],
+    prices: &P,
+    currency: &str,
+) -> Result<PortfolioReport> {
+    let currency = currency.to_ascii_uppercase();
+    let mut totals: Vec<Balance> = Vec::new();
+    for balance in wallets.iter().flat_map(|w| &w.balances) {
+        let held = holding(balance);
+        match totals
+            .iter_mut()
+            .find(|t| t.asset == held.asset && t.category == held.category)
+        {
+            Some(total) => total.merge(&held)?,
+            None => totals.push(held),
+        }
+    }
+
+    // Prices of every asset held, and of the assets behind liquid-staking
+    // tokens, fetched once each.
+    let mut priced: Vec<(Asset, Option<Decimal>)> = Vec::new();
+    let underlying = totals
+        .iter()
+        .filter_map(|t| t.underlying.as_ref().map(|u| &u.asset));
+    for asset in totals.iter().map(|t| &t.asset).chain(underlying) {
+        if !priced.iter().any(|(known, _)| known == asset) {
+            priced.push((asset.clone(), prices.price(asset, &currency)?));
+        }
+    }
+    let price_of = |asset: &Asset| {
+        priced
+            .iter()
+            .find(|(known, _)| known == asset)
+            .and_then(|(_, price)| *price)
+    };
+
+    let mut assets = Vec::with_capacity(totals.len());
+    for total in totals {
+        assets.push(valuation(total, &price_of)?);
+    }
+    sort_by_value(&mut assets);
+
+    let mut valued_wallets = Vec::with_capacity(wallets.len());
+    for wallet in wallets {
+        let mut holdings = Vec::with_capacity(wallet.balances.len());
+        for balance in &wallet.balances {
+            holdings.push(valuation(holding(balance), &price_of)?);
+        }
+        sort_by_value(&mut holdings);
+        valued_wallets.push(WalletValuation {
+            id: wallet.id.clone(),
+            name: wallet.name.clone(),
+            total: sum(&holdings)?,
+            categories: by_category(&holdings)?,
+            assets: holdings,
+        });
+    }
+
+    Ok(PortfolioReport {
+        currency,
+        total: sum(&assets)?,
+        categories: by_category(&assets)?,
+        assets,
+        wallets: valued_wallets,
+    })
+}
+
+/// `balance` with its unconfirmed funds counted in, as it is valued.
+fn holding(balance: &Balance) -> Balance {
+    let mut held =
+        Balance::new(balance.asset.clone(), balance.total()).with_category(balance.category);
+    held.underlying = balance.underlying.clone();
+    held
+}
+
+fn valuation(
+    held: Balance,
+    price_of: &dyn Fn(&Asset) -> Option<Decimal>,
+) -> Result<AssetValuation> {
+    let overflow = |asset: &Asset| Error::Overflow {
+        asset: asset.clone(),
+    };
+    let value_at = |asset: &Asset, amount: Amount, price: Decimal| {
+        whole_units(asset, amount)
+            .and_then(|units| units.checked_mul(price))
+            .ok_or_else(|| overflow(asset))
+    };
+    let (price, value) = match (price_of(&held.asset), &held.underlying) {
+        (Some(price), _) => (
+            Some(price),
+            Some(value_at(&held.asset, held.confirmed, price)?),
+        ),
+        (None, Some(underlying)) => match price_of(&underlying.asset) {
+            Some(price) => {
+                let value = value_at(&underlying.asset, underlying.amount, price)?;
+                let units = whole_units(&held.asset, held.confirmed)
+                    .ok_or_else(|| overflow(&held.asset))?;
+                (value.checked_div(units), Some(value))
+            }
+            None => (None, None),
+        },
+        (None, None) => (None, None),
+    };
+    Ok(AssetValuation {
+        asset: held.asset,
+        category: held.category,
+        amount: held.confirmed,
+        underlying: held.underlying,
+        price,
+        value,
+    })
+}
+
+/// `amount` in whole units of `asset`. Digits beyond what a `Decimal` can
+/// hold are rounded off, which only affects dust of 18-decimal tokens.
+pub(crate) fn whole_units(asset: &Asset, amount: Amount) -> Option<Decimal> {
+    Decimal::from_str(&amount.to_decimal_string(asset.decimals)).ok()
+}
+
+fn sum(assets: &[AssetValuation]) -> Result<Decimal> {
+    sum_of(assets.iter())
+}
+
+fn by_category(assets: &[AssetValuation]) -> Result<BTreeMap<Category, Decimal>> {
+    let mut categories = BTreeMap::new();
+    for category in Category::ALL {
+        let held: Vec<&AssetValuation> = assets.iter().filter(|a| a.category == category).collect();
+        if !held.is_empty() {
+            categories.insert(category, sum_of(held.into_iter())?);
+        }
+    }
+    Ok(categories)
+}
+
+fn sum_of<'a>(assets: impl Iterator<Item = &'a AssetValuation>) -> Result<Decimal> {
+    assets
+        .filter_map(|a| a.value.map(|value| (&a.asset, value)))
+        .try_fold(Decimal::ZERO, |total, (asset, value)| {
+            total.checked_add(value).ok_or_else(|| Error::Overflow {
+                asset: asset.clone(),
+            })
+        })
+}
+
+fn sort_by_value(assets: &mut [AssetValuation]) {
+    assets.sort_by(|a, b| {
+        match (a.value, b.value) {
+            (Some(a), Some(b)) => b.cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+        .then_with(|| a.asset.cmp(&b.asset))
+        .then_with(|| a.category.cmp(&b.category))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use walletb_core::Chain;
+
+    use super::*;
+    use crate::PriceTable;
+
+    #[test]
+    fn converts_base_units_with_asset_decimals() {
+        let eth = Asset::native(Chain::ETHEREUM, "ETH", 18);
+        let units = whole_units(&eth, Amount::from_u128(1_500_000_000_000_000_000)).unwrap();
+        assert_eq!(units, Decimal::from_str("1.5").unwrap());
+
+        let huge =
+            Amount::from_decimal_str("123456789012345678901.123456789012345678", 18).unwrap();
+        assert!(whole_units(&eth, huge).is_some());
+    }
+
+    #[test]
+    fn leaves_unpriced_assets_out_of_the_total() {
+        let btc = Asset::native(Chain::Bitcoin, "BTC", 8);
+        let meme = Asset::token(Chain::ETHEREUM, "0x1234", "MEME", 18);
+        let wallets = [WalletBalances {
+            id: "w".into(),
+            name: "W".into(),
+            balances: vec![
+                Balance::new(btc.clone(), Amount::from_u64(50_000_000)),
+                Balance::new(meme.clone(), Amount::from_u64(1)),
+            ],
+        }];
+        let prices = PriceTable::from_csv("symbol,currency,price\nBTC,USD,60000\n").unwrap();
+        let report = value_balances(&wallets, &prices, "usd").unwrap();
+        assert_eq!(report.currency, "USD");
+        assert_eq!(report.total, Decimal::from(30_000));
+        assert_eq!(report.assets[0].asset, btc);
+        assert_eq!(report.unpriced().collect::<Vec<_>>(), [&meme]);
+    }
+
+    #[test]
+    fn splits_totals_by_category() {
+        let eth = Asset::native(Chain::ETHEREUM, "ETH", 18);
+        let reth = Asset::token(Chain::ETHEREUM, "0xae78…", "rETH", 18);
+        let whole = |s: &str| Amount::from_decimal_str(s, 18).unwrap();
+        let wallets = [
+            WalletBalances {
+                id: "hot".into(),
+                name: "Hot".into(),
+                balances: vec![
+                    Balance::new(eth.clone(), whole("1")),
+                    Balance::new(reth.clone(), whole("2"))
+                        .with_underlying(eth.clone(), whole("2.2")),
+                ],
+            },
+            WalletBalances {
+                id: "validators".into(),
+                name: "Validators".into(),
+                balances: vec![
+                    Balance::new(eth.clone(), whole("32")).with_category(Category::Staked),
+                    Balance::new(eth.clone(), whole("0.5"))
+                        .with_category(Category::PendingWithdrawal),
+                ],
+            },
+        ];
+        let prices = PriceTable::from_csv("symbol,currency,price\nETH,USD,2000\n").unwrap();
+        let report = value_balances(&wallets, &prices, "USD").unwrap();
+        // rETH has no price of its own and is valued as 2.2 ETH.
+        let held = report.assets.iter().find(|a| a.asset == reth).unwrap();
+        assert_eq!(held.value, Some(Decimal::from(4_400)));
+        assert_eq!(held.price, Some(Decimal::from(2_200)));
+        assert_eq!(report.unpriced().count(), 0);
+
+        assert_eq!(report.total, Decimal::from(71_400));
+        assert_eq!(report.available(), Decimal::from(6_400));
+        assert_eq!(report.categories[&Category::Staked], Decimal::from(64_000));
+        assert_eq!(
+            report.categories[&Category::PendingWithdrawal],
+            Decimal::from(1_000)
+        );
+        assert!(!report.categories.contains_key(&Category::Locked));
+        assert_eq!(report.wallets[1].categories.len(), 2);
+        assert_eq!(report.assets[0].category, Category::Staked);
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{OutPoint, Txid};
+use walletb_bitcoin::{BitcoinSource, MemoryUtxoSet, Network, Utxo};
+use walletb_core::{Address, Amount, Asset, Balance, Chain};
+use walletb_portfolio::{AddressWallet, Decimal, Error, Portfolio, PriceFile};
+
+const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn dec(s: &str) -> Decimal {
+    Decimal::from_str(s).unwrap()
+}
+
+fn eth() -> Asset {
+    Asset::native(Chain::ETHEREUM, "ETH", 18)
+}
+
+fn usdc() -> Asset {
+    Asset::token(Chain::ETHEREUM, USDC, "USDC", 6)
+}
+
+fn bitcoin_wallet() -> AddressWallet<BitcoinSource<MemoryUtxoSet>> {
+    let address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    let script = walletb_bitcoin::parse_address(address, Network::Bitcoin)
+        .unwrap()
+        .0
+        .script_pubkey();
+    let set = [(1, 150_000_000), (2, 50_000_000)]
+        .into_iter()
+        .map(|(n, sats)| Utxo {
+            outpoint: OutPoint::new(Txid::from_byte_array([n; 32]), 0),
+            script_pubkey: script.clone(),
+            value: bitcoin::Amount::from_sat(sats),
+            height: Some(800_000),
+        })
+        .collect();
+    AddressWallet::new(
+        BitcoinSource::new(Network::Bitcoin, set),
+        vec![Address::new(Chain::Bitcoin, address)],
+    )
+}
+
+fn portfolio() -> Portfolio {
+    Portfolio::new()
+        .with_wallet("cold", "Cold storage", bitcoin_wallet())
+        .with_wallet("treasury", "Treasury", || {
+            Ok(vec![
+                Balance::new(eth(), Amount::from_decimal_str("10", 18).unwrap()),
+                Balance::new(usdc(), Amount::from_decimal_str("250000", 6).unwrap()),
+            ])
+        })
+        .with_wallet("ops", "Operations", || {
+            Ok(vec![Balance::new(
+                eth(),
+                Amount::from_decimal_str("0.5", 18).unwrap(),
+            )])
+        })
+}
+
+#[test]
+fn totals_and_breaks_down_net_worth_from_csv_prices() {
+    let prices = PriceFile::open(fixture("prices.csv")).unwrap();
+    let report = portfolio().report(&prices, "USD").unwrap();
+
+    // 2 BTC, 10.5 ETH and 250k USDC.
+    let btc = dec("125356.58");
+    let ether = dec("36052.800");
+    let dollars = dec("249975.0000");
+    assert_eq!(report.total, btc + ether + dollars);
+    assert_eq!(report.unpriced().count(), 0);
+
+    let assets: Vec<&str> = report
+        .assets
+        .iter()
+        .map(|a| a.asset.symbol.as_str())
+        .collect();
+    assert_eq!(assets, ["USDC", "BTC", "ETH"]);
+    assert_eq!(
+        report.assets[2].amount,
+        Amount::from_decimal_str("10.5", 18).unwrap()
+    );
+
+    let wallets: Vec<(&str, Decimal)> = report
+        .wallets
+        .iter()
+        .map(|w| (w.id.as_str(), w.total))
+        .collect();
+    assert_eq!(
+        wallets,
+        [
+            ("cold", btc),
+            ("treasury", dollars + dec("34336.00")),
+            ("ops", dec("1716.80")),
+        ]
+    );
+}
+
+#[test]
+fn values_in_another_currency_from_json_prices() {
+    let prices = PriceFile::open(fixture("prices.json")).unwrap();
+    let report = portfolio().report(&prices, "eur").unwrap();
+    assert_eq!(report.currency, "EUR");
+    assert_eq!(report.unpriced().collect::<Vec<_>>(), [&usdc()]);
+    assert_eq!(report.total, dec("116942.04") + dec("33636.645"));
+}
+
+#[test]
+fn names_the_wallet_that_failed() {
+    let failing = Portfolio::new()
+        .with_wallet("cold", "Cold storage", bitcoin_wallet())
+        .with_wallet("broken", "Broken", || {
+            Err(walletb_core::Error::UnknownChain("dogecoin".into()))
+        });
+    let prices = PriceFile::open(fixture("prices.csv")).unwrap();
+    assert!(matches!(
+        failing.report(&prices, "USD"),
+        Err(Error::Wallet { wallet, .. }) if wallet == "broken"
+    ));
+}
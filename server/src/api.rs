@@ -0,0 +1,326 @@
+use std::fmt::Display;
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use walletb_cli::{Config, Wallets};
+use walletb_core::PointInTime;
+use walletb_portfolio::{value_balances, PriceFile};
+
+use crate::{openapi, Admission, ApiKeys, Error, Result};
+
+/// An HTTP request, as far as the API looks at it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// Path including any query string.
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn get(url: impl Into<String>) -> Self {
+        Request {
+            method: "GET".to_owned(),
+            url: url.into(),
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn path(&self) -> &str {
+        self.url.split('?').next().unwrap_or_default()
+    }
+
+    /// Every value of query parameter `name`, percent-decoded.
+    pub fn query(&self, name: &str) -> Vec<String> {
+        let Some((_, query)) = self.url.split_once('?') else {
+            return Vec::new();
+        };
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .filter(|(k, _)| *k == name)
+            .map(|(_, v)| decode(v, true))
+            .collect()
+    }
+
+    /// The secret of an `Authorization: Bearer` or `X-Api-Key` header.
+    fn api_key(&self) -> Option<&str> {
+        self.header("Authorization")
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .or_else(|| self.header("X-Api-Key"))
+            .map(str::trim)
+    }
+}
+
+/// A JSON response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Value,
+    /// The name of the key the request was made with, once recognized.
+    pub key: Option<String>,
+}
+
+impl Response {
+    fn json(body: &impl Serialize) -> Self {
+        Response {
+            status: 200,
+            headers: Vec::new(),
+            body: serde_json::to_value(body).expect("serializable response"),
+            key: None,
+        }
+    }
+
+    fn error(status: u16, message: impl Display) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: json!({ "error": message.to_string() }),
+            key: None,
+        }
+    }
+
+    fn with_header(mut self, name: &str, value: impl Display) -> Self {
+        self.headers.push((name.to_owned(), value.to_string()));
+        self
+    }
+}
+
+/// The balance engine behind the HTTP API: a config's wallets, read on
+/// request, and the keys allowed to read them.
+///
+/// Every route but `/openapi.json` and `/health` needs a key, sent as
+/// `Authorization: Bearer <key>` or `X-Api-Key: <key>`, and spends one
+/// request of its per-minute budget.
+pub struct Api {
+    config: Config,
+    wallets: Wallets,
+    keys: ApiKeys,
+}
+
+impl Api {
+    /// Opens every source and wallet of `config`, which must list at least
+    /// one key under `[server]`.
+    pub fn open(config: Config) -> Result<Self> {
+        let keys = config
+            .server
+            .as_ref()
+            .map(|server| ApiKeys::new(&server.keys, Instant::now()))
+            .filter(|keys| !keys.is_empty())
+            .ok_or(Error::NoKeys)?;
+        let wallets = Wallets::open(&config)?;
+        Ok(Api {
+            config,
+            wallets,
+            keys,
+        })
+    }
+
+    pub fn handle(&mut self, request: &Request) -> Response {
+        self.handle_at(request, Instant::now())
+    }
+
+    /// Answers `request` as if it arrived at `now`, which only matters to
+    /// rate limits.
+    pub fn handle_at(&mut self, request: &Request, now: Instant) -> Response {
+        if request.method != "GET" {
+            return Response::error(405, format!("{} is not supported", request.method))
+                .with_header("Allow", "GET");
+        }
+        match request.path() {
+            "/openapi.json" => return Response::json(&openapi()),
+            "/health" => return Response::json(&json!({ "status": "ok" })),
+            _ => {}
+        }
+        let (key, limit, remaining) = match self.keys.admit(request.api_key(), now) {
+            Admission::Allowed {
+                key,
+                limit,
+                remaining,
+            } => (key, limit, remaining),
+            Admission::Unauthorized => {
+                return Response::error(401, "missing or unknown API key")
+                    .with_header("WWW-Authenticate", "Bearer");
+            }
+            Admission::Limited {
+                key,
+                limit,
+                retry_after,
+            } => {
+                let mut response = Response::error(
+                    429,
+                    format!("key `{key}` is limited to {limit} requests per minute"),
+                )
+                .with_header("Retry-After", retry_after.as_secs_f64().ceil())
+                .with_header("X-RateLimit-Limit", limit)
+                .with_header("X-RateLimit-Remaining", 0);
+                response.key = Some(key);
+                return response;
+            }
+        };
+        let mut response = self
+            .route(request)
+            .unwrap_or_else(|err| Response::error(status(&err), err))
+            .with_header("X-RateLimit-Limit", limit)
+            .with_header("X-RateLimit-Remaining", remaining);
+        response.key = Some(key);
+        response
+    }
+
+    fn route(&self, request: &Request) -> Result<Response, walletb_cli::Error> {
+        let path = request.path();
+        if path == "/wallets" {
+            let wallets: Vec<Value> = self
+                .wallets
+                .iter()
+                .map(|w| json!({ "id": w.id, "name": w.name, "source": w.source() }))
+                .collect();
+            return Ok(Response::json(&wallets));
+        }
+        if let Some(id) = path
+            .strip_prefix("/wallets/")
+            .and_then(|rest| rest.strip_suffix("/balance"))
+        {
+            let wallet = self.wallets.select(&[decode(id, false)])?[0];
+            return Ok(Response::json(&self.wallets.balances(wallet)?));
+        }
+        match path {
+            "/portfolio" => self.portfolio(request),
+            "/history" => self.history(request),
+            _ => Ok(Response::error(404, format!("no route {path}"))),
+        }
+    }
+
+    fn portfolio(&self, request: &Request) -> Result<Response, walletb_cli::Error> {
+        let at = match points(request)?.as_slice() {
+            [] => None,
+            [at] => Some(*at),
+            _ => return Ok(Response::error(400, "`at` may be given once")),
+        };
+        // Read on every request so a price file updated in place is seen.
+        let prices = match &self.config.prices {
+            Some(path) => PriceFile::open(self.config.resolve(path))?,
+            None => return Err(walletb_cli::Error::NoPrices),
+        };
+        let currency = request
+            .query("currency")
+            .pop()
+            .unwrap_or_else(|| self.config.currency.clone());
+        let balances = self
+            .wallets
+            .iter()
+            .map(|w| match at {
+                Some(at) => self.wallets.balances_at(w, at),
+                None => self.wallets.balances(w),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Response::json(&value_balances(
+            &balances, &prices, &currency,
+        )?))
+    }
+
+    fn history(&self, request: &Request) -> Result<Response, walletb_cli::Error> {
+        let points = points(request)?;
+        if points.is_empty() {
+            return Ok(Response::error(400, "`at` is required"));
+        }
+        let mut entries = Vec::new();
+        for wallet in self.wallets.select(&request.query("wallet"))? {
+            for &at in &points {
+                let read = self.wallets.balances_at(wallet, at)?;
+                entries.push(json!({
+                    "at": at.to_string(),
+                    "id": read.id,
+                    "name": read.name,
+                    "balances": read.balances,
+                }));
+            }
+        }
+        Ok(Response::json(&entries))
+    }
+}
+
+/// The `at` parameters of a request.
+fn points(request: &Request) -> Result<Vec<PointInTime>, walletb_cli::Error> {
+    request
+        .query("at")
+        .iter()
+        .map(|at| Ok(at.parse()?))
+        .collect()
+}
+
+fn status(err: &walletb_cli::Error) -> u16 {
+    use walletb_cli::Error::*;
+    match err {
+        UnknownWallet(_) => 404,
+        Core(walletb_core::Error::InvalidPointInTime(_)) => 400,
+        NoHistory(_) => 422,
+        NoPrices => 503,
+        Portfolio(walletb_portfolio::Error::Wallet { .. }) => 502,
+        _ => 500,
+    }
+}
+
+/// Percent-decodes `s`, reading `+` as a space if it is a `form` value, as
+/// query values are; in a path segment `+` is itself.
+fn decode(s: &str, form: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b'+' if form => out.push(b' '),
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_query_values() {
+        let request =
+            Request::get("/history?at=2024-01-31T00%3A00%3A00Z&at=height:5&wallet=a+b&x=%zz");
+        assert_eq!(request.query("at"), ["2024-01-31T00:00:00Z", "height:5"]);
+        assert_eq!(request.query("wallet"), ["a b"]);
+        assert_eq!(request.query("x"), ["%zz"]);
+        assert!(request.query("currency").is_empty());
+    }
+
+    #[test]
+    fn keeps_plus_signs_in_path_segments() {
+        assert_eq!(decode("ops+treasury%2Fcold", false), "ops+treasury/cold");
+        assert_eq!(decode("ops+treasury", true), "ops treasury");
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use walletb_cli::ApiKeyConfig;
+
+/// The keys allowed to call the API, each with its own request budget.
+#[derive(Debug)]
+pub struct ApiKeys {
+    keys: Vec<Key>,
+}
+
+#[derive(Debug)]
+struct Key {
+    name: String,
+    /// SHA-256 of the secret, so every comparison is of the same length.
+    digest: [u8; 32],
+    bucket: Bucket,
+}
+
+/// A token bucket holding up to `limit` requests and refilled at `limit`
+/// per minute, so a key may burst through its whole minute at once but
+/// not exceed it on average.
+#[derive(Debug)]
+struct Bucket {
+    limit: u32,
+    tokens: f64,
+    updated: Instant,
+}
+
+/// What [`ApiKeys::admit`] decided about a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    /// The request may proceed; `remaining` more fit in the key's budget
+    /// right now.
+    Allowed {
+        key: String,
+        limit: u32,
+        remaining: u32,
+    },
+    /// No key, or one that is not configured.
+    Unauthorized,
+    /// The key spent its budget; another request fits after `retry_after`.
+    Limited {
+        key: String,
+        limit: u32,
+        retry_after: Duration,
+    },
+}
+
+impl ApiKeys {
+    /// Every key starts with a full budget at `now`.
+    pub fn new(keys: &[ApiKeyConfig], now: Instant) -> Self {
+        ApiKeys {
+            keys: keys
+                .iter()
+                .map(|key| Key {
+                    name: key.name.clone(),
+                    digest: Sha256::digest(&key.key).into(),
+                    bucket: Bucket {
+                        limit: key.requests_per_minute,
+                        tokens: f64::from(key.requests_per_minute),
+                        updated: now,
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Looks up the key `secret` belongs to and, if there is one, takes a
+    /// request from its budget.
+    pub fn admit(&mut self, secret: Option<&str>, now: Instant) -> Admission {
+        let Some(secret) = secret else {
+            return Admission::Unauthorized;
+        };
+        // Compare digests against every key, so timing does not tell how
+        // long a key is, how much of a guess was right or which key it was
+        // close to.
+        let digest: [u8; 32] = Sha256::digest(secret).into();
+        let mut found = None;
+        for (i, key) in self.keys.iter().enumerate() {
+            if same(&key.digest, &digest) {
+                found = Some(i);
+            }
+        }
+        let Some(key) = found.map(|i| &mut self.keys[i]) else {
+            return Admission::Unauthorized;
+        };
+        let limit = key.bucket.limit;
+        match key.bucket.take(now) {
+            Ok(remaining) => Admission::Allowed {
+                key: key.name.clone(),
+                limit,
+                remaining,
+            },
+            Err(retry_after) => Admission::Limited {
+                key: key.name.clone(),
+                limit,
+                retry_after,
+            },
+        }
+    }
+}
+
+impl Bucket {
+    /// Takes one request, returning how many more fit, or how long until
+    /// one does.
+    fn take(&mut self, now: Instant) -> Result<u32, Duration> {
+        let per_second = f64::from(self.limit) / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(f64::from(self.limit));
+        self.updated = now;
+        if self.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second));
+        }
+        self.tokens -= 1.0;
+        Ok(self.tokens as u32)
+    }
+}
+
+fn same(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(now: Instant) -> ApiKeys {
+        let key = |name: &str, key: &str, requests_per_minute| ApiKeyConfig {
+            name: name.to_owned(),
+            key: key.to_owned(),
+            requests_per_minute,
+        };
+        ApiKeys::new(
+            &[
+                key("billing", "billing-0123456789", 2),
+                key("risk", "risk-0123456789ab", 60),
+            ],
+            now,
+        )
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_keys() {
+        let now = Instant::now();
+        let mut keys = keys(now);
+        assert_eq!(keys.admit(None, now), Admission::Unauthorized);
+        for guess in ["billing-012345678", "billing-0123456789a"] {
+            assert_eq!(keys.admit(Some(guess), now), Admission::Unauthorized);
+        }
+        assert_eq!(
+            keys.admit(Some("risk-0123456789ab"), now),
+            Admission::Allowed {
+                key: "risk".into(),
+                limit: 60,
+                remaining: 59,
+            }
+        );
+    }
+
+    #[test]
+    fn limits_each_key_to_its_own_rate() {
+        let start = Instant::now();
+        let mut keys = keys(start);
+        let billing = Some("billing-0123456789");
+        assert!(matches!(
+            keys.admit(billing, start),
+            Admission::Allowed { remaining: 1, .. }
+        ));
+        assert!(matches!(
+            keys.admit(billing, start),
+            Admission::Allowed { remaining: 0, .. }
+        ));
+        assert_eq!(
+            keys.admit(billing, start),
+            Admission::Limited {
+                key: "billing".into(),
+                limit: 2,
+                retry_after: Duration::from_secs(30),
+            }
+        );
+        // Another key's budget is untouched.
+        assert!(matches!(
+            keys.admit(Some("risk-0123456789ab"), start),
+            Admission::Allowed { .. }
+        ));
+
+        // Two a minute is one every 30 seconds, and never more than two
+        // saved up.
+        let later = start + Duration::from_secs(30);
+        assert!(matches!(
+            keys.admit(billing, later),
+            Admission::Allowed { remaining: 0, .. }
+        ));
+        let much_later = later + Duration::from_secs(3600);
+        assert!(matches!(
+            keys.admit(billing, much_later),
+            Admission::Allowed { remaining: 1, .. }
+        ));
+    }
+}
@@ -0,0 +1,16 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no API keys configured; add a `[[server.keys]]` table to the config")]
+    NoKeys,
+
+    #[error("cannot listen on {addr}: {reason}")]
+    Bind { addr: String, reason: String },
+
+    #[error(transparent)]
+    Cli(#[from] walletb_cli::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,30 @@
+//! An HTTP/JSON API over walletb's balance engine.
+//!
+//! [`Api`] reads the wallets of a `walletb` [`Config`](walletb_cli::Config)
+//! on request, behind API keys that each have their own per-minute rate
+//! limit; [`Server`] serves it over HTTP. The routes are:
+//!
+//! | Route | Answer |
+//! |-------|--------|
+//! | `GET /wallets` | The configured wallets |
+//! | `GET /wallets/{id}/balance` | One wallet's current balances |
+//! | `GET /portfolio?currency=&at=` | Every wallet valued in fiat |
+//! | `GET /history?at=&wallet=` | Balances at past heights or dates |
+//! | `GET /openapi.json` | The OpenAPI description, see [`openapi`] |
+//! | `GET /health` | Whether the server is up |
+//!
+//! Errors are `{"error": "..."}` with a status telling what went wrong:
+//! 401 for a missing or unknown key, 429 once a key's budget is spent, 404
+//! for an unknown wallet and 502 when a balance source fails.
+
+mod api;
+mod auth;
+mod error;
+mod openapi;
+mod server;
+
+pub use api::{Api, Request, Response};
+pub use auth::{Admission, ApiKeys};
+pub use error::{Error, Result};
+pub use openapi::openapi;
+pub use server::Server;
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use walletb_cli::Config;
+use walletb_server::{openapi, Api, Result, Server};
+
+/// Serve wallet balances over HTTP.
+#[derive(Debug, Parser)]
+#[command(name = "walletb-server", version)]
+struct Cli {
+    /// Config file naming the sources, wallets and API keys.
+    #[arg(long, short, env = "WALLETB_CONFIG", default_value = "walletb.toml")]
+    config: PathBuf,
+
+    /// `host:port` to listen on, overriding the config.
+    #[arg(long)]
+    listen: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the OpenAPI description of the API and exit.
+    Openapi,
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("walletb-server: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    if let Some(Command::Openapi) = cli.command {
+        println!("{:#}", openapi());
+        return Ok(());
+    }
+    let config = Config::load(&cli.config)?;
+    let listen = cli
+        .listen
+        .or_else(|| config.server.as_ref().map(|s| s.listen.clone()))
+        .unwrap_or_else(|| "127.0.0.1:8080".to_owned());
+    let server = Server::bind(Api::open(config)?, &listen)?;
+    match server.local_addr() {
+        Some(addr) => println!("listening on http://{addr}"),
+        None => println!("listening on {listen}"),
+    }
+    server.run()
+}
@@ -0,0 +1,283 @@
+use serde_json::{json, Value};
+
+/// The OpenAPI 3.0 description of the API, as served at `/openapi.json`.
+///
+/// Amounts are strings of base units and values are decimal strings, so
+/// no client loses precision to floating point.
+pub fn openapi() -> Value {
+    let error = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } },
+        })
+    };
+    let ok = |description: &str, schema: Value| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": schema } },
+        })
+    };
+    let schema = |name: &str| json!({ "$ref": format!("#/components/schemas/{name}") });
+    let at = |required: bool, description: &str| {
+        json!({
+            "name": "at",
+            "in": "query",
+            "required": required,
+            "description": description,
+            "schema": { "type": "string", "example": "2024-06-30" },
+        })
+    };
+    let denied = json!({
+        "401": error("Missing or unknown API key."),
+        "429": error("The key's rate limit is spent; retry after `Retry-After` seconds."),
+    });
+    let read = |mut responses: Value| {
+        let responses_map = responses.as_object_mut().expect("responses object");
+        for (status, response) in denied.as_object().expect("responses object") {
+            responses_map.insert(status.clone(), response.clone());
+        }
+        responses_map.insert(
+            "502".into(),
+            error("A balance source failed or gave an invalid answer."),
+        );
+        responses
+    };
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "walletb",
+            "description": "Wallet balances across chains.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "security": [{ "bearer": [] }, { "apiKey": [] }],
+        "paths": {
+            "/wallets": {
+                "get": {
+                    "summary": "List the configured wallets.",
+                    "operationId": "listWallets",
+                    "responses": read(json!({
+                        "200": ok("Every wallet.", json!({ "type": "array", "items": schema("Wallet") })),
+                    })),
+                },
+            },
+            "/wallets/{id}/balance": {
+                "get": {
+                    "summary": "Read one wallet's current balances.",
+                    "operationId": "walletBalance",
+                    "parameters": [{
+                        "name": "id",
+                        "in": "path",
+                        "required": true,
+                        "schema": { "type": "string" },
+                    }],
+                    "responses": read(json!({
+                        "200": ok("The wallet's balances.", schema("WalletBalances")),
+                        "404": error("No wallet has this id."),
+                    })),
+                },
+            },
+            "/portfolio": {
+                "get": {
+                    "summary": "Value every wallet in one fiat currency.",
+                    "operationId": "portfolio",
+                    "parameters": [
+                        {
+                            "name": "currency",
+                            "in": "query",
+                            "required": false,
+                            "description": "Overrides the configured currency.",
+                            "schema": { "type": "string", "example": "USD" },
+                        },
+                        at(false, "Value the balances held at this point instead of now."),
+                    ],
+                    "responses": read(json!({
+                        "200": ok("The valued portfolio.", schema("PortfolioReport")),
+                        "400": error("`at` is not a point in time."),
+                        "422": error("A source cannot report past balances."),
+                        "503": error("No price file is configured."),
+                    })),
+                },
+            },
+            "/history": {
+                "get": {
+                    "summary": "Read balances at past block heights or dates.",
+                    "operationId": "history",
+                    "parameters": [
+                        at(true, "`height:N`, a date `YYYY-MM-DD` (end of that UTC day) or a UTC timestamp; repeat for several."),
+                        {
+                            "name": "wallet",
+                            "in": "query",
+                            "required": false,
+                            "description": "Wallet ids; every wallet if none are given.",
+                            "schema": { "type": "string" },
+                        },
+                    ],
+                    "responses": read(json!({
+                        "200": ok("Balances per point and wallet.", json!({ "type": "array", "items": schema("HistoricalBalances") })),
+                        "400": error("`at` is missing or not a point in time."),
+                        "404": error("No wallet has this id."),
+                        "422": error("A source cannot report past balances."),
+                    })),
+                },
+            },
+        },
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-Api-Key" },
+            },
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "required": ["error"],
+                    "properties": { "error": { "type": "string" } },
+                },
+                "Wallet": {
+                    "type": "object",
+                    "required": ["id", "name", "source"],
+                    "properties": {
+                        "id": { "type": "string" },
+                        "name": { "type": "string" },
+                        "source": { "type": "string" },
+                    },
+                },
+                "Asset": {
+                    "type": "object",
+                    "required": ["chain", "kind", "symbol", "decimals"],
+                    "properties": {
+                        "chain": { "type": "string", "example": "ethereum" },
+                        "kind": {
+                            "type": "object",
+                            "required": ["type"],
+                            "properties": {
+                                "type": { "type": "string", "enum": ["native", "token", "nft"] },
+                                "contract": { "type": "string" },
+                                "token_id": { "type": "string" },
+                            },
+                        },
+                        "symbol": { "type": "string" },
+                        "decimals": { "type": "integer", "minimum": 0 },
+                    },
+                },
+                "Amount": {
+                    "type": "string",
+                    "description": "Base units (satoshi, wei, ...).",
+                    "example": "1500000000000000000",
+                },
+                "Decimal": { "type": "string", "example": "3433.60" },
+                "Category": {
+                    "type": "string",
+                    "enum": ["available", "staked", "locked", "pending_withdrawal"],
+                },
+                "Underlying": {
+                    "type": "object",
+                    "required": ["asset", "amount"],
+                    "properties": {
+                        "asset": schema("Asset"),
+                        "amount": schema("Amount"),
+                    },
+                },
+                "Balance": {
+                    "type": "object",
+                    "required": ["asset", "confirmed", "unconfirmed", "category"],
+                    "properties": {
+                        "asset": schema("Asset"),
+                        "confirmed": schema("Amount"),
+                        "unconfirmed": schema("Amount"),
+                        "category": schema("Category"),
+                        "underlying": schema("Underlying"),
+                    },
+                },
+                "WalletBalances": {
+                    "type": "object",
+                    "required": ["id", "name", "balances"],
+                    "properties": {
+                        "id": { "type": "string" },
+                        "name": { "type": "string" },
+                        "balances": { "type": "array", "items": schema("Balance") },
+                    },
+                },
+                "HistoricalBalances": {
+                    "type": "object",
+                    "required": ["at", "id", "name", "balances"],
+                    "properties": {
+                        "at": { "type": "string" },
+                        "id": { "type": "string" },
+                        "name": { "type": "string" },
+                        "balances": { "type": "array", "items": schema("Balance") },
+                    },
+                },
+                "AssetValuation": {
+                    "type": "object",
+                    "required": ["asset", "category", "amount", "price", "value"],
+                    "properties": {
+                        "asset": schema("Asset"),
+                        "category": schema("Category"),
+                        "amount": schema("Amount"),
+                        "underlying": schema("Underlying"),
+                        "price": { "allOf": [schema("Decimal")], "nullable": true },
+                        "value": { "allOf": [schema("Decimal")], "nullable": true },
+                    },
+                },
+                "CategoryTotals": {
+                    "type": "object",
+                    "description": "Value per category held.",
+                    "additionalProperties": schema("Decimal"),
+                },
+                "WalletValuation": {
+                    "type": "object",
+                    "required": ["id", "name", "total", "categories", "assets"],
+                    "properties": {
+                        "id": { "type": "string" },
+                        "name": { "type": "string" },
+                        "total": schema("Decimal"),
+                        "categories": schema("CategoryTotals"),
+                        "assets": { "type": "array", "items": schema("AssetValuation") },
+                    },
+                },
+                "PortfolioReport": {
+                    "type": "object",
+                    "required": ["currency", "total", "categories", "assets", "wallets"],
+                    "properties": {
+                        "currency": { "type": "string" },
+                        "total": schema("Decimal"),
+                        "categories": schema("CategoryTotals"),
+                        "assets": { "type": "array", "items": schema("AssetValuation") },
+                        "wallets": { "type": "array", "items": schema("WalletValuation") },
+                    },
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every `$ref` names a schema the document defines.
+    #[test]
+    fn references_resolve() {
+        fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(r)) = map.get("$ref") {
+                        found.push(r);
+                    }
+                    map.values().for_each(|v| refs(v, found));
+                }
+                Value::Array(items) => items.iter().for_each(|v| refs(v, found)),
+                _ => {}
+            }
+        }
+        let doc = openapi();
+        let mut found = Vec::new();
+        refs(&doc, &mut found);
+        assert!(!found.is_empty());
+        for r in found {
+            let name = r.strip_prefix("#/components/schemas/").unwrap();
+            assert!(doc["components"]["schemas"][name].is_object(), "{r}");
+        }
+    }
+}
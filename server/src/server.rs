@@ -0,0 +1,63 @@
+use std::net::SocketAddr;
+
+use crate::{Api, Error, Request, Result};
+
+/// The API served over HTTP/1.1.
+///
+/// Requests are answered one at a time, in arrival order: a balance read
+/// holds its source's connection for its whole duration, and the callers
+/// are a handful of internal services.
+pub struct Server {
+    http: tiny_http::Server,
+    api: Api,
+}
+
+impl Server {
+    /// Listens on `addr`, a `host:port`; port 0 picks a free one.
+    pub fn bind(api: Api, addr: &str) -> Result<Self> {
+        let http = tiny_http::Server::http(addr).map_err(|e| Error::Bind {
+            addr: addr.to_owned(),
+            reason: e.to_string(),
+        })?;
+        Ok(Server { http, api })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Serves requests until the process ends, logging each to standard
+    /// error.
+    pub fn run(mut self) -> Result<()> {
+        for request in self.http.incoming_requests() {
+            let received = Request {
+                method: request.method().to_string(),
+                url: request.url().to_owned(),
+                headers: request
+                    .headers()
+                    .iter()
+                    .map(|h| (h.field.to_string(), h.value.to_string()))
+                    .collect(),
+            };
+            let response = self.api.handle(&received);
+            eprintln!(
+                "{} {} {} {}",
+                response.key.as_deref().unwrap_or("-"),
+                received.method,
+                received.url,
+                response.status
+            );
+            let mut reply = tiny_http::Response::from_string(response.body.to_string())
+                .with_status_code(response.status);
+            let headers = [("Content-Type".to_owned(), "application/json".to_owned())];
+            for (name, value) in headers.iter().chain(&response.headers) {
+                let header = tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes())
+                    .expect("valid header");
+                reply.add_header(header);
+            }
+            // The client may have gone away; that is no reason to stop.
+            let _ = request.respond(reply);
+        }
+        Ok(())
+    }
+}
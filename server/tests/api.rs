@@ -0,0 +1,276 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+
+use serde_json::{json, Value};
+use walletb_core::testing::{MockServer, RpcFailure};
+
+const BILLING: &str = "billing-0123456789";
+const RISK: &str = "risk-0123456789abc";
+
+/// A running `walletb-server` reading one Ethereum wallet, `hot`, from a
+/// mock node that has a block a day from 2023-12-31T00:00:00Z up to block
+/// 16. The wallet holds 1.5 ETH and 2500.5 USDC from block 10 on, and
+/// nothing before.
+struct Setup {
+    dir: PathBuf,
+    url: String,
+    child: Child,
+    _node: MockServer,
+}
+
+impl Setup {
+    fn new(name: &str) -> Self {
+        let node = MockServer::json_rpc(|method, params| {
+            let block = |i: usize| {
+                let tag = params[i].as_str().unwrap_or_default();
+                u64::from_str_radix(tag.trim_start_matches("0x"), 16).unwrap()
+            };
+            match method {
+                "eth_blockNumber" => Ok(json!("0x10")),
+                "eth_getBlockByNumber" => Ok(json!({
+                    "timestamp": format!("0x{:x}", 1_703_980_800 + block(0) * 86_400),
+                })),
+                "eth_getBalance" if block(1) < 10 => Ok(json!("0x0")),
+                "eth_getBalance" => Ok(json!("0x14d1120d7b160000")),
+                "eth_call" if block(1) < 10 => Ok(json!(format!("0x{:064x}", 0))),
+                "eth_call" => Ok(json!(format!("0x{:064x}", 2_500_500_000u64))),
+                _ => Err(RpcFailure::method_not_found(method)),
+            }
+        });
+        let dir =
+            std::env::temp_dir().join(format!("walletb-server-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("prices.csv"),
+            "symbol,chain,contract,currency,price
+ETH,ethereum,,USD,3433.60
+USDC,ethereum,0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48,USD,0.9999
+",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("walletb.toml"),
+            format!(
+                "prices = \"prices.csv\"
+
+[sources.eth]
+kind = \"ethereum\"
+url = \"{}\"
+tokens = [{{ contract = \"0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48\", symbol = \"USDC\", decimals = 6 }}]
+
+[[wallets]]
+id = \"hot\"
+name = \"Hot wallet\"
+source = \"eth\"
+addresses = [\"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\"]
+
+[server]
+listen = \"127.0.0.1:0\"
+
+[[server.keys]]
+name = \"billing\"
+key = \"{BILLING}\"
+requests_per_minute = 2
+
+[[server.keys]]
+name = \"risk\"
+key = \"{RISK}\"
+",
+                node.url()
+            ),
+        )
+        .unwrap();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_walletb-server"))
+            .arg("--config")
+            .arg(dir.join("walletb.toml"))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let url = line
+            .trim()
+            .strip_prefix("listening on ")
+            .unwrap_or_else(|| panic!("unexpected first line {line:?}"))
+            .to_owned();
+        Setup {
+            dir,
+            url,
+            child,
+            _node: node,
+        }
+    }
+
+    /// GETs `path` with `key` as a bearer token, returning the status and
+    /// the response.
+    fn get(&self, path: &str, key: Option<&str>) -> (u16, ureq::Response) {
+        let mut request = ureq::get(&format!("{}{path}", self.url));
+        if let Some(key) = key {
+            request = request.set("Authorization", &format!("Bearer {key}"));
+        }
+        match request.call() {
+            Ok(response) => (response.status(), response),
+            Err(ureq::Error::Status(status, response)) => (status, response),
+            Err(e) => panic!("{path}: {e}"),
+        }
+    }
+
+    /// GETs `path` with the risk key, expecting `status`, and returns the
+    /// body.
+    fn json(&self, path: &str, status: u16) -> Value {
+        let (got, response) = self.get(path, Some(RISK));
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(got, status, "{path}: {body}");
+        body
+    }
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn serves_wallet_balances_to_known_keys() {
+    let setup = Setup::new("balance");
+
+    let (status, response) = setup.get("/wallets/hot/balance", None);
+    assert_eq!(status, 401);
+    assert_eq!(response.header("WWW-Authenticate"), Some("Bearer"));
+    let (status, _) = setup.get("/wallets/hot/balance", Some("risk-0123456789abd"));
+    assert_eq!(status, 401);
+
+    assert_eq!(
+        setup.json("/wallets", 200),
+        json!([{ "id": "hot", "name": "Hot wallet", "source": "eth" }])
+    );
+    let hot = setup.json("/wallets/hot/balance", 200);
+    assert_eq!(hot["id"], "hot");
+    assert_eq!(hot["balances"][0]["asset"]["symbol"], "ETH");
+    assert_eq!(hot["balances"][0]["confirmed"], "1500000000000000000");
+    assert_eq!(hot["balances"][0]["category"], "available");
+    assert_eq!(hot["balances"][1]["confirmed"], "2500500000");
+
+    // The key may also come in its own header.
+    let response = ureq::get(&format!("{}/wallets/hot/balance", setup.url))
+        .set("X-Api-Key", RISK)
+        .call()
+        .unwrap();
+    assert_eq!(response.header("X-RateLimit-Limit"), Some("60"));
+
+    let missing = setup.json("/wallets/cold/balance", 404);
+    assert_eq!(missing["error"], "no wallet `cold` in the config");
+    setup.json("/balances", 404);
+}
+
+#[test]
+fn values_the_portfolio_and_reads_history() {
+    let setup = Setup::new("portfolio");
+
+    let report = setup.json("/portfolio", 200);
+    assert_eq!(report["currency"], "USD");
+    assert_eq!(report["total"], "7650.64995");
+    assert_eq!(report["categories"]["available"], "7650.64995");
+
+    // On 2023-12-31 the wallet was still empty.
+    let report = setup.json("/portfolio?at=2023-12-31", 200);
+    assert_eq!(report["total"], "0");
+
+    let history = setup.json("/history?at=height:9&at=height:10&wallet=hot", 200);
+    let rows: Vec<(&str, &str)> = history
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            (
+                entry["at"].as_str().unwrap(),
+                entry["balances"][0]["confirmed"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [("height:9", "0"), ("height:10", "1500000000000000000")]
+    );
+
+    // Dates and timestamps may come percent-encoded.
+    let at_time = setup.json("/history?at=2024-01-10T00%3A00%3A00Z", 200);
+    assert_eq!(at_time[0]["at"], "2024-01-10T00:00:00Z");
+    assert_eq!(
+        at_time[0]["balances"][0]["confirmed"],
+        "1500000000000000000"
+    );
+
+    assert_eq!(setup.json("/history", 400)["error"], "`at` is required");
+    setup.json("/history?at=yesterday", 400);
+}
+
+#[test]
+fn limits_each_key_to_its_rate() {
+    let setup = Setup::new("limits");
+    for remaining in ["1", "0"] {
+        let (status, response) = setup.get("/wallets", Some(BILLING));
+        assert_eq!(status, 200);
+        assert_eq!(response.header("X-RateLimit-Remaining"), Some(remaining));
+    }
+    let (status, response) = setup.get("/wallets", Some(BILLING));
+    assert_eq!(status, 429);
+    assert_eq!(response.header("Retry-After"), Some("30"));
+    let body: Value = response.into_json().unwrap();
+    assert_eq!(
+        body["error"],
+        "key `billing` is limited to 2 requests per minute"
+    );
+
+    // Other keys, and the unauthenticated routes, are unaffected.
+    setup.json("/wallets", 200);
+    let (status, response) = setup.get("/openapi.json", None);
+    assert_eq!(status, 200);
+    let doc: Value = response.into_json().unwrap();
+    assert!(doc["paths"]["/wallets/{id}/balance"]["get"].is_object());
+}
+
+#[test]
+fn prints_the_openapi_description_and_needs_keys_to_serve() {
+    let output = Command::new(env!("CARGO_BIN_EXE_walletb-server"))
+        .arg("openapi")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let doc: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(doc["openapi"], "3.0.3");
+    let paths: Vec<&String> = doc["paths"].as_object().unwrap().keys().collect();
+    assert_eq!(
+        paths,
+        [
+            "/history",
+            "/portfolio",
+            "/wallets",
+            "/wallets/{id}/balance"
+        ]
+    );
+
+    let dir = std::env::temp_dir().join(format!("walletb-server-{}-nokeys", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("walletb.toml"),
+        "[server]\nlisten = \"127.0.0.1:0\"\n",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_walletb-server"))
+        .arg("--config")
+        .arg(dir.join("walletb.toml"))
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no API keys configured"));
+}
@@ -0,0 +1,578 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use walletb_core::rpc::RpcCall;
+use walletb_core::{Category, JsonRpcClient};
+
+use crate::{Error, Pubkey, Result, STAKE_PROGRAM, TOKEN_2022_PROGRAM, TOKEN_PROGRAM};
+
+/// How final the state a read sees must be.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Commitment {
+    /// Voted on by a supermajority; may still, rarely, be rolled back.
+    Confirmed,
+    /// Rooted by a supermajority and never rolled back.
+    #[default]
+    Finalized,
+}
+
+impl Commitment {
+    fn as_str(self) -> &'static str {
+        match self {
+            Commitment::Confirmed => "confirmed",
+            Commitment::Finalized => "finalized",
+        }
+    }
+}
+
+/// An SPL token account, of the original Token program or of Token-2022.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenAccount {
+    pub address: Pubkey,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    /// The program the mint belongs to.
+    pub program: Pubkey,
+    /// Base units of the mint.
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+/// A stake delegated to a validator's vote account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delegation {
+    pub voter: Pubkey,
+    /// Lamports delegated, excluding the rent-exempt reserve.
+    pub stake: u64,
+    pub activation_epoch: u64,
+    /// The epoch deactivation was requested in, if it was.
+    pub deactivation_epoch: Option<u64>,
+}
+
+/// A stake account whose withdraw authority was looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StakeAccount {
+    pub address: Pubkey,
+    pub withdrawer: Pubkey,
+    /// Everything in the account: stake, rewards, the rent-exempt reserve
+    /// and any undelegated lamports.
+    pub lamports: u64,
+    pub rent_exempt_reserve: u64,
+    /// `None` for an account that was never delegated.
+    pub delegation: Option<Delegation>,
+    /// The epoch and Unix time until which withdrawals are locked; zero
+    /// for no lockup.
+    pub lockup_epoch: u64,
+    pub lockup_unix_timestamp: i64,
+}
+
+impl StakeAccount {
+    /// Where the account's lamports stand at Unix time `now`: locked while
+    /// a dated lockup lasts, then staked while delegated, pending
+    /// withdrawal once deactivation was requested, and available to
+    /// withdraw if never delegated.
+    ///
+    /// Lockups by epoch are not judged, as that needs the current epoch;
+    /// they are rare outside of genesis allocations.
+    pub fn category(&self, now: i64) -> Category {
+        if self.lockup_unix_timestamp > now {
+            return Category::Locked;
+        }
+        match &self.delegation {
+            Some(delegation) if delegation.deactivation_epoch.is_none() => Category::Staked,
+            Some(_) => Category::PendingWithdrawal,
+            None => Category::Available,
+        }
+    }
+}
+
+/// What one owner holds, as read in a [`SolanaClient::accounts`] batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerAccounts {
+    pub owner: Pubkey,
+    /// The owner's own lamports.
+    pub lamports: u64,
+    pub token_accounts: Vec<TokenAccount>,
+    /// Stake accounts the owner can withdraw from.
+    pub stake_accounts: Vec<StakeAccount>,
+}
+
+/// A transaction signature an address appears in, as listed by
+/// `getSignaturesForAddress`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureInfo {
+    pub signature: String,
+    pub slot: u64,
+    /// Unix time the block was produced, if the node knows it.
+    pub block_time: Option<i64>,
+}
+
+/// A token account's holding before or after a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBalance {
+    /// Index into the transaction's accounts.
+    pub account_index: usize,
+    pub mint: Pubkey,
+    pub owner: Option<Pubkey>,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+/// A confirmed transaction, reduced to what it did to balances.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfirmedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    /// Lamports paid by the fee payer, the first account.
+    pub fee: u64,
+    /// Whether the transaction failed, changing nothing but the fee payer's
+    /// balance.
+    pub failed: bool,
+    /// Every account the transaction loaded, those from address lookup
+    /// tables last, in the order the balances refer to.
+    pub accounts: Vec<Pubkey>,
+    pub pre_balances: Vec<u64>,
+    pub post_balances: Vec<u64>,
+    pub pre_token_balances: Vec<TokenBalance>,
+    pub post_token_balances: Vec<TokenBalance>,
+}
+
+/// A Solana JSON-RPC client.
+#[derive(Debug)]
+pub struct SolanaClient {
+    rpc: JsonRpcClient,
+    commitment: Commitment,
+}
+
+impl SolanaClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        SolanaClient {
+            rpc: JsonRpcClient::new(url),
+            commitment: Commitment::default(),
+        }
+    }
+
+    /// Reads at `commitment` instead of [`Commitment::Finalized`].
+    pub fn with_commitment(mut self, commitment: Commitment) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        self.rpc.url()
+    }
+
+    pub fn slot(&self) -> Result<u64> {
+        Ok(self.rpc.call(
+            "getSlot",
+            json!([{ "commitment": self.commitment.as_str() }]),
+        )?)
+    }
+
+    pub fn balance(&self, owner: &Pubkey) -> Result<u64> {
+        let value = self.rpc.call("getBalance", self.balance_params(owner))?;
+        Ok(parse::<WithContext<u64>>("getBalance", value)?.value)
+    }
+
+    /// The owner's token accounts under `program`, the Token or Token-2022
+    /// program.
+    pub fn token_accounts(&self, owner: &Pubkey, program: &Pubkey) -> Result<Vec<TokenAccount>> {
+        let method = "getTokenAccountsByOwner";
+        let value = self
+            .rpc
+            .call(method, self.token_accounts_params(owner, program))?;
+        token_accounts(value, program)
+    }
+
+    /// The stake accounts `withdrawer` is the withdraw authority of.
+    ///
+    /// The withdraw authority, not the stake authority, is who the funds
+    /// belong to: a staking service may hold the latter to manage
+    /// delegations.
+    pub fn stake_accounts(&self, withdrawer: &Pubkey) -> Result<Vec<StakeAccount>> {
+        let method = "getProgramAccounts";
+        let value = self
+            .rpc
+            .call(method, self.stake_accounts_params(withdrawer))?;
+        stake_accounts(value, withdrawer)
+    }
+
+    /// Reads the balance, token accounts of both token programs and stake
+    /// accounts of every owner in one batch, and returns them with the
+    /// highest slot any of the reads was served at.
+    pub fn accounts(&self, owners: &[Pubkey]) -> Result<(u64, Vec<OwnerAccounts>)> {
+        let mut calls = Vec::with_capacity(owners.len() * 4);
+        for owner in owners {
+            calls.push(RpcCall::new("getBalance", self.balance_params(owner)));
+            for program in token_programs() {
+                calls.push(RpcCall::new(
+                    "getTokenAccountsByOwner",
+                    self.token_accounts_params(owner, &program),
+                ));
+            }
+            calls.push(RpcCall::new(
+                "getProgramAccounts",
+                self.stake_accounts_params(owner),
+            ));
+        }
+        let mut results = self.rpc.batch(&calls)?.into_iter();
+        let mut next = || results.next().expect("one result per call");
+        let mut slot = 0;
+        let mut read = Vec::with_capacity(owners.len());
+        for owner in owners {
+            let balance = parse::<WithContext<u64>>("getBalance", next()?)?;
+            let mut token_accounts = Vec::new();
+            for program in token_programs() {
+                token_accounts.extend(self::token_accounts(next()?, &program)?);
+            }
+            let stake_accounts = self::stake_accounts(next()?, owner)?;
+            slot = slot.max(balance.context.slot);
+            read.push(OwnerAccounts {
+                owner: *owner,
+                lamports: balance.value,
+                token_accounts,
+                stake_accounts,
+            });
+        }
+        Ok((slot, read))
+    }
+
+    /// Up to `limit` signatures of transactions involving `address`, newest
+    /// first, starting before the signature `before` if given.
+    pub fn signatures(
+        &self,
+        address: &Pubkey,
+        before: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>> {
+        let method = "getSignaturesForAddress";
+        let mut config = json!({ "limit": limit, "commitment": self.commitment.as_str() });
+        if let Some(before) = before {
+            config["before"] = json!(before);
+        }
+        let value = self
+            .rpc
+            .call(method, json!([address.to_string(), config]))?;
+        let listed: Vec<SignatureEntry> = parse(method, value)?;
+        Ok(listed
+            .into_iter()
+            .map(|entry| SignatureInfo {
+                signature: entry.signature,
+                slot: entry.slot,
+                block_time: entry.block_time,
+            })
+            .collect())
+    }
+
+    /// The transactions with `signatures`, in as few HTTP requests as the
+    /// client's batch size allows; `None` for any the node no longer has.
+    pub fn transactions(&self, signatures: &[String]) -> Result<Vec<Option<ConfirmedTransaction>>> {
+        let method = "getTransaction";
+        let calls: Vec<RpcCall> = signatures
+            .iter()
+            .map(|signature| {
+                RpcCall::new(
+                    method,
+                    json!([
+                        signature,
+                        {
+                            "encoding": "json",
+                            "maxSupportedTransactionVersion": 0,
+                            "commitment": self.commitment.as_str(),
+                        },
+                    ]),
+                )
+            })
+            .collect();
+        self.rpc
+            .batch(&calls)?
+            .into_iter()
+            .zip(signatures)
+            .map(|(result, signature)| {
+                let found: Option<TransactionData> = parse(method, result?)?;
+                Ok(found.map(|tx| {
+                    let meta = tx.meta;
+                    let mut accounts = tx.transaction.message.account_keys;
+                    if let Some(loaded) = meta.loaded_addresses {
+                        accounts.extend(loaded.writable);
+                        accounts.extend(loaded.readonly);
+                    }
+                    let token_balances = |balances: Vec<TokenBalanceData>| {
+                        balances
+                            .into_iter()
+                            .filter_map(|b| {
+                                Some(TokenBalance {
+                                    account_index: b.account_index,
+                                    mint: b.mint,
+                                    owner: b.owner,
+                                    amount: b.ui_token_amount.amount.parse().ok()?,
+                                    decimals: b.ui_token_amount.decimals,
+                                })
+                            })
+                            .collect()
+                    };
+                    ConfirmedTransaction {
+                        signature: signature.clone(),
+                        slot: tx.slot,
+                        block_time: tx.block_time,
+                        fee: meta.fee,
+                        failed: !meta.err.is_null(),
+                        accounts,
+                        pre_balances: meta.pre_balances,
+                        post_balances: meta.post_balances,
+                        pre_token_balances: token_balances(meta.pre_token_balances),
+                        post_token_balances: token_balances(meta.post_token_balances),
+                    }
+                }))
+            })
+            .collect()
+    }
+
+    fn balance_params(&self, owner: &Pubkey) -> Value {
+        json!([owner.to_string(), { "commitment": self.commitment.as_str() }])
+    }
+
+    fn token_accounts_params(&self, owner: &Pubkey, program: &Pubkey) -> Value {
+        json!([
+            owner.to_string(),
+            { "programId": program.to_string() },
+            { "encoding": "jsonParsed", "commitment": self.commitment.as_str() },
+        ])
+    }
+
+    fn stake_accounts_params(&self, withdrawer: &Pubkey) -> Value {
+        json!([
+            STAKE_PROGRAM,
+            {
+                "encoding": "jsonParsed",
+                "commitment": self.commitment.as_str(),
+                // The withdraw authority follows the 4-byte state tag, the
+                // 8-byte rent-exempt reserve and the stake authority.
+                "filters": [{ "memcmp": { "offset": 44, "bytes": withdrawer.to_string() } }],
+            },
+        ])
+    }
+}
+
+fn token_programs() -> [Pubkey; 2] {
+    [TOKEN_PROGRAM, TOKEN_2022_PROGRAM].map(|id| id.parse().expect("valid program id"))
+}
+
+fn parse<T: DeserializeOwned>(method: &str, value: Value) -> Result<T> {
+    serde_json::from_value(value).map_err(|e| Error::invalid_response(method, e.to_string()))
+}
+
+fn token_accounts(value: Value, program: &Pubkey) -> Result<Vec<TokenAccount>> {
+    let method = "getTokenAccountsByOwner";
+    let response: WithContext<Vec<KeyedAccount<TokenData>>> = parse(method, value)?;
+    response
+        .value
+        .into_iter()
+        .map(|keyed| {
+            let info = keyed.account.data.parsed.info;
+            Ok(TokenAccount {
+                address: keyed.pubkey,
+                owner: info.owner,
+                mint: info.mint,
+                program: *program,
+                amount: info
+                    .token_amount
+                    .amount
+                    .parse()
+                    .map_err(|_| Error::invalid_response(method, "token amount"))?,
+                decimals: info.token_amount.decimals,
+            })
+        })
+        .collect()
+}
+
+fn stake_accounts(value: Value, withdrawer: &Pubkey) -> Result<Vec<StakeAccount>> {
+    let method = "getProgramAccounts";
+    let accounts: Vec<KeyedAccount<StakeData>> = parse(method, value)?;
+    let number = |s: &str| {
+        s.parse::<u64>()
+            .map_err(|_| Error::invalid_response(method, "stake amount"))
+    };
+    accounts
+        .into_iter()
+        // The node filtered on the withdrawer; check rather than trust the
+        // byte offset.
+        .filter(|keyed| keyed.account.data.parsed.info.meta.authorized.withdrawer == *withdrawer)
+        .map(|keyed| {
+            let info = keyed.account.data.parsed.info;
+            let delegation = match info.stake {
+                Some(stake) => {
+                    let d = stake.delegation;
+                    let deactivation = number(&d.deactivation_epoch)?;
+                    Some(Delegation {
+                        voter: d.voter,
+                        stake: number(&d.stake)?,
+                        activation_epoch: number(&d.activation_epoch)?,
+                        deactivation_epoch: (deactivation != u64::MAX).then_some(deactivation),
+                    })
+                }
+                None => None,
+            };
+            Ok(StakeAccount {
+                address: keyed.pubkey,
+                withdrawer: *withdrawer,
+                lamports: keyed.account.lamports,
+                rent_exempt_reserve: number(&info.meta.rent_exempt_reserve)?,
+                delegation,
+                lockup_epoch: info.meta.lockup.epoch,
+                lockup_unix_timestamp: info.meta.lockup.unix_timestamp,
+            })
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct WithContext<T> {
+    context: Context,
+    value: T,
+}
+
+#[derive(Deserialize)]
+struct Context {
+    slot: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignatureEntry {
+    signature: String,
+    slot: u64,
+    #[serde(default)]
+    block_time: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionData {
+    slot: u64,
+    #[serde(default)]
+    block_time: Option<i64>,
+    transaction: TransactionBody,
+    meta: TransactionMeta,
+}
+
+#[derive(Deserialize)]
+struct TransactionBody {
+    message: Message,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    account_keys: Vec<Pubkey>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionMeta {
+    #[serde(default)]
+    err: Value,
+    fee: u64,
+    pre_balances: Vec<u64>,
+    post_balances: Vec<u64>,
+    #[serde(default)]
+    pre_token_balances: Vec<TokenBalanceData>,
+    #[serde(default)]
+    post_token_balances: Vec<TokenBalanceData>,
+    #[serde(default)]
+    loaded_addresses: Option<LoadedAddresses>,
+}
+
+#[derive(Deserialize)]
+struct LoadedAddresses {
+    writable: Vec<Pubkey>,
+    readonly: Vec<Pubkey>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenBalanceData {
+    account_index: usize,
+    mint: Pubkey,
+    #[serde(default)]
+    owner: Option<Pubkey>,
+    ui_token_amount: TokenAmount,
+}
+
+#[derive(Deserialize)]
+struct KeyedAccount<T> {
+    pubkey: Pubkey,
+    account: Account<T>,
+}
+
+#[derive(Deserialize)]
+struct Account<T> {
+    lamports: u64,
+    data: ParsedData<T>,
+}
+
+#[derive(Deserialize)]
+struct ParsedData<T> {
+    parsed: Parsed<T>,
+}
+
+#[derive(Deserialize)]
+struct Parsed<T> {
+    info: T,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenData {
+    mint: Pubkey,
+    owner: Pubkey,
+    token_amount: TokenAmount,
+}
+
+#[derive(Deserialize)]
+struct TokenAmount {
+    amount: String,
+    decimals: u8,
+}
+
+#[derive(Deserialize)]
+struct StakeData {
+    meta: StakeMeta,
+    #[serde(default)]
+    stake: Option<StakeInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StakeMeta {
+    rent_exempt_reserve: String,
+    authorized: Authorized,
+    lockup: Lockup,
+}
+
+#[derive(Deserialize)]
+struct Authorized {
+    withdrawer: Pubkey,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Lockup {
+    epoch: u64,
+    unix_timestamp: i64,
+}
+
+#[derive(Deserialize)]
+struct StakeInfo {
+    delegation: DelegationInfo,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DelegationInfo {
+    voter: Pubkey,
+    stake: String,
+    activation_epoch: String,
+    deactivation_epoch: String,
+}
@@ -0,0 +1,38 @@
+//! Solana support for walletb.
+//!
+//! [`SolanaSource`] reads SOL and SPL token balances over JSON-RPC: an
+//! address's own lamports, its token accounts under both the Token and
+//! Token-2022 programs, and the stake accounts it can withdraw from, all in
+//! one batched request.
+//!
+//! Solana nodes keep no account history, so there is no
+//! [`HistoricalSource`](walletb_core::HistoricalSource) here. They do list
+//! the transactions an address took part in, which is what
+//! [`TransactionSource`](walletb_core::TransactionSource) reads.
+
+mod client;
+mod error;
+mod pubkey;
+mod source;
+
+pub use client::{
+    Commitment, ConfirmedTransaction, Delegation, OwnerAccounts, SignatureInfo, SolanaClient,
+    StakeAccount, TokenAccount, TokenBalance,
+};
+pub use error::{Error, Result};
+pub use pubkey::Pubkey;
+pub use source::{Snapshot, SolanaSource};
+
+use walletb_core::{Asset, Chain};
+
+/// The SPL Token program.
+pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+/// The Token-2022 program, for mints with extensions.
+pub const TOKEN_2022_PROGRAM: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+/// The native stake program.
+pub const STAKE_PROGRAM: &str = "Stake11111111111111111111111111111111111111";
+
+/// SOL, denominated in lamports.
+pub fn sol() -> Asset {
+    Asset::native(Chain::Solana, "SOL", 9)
+}
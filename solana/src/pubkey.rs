@@ -0,0 +1,128 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{Error, Result};
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// A 32-byte Solana account address: a wallet, a token or stake account,
+/// a mint or a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pubkey([u8; 32]);
+
+impl Pubkey {
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Pubkey(bytes)
+    }
+
+    pub const fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl FromStr for Pubkey {
+    type Err = Error;
+
+    /// Parses the base58 form.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidAddress {
+            address: s.to_owned(),
+            reason: reason.to_owned(),
+        };
+        // Big-endian base-256 digits of the number, least significant last.
+        let mut bytes: Vec<u8> = Vec::with_capacity(32);
+        for c in s.bytes() {
+            let mut carry = ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .ok_or_else(|| invalid("not base58"))? as u32;
+            for byte in bytes.iter_mut().rev() {
+                carry += u32::from(*byte) * 58;
+                *byte = carry as u8;
+                carry >>= 8;
+            }
+            while carry > 0 {
+                bytes.insert(0, carry as u8);
+                carry >>= 8;
+            }
+        }
+        // Each leading '1' is a leading zero byte.
+        let zeros = s.bytes().take_while(|&c| c == b'1').count();
+        let mut decoded = vec![0u8; zeros];
+        decoded.extend(bytes);
+        let bytes: [u8; 32] = decoded
+            .try_into()
+            .map_err(|_| invalid("expected 32 bytes"))?;
+        Ok(Pubkey(bytes))
+    }
+}
+
+/// Displays the base58 form.
+impl fmt::Display for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Base-58 digits of the number, least significant last.
+        let mut digits: Vec<u8> = Vec::with_capacity(44);
+        for &byte in &self.0 {
+            let mut carry = u32::from(byte);
+            for digit in digits.iter_mut().rev() {
+                carry += u32::from(*digit) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            }
+            while carry > 0 {
+                digits.insert(0, (carry % 58) as u8);
+                carry /= 58;
+            }
+        }
+        let zeros = self.0.iter().take_while(|&&b| b == 0).count();
+        let text: String = std::iter::repeat('1')
+            .take(zeros)
+            .chain(digits.iter().map(|&d| char::from(ALPHABET[usize::from(d)])))
+            .collect();
+        f.write_str(&text)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pubkey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_base58() {
+        assert_eq!(
+            "11111111111111111111111111111111"
+                .parse::<Pubkey>()
+                .unwrap(),
+            Pubkey([0; 32])
+        );
+        for key in [
+            "11111111111111111111111111111111",
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            "Stake11111111111111111111111111111111111111",
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+        ] {
+            assert_eq!(key.parse::<Pubkey>().unwrap().to_string(), key);
+        }
+        let mut one = [0; 32];
+        one[31] = 1;
+        assert_eq!(Pubkey(one).to_string(), "11111111111111111111111111111112");
+    }
+
+    #[test]
+    fn rejects_malformed_keys() {
+        assert!("0OIl".parse::<Pubkey>().is_err());
+        assert!("1111".parse::<Pubkey>().is_err());
+        assert!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1vEPjF"
+            .parse::<Pubkey>()
+            .is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use walletb_core::{
+    Address, Amount, Asset, Balance, BalanceSource, Category, Chain, LedgerEntry, Period,
+    TransactionSource,
+};
+
+use crate::{sol, ConfirmedTransaction, OwnerAccounts, Pubkey, Result, SolanaClient};
+
+/// Signatures asked for per `getSignaturesForAddress` page, the most a
+/// node returns.
+const SIGNATURE_PAGE: usize = 1_000;
+
+/// Transactions fetched per round of a ledger read, so a long history is
+/// never requested or held whole.
+const TRANSACTION_CHUNK: usize = 100;
+
+/// Well-known mints, named without configuration.
+const KNOWN_MINTS: [(&str, &str, u8); 4] = [
+    ("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "USDC", 6),
+    ("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB", "USDT", 6),
+    ("mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So", "mSOL", 9),
+    ("J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYac6Y7kGCPn", "JitoSOL", 9),
+];
+
+/// Everything a set of owners held at one slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub slot: u64,
+    pub owners: Vec<OwnerAccounts>,
+}
+
+/// A [`BalanceSource`] for SOL and every SPL token an address holds.
+///
+/// Each read fetches, for every address, its own lamports, its token
+/// accounts under both the Token and Token-2022 programs, and the stake
+/// accounts it is the withdraw authority of, in one batch. SOL in stake
+/// accounts is reported apart from the owner's own, by the
+/// [`category`](crate::StakeAccount::category) of each account.
+///
+/// Token accounts are found by owner, so no token list is needed; the
+/// listed ones only give mints a symbol. Other mints are shown by an
+/// abbreviation of their address.
+#[derive(Debug)]
+pub struct SolanaSource {
+    client: SolanaClient,
+    /// Named mints, by address.
+    tokens: HashMap<String, Asset>,
+}
+
+impl SolanaSource {
+    pub fn new(client: SolanaClient) -> Self {
+        let source = SolanaSource {
+            client,
+            tokens: HashMap::new(),
+        };
+        KNOWN_MINTS
+            .iter()
+            .fold(source, |source, (mint, symbol, decimals)| {
+                source.with_token(Asset::token(Chain::Solana, *mint, *symbol, *decimals))
+            })
+    }
+
+    /// Names a mint; the asset's contract is the mint address.
+    pub fn with_token(mut self, token: Asset) -> Self {
+        if let Some(mint) = token.contract() {
+            self.tokens.insert(mint.to_owned(), token);
+        }
+        self
+    }
+
+    pub fn client(&self) -> &SolanaClient {
+        &self.client
+    }
+
+    pub fn snapshot(&self, addresses: &[Address]) -> Result<Snapshot> {
+        let owners = addresses
+            .iter()
+            .map(|a| {
+                a.expect_chain(Chain::Solana)?;
+                a.as_str().parse()
+            })
+            .collect::<Result<Vec<Pubkey>>>()?;
+        let (slot, owners) = self.client.accounts(&owners)?;
+        Ok(Snapshot { slot, owners })
+    }
+
+    /// Sums a snapshot per asset and category: available SOL first, then
+    /// tokens with a non-zero total, then SOL in stake accounts by
+    /// category.
+    pub fn totals(&self, snapshot: &Snapshot) -> Vec<Balance> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        let mut lamports: BTreeMap<Category, Amount> = BTreeMap::new();
+        let mut tokens: BTreeMap<Asset, Amount> = BTreeMap::new();
+        for owner in &snapshot.owners {
+            let available = lamports.entry(Category::Available).or_default();
+            *available = *available + Amount::from(owner.lamports);
+            for stake in &owner.stake_accounts {
+                let total = lamports.entry(stake.category(now)).or_default();
+                *total = *total + Amount::from(stake.lamports);
+            }
+            for account in &owner.token_accounts {
+                let asset = self.asset(&account.mint, account.decimals);
+                let total = tokens.entry(asset).or_default();
+                *total = *total + Amount::from(account.amount);
+            }
+        }
+        let available = lamports.remove(&Category::Available).unwrap_or_default();
+        std::iter::once(Balance::new(sol(), available))
+            .chain(
+                tokens
+                    .into_iter()
+                    .filter(|(_, amount)| !amount.is_zero())
+                    .map(|(asset, amount)| Balance::new(asset, amount)),
+            )
+            .chain(
+                lamports
+                    .into_iter()
+                    .map(|(category, amount)| Balance::new(sol(), amount).with_category(category)),
+            )
+            .collect()
+    }
+
+    /// The ledger of `addresses` over `period`, where heights are slots.
+    ///
+    /// A token payment only touches the recipient's token account, not its
+    /// owner, so transactions are listed for the owners and for the token
+    /// accounts they hold now; payments into accounts since closed are
+    /// missed. How far back a node can list depends on the ledger history
+    /// it keeps.
+    pub fn ledger(&self, addresses: &[Address], period: Period) -> Result<Vec<LedgerEntry>> {
+        let snapshot = self.snapshot(addresses)?;
+        let owners: HashSet<Pubkey> = snapshot.owners.iter().map(|o| o.owner).collect();
+        let mut listed: BTreeSet<(u64, String)> = BTreeSet::new();
+        let watched = snapshot.owners.iter().flat_map(|o| {
+            std::iter::once(o.owner).chain(o.token_accounts.iter().map(|a| a.address))
+        });
+        for address in watched {
+            let mut before: Option<String> = None;
+            'pages: loop {
+                let page = self
+                    .client
+                    .signatures(&address, before.as_deref(), SIGNATURE_PAGE)?;
+                for info in &page {
+                    let time = info.block_time.map_or(0, |t| t.max(0) as u64);
+                    if period
+                        .after
+                        .is_some_and(|after| after.includes(info.slot, time))
+                    {
+                        break 'pages;
+                    }
+                    if period.contains(info.slot, time) {
+                        listed.insert((info.slot, info.signature.clone()));
+                    }
+                }
+                match page.last() {
+                    Some(last) if page.len() == SIGNATURE_PAGE => {
+                        before = Some(last.signature.clone());
+                    }
+                    _ => break,
+                }
+            }
+        }
+        let signatures: Vec<String> = listed.into_iter().map(|(_, s)| s).collect();
+        let mut entries = Vec::new();
+        for chunk in signatures.chunks(TRANSACTION_CHUNK) {
+            for tx in self.client.transactions(chunk)?.into_iter().flatten() {
+                entries.extend(self.entries(&tx, &owners));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// What `tx` moved in and out of `owners`: SOL first, fee included,
+    /// then each token.
+    fn entries(&self, tx: &ConfirmedTransaction, owners: &HashSet<Pubkey>) -> Vec<LedgerEntry> {
+        let entry =
+            |asset: Asset, change: i128, fee: u64, counterparty: Option<Pubkey>| LedgerEntry {
+                txid: tx.signature.clone(),
+                height: tx.slot,
+                time: tx.block_time.map_or(0, |t| t.max(0) as u64),
+                asset,
+                received: Amount::from_u128(change.max(0) as u128),
+                sent: Amount::from_u128(change.min(0).unsigned_abs()),
+                fee: Amount::from_u64(fee),
+                counterparty: counterparty.map(|key| key.to_string()),
+            };
+        let mut entries = Vec::new();
+
+        // Lamport changes per account, with the fee given back to its payer
+        // so that what is left is what moved.
+        let fee = match tx.accounts.first() {
+            Some(payer) if owners.contains(payer) => tx.fee,
+            _ => 0,
+        };
+        let changes: Vec<(Pubkey, i128)> = tx
+            .accounts
+            .iter()
+            .zip(tx.pre_balances.iter().zip(&tx.post_balances))
+            .enumerate()
+            .map(|(i, (key, (pre, post)))| {
+                let paid = if i == 0 { i128::from(tx.fee) } else { 0 };
+                (*key, i128::from(*post) - i128::from(*pre) + paid)
+            })
+            .collect();
+        let moved: i128 = changes
+            .iter()
+            .filter(|(key, _)| owners.contains(key))
+            .map(|(_, change)| change)
+            .sum();
+        if moved != 0 || fee != 0 {
+            entries.push(entry(
+                sol(),
+                moved,
+                fee,
+                counterparty(&changes, owners, moved),
+            ));
+        }
+
+        // Token changes per mint, of accounts the owners own, and per other
+        // owner for the counterparty.
+        let mut tokens: BTreeMap<Pubkey, (u8, Vec<(Pubkey, i128)>)> = BTreeMap::new();
+        for (balances, sign) in [(&tx.pre_token_balances, -1), (&tx.post_token_balances, 1)] {
+            for balance in balances {
+                let holder = balance
+                    .owner
+                    .or_else(|| tx.accounts.get(balance.account_index).copied());
+                let Some(holder) = holder else { continue };
+                let (_, changes) = tokens
+                    .entry(balance.mint)
+                    .or_insert((balance.decimals, Vec::new()));
+                changes.push((holder, sign * i128::from(balance.amount)));
+            }
+        }
+        for (mint, (decimals, changes)) in tokens {
+            let mut by_holder: BTreeMap<Pubkey, i128> = BTreeMap::new();
+            for (holder, change) in changes {
+                *by_holder.entry(holder).or_default() += change;
+            }
+            let changes: Vec<(Pubkey, i128)> = by_holder.into_iter().collect();
+            let moved: i128 = changes
+                .iter()
+                .filter(|(key, _)| owners.contains(key))
+                .map(|(_, change)| change)
+                .sum();
+            if moved != 0 {
+                let asset = self.asset(&mint, decimals);
+                entries.push(entry(
+                    asset,
+                    moved,
+                    0,
+                    counterparty(&changes, owners, moved),
+                ));
+            }
+        }
+        entries
+    }
+
+    /// The asset a mint's tokens are counted as.
+    fn asset(&self, mint: &Pubkey, decimals: u8) -> Asset {
+        let mint = mint.to_string();
+        match self.tokens.get(&mint) {
+            Some(asset) => asset.clone(),
+            None => {
+                let symbol = format!("{}…{}", &mint[..4], &mint[mint.len() - 4..]);
+                Asset::token(Chain::Solana, mint, symbol, decimals)
+            }
+        }
+    }
+}
+
+/// The account, not one of `owners`, whose balance moved the most the
+/// other way from the owners' `moved`.
+fn counterparty(
+    changes: &[(Pubkey, i128)],
+    owners: &HashSet<Pubkey>,
+    moved: i128,
+) -> Option<Pubkey> {
+    changes
+        .iter()
+        .filter(|(key, change)| !owners.contains(key) && change.signum() == -moved.signum())
+        .max_by_key(|(_, change)| change.abs())
+        .map(|(key, _)| *key)
+        .filter(|_| moved != 0)
+}
+
+impl BalanceSource for SolanaSource {
+    fn chain(&self) -> Chain {
+        Chain::Solana
+    }
+
+    fn balances(&self, addresses: &[Address]) -> walletb_core::Result<Vec<Balance>> {
+        Ok(self.totals(&self.snapshot(addresses)?))
+    }
+}
+
+impl TransactionSource for SolanaSource {
+    fn transactions(
+        &self,
+        addresses: &[Address],
+        period: Period,
+    ) -> walletb_core::Result<Vec<LedgerEntry>> {
+        Ok(self.ledger(addresses, period)?)
+    }
+}
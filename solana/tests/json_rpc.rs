@@ -0,0 +1,214 @@
+use serde_json::{json, Value};
+use walletb_core::testing::{MockServer, RpcFailure};
+use walletb_core::{Address, Asset, BalanceSource, Category, Chain};
+use walletb_solana::{
+    Pubkey, SolanaClient, SolanaSource, STAKE_PROGRAM, TOKEN_2022_PROGRAM, TOKEN_PROGRAM,
+};
+
+const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+fn key(n: u8) -> String {
+    Pubkey::from_bytes([n; 32]).to_string()
+}
+
+fn alice() -> String {
+    key(1)
+}
+
+fn bob() -> String {
+    key(2)
+}
+
+/// A Token-2022 mint that nothing names.
+fn pyusd() -> String {
+    key(9)
+}
+
+fn token_account(address: u8, owner: &str, mint: &str, amount: u64, decimals: u8) -> Value {
+    json!({
+        "pubkey": key(address),
+        "account": {
+            "lamports": 2_039_280,
+            "owner": TOKEN_PROGRAM,
+            "data": {
+                "program": "spl-token",
+                "parsed": {
+                    "type": "account",
+                    "info": {
+                        "mint": mint,
+                        "owner": owner,
+                        "state": "initialized",
+                        "tokenAmount": {
+                            "amount": amount.to_string(),
+                            "decimals": decimals,
+                            "uiAmountString": "",
+                        },
+                    },
+                },
+                "space": 165,
+            },
+        },
+    })
+}
+
+fn stake_account(address: u8, withdrawer: &str, lamports: u64, deactivation: u64) -> Value {
+    json!({
+        "pubkey": key(address),
+        "account": {
+            "lamports": lamports,
+            "owner": STAKE_PROGRAM,
+            "data": {
+                "program": "stake",
+                "parsed": {
+                    "type": "delegated",
+                    "info": {
+                        "meta": {
+                            "rentExemptReserve": "2282880",
+                            "authorized": { "staker": key(7), "withdrawer": withdrawer },
+                            "lockup": { "custodian": key(0), "epoch": 0, "unixTimestamp": 0 },
+                        },
+                        "stake": {
+                            "delegation": {
+                                "voter": key(8),
+                                "stake": (lamports - 2_282_880).to_string(),
+                                "activationEpoch": "600",
+                                "deactivationEpoch": deactivation.to_string(),
+                                "warmupCooldownRate": 0.25,
+                            },
+                            "creditsObserved": 1234,
+                        },
+                    },
+                },
+                "space": 200,
+            },
+        },
+    })
+}
+
+/// A node at slot 300 where Alice holds 1.5 SOL, two USDC accounts, an
+/// empty and a funded account of an unnamed Token-2022 mint and two stake
+/// accounts, one of which is deactivating; Bob holds 0.25 SOL and 5 USDC.
+/// A stake account Alice only has stake authority over is answered too,
+/// as a careless node might.
+fn node() -> MockServer {
+    MockServer::json_rpc(|method, params| {
+        let owner = params[0].as_str().unwrap();
+        let context = |value: Value| json!({ "context": { "slot": 300 }, "value": value });
+        match method {
+            "getBalance" if owner == alice() => Ok(context(json!(1_500_000_000u64))),
+            "getBalance" => Ok(context(json!(250_000_000u64))),
+            "getTokenAccountsByOwner" => {
+                let program = params[1]["programId"].as_str().unwrap();
+                assert_eq!(params[2]["encoding"], "jsonParsed");
+                Ok(context(match (owner == alice(), program) {
+                    (true, TOKEN_PROGRAM) => json!([
+                        token_account(10, owner, USDC, 1_000_000_000, 6),
+                        token_account(11, owner, USDC, 500_000, 6),
+                    ]),
+                    (true, TOKEN_2022_PROGRAM) => json!([
+                        token_account(12, owner, &pyusd(), 0, 6),
+                        token_account(13, owner, &pyusd(), 7_000_000, 6),
+                    ]),
+                    (false, TOKEN_PROGRAM) => json!([token_account(14, owner, USDC, 5_000_000, 6)]),
+                    _ => json!([]),
+                }))
+            }
+            "getProgramAccounts" => {
+                assert_eq!(owner, STAKE_PROGRAM);
+                let withdrawer = params[1]["filters"][0]["memcmp"]["bytes"].as_str().unwrap();
+                if withdrawer != alice() {
+                    return Ok(json!([]));
+                }
+                Ok(json!([
+                    stake_account(20, withdrawer, 10_002_282_880, u64::MAX),
+                    stake_account(21, withdrawer, 2_002_282_880, 610),
+                    stake_account(22, &bob(), 99_000_000_000, u64::MAX),
+                ]))
+            }
+            _ => Err(RpcFailure::method_not_found(method)),
+        }
+    })
+}
+
+#[test]
+fn reads_sol_tokens_and_stake() {
+    let server = node();
+    let source = SolanaSource::new(SolanaClient::new(server.url()));
+    let balances = source
+        .balances(&[Address::new(Chain::Solana, alice())])
+        .unwrap();
+    let rows: Vec<String> = balances.iter().map(|b| b.to_string()).collect();
+    // 1.5 SOL on hand, and 10.00228288 SOL delegated and 2.00228288 SOL
+    // deactivating in stake accounts.
+    assert_eq!(
+        rows,
+        [
+            "1.5 SOL",
+            "1000.5 USDC",
+            &format!("7 {}…{}", &pyusd()[..4], &pyusd()[pyusd().len() - 4..]),
+            "10.00228288 SOL staked",
+            "2.00228288 SOL pending_withdrawal",
+        ]
+    );
+    assert_eq!(
+        balances[2].asset,
+        Asset::token(Chain::Solana, pyusd(), balances[2].asset.symbol.clone(), 6)
+    );
+
+    // Four reads, sent as one batch.
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].json().as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn keeps_stake_accounts_apart() {
+    let server = node();
+    let source = SolanaSource::new(SolanaClient::new(server.url()));
+    let snapshot = source
+        .snapshot(&[
+            Address::new(Chain::Solana, alice()),
+            Address::new(Chain::Solana, bob()),
+        ])
+        .unwrap();
+    assert_eq!(snapshot.slot, 300);
+    let stakes = &snapshot.owners[0].stake_accounts;
+    assert_eq!(stakes.len(), 2);
+    let delegation = stakes[0].delegation.as_ref().unwrap();
+    assert_eq!(delegation.stake, 10_000_000_000);
+    assert_eq!(delegation.deactivation_epoch, None);
+    assert_eq!(
+        stakes[1].delegation.as_ref().unwrap().deactivation_epoch,
+        Some(610)
+    );
+    assert!(snapshot.owners[1].stake_accounts.is_empty());
+
+    assert_eq!(stakes[0].category(0), Category::Staked);
+    assert_eq!(stakes[1].category(0), Category::PendingWithdrawal);
+    let mut locked = stakes[0].clone();
+    locked.lockup_unix_timestamp = 2_000_000_000;
+    assert_eq!(locked.category(1_800_000_000), Category::Locked);
+    assert_eq!(locked.category(2_000_000_000), Category::Staked);
+    locked.delegation = None;
+    assert_eq!(locked.category(2_000_000_000), Category::Available);
+
+    let totals = source.totals(&snapshot);
+    assert_eq!(totals[0].to_string(), "1.75 SOL");
+    assert_eq!(totals[1].to_string(), "1005.5 USDC");
+}
+
+#[test]
+fn rejects_foreign_addresses() {
+    let server = node();
+    let source = SolanaSource::new(SolanaClient::new(server.url()));
+    assert!(source
+        .balances(&[Address::new(Chain::ETHEREUM, alice())])
+        .is_err());
+    assert!(source
+        .balances(&[Address::new(
+            Chain::Solana,
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        )])
+        .is_err());
+    assert!(server.requests().is_empty());
+}
@@ -0,0 +1,188 @@
+use serde_json::{json, Value};
+use walletb_core::testing::{MockServer, RpcFailure};
+use walletb_core::{Address, Chain, Period, PointInTime, TransactionSource};
+use walletb_solana::{Pubkey, SolanaClient, SolanaSource, TOKEN_PROGRAM};
+
+const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+const GENESIS: i64 = 1_704_067_200;
+const SOL: u64 = 1_000_000_000;
+
+fn key(n: u8) -> String {
+    Pubkey::from_bytes([n; 32]).to_string()
+}
+
+fn alice() -> String {
+    key(1)
+}
+
+fn bob() -> String {
+    key(2)
+}
+
+fn token_balance(index: usize, owner: &str, units: u64) -> Value {
+    json!({
+        "accountIndex": index,
+        "mint": USDC,
+        "owner": owner,
+        "programId": TOKEN_PROGRAM,
+        "uiTokenAmount": { "amount": units.to_string(), "decimals": 6, "uiAmountString": "" },
+    })
+}
+
+/// Carol pays Alice 0.5 SOL in slot 50 and Bob pays her 1 SOL in slot
+/// 100; Alice pays Bob 10 USDC from her token account in slot 200. Blocks
+/// are a second apart.
+fn node() -> MockServer {
+    MockServer::json_rpc(|method, params| {
+        let context = |value: Value| json!({ "context": { "slot": 300 }, "value": value });
+        match method {
+            "getBalance" => Ok(context(json!(2 * SOL))),
+            "getProgramAccounts" => Ok(json!([])),
+            "getTokenAccountsByOwner" => {
+                let owner = params[0].as_str().unwrap();
+                if owner != alice() || params[1]["programId"] != TOKEN_PROGRAM {
+                    return Ok(context(json!([])));
+                }
+                Ok(context(json!([{
+                    "pubkey": key(10),
+                    "account": {
+                        "lamports": 2_039_280,
+                        "owner": TOKEN_PROGRAM,
+                        "data": {
+                            "program": "spl-token",
+                            "parsed": {
+                                "type": "account",
+                                "info": {
+                                    "mint": USDC,
+                                    "owner": owner,
+                                    "state": "initialized",
+                                    "tokenAmount": {
+                                        "amount": "990000000",
+                                        "decimals": 6,
+                                        "uiAmountString": "",
+                                    },
+                                },
+                            },
+                            "space": 165,
+                        },
+                    },
+                }])))
+            }
+            "getSignaturesForAddress" => {
+                let address = params[0].as_str().unwrap();
+                let listed = |signature: &str, slot: i64| json!({ "signature": signature, "slot": slot, "blockTime": GENESIS + slot, "err": null });
+                Ok(if address == alice() {
+                    json!([
+                        listed("pay-usdc", 200),
+                        listed("get-sol", 100),
+                        listed("old", 50)
+                    ])
+                } else if address == key(10) {
+                    json!([listed("pay-usdc", 200)])
+                } else {
+                    json!([])
+                })
+            }
+            "getTransaction" => {
+                let signature = params[0].as_str().unwrap();
+                let (slot, accounts, pre, post, pre_tokens, post_tokens) = match signature {
+                    "old" => (
+                        50,
+                        vec![key(3), alice()],
+                        vec![SOL, SOL / 2],
+                        vec![SOL / 2 - 5_000, SOL],
+                        json!([]),
+                        json!([]),
+                    ),
+                    "get-sol" => (
+                        100,
+                        vec![bob(), alice(), key(0)],
+                        vec![5 * SOL, SOL, 1],
+                        vec![4 * SOL - 5_000, 2 * SOL, 1],
+                        json!([]),
+                        json!([]),
+                    ),
+                    "pay-usdc" => (
+                        200,
+                        vec![alice(), key(10), key(14), TOKEN_PROGRAM.to_owned()],
+                        vec![2 * SOL, 2_039_280, 2_039_280, 1],
+                        vec![2 * SOL - 5_000, 2_039_280, 2_039_280, 1],
+                        json!([
+                            token_balance(1, &alice(), 1_000_000_000),
+                            token_balance(2, &bob(), 0)
+                        ]),
+                        json!([
+                            token_balance(1, &alice(), 990_000_000),
+                            token_balance(2, &bob(), 10_000_000)
+                        ]),
+                    ),
+                    _ => return Err(RpcFailure::method_not_found(method)),
+                };
+                Ok(json!({
+                    "slot": slot,
+                    "blockTime": GENESIS + slot,
+                    "transaction": { "message": { "accountKeys": accounts } },
+                    "meta": {
+                        "err": null,
+                        "fee": 5_000,
+                        "preBalances": pre,
+                        "postBalances": post,
+                        "preTokenBalances": pre_tokens,
+                        "postTokenBalances": post_tokens,
+                    },
+                }))
+            }
+            _ => Err(RpcFailure::method_not_found(method)),
+        }
+    })
+}
+
+#[test]
+fn lists_sol_and_token_movements_with_fees() {
+    let server = node();
+    let source = SolanaSource::new(SolanaClient::new(server.url()));
+    let alice = [Address::new(Chain::Solana, alice())];
+    let slots = Period {
+        after: Some(PointInTime::Height(60)),
+        until: None,
+    };
+    let entries = source.transactions(&alice, slots).unwrap();
+    let rows: Vec<(&str, &str, String, String, String)> = entries
+        .iter()
+        .map(|e| {
+            let decimals = e.asset.decimals;
+            (
+                e.txid.as_str(),
+                e.asset.symbol.as_str(),
+                e.received.to_decimal_string(decimals),
+                e.sent.to_decimal_string(decimals),
+                e.fee.to_decimal_string(decimals),
+            )
+        })
+        .collect();
+    let zero = || "0".to_owned();
+    assert_eq!(
+        rows,
+        [
+            // Bob paid the fee of his payment.
+            ("get-sol", "SOL", "1".to_owned(), zero(), zero()),
+            // A token payment costs its sender SOL.
+            ("pay-usdc", "SOL", zero(), zero(), "0.000005".to_owned()),
+            ("pay-usdc", "USDC", zero(), "10".to_owned(), zero()),
+        ]
+    );
+    assert_eq!(entries[0].counterparty, Some(bob()));
+    assert_eq!(entries[1].counterparty, None);
+    // The token account's owner, not the account, is who was paid.
+    assert_eq!(entries[2].counterparty, Some(bob()));
+    assert_eq!((entries[2].height, entries[2].time), (200, 1_704_067_400));
+
+    let dates = Period {
+        after: None,
+        until: Some(PointInTime::Time(1_704_067_350)),
+    };
+    let entries = source.transactions(&alice, dates).unwrap();
+    let listed: Vec<&str> = entries.iter().map(|e| e.txid.as_str()).collect();
+    assert_eq!(listed, ["old", "get-sol"]);
+    assert_eq!(entries[0].counterparty, Some(key(3)));
+}
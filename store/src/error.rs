@@ -0,0 +1,34 @@
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("database schema version {found} is newer than this build supports ({supported})")]
+    SchemaTooNew { found: u32, supported: u32 },
+
+    #[error("no wallet `{0}` in the store")]
+    UnknownWallet(String),
+
+    #[error(
+        "wallet `{wallet}`: the chain reorganized below every block kept since height {height}; \
+         reset its sync to start over"
+    )]
+    ReorgTooDeep { wallet: String, height: u64 },
+
+    #[error("corrupt store: {0}")]
+    Corrupt(String),
+
+    #[error(transparent)]
+    Bitcoin(#[from] walletb_bitcoin::Error),
+
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<Error> for walletb_core::Error {
+    fn from(err: Error) -> Self {
+        walletb_core::Error::source(err)
+    }
+}
@@ -0,0 +1,26 @@
+//! Persistent cache for walletb.
+//!
+//! A [`Store`] is an embedded SQLite database holding the configured
+//! wallets, the HD addresses derived for them, the transactions seen, a
+//! history of balance snapshots and how far each wallet has been synced.
+//! With it an xpub scan resumes from the last known used address (see
+//! [`walletb_bitcoin::discover_from`]) instead of from index 0, and
+//! incremental sync resumes from the stored [`SyncPoint`].
+//!
+//! [`Syncer`] is that sync: it follows a
+//! [`BlockSource`](walletb_bitcoin::BlockSource) one block at a time and
+//! rolls back blocks that a reorganization orphaned, identifying them by
+//! hash.
+//!
+//! The schema is versioned through SQLite's `user_version` and upgraded in
+//! place by [`Store::open`].
+
+mod error;
+mod migrations;
+mod store;
+mod sync;
+
+pub use error::{Error, Result};
+pub use migrations::SCHEMA_VERSION;
+pub use store::{BalanceSnapshot, Store, SyncPoint, TxRecord, WalletRecord};
+pub use sync::{SyncReport, Syncer, REORG_WINDOW};
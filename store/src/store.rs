@@ -0,0 +1,319 @@
+use std::path::Path;
+
+use bitcoin::address::NetworkUnchecked;
+use rusqlite::{params, Connection, OptionalExtension};
+use walletb_bitcoin::{DerivedAddress, Discovery, Keychain};
+use walletb_core::Balance;
+
+use crate::migrations::{self, migrate};
+use crate::{Error, Result};
+
+/// A configured wallet as last seen by the store.
+///
+/// `spec` is whatever identifies the wallet's keys (an xpub, descriptor or
+/// address list). When it or the source changes, everything cached for the
+/// wallet is dropped, since it may no longer describe the same funds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletRecord {
+    pub id: String,
+    pub name: String,
+    pub source: String,
+    pub spec: String,
+}
+
+/// A transaction seen for a wallet, confirmed or not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxRecord {
+    pub txid: String,
+    /// `None` while unconfirmed.
+    pub height: Option<u64>,
+    pub block_hash: Option<String>,
+    /// When the transaction was first seen, in seconds since the Unix epoch.
+    pub seen_at: u64,
+}
+
+/// The last block a wallet was synced to. The hash lets a later sync notice
+/// that the block was reorganized away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncPoint {
+    pub height: u64,
+    pub block_hash: String,
+}
+
+/// A wallet's balances as read at one moment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceSnapshot {
+    /// The block the balances were read at, if the source reports one.
+    pub height: Option<u64>,
+    /// Seconds since the Unix epoch.
+    pub taken_at: u64,
+    pub balances: Vec<Balance>,
+}
+
+/// An embedded SQLite database caching what walletb has learned about each
+/// wallet, so that later runs can resume instead of starting over.
+///
+/// The schema is created and upgraded on [`open`](Store::open).
+#[derive(Debug)]
+pub struct Store {
+    pub(crate) conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(Store { conn })
+    }
+
+    pub fn schema_version(&self) -> Result<u32> {
+        migrations::version(&self.conn)
+    }
+
+    /// Records `wallet`, returning `true` if its cache was dropped because
+    /// its source or keys changed.
+    pub fn save_wallet(&self, wallet: &WalletRecord) -> Result<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        let changed = match self.wallet(&wallet.id)? {
+            Some(old) if old.source != wallet.source || old.spec != wallet.spec => {
+                tx.execute("DELETE FROM wallets WHERE id = ?1", [&wallet.id])?;
+                true
+            }
+            _ => false,
+        };
+        tx.execute(
+            "INSERT INTO wallets (id, name, source, spec) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (id) DO UPDATE SET name = excluded.name",
+            params![wallet.id, wallet.name, wallet.source, wallet.spec],
+        )?;
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    pub fn wallet(&self, id: &str) -> Result<Option<WalletRecord>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id, name, source, spec FROM wallets WHERE id = ?1",
+                [id],
+                wallet_record,
+            )
+            .optional()?)
+    }
+
+    pub fn wallets(&self) -> Result<Vec<WalletRecord>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, source, spec FROM wallets ORDER BY id")?;
+        let rows = stmt.query_map([], wallet_record)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Removes a wallet and everything cached for it. Returns whether it
+    /// existed.
+    pub fn remove_wallet(&self, id: &str) -> Result<bool> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM wallets WHERE id = ?1", [id])?
+            > 0)
+    }
+
+    /// Stores the addresses visited by a scan, replacing earlier entries at
+    /// the same positions.
+    pub fn save_addresses(&self, wallet_id: &str, addresses: &[DerivedAddress]) -> Result<()> {
+        self.expect_wallet(wallet_id)?;
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO addresses (wallet_id, keychain, idx, address, used)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for a in addresses {
+                stmt.execute(params![
+                    wallet_id,
+                    a.keychain.index(),
+                    a.index,
+                    a.address.to_string(),
+                    a.used
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The stored addresses of a wallet as a [`Discovery`] that a new scan
+    /// can resume from.
+    pub fn discovery(&self, wallet_id: &str) -> Result<Discovery> {
+        let mut stmt = self.conn.prepare(
+            "SELECT keychain, idx, address, used FROM addresses
+             WHERE wallet_id = ?1 ORDER BY keychain, idx",
+        )?;
+        let rows = stmt.query_map([wallet_id], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, bool>(3)?,
+            ))
+        })?;
+        let mut addresses = Vec::new();
+        for row in rows {
+            let (keychain, index, address, used) = row?;
+            let keychain = match keychain {
+                0 => Keychain::External,
+                1 => Keychain::Internal,
+                other => return Err(Error::Corrupt(format!("keychain {other}"))),
+            };
+            // The address was derived for this wallet's network when stored.
+            let address = address
+                .parse::<bitcoin::Address<NetworkUnchecked>>()
+                .map_err(|e| Error::Corrupt(format!("address `{address}`: {e}")))?
+                .assume_checked();
+            addresses.push(DerivedAddress {
+                keychain,
+                index,
+                address,
+                used,
+            });
+        }
+        Ok(Discovery::from_addresses(addresses))
+    }
+
+    /// Stores transactions seen for a wallet. A transaction seen again
+    /// keeps its first `seen_at` but takes the new height and block hash.
+    pub fn record_transactions(&self, wallet_id: &str, txs: &[TxRecord]) -> Result<()> {
+        self.expect_wallet(wallet_id)?;
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO transactions (wallet_id, txid, height, block_hash, seen_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (wallet_id, txid) DO UPDATE
+                 SET height = excluded.height, block_hash = excluded.block_hash",
+            )?;
+            for t in txs {
+                stmt.execute(params![
+                    wallet_id,
+                    t.txid,
+                    t.height,
+                    t.block_hash,
+                    t.seen_at
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// A wallet's transactions, unconfirmed last.
+    pub fn transactions(&self, wallet_id: &str) -> Result<Vec<TxRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT txid, height, block_hash, seen_at FROM transactions
+             WHERE wallet_id = ?1 ORDER BY height IS NULL, height, seen_at, txid",
+        )?;
+        let rows = stmt.query_map([wallet_id], |row| {
+            Ok(TxRecord {
+                txid: row.get(0)?,
+                height: row.get(1)?,
+                block_hash: row.get(2)?,
+                seen_at: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn save_snapshot(&self, wallet_id: &str, snapshot: &BalanceSnapshot) -> Result<()> {
+        self.expect_wallet(wallet_id)?;
+        self.conn.execute(
+            "INSERT INTO snapshots (wallet_id, height, taken_at, balances)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                wallet_id,
+                snapshot.height,
+                snapshot.taken_at,
+                serde_json::to_string(&snapshot.balances)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn latest_snapshot(&self, wallet_id: &str) -> Result<Option<BalanceSnapshot>> {
+        Ok(self
+            .snapshots_where(wallet_id, "ORDER BY taken_at DESC, id DESC LIMIT 1")?
+            .pop())
+    }
+
+    /// Every snapshot of a wallet, oldest first.
+    pub fn snapshots(&self, wallet_id: &str) -> Result<Vec<BalanceSnapshot>> {
+        self.snapshots_where(wallet_id, "ORDER BY taken_at, id")
+    }
+
+    fn snapshots_where(&self, wallet_id: &str, order: &str) -> Result<Vec<BalanceSnapshot>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT height, taken_at, balances FROM snapshots WHERE wallet_id = ?1 {order}"
+        ))?;
+        let rows = stmt.query_map([wallet_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?))
+        })?;
+        rows.map(|row| {
+            let (height, taken_at, balances) = row?;
+            Ok(BalanceSnapshot {
+                height,
+                taken_at,
+                balances: serde_json::from_str(&balances)?,
+            })
+        })
+        .collect()
+    }
+
+    pub fn sync_point(&self, wallet_id: &str) -> Result<Option<SyncPoint>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT height, block_hash FROM sync_points WHERE wallet_id = ?1",
+                [wallet_id],
+                |row| {
+                    Ok(SyncPoint {
+                        height: row.get(0)?,
+                        block_hash: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    pub fn set_sync_point(&self, wallet_id: &str, point: &SyncPoint) -> Result<()> {
+        self.expect_wallet(wallet_id)?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sync_points (wallet_id, height, block_hash)
+             VALUES (?1, ?2, ?3)",
+            params![wallet_id, point.height, point.block_hash],
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn expect_wallet(&self, id: &str) -> Result<()> {
+        match self.wallet(id)? {
+            Some(_) => Ok(()),
+            None => Err(Error::UnknownWallet(id.to_owned())),
+        }
+    }
+}
+
+fn wallet_record(row: &rusqlite::Row<'_>) -> rusqlite::Result<WalletRecord> {
+    Ok(WalletRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        source: row.get(2)?,
+        spec: row.get(3)?,
+    })
+}
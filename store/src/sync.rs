@@ -0,0 +1,376 @@
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::{Block, ScriptBuf, Transaction};
+use rusqlite::{params, OptionalExtension};
+use walletb_bitcoin::{btc, BlockSource};
+use walletb_core::{Amount, Balance};
+
+use crate::{BalanceSnapshot, Error, Result, Store, SyncPoint};
+
+/// How many of the most recent block hashes are kept per wallet. A
+/// reorganization deeper than this cannot be traced back to its fork point
+/// and needs [`Store::reset_sync`].
+pub const REORG_WINDOW: u32 = 100;
+
+/// What one [`Syncer::sync`] run changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// The block the wallet is synced to, or `None` while the chain has not
+    /// reached the start height.
+    pub tip: Option<SyncPoint>,
+    /// Blocks applied.
+    pub applied: u32,
+    /// Blocks undone because they were reorganized away.
+    pub rolled_back: u32,
+    /// Transactions whose block was reorganized away. They are pending
+    /// again unless a block applied later in the run confirmed them anew.
+    pub reverted: Vec<String>,
+    /// Pending transactions forgotten because a confirmed transaction spent
+    /// one of the same outputs.
+    pub dropped: Vec<String>,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.applied == 0 && self.rolled_back == 0
+    }
+}
+
+/// Follows a [`BlockSource`] for one wallet, keeping the wallet's
+/// transactions, outputs and balance in the store current one block at a
+/// time.
+///
+/// The last synced block is identified by hash, not just height. When that
+/// hash is no longer in the best chain, the blocks above the fork point are
+/// rolled back and their transactions marked pending before the new branch
+/// is applied, so a balance never includes transactions from orphaned
+/// blocks. Each block is applied, and each rollback made, in one database
+/// transaction.
+///
+/// Only outputs paying to the given scripts are tracked. For an HD wallet,
+/// pass [`Discovery::scripts`](walletb_bitcoin::Discovery::scripts) of the
+/// last [`discover`](walletb_bitcoin::discover), which includes the unused
+/// addresses within the gap limit.
+pub struct Syncer<'a, B> {
+    store: &'a Store,
+    backend: B,
+    wallet_id: String,
+    scripts: HashSet<ScriptBuf>,
+    start_height: u32,
+}
+
+impl<'a, B: BlockSource> Syncer<'a, B> {
+    pub fn new(
+        store: &'a Store,
+        backend: B,
+        wallet_id: impl Into<String>,
+        scripts: impl IntoIterator<Item = ScriptBuf>,
+    ) -> Self {
+        Syncer {
+            store,
+            backend,
+            wallet_id: wallet_id.into(),
+            scripts: scripts.into_iter().collect(),
+            start_height: 0,
+        }
+    }
+
+    /// Sets the height a wallet that has never been synced starts from,
+    /// typically the block its keys were created in. Defaults to 0.
+    pub fn with_start_height(mut self, height: u32) -> Self {
+        self.start_height = height;
+        self
+    }
+
+    /// Brings the wallet up to the backend's tip. Call it again whenever the
+    /// tip moves, e.g. on a timer or a new-block notification.
+    ///
+    /// If anything changed, the resulting balance is also saved as a
+    /// snapshot at the new tip.
+    pub fn sync(&self) -> Result<SyncReport> {
+        self.store.expect_wallet(&self.wallet_id)?;
+        let mut report = SyncReport::default();
+        loop {
+            let point = self.store.sync_point(&self.wallet_id)?;
+            let next = match &point {
+                Some(point) => {
+                    let height = block_height(point.height)?;
+                    let hash = self.backend.block_hash(height)?;
+                    if hash.map(|h| h.to_string()).as_ref() != Some(&point.block_hash) {
+                        self.roll_back(point, &mut report)?;
+                        continue;
+                    }
+                    height + 1
+                }
+                None => self.start_height,
+            };
+            let Some(hash) = self.backend.block_hash(next)? else {
+                break;
+            };
+            let block = self.backend.block(&hash)?;
+            // A block that does not build on ours means the chain moved
+            // since we checked; start over from the check.
+            if point.is_some_and(|p| block.header.prev_blockhash.to_string() != p.block_hash) {
+                continue;
+            }
+            self.apply(next, &block, &mut report)?;
+        }
+        report.tip = self.store.sync_point(&self.wallet_id)?;
+        if !report.is_empty() {
+            let snapshot = BalanceSnapshot {
+                height: report.tip.as_ref().map(|tip| tip.height),
+                taken_at: unix_now(),
+                balances: vec![self.store.synced_balance(&self.wallet_id)?],
+            };
+            self.store.save_snapshot(&self.wallet_id, &snapshot)?;
+        }
+        Ok(report)
+    }
+
+    fn apply(&self, height: u32, block: &Block, report: &mut SyncReport) -> Result<()> {
+        let hash = block.block_hash().to_string();
+        let tx = self.store.conn.unchecked_transaction()?;
+        for t in &block.txdata {
+            self.apply_tx(t, height, &hash, u64::from(block.header.time), report)?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO blocks (wallet_id, height, hash) VALUES (?1, ?2, ?3)",
+            params![self.wallet_id, height, hash],
+        )?;
+        tx.execute(
+            "DELETE FROM blocks WHERE wallet_id = ?1 AND height <= ?2",
+            params![self.wallet_id, i64::from(height) - i64::from(REORG_WINDOW)],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO sync_points (wallet_id, height, block_hash)
+             VALUES (?1, ?2, ?3)",
+            params![self.wallet_id, height, hash],
+        )?;
+        tx.commit()?;
+        report.applied += 1;
+        Ok(())
+    }
+
+    /// Records `t` if it pays to or spends from the wallet. Runs inside the
+    /// block's database transaction.
+    fn apply_tx(
+        &self,
+        t: &Transaction,
+        height: u32,
+        block_hash: &str,
+        time: u64,
+        report: &mut SyncReport,
+    ) -> Result<()> {
+        let conn = &self.store.conn;
+        let txid = t.compute_txid().to_string();
+        let mut spends = Vec::new();
+        for input in &t.input {
+            let prev = input.previous_output;
+            let spender: Option<Option<String>> = conn
+                .query_row(
+                    "SELECT spent_by FROM outputs WHERE wallet_id = ?1 AND txid = ?2 AND vout = ?3",
+                    params![self.wallet_id, prev.txid.to_string(), prev.vout],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match spender {
+                None => continue,
+                Some(Some(other)) if other != txid => self.drop_pending(&other, report)?,
+                Some(_) => {}
+            }
+            spends.push(prev);
+        }
+        let mut received = false;
+        for (vout, output) in t.output.iter().enumerate() {
+            if self.scripts.contains(&output.script_pubkey) {
+                received = true;
+                conn.execute(
+                    "INSERT OR IGNORE INTO outputs (wallet_id, txid, vout, value)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![self.wallet_id, txid, vout as u32, output.value.to_sat()],
+                )?;
+            }
+        }
+        if spends.is_empty() && !received {
+            return Ok(());
+        }
+        conn.execute(
+            "INSERT INTO transactions (wallet_id, txid, height, block_hash, seen_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (wallet_id, txid) DO UPDATE
+             SET height = excluded.height, block_hash = excluded.block_hash",
+            params![self.wallet_id, txid, height, block_hash, time],
+        )?;
+        for prev in spends {
+            conn.execute(
+                "UPDATE outputs SET spent_by = ?4 WHERE wallet_id = ?1 AND txid = ?2 AND vout = ?3",
+                params![self.wallet_id, prev.txid.to_string(), prev.vout, txid],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Forgets a pending transaction that lost a double spend, along with
+    /// any pending transactions spending its outputs.
+    fn drop_pending(&self, txid: &str, report: &mut SyncReport) -> Result<()> {
+        let conn = &self.store.conn;
+        let height: Option<Option<u64>> = conn
+            .query_row(
+                "SELECT height FROM transactions WHERE wallet_id = ?1 AND txid = ?2",
+                params![self.wallet_id, txid],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(Some(height)) = height {
+            return Err(Error::Corrupt(format!(
+                "an output spent by {txid}, confirmed at {height}, is spent again"
+            )));
+        }
+        let children: Vec<String> = conn
+            .prepare(
+                "SELECT DISTINCT spent_by FROM outputs
+                 WHERE wallet_id = ?1 AND txid = ?2 AND spent_by IS NOT NULL",
+            )?
+            .query_map(params![self.wallet_id, txid], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        for child in children {
+            self.drop_pending(&child, report)?;
+        }
+        conn.execute(
+            "DELETE FROM outputs WHERE wallet_id = ?1 AND txid = ?2",
+            params![self.wallet_id, txid],
+        )?;
+        conn.execute(
+            "UPDATE outputs SET spent_by = NULL WHERE wallet_id = ?1 AND spent_by = ?2",
+            params![self.wallet_id, txid],
+        )?;
+        conn.execute(
+            "DELETE FROM transactions WHERE wallet_id = ?1 AND txid = ?2",
+            params![self.wallet_id, txid],
+        )?;
+        report.dropped.push(txid.to_owned());
+        Ok(())
+    }
+
+    /// Undoes the blocks above the highest kept block still in the best
+    /// chain, marking their transactions pending.
+    fn roll_back(&self, point: &SyncPoint, report: &mut SyncReport) -> Result<()> {
+        let fork = self.fork_point(point)?;
+        let above = fork.as_ref().map_or(-1, |f| f.height as i64);
+        let tx = self.store.conn.unchecked_transaction()?;
+        let reverted: Vec<String> = tx
+            .prepare(
+                "SELECT txid FROM transactions WHERE wallet_id = ?1 AND height > ?2
+                 ORDER BY height, txid",
+            )?
+            .query_map(params![self.wallet_id, above], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        tx.execute(
+            "UPDATE transactions SET height = NULL, block_hash = NULL
+             WHERE wallet_id = ?1 AND height > ?2",
+            params![self.wallet_id, above],
+        )?;
+        // The window always reaches the fork point, so these are exactly
+        // the blocks being undone.
+        let undone = tx.execute(
+            "DELETE FROM blocks WHERE wallet_id = ?1 AND height > ?2",
+            params![self.wallet_id, above],
+        )?;
+        match &fork {
+            Some(fork) => tx.execute(
+                "UPDATE sync_points SET height = ?2, block_hash = ?3 WHERE wallet_id = ?1",
+                params![self.wallet_id, fork.height, fork.block_hash],
+            )?,
+            None => tx.execute(
+                "DELETE FROM sync_points WHERE wallet_id = ?1",
+                [&self.wallet_id],
+            )?,
+        };
+        tx.commit()?;
+        report.rolled_back += undone as u32;
+        report.reverted.extend(reverted);
+        Ok(())
+    }
+
+    /// The highest kept block that is still in the best chain, or `None` if
+    /// every block since the start height was reorganized away.
+    fn fork_point(&self, point: &SyncPoint) -> Result<Option<SyncPoint>> {
+        let kept: Vec<SyncPoint> = self
+            .store
+            .conn
+            .prepare("SELECT height, hash FROM blocks WHERE wallet_id = ?1 ORDER BY height DESC")?
+            .query_map([&self.wallet_id], |row| {
+                Ok(SyncPoint {
+                    height: row.get(0)?,
+                    block_hash: row.get(1)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        for block in &kept {
+            let hash = self.backend.block_hash(block_height(block.height)?)?;
+            if hash.is_some_and(|h| h.to_string() == block.block_hash) {
+                return Ok(Some(block.clone()));
+            }
+        }
+        match kept.last() {
+            Some(lowest) if lowest.height <= u64::from(self.start_height) => Ok(None),
+            _ => Err(Error::ReorgTooDeep {
+                wallet: self.wallet_id.clone(),
+                height: kept.last().map_or(point.height, |b| b.height),
+            }),
+        }
+    }
+}
+
+impl Store {
+    /// The balance of the outputs tracked by [`Syncer`]: unspent outputs of
+    /// confirmed transactions are confirmed, those of pending ones
+    /// unconfirmed. An output spent by a pending transaction counts as
+    /// spent.
+    pub fn synced_balance(&self, wallet_id: &str) -> Result<Balance> {
+        let mut stmt = self.conn.prepare(
+            "SELECT o.value, t.height IS NOT NULL FROM outputs o
+             JOIN transactions t ON t.wallet_id = o.wallet_id AND t.txid = o.txid
+             WHERE o.wallet_id = ?1 AND o.spent_by IS NULL",
+        )?;
+        let rows = stmt.query_map([wallet_id], |row| {
+            Ok((row.get::<_, u64>(0)?, row.get::<_, bool>(1)?))
+        })?;
+        let (mut confirmed, mut unconfirmed) = (0u64, 0u64);
+        for row in rows {
+            match row? {
+                (value, true) => confirmed += value,
+                (value, false) => unconfirmed += value,
+            }
+        }
+        Ok(Balance::new(btc(), Amount::from_u64(confirmed))
+            .with_unconfirmed(Amount::from_u64(unconfirmed)))
+    }
+
+    /// Forgets everything synced for a wallet, so that the next sync starts
+    /// over from the start height. The wallet, its addresses and its
+    /// snapshots are kept.
+    pub fn reset_sync(&self, wallet_id: &str) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for table in ["outputs", "blocks", "sync_points", "transactions"] {
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE wallet_id = ?1"),
+                [wallet_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn block_height(height: u64) -> Result<u32> {
+    u32::try_from(height).map_err(|_| Error::Corrupt(format!("block height {height}")))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{Amount, OutPoint, Txid};
+use walletb_bitcoin::{
+    btc, discover, discover_from, AccountXpub, Keychain, Keychains, MemoryUtxoSet, Network, Utxo,
+};
+use walletb_core::Balance;
+use walletb_store::{
+    BalanceSnapshot, Error, Store, SyncPoint, TxRecord, WalletRecord, SCHEMA_VERSION,
+};
+
+const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+
+fn db_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("walletb-store-{}-{name}.db", std::process::id()))
+}
+
+fn account() -> AccountXpub {
+    AccountXpub::parse(ZPUB, Network::Bitcoin).unwrap()
+}
+
+fn cold() -> WalletRecord {
+    WalletRecord {
+        id: "cold".into(),
+        name: "Cold storage".into(),
+        source: "btc".into(),
+        spec: format!("xpub:{ZPUB}"),
+    }
+}
+
+fn funded(indexes: &[u32]) -> MemoryUtxoSet {
+    indexes
+        .iter()
+        .map(|&index| Utxo {
+            outpoint: OutPoint::new(Txid::from_byte_array([index as u8; 32]), 0),
+            script_pubkey: account()
+                .derive(Keychain::External, index)
+                .unwrap()
+                .script_pubkey(),
+            value: Amount::from_sat(10_000),
+            height: Some(800_000),
+        })
+        .collect()
+}
+
+fn tx(n: u8, height: Option<u64>, seen_at: u64) -> TxRecord {
+    TxRecord {
+        txid: format!("{n:02x}").repeat(32),
+        height,
+        block_hash: height.map(|h| format!("{h:064x}")),
+        seen_at,
+    }
+}
+
+#[test]
+fn survives_a_reopen_and_resumes_discovery() {
+    let path = db_path("reopen");
+    let _ = std::fs::remove_file(&path);
+    let set = funded(&[0, 3]);
+    let snapshot = BalanceSnapshot {
+        height: Some(800_000),
+        taken_at: 1_719_792_000,
+        balances: vec![Balance::new(btc(), walletb_core::Amount::from_u64(20_000))],
+    };
+    {
+        let store = Store::open(&path).unwrap();
+        assert!(!store.save_wallet(&cold()).unwrap());
+        let discovery = discover(&account(), &set, 20).unwrap();
+        store.save_addresses("cold", &discovery.addresses).unwrap();
+        store.save_snapshot("cold", &snapshot).unwrap();
+        store
+            .set_sync_point(
+                "cold",
+                &SyncPoint {
+                    height: 800_000,
+                    block_hash: "00".repeat(32),
+                },
+            )
+            .unwrap();
+    }
+
+    let store = Store::open(&path).unwrap();
+    assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+    assert_eq!(store.wallets().unwrap(), [cold()]);
+    assert_eq!(store.latest_snapshot("cold").unwrap(), Some(snapshot));
+    assert_eq!(store.sync_point("cold").unwrap().unwrap().height, 800_000);
+
+    let known = store.discovery("cold").unwrap();
+    assert_eq!(known.next_index(Keychain::External), 4);
+    assert_eq!(known.used().count(), 2);
+
+    // A new deposit further out is found by resuming from the cache.
+    let resumed = discover_from(&account(), &funded(&[0, 3, 9]), 20, &known).unwrap();
+    assert_eq!(resumed.next_index(Keychain::External), 10);
+    store.save_addresses("cold", &resumed.addresses).unwrap();
+    assert_eq!(store.discovery("cold").unwrap().used().count(), 3);
+
+    drop(store);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn changing_keys_drops_the_cache() {
+    let store = Store::open_in_memory().unwrap();
+    store.save_wallet(&cold()).unwrap();
+    let discovery = discover(&account(), &funded(&[0]), 20).unwrap();
+    store.save_addresses("cold", &discovery.addresses).unwrap();
+    store
+        .record_transactions("cold", &[tx(1, Some(800_000), 10)])
+        .unwrap();
+
+    // A rename keeps everything.
+    let renamed = WalletRecord {
+        name: "Vault".into(),
+        ..cold()
+    };
+    assert!(!store.save_wallet(&renamed).unwrap());
+    assert_eq!(store.wallet("cold").unwrap().unwrap().name, "Vault");
+    assert_eq!(store.transactions("cold").unwrap().len(), 1);
+
+    let rekeyed = WalletRecord {
+        spec: "descriptor:wpkh(...)".into(),
+        ..renamed
+    };
+    assert!(store.save_wallet(&rekeyed).unwrap());
+    assert!(store.discovery("cold").unwrap().addresses.is_empty());
+    assert!(store.transactions("cold").unwrap().is_empty());
+
+    assert!(store.remove_wallet("cold").unwrap());
+    assert!(!store.remove_wallet("cold").unwrap());
+}
+
+#[test]
+fn records_transactions_once_and_tracks_confirmation() {
+    let store = Store::open_in_memory().unwrap();
+    store.save_wallet(&cold()).unwrap();
+    store
+        .record_transactions("cold", &[tx(2, None, 200), tx(1, Some(800_001), 100)])
+        .unwrap();
+    // Tx 2 confirms; seeing it again does not move its first-seen time.
+    store
+        .record_transactions("cold", &[tx(2, Some(800_002), 300), tx(3, None, 400)])
+        .unwrap();
+
+    let txs = store.transactions("cold").unwrap();
+    let order: Vec<(Option<u64>, u64)> = txs.iter().map(|t| (t.height, t.seen_at)).collect();
+    assert_eq!(
+        order,
+        [(Some(800_001), 100), (Some(800_002), 200), (None, 400)]
+    );
+}
+
+#[test]
+fn rejects_data_for_unknown_wallets() {
+    let store = Store::open_in_memory().unwrap();
+    assert!(matches!(
+        store.record_transactions("ghost", &[tx(1, None, 0)]),
+        Err(Error::UnknownWallet(id)) if id == "ghost"
+    ));
+    assert!(store.latest_snapshot("ghost").unwrap().is_none());
+}
@@ -0,0 +1,254 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::hashes::Hash;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use walletb_bitcoin::{AccountXpub, BlockSource, Keychain, Keychains, MemoryChain, Network};
+use walletb_store::{Error, Store, Syncer, WalletRecord, REORG_WINDOW};
+
+const ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+
+fn script(keychain: Keychain, index: u32) -> ScriptBuf {
+    AccountXpub::parse(ZPUB, Network::Bitcoin)
+        .unwrap()
+        .derive(keychain, index)
+        .unwrap()
+        .script_pubkey()
+}
+
+/// The wallet's first two receive and change scripts.
+fn scripts() -> Vec<ScriptBuf> {
+    [Keychain::External, Keychain::Internal]
+        .into_iter()
+        .flat_map(|k| (0..2).map(move |i| script(k, i)))
+        .collect()
+}
+
+fn payee() -> ScriptBuf {
+    ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros())
+}
+
+fn store() -> Store {
+    let store = Store::open_in_memory().unwrap();
+    store
+        .save_wallet(&WalletRecord {
+            id: "cold".into(),
+            name: "Cold storage".into(),
+            source: "btc".into(),
+            spec: format!("xpub:{ZPUB}"),
+        })
+        .unwrap();
+    store
+}
+
+/// A transaction spending `inputs` (or a made-up outside output, marked by
+/// `n`, if there are none) to `outputs`.
+fn pay(n: u8, inputs: &[OutPoint], outputs: &[(ScriptBuf, u64)]) -> Transaction {
+    let outside = [OutPoint::new(Txid::from_byte_array([n; 32]), 0)];
+    let inputs = if inputs.is_empty() { &outside } else { inputs };
+    Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|&previous_output| TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: outputs
+            .iter()
+            .map(|(script_pubkey, sats)| TxOut {
+                value: Amount::from_sat(*sats),
+                script_pubkey: script_pubkey.clone(),
+            })
+            .collect(),
+    }
+}
+
+fn sats(n: u64) -> walletb_core::Amount {
+    walletb_core::Amount::from_u64(n)
+}
+
+#[test]
+fn follows_the_chain_block_by_block() {
+    let store = store();
+    let mut chain = MemoryChain::new();
+    let funding = pay(1, &[], &[(script(Keychain::External, 0), 50_000)]);
+    chain.push(vec![pay(9, &[], &[(payee(), 1)]), funding.clone()]);
+    chain.push(Vec::new());
+
+    let syncer = Syncer::new(&store, &chain, "cold", scripts()).with_start_height(1);
+    let report = syncer.sync().unwrap();
+    assert_eq!(report.applied, 2);
+    assert_eq!(report.tip.unwrap().height, 2);
+    assert_eq!(
+        store.synced_balance("cold").unwrap().confirmed,
+        sats(50_000)
+    );
+    // Only the wallet's own transaction is recorded.
+    assert_eq!(store.transactions("cold").unwrap().len(), 1);
+
+    let spend = pay(
+        0,
+        &[OutPoint::new(funding.compute_txid(), 0)],
+        &[(payee(), 20_000), (script(Keychain::Internal, 0), 29_000)],
+    );
+    let tip = chain.push(vec![spend]);
+    let syncer = Syncer::new(&store, &chain, "cold", scripts());
+    let report = syncer.sync().unwrap();
+    assert_eq!(report.applied, 1);
+    assert_eq!(report.tip.unwrap().block_hash, tip.to_string());
+    let balance = store.synced_balance("cold").unwrap();
+    assert_eq!(balance.confirmed, sats(29_000));
+    assert!(balance.unconfirmed.is_zero());
+    let snapshot = store.latest_snapshot("cold").unwrap().unwrap();
+    assert_eq!(snapshot.height, Some(3));
+    assert_eq!(snapshot.balances, [balance]);
+
+    // Nothing new: nothing applied and no snapshot taken.
+    assert!(syncer.sync().unwrap().is_empty());
+    assert_eq!(store.snapshots("cold").unwrap().len(), 2);
+}
+
+#[test]
+fn rolls_back_orphaned_blocks_and_marks_their_transactions_pending() {
+    let store = store();
+    let mut chain = MemoryChain::new();
+    let funding = pay(1, &[], &[(script(Keychain::External, 0), 50_000)]);
+    let spend = pay(
+        0,
+        &[OutPoint::new(funding.compute_txid(), 0)],
+        &[(payee(), 20_000), (script(Keychain::Internal, 0), 29_000)],
+    );
+    let deposit = pay(2, &[], &[(script(Keychain::External, 1), 10_000)]);
+    chain.push(vec![funding]);
+    chain.push(vec![spend.clone()]);
+    chain.push(vec![deposit.clone()]);
+    let syncer = Syncer::new(&store, &chain, "cold", scripts());
+    syncer.sync().unwrap();
+    assert_eq!(
+        store.synced_balance("cold").unwrap().confirmed,
+        sats(39_000)
+    );
+
+    // Blocks 2 and 3 are replaced by a longer branch that confirms the
+    // spend again, a block later, but not the deposit.
+    chain.reorg(2);
+    chain.push(Vec::new());
+    chain.push(vec![spend.clone()]);
+    chain.push(Vec::new());
+    let syncer = Syncer::new(&store, &chain, "cold", scripts());
+    let report = syncer.sync().unwrap();
+    assert_eq!(report.rolled_back, 2);
+    assert_eq!(report.applied, 3);
+    assert_eq!(
+        report.reverted,
+        [
+            spend.compute_txid().to_string(),
+            deposit.compute_txid().to_string()
+        ]
+    );
+    assert!(report.dropped.is_empty());
+    assert_eq!(
+        report.tip.unwrap().block_hash,
+        chain.tip().unwrap().hash.to_string()
+    );
+
+    let txs = store.transactions("cold").unwrap();
+    let heights: Vec<Option<u64>> = txs.iter().map(|t| t.height).collect();
+    assert_eq!(heights, [Some(1), Some(3), None]);
+    assert_eq!(
+        txs[1].block_hash,
+        Some(chain.block_hash(3).unwrap().unwrap().to_string())
+    );
+    let balance = store.synced_balance("cold").unwrap();
+    assert_eq!(balance.confirmed, sats(29_000));
+    assert_eq!(balance.unconfirmed, sats(10_000));
+}
+
+#[test]
+fn drops_pending_transactions_that_lose_a_double_spend() {
+    let store = store();
+    let mut chain = MemoryChain::new();
+    let funding = pay(1, &[], &[(script(Keychain::External, 0), 50_000)]);
+    let coin = OutPoint::new(funding.compute_txid(), 0);
+    let spend = pay(0, &[coin], &[(script(Keychain::Internal, 0), 49_000)]);
+    // Spends the change of `spend`, so it goes down with it.
+    let child = pay(
+        0,
+        &[OutPoint::new(spend.compute_txid(), 0)],
+        &[(payee(), 48_000)],
+    );
+    chain.push(vec![funding]);
+    chain.push(vec![spend.clone()]);
+    chain.push(vec![child.clone()]);
+    Syncer::new(&store, &chain, "cold", scripts())
+        .sync()
+        .unwrap();
+
+    chain.reorg(2);
+    let replacement = pay(0, &[coin], &[(payee(), 49_500)]);
+    chain.push(vec![replacement.clone()]);
+    chain.push(Vec::new());
+    let report = Syncer::new(&store, &chain, "cold", scripts())
+        .sync()
+        .unwrap();
+    assert_eq!(
+        report.dropped,
+        [
+            child.compute_txid().to_string(),
+            spend.compute_txid().to_string()
+        ]
+    );
+
+    let txs: Vec<String> = store
+        .transactions("cold")
+        .unwrap()
+        .into_iter()
+        .map(|t| t.txid)
+        .collect();
+    assert_eq!(txs.len(), 2);
+    assert_eq!(txs[1], replacement.compute_txid().to_string());
+    assert_eq!(
+        store.synced_balance("cold").unwrap(),
+        walletb_core::Balance::zero(walletb_bitcoin::btc())
+    );
+}
+
+#[test]
+fn refuses_reorgs_deeper_than_the_window() {
+    let store = store();
+    let mut chain = MemoryChain::new();
+    chain.push(vec![pay(
+        1,
+        &[],
+        &[(script(Keychain::External, 0), 50_000)],
+    )]);
+    for _ in 0..REORG_WINDOW + 5 {
+        chain.push(Vec::new());
+    }
+    Syncer::new(&store, &chain, "cold", scripts())
+        .sync()
+        .unwrap();
+
+    chain.reorg(2);
+    for _ in 0..REORG_WINDOW + 10 {
+        chain.push(Vec::new());
+    }
+    let syncer = Syncer::new(&store, &chain, "cold", scripts());
+    assert!(matches!(
+        syncer.sync(),
+        Err(Error::ReorgTooDeep { wallet, height }) if wallet == "cold" && height == 7
+    ));
+
+    store.reset_sync("cold").unwrap();
+    assert!(store.sync_point("cold").unwrap().is_none());
+    let report = syncer.sync().unwrap();
+    assert_eq!(report.applied, chain.height() + 1);
+    assert_eq!(
+        store.synced_balance("cold").unwrap().confirmed,
+        sats(50_000)
+    );
+}